@@ -0,0 +1,520 @@
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::BTreeSet;
+use std::rc::{Rc, Weak};
+
+use windows::core::{implement, Interface, Result};
+use windows::Win32::Foundation::{BOOL, E_INVALIDARG, HWND, TRUE};
+use windows::Win32::Graphics::Gdi::InvalidateRect;
+use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+use windows::Win32::UI::Animation::{
+    IUIAnimationManager2, IUIAnimationStoryboard2, IUIAnimationTimer,
+    IUIAnimationTimerEventHandler, IUIAnimationTimerEventHandler_Impl,
+    IUIAnimationTimerUpdateHandler, IUIAnimationTransition2, IUIAnimationVariable2,
+    UIAnimationManager2, UIAnimationTimer, UI_ANIMATION_IDLE_BEHAVIOR_DISABLE,
+    UI_ANIMATION_STORYBOARD_CANCELLED, UI_ANIMATION_STORYBOARD_FINISHED,
+    UI_ANIMATION_STORYBOARD_INSUFFICIENT_PRIORITY,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    KillTimer, SetTimer, SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION,
+    SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+};
+
+use crate::theme::Tokens;
+use crate::QT;
+
+/// How components advance their animations.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AnimationMode {
+    /// Animations run in real time.
+    Normal,
+    /// Every animation jumps to its final value as soon as it is scheduled.
+    Instant,
+    /// Time only moves when [`QT::tick_animations`] is called, for deterministic screenshots.
+    Manual,
+}
+
+/// How long transitions take, in seconds. Defaults to the theme's duration tokens.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AnimationDurations {
+    /// 50 ms by default.
+    pub ultra_fast: f64,
+    /// Hover and press feedback, and the dialog scrim. 100 ms by default.
+    pub fast: f64,
+    /// 200 ms by default.
+    pub normal: f64,
+    /// The focus indicator of inputs. 250 ms by default.
+    pub gentle: f64,
+    /// 300 ms by default.
+    pub slow: f64,
+}
+
+/// The cubic Bézier control points `[x1, y1, x2, y2]` of transitions. Defaults to the theme's
+/// curve tokens.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AnimationCurves {
+    pub accelerate_max: [f64; 4],
+    pub accelerate_min: [f64; 4],
+    pub decelerate_max: [f64; 4],
+    /// The focus indicator of inputs.
+    pub decelerate_mid: [f64; 4],
+    pub decelerate_min: [f64; 4],
+    pub easy_ease_max: [f64; 4],
+    /// Hover and press feedback, and the dialog scrim.
+    pub easy_ease: [f64; 4],
+}
+
+macro_rules! token_overrides {
+    ($($(#[$meta:meta])* $name:ident: $type:ty,)*) => {
+        /// Changes to the motion tokens, applied with [`QT::override_tokens`]. Tokens that are not
+        /// overridden keep their current value.
+        #[derive(Copy, Clone, PartialEq, Debug)]
+        pub struct TokenOverrides {
+            $($name: Option<$type>,)*
+            duration_scale: f64,
+        }
+
+        impl Default for TokenOverrides {
+            fn default() -> Self {
+                TokenOverrides {
+                    $($name: None,)*
+                    duration_scale: 1f64,
+                }
+            }
+        }
+
+        impl TokenOverrides {
+            $(
+                $(#[$meta])*
+                pub fn $name(mut self, value: $type) -> Self {
+                    self.$name = Some(value);
+                    self
+                }
+            )*
+        }
+    };
+}
+
+token_overrides! {
+    /// In seconds, like every duration.
+    duration_ultra_fast: f64,
+    duration_fast: f64,
+    duration_normal: f64,
+    duration_gentle: f64,
+    duration_slow: f64,
+    /// The control points `[x1, y1, x2, y2]`, like every curve.
+    curve_accelerate_max: [f64; 4],
+    curve_accelerate_min: [f64; 4],
+    curve_decelerate_max: [f64; 4],
+    curve_decelerate_mid: [f64; 4],
+    curve_decelerate_min: [f64; 4],
+    curve_easy_ease_max: [f64; 4],
+    curve_easy_ease: [f64; 4],
+}
+
+impl TokenOverrides {
+    /// Multiplies every duration, overridden or not, so `2.0` slows animations down for demos
+    /// and `0.0` makes them snap.
+    pub fn scale_durations(mut self, factor: f64) -> Self {
+        self.duration_scale = factor;
+        self
+    }
+}
+
+/// The animation manager and timer that every component of a `QT` shares.
+struct Timeline {
+    manager: IUIAnimationManager2,
+    timer: IUIAnimationTimer,
+}
+
+/// Shared by every clone of a `QT`, so that one mode applies to all of its components.
+pub(crate) struct AnimationClock {
+    mode: Cell<AnimationMode>,
+    durations: Cell<AnimationDurations>,
+    curves: Cell<AnimationCurves>,
+    manual_time: Cell<f64>,
+    /// Created with the first component, since COM may not be initialized before.
+    timeline: OnceCell<Timeline>,
+    /// The update handler of each component window.
+    handlers: RefCell<Vec<(HWND, IUIAnimationTimerEventHandler)>>,
+    /// Storyboards that may still change the variables of their window.
+    storyboards: RefCell<Vec<(HWND, IUIAnimationStoryboard2)>>,
+    is_finishing: Cell<bool>,
+}
+
+impl AnimationClock {
+    pub(crate) fn new(tokens: &Tokens) -> Self {
+        AnimationClock {
+            mode: Cell::new(AnimationMode::Normal),
+            durations: Cell::new(AnimationDurations {
+                ultra_fast: tokens.duration_ultra_fast,
+                fast: tokens.duration_fast,
+                normal: tokens.duration_normal,
+                gentle: tokens.duration_gentle,
+                slow: tokens.duration_slow,
+            }),
+            curves: Cell::new(AnimationCurves {
+                accelerate_max: tokens.curve_accelerate_max,
+                accelerate_min: tokens.curve_accelerate_min,
+                decelerate_max: tokens.curve_decelerate_max,
+                decelerate_mid: tokens.curve_decelerate_mid,
+                decelerate_min: tokens.curve_decelerate_min,
+                easy_ease_max: tokens.curve_easy_ease_max,
+                easy_ease: tokens.curve_easy_ease,
+            }),
+            manual_time: Cell::new(0f64),
+            timeline: OnceCell::new(),
+            handlers: RefCell::new(Vec::new()),
+            storyboards: RefCell::new(Vec::new()),
+            is_finishing: Cell::new(false),
+        }
+    }
+
+    fn timeline(self: &Rc<Self>) -> Result<&Timeline> {
+        if let Some(timeline) = self.timeline.get() {
+            return Ok(timeline);
+        }
+        let timeline = unsafe {
+            let manager: IUIAnimationManager2 =
+                CoCreateInstance(&UIAnimationManager2, None, CLSCTX_INPROC_SERVER)?;
+            let timer: IUIAnimationTimer =
+                CoCreateInstance(&UIAnimationTimer, None, CLSCTX_INPROC_SERVER)?;
+            let event_handler: IUIAnimationTimerEventHandler = TimelineEventHandler {
+                clock: Rc::downgrade(self),
+            }
+            .into();
+            timer.SetTimerEventHandler(&event_handler)?;
+            let timeline = Timeline { manager, timer };
+            connect_timer(&timeline, self.mode.get())?;
+            timeline
+        };
+        Ok(self.timeline.get_or_init(|| timeline))
+    }
+
+    // Only windows with a running storyboard are updated, the last time on the frame it ends.
+    unsafe fn post_update(&self) -> Result<()> {
+        let windows: Vec<HWND> = self
+            .storyboards
+            .borrow()
+            .iter()
+            .map(|(window, _)| *window)
+            .collect();
+        self.storyboards
+            .borrow_mut()
+            .retain(|(_, storyboard)| is_running(storyboard));
+        // Handlers may create or destroy components, so they must not see the lists borrowed.
+        let handlers: Vec<IUIAnimationTimerEventHandler> = self
+            .handlers
+            .borrow()
+            .iter()
+            .filter(|(window, _)| windows.contains(window))
+            .map(|(_, handler)| handler.clone())
+            .collect();
+        for handler in handlers {
+            handler.OnPostUpdate()?;
+        }
+        Ok(())
+    }
+}
+
+#[implement(IUIAnimationTimerEventHandler)]
+struct TimelineEventHandler {
+    clock: Weak<AnimationClock>,
+}
+
+impl IUIAnimationTimerEventHandler_Impl for TimelineEventHandler_Impl {
+    fn OnPreUpdate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn OnPostUpdate(&self) -> Result<()> {
+        match self.clock.upgrade() {
+            Some(clock) => unsafe { clock.post_update() },
+            None => Ok(()),
+        }
+    }
+
+    fn OnRenderingTooSlow(&self, _frames_per_second: u32) -> Result<()> {
+        Ok(())
+    }
+}
+
+unsafe fn is_running(storyboard: &IUIAnimationStoryboard2) -> bool {
+    !matches!(
+        storyboard.GetStatus(),
+        Ok(UI_ANIMATION_STORYBOARD_FINISHED
+            | UI_ANIMATION_STORYBOARD_CANCELLED
+            | UI_ANIMATION_STORYBOARD_INSUFFICIENT_PRIORITY)
+            | Err(_)
+    )
+}
+
+// Roughly one frame at 60 Hz.
+const FRAME_INTERVAL: u32 = 16;
+
+thread_local! {
+    /// Windows whose animations advanced since the last frame, as raw handles.
+    static DIRTY_WINDOWS: RefCell<BTreeSet<isize>> = const { RefCell::new(BTreeSet::new()) };
+    static FRAME_TIMER: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Animation post-update handlers call this instead of invalidating right away, so that every
+/// window animating on this thread is invalidated once per frame, all at the same time.
+pub(crate) fn invalidate_on_next_frame(window: HWND) {
+    DIRTY_WINDOWS.with_borrow_mut(|windows| windows.insert(window.0 as isize));
+    if FRAME_TIMER.get() != 0 {
+        return;
+    }
+    let timer = unsafe { SetTimer(None, 0, FRAME_INTERVAL, Some(Some(on_frame))) };
+    if timer == 0 {
+        flush_frame();
+    } else {
+        FRAME_TIMER.set(timer);
+    }
+}
+
+/// Invalidates every window marked since the last frame.
+pub(crate) fn flush_frame() {
+    let timer = FRAME_TIMER.replace(0);
+    if timer != 0 {
+        unsafe {
+            _ = KillTimer(None, timer);
+        }
+    }
+    for window in DIRTY_WINDOWS.with_borrow_mut(std::mem::take) {
+        unsafe {
+            _ = InvalidateRect(Some(HWND(window as _)), None, false);
+        }
+    }
+}
+
+unsafe extern "system" fn on_frame(_window: HWND, _message: u32, _id: usize, _time: u32) {
+    flush_frame();
+}
+
+/// Whether the user turned off animations in Windows, in which case normal mode behaves as
+/// instant.
+pub(crate) fn is_reduced_motion() -> bool {
+    let mut enabled = TRUE;
+    unsafe {
+        SystemParametersInfoW(
+            SPI_GETCLIENTAREAANIMATION,
+            0,
+            Some(&mut enabled as *mut BOOL as _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS::default(),
+        )
+        .is_ok()
+            && !enabled.as_bool()
+    }
+}
+
+// In manual mode the timer stops driving the manager, and ticks do it instead.
+unsafe fn connect_timer(timeline: &Timeline, mode: AnimationMode) -> Result<()> {
+    if mode == AnimationMode::Manual {
+        timeline
+            .timer
+            .SetTimerUpdateHandler(None, UI_ANIMATION_IDLE_BEHAVIOR_DISABLE)
+    } else {
+        let update_handler = timeline.manager.cast::<IUIAnimationTimerUpdateHandler>()?;
+        timeline
+            .timer
+            .SetTimerUpdateHandler(&update_handler, UI_ANIMATION_IDLE_BEHAVIOR_DISABLE)
+    }
+}
+
+impl QT {
+    pub fn animation_mode(&self) -> AnimationMode {
+        self.animation.mode.get()
+    }
+
+    pub fn set_animation_mode(&self, mode: AnimationMode) -> Result<()> {
+        if mode == AnimationMode::Manual && self.animation.mode.get() != AnimationMode::Manual {
+            unsafe {
+                let timer: IUIAnimationTimer =
+                    CoCreateInstance(&UIAnimationTimer, None, CLSCTX_INPROC_SERVER)?;
+                self.animation.manual_time.set(timer.GetTime()?);
+            }
+        }
+        self.animation.mode.set(mode);
+        if let Some(timeline) = self.animation.timeline.get() {
+            unsafe { connect_timer(timeline, mode)? };
+        }
+        Ok(())
+    }
+
+    pub fn animation_durations(&self) -> AnimationDurations {
+        self.animation.durations.get()
+    }
+
+    /// Applies to transitions that start afterwards.
+    pub fn set_animation_durations(&self, durations: AnimationDurations) {
+        self.animation.durations.set(durations);
+    }
+
+    pub fn animation_curves(&self) -> AnimationCurves {
+        self.animation.curves.get()
+    }
+
+    /// Applies to transitions that start afterwards.
+    pub fn set_animation_curves(&self, curves: AnimationCurves) {
+        self.animation.curves.set(curves);
+    }
+
+    /// Applies to transitions that start afterwards. Zero durations make every transition snap
+    /// to its final value, and doubled ones slow them down for demos.
+    ///
+    /// ```no_run
+    /// use quelthalas::animation::TokenOverrides;
+    /// use quelthalas::QT;
+    ///
+    /// let qt = QT::default();
+    /// // Hover and focus states snap instead of fading.
+    /// qt.override_tokens(&TokenOverrides::default().scale_durations(0.0));
+    /// assert_eq!(qt.animation_durations().fast, 0.0);
+    ///
+    /// // Focus eases linearly and takes twice as long as hover feedback.
+    /// qt.override_tokens(
+    ///     &TokenOverrides::default()
+    ///         .duration_fast(0.1)
+    ///         .duration_gentle(0.2)
+    ///         .curve_decelerate_mid([0.0, 0.0, 1.0, 1.0]),
+    /// );
+    /// ```
+    pub fn override_tokens(&self, overrides: &TokenOverrides) {
+        let durations = self.animation.durations.get();
+        let scale = overrides.duration_scale;
+        self.animation.durations.set(AnimationDurations {
+            ultra_fast: overrides
+                .duration_ultra_fast
+                .unwrap_or(durations.ultra_fast)
+                * scale,
+            fast: overrides.duration_fast.unwrap_or(durations.fast) * scale,
+            normal: overrides.duration_normal.unwrap_or(durations.normal) * scale,
+            gentle: overrides.duration_gentle.unwrap_or(durations.gentle) * scale,
+            slow: overrides.duration_slow.unwrap_or(durations.slow) * scale,
+        });
+        let curves = self.animation.curves.get();
+        self.animation.curves.set(AnimationCurves {
+            accelerate_max: overrides
+                .curve_accelerate_max
+                .unwrap_or(curves.accelerate_max),
+            accelerate_min: overrides
+                .curve_accelerate_min
+                .unwrap_or(curves.accelerate_min),
+            decelerate_max: overrides
+                .curve_decelerate_max
+                .unwrap_or(curves.decelerate_max),
+            decelerate_mid: overrides
+                .curve_decelerate_mid
+                .unwrap_or(curves.decelerate_mid),
+            decelerate_min: overrides
+                .curve_decelerate_min
+                .unwrap_or(curves.decelerate_min),
+            easy_ease_max: overrides
+                .curve_easy_ease_max
+                .unwrap_or(curves.easy_ease_max),
+            easy_ease: overrides.curve_easy_ease.unwrap_or(curves.easy_ease),
+        });
+    }
+
+    /// Advances every animation by `seconds` while in [`AnimationMode::Manual`].
+    pub fn tick_animations(&self, seconds: f64) -> Result<()> {
+        if self.animation.mode.get() != AnimationMode::Manual {
+            return Err(E_INVALIDARG.into());
+        }
+        let time = self.animation.manual_time.get() + seconds;
+        self.animation.manual_time.set(time);
+        if let Some(timeline) = self.animation.timeline.get() {
+            unsafe {
+                timeline.manager.Update(time, None)?;
+                self.animation.post_update()?;
+            }
+        }
+        // A tick is followed by a capture, so its frame cannot wait for the timer.
+        flush_frame();
+        Ok(())
+    }
+
+    /// The animation manager that every component creates its variables and storyboards with.
+    pub(crate) fn animation_manager(&self) -> Result<IUIAnimationManager2> {
+        Ok(self.animation.timeline()?.manager.clone())
+    }
+
+    /// Components register the handler that updates their window when they are created, and
+    /// unregister it when they are destroyed, which abandons their running storyboards.
+    pub(crate) fn register_animation(
+        &self,
+        window: HWND,
+        event_handler: &IUIAnimationTimerEventHandler,
+    ) {
+        self.animation
+            .handlers
+            .borrow_mut()
+            .push((window, event_handler.clone()));
+    }
+
+    pub(crate) fn unregister_animation(&self, window: HWND) {
+        self.animation
+            .handlers
+            .borrow_mut()
+            .retain(|(handler_window, _)| *handler_window != window);
+        let storyboards = self.animation.storyboards.take();
+        let (abandoned, running) = storyboards
+            .into_iter()
+            .partition(|(storyboard_window, _)| *storyboard_window == window);
+        self.animation.storyboards.replace(running);
+        for (_, storyboard) in abandoned {
+            _ = unsafe { storyboard.Abandon() };
+        }
+        DIRTY_WINDOWS.with_borrow_mut(|windows| windows.remove(&(window.0 as isize)));
+    }
+
+    /// Every component schedules through here, so that the animation mode applies to all of them
+    /// and `window` is updated while the storyboard runs.
+    pub(crate) unsafe fn schedule_storyboard(
+        &self,
+        window: HWND,
+        storyboard: &IUIAnimationStoryboard2,
+    ) -> Result<()> {
+        let timeline = self.animation.timeline()?;
+        let mode = match self.animation.mode.get() {
+            AnimationMode::Normal if is_reduced_motion() => AnimationMode::Instant,
+            mode => mode,
+        };
+        let time = match mode {
+            AnimationMode::Manual => self.animation.manual_time.get(),
+            _ => timeline.timer.GetTime()?,
+        };
+        storyboard.Schedule(time, None)?;
+        self.animation
+            .storyboards
+            .borrow_mut()
+            .push((window, storyboard.clone()));
+        if mode != AnimationMode::Instant {
+            return Ok(());
+        }
+        timeline.manager.FinishAllStoryboards(0f64)?;
+        // A handler that schedules again, like a looping progress bar, must not recurse.
+        if self.animation.is_finishing.replace(true) {
+            return timeline.manager.Update(time, None);
+        }
+        let result = timeline
+            .manager
+            .Update(time, None)
+            .and_then(|_| self.animation.post_update());
+        self.animation.is_finishing.set(false);
+        _ = InvalidateRect(Some(window), None, false);
+        result
+    }
+
+    pub(crate) unsafe fn schedule_transition(
+        &self,
+        window: HWND,
+        variable: &IUIAnimationVariable2,
+        transition: &IUIAnimationTransition2,
+    ) -> Result<IUIAnimationStoryboard2> {
+        let storyboard = self.animation_manager()?.CreateStoryboard()?;
+        storyboard.AddTransition(variable, transition)?;
+        self.schedule_storyboard(window, &storyboard)?;
+        Ok(storyboard)
+    }
+}
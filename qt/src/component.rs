@@ -1,5 +1,92 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use windows::core::Result;
+use windows::Win32::Foundation::{E_INVALIDARG, HWND};
+
+use crate::QT;
+
 pub mod button;
 pub mod dialog;
+pub mod hover_card;
 pub mod input;
 pub mod menu;
+pub mod number_box;
 pub mod progress_bar;
+pub mod rating;
+pub mod segmented_control;
+pub mod spinner;
+mod suggestion_list;
+pub mod tag;
+mod tooltip;
+
+/// A child control that can be created from a position and a set of options.
+pub trait Component {
+    type Options;
+
+    fn create(qt: &QT, parent_window: HWND, x: i32, y: i32, options: Self::Options)
+        -> Result<HWND>;
+}
+
+type Factory = Box<dyn Fn(&QT, HWND, i32, i32, Box<dyn Any>) -> Result<HWND>>;
+
+/// Creates components by name, for code that only knows what to build at run time.
+#[derive(Default)]
+pub struct Registry {
+    factories: HashMap<String, Factory>,
+}
+
+impl Registry {
+    /// A registry with every built-in component, named after its module.
+    pub fn with_builtins() -> Self {
+        let mut registry = Registry::default();
+        registry.register::<button::Button>("button");
+        registry.register::<input::Input>("input");
+        registry.register::<number_box::NumberBox>("number_box");
+        registry.register::<progress_bar::ProgressBar>("progress_bar");
+        registry.register::<rating::Rating>("rating");
+        registry.register::<segmented_control::SegmentedControl>("segmented_control");
+        registry.register::<spinner::Spinner>("spinner");
+        registry.register::<tag::Tag>("tag");
+        registry
+    }
+
+    pub fn register<C: Component + 'static>(&mut self, name: &str)
+    where
+        C::Options: 'static,
+    {
+        self.factories.insert(
+            name.to_string(),
+            Box::new(|qt, parent_window, x, y, options| {
+                let options = options.downcast::<C::Options>().map_err(|_| E_INVALIDARG)?;
+                C::create(qt, parent_window, x, y, *options)
+            }),
+        );
+    }
+
+    /// Fails with `E_INVALIDARG` when `name` is unknown or `options` is of the wrong type.
+    pub fn create(
+        &self,
+        name: &str,
+        qt: &QT,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: Box<dyn Any>,
+    ) -> Result<HWND> {
+        let factory = self.factories.get(name).ok_or(E_INVALIDARG)?;
+        factory(qt, parent_window, x, y, options)
+    }
+}
+
+impl QT {
+    pub fn create<C: Component>(
+        &self,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: C::Options,
+    ) -> Result<HWND> {
+        C::create(self, parent_window, x, y, options)
+    }
+}
@@ -1,91 +1,190 @@
+use std::cell::Cell;
 use std::mem::size_of;
 
 use windows::core::*;
 use windows::Foundation::Numerics::Matrix3x2;
 use windows::Win32::Foundation::*;
 use windows::Win32::Graphics::Direct2D::Common::{
-    D2D1_COLOR_F, D2D_RECT_F, D2D_SIZE_F, D2D_SIZE_U,
+    D2D1_COLOR_F, D2D_POINT_2F, D2D_RECT_F, D2D_SIZE_F, D2D_SIZE_U,
 };
 use windows::Win32::Graphics::Direct2D::{
     D2D1CreateFactory, ID2D1DeviceContext5, ID2D1Factory1, ID2D1HwndRenderTarget, ID2D1StrokeStyle,
-    ID2D1SvgAttribute, ID2D1SvgDocument, D2D1_DRAW_TEXT_OPTIONS_NONE, D2D1_FACTORY_OPTIONS,
-    D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_HWND_RENDER_TARGET_PROPERTIES,
-    D2D1_RENDER_TARGET_PROPERTIES, D2D1_ROUNDED_RECT, D2D1_STROKE_STYLE_PROPERTIES1,
-    D2D1_SVG_PAINT_TYPE_COLOR,
+    ID2D1SvgAttribute, ID2D1SvgDocument, D2D1_ANTIALIAS_MODE_ALIASED, D2D1_DRAW_TEXT_OPTIONS_NONE,
+    D2D1_FACTORY_OPTIONS, D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_HWND_RENDER_TARGET_PROPERTIES,
+    D2D1_ROUNDED_RECT, D2D1_STROKE_STYLE_PROPERTIES1, D2D1_SVG_PAINT_TYPE_COLOR,
 };
 use windows::Win32::Graphics::DirectWrite::{
     DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, DWRITE_FACTORY_TYPE_SHARED,
     DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL, DWRITE_MEASURING_MODE_NATURAL,
-    DWRITE_PARAGRAPH_ALIGNMENT_CENTER, DWRITE_TEXT_ALIGNMENT_CENTER, DWRITE_TEXT_METRICS,
+    DWRITE_PARAGRAPH_ALIGNMENT_CENTER, DWRITE_TEXT_ALIGNMENT_CENTER, DWRITE_TEXT_ALIGNMENT_LEADING,
+    DWRITE_TEXT_ALIGNMENT_TRAILING, DWRITE_TEXT_METRICS, DWRITE_TEXT_RANGE,
 };
 use windows::Win32::Graphics::Gdi::{
     BeginPaint, CreateRectRgn, CreateRoundRectRgn, DeleteObject, EndPaint, GetWindowRgn,
-    InvalidateRect, PtInRegion, SetWindowRgn, PAINTSTRUCT,
+    InvalidateRect, PtInRegion, ScreenToClient, SetWindowRgn, HDC, PAINTSTRUCT, RGN_ERROR,
 };
 use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
 use windows::Win32::UI::Animation::{
-    IUIAnimationManager2, IUIAnimationTimer, IUIAnimationTimerEventHandler_Impl,
-    IUIAnimationTransitionLibrary2, IUIAnimationVariable2, UIAnimationTimer,
-    UIAnimationTransitionLibrary2,
-};
-use windows::Win32::UI::Animation::{
-    IUIAnimationTimerEventHandler, IUIAnimationTimerUpdateHandler, UIAnimationManager2,
-    UI_ANIMATION_IDLE_BEHAVIOR_DISABLE,
+    IUIAnimationManager2, IUIAnimationTimerEventHandler, IUIAnimationTimerEventHandler_Impl,
+    IUIAnimationTransitionLibrary2, IUIAnimationVariable2, UIAnimationTransitionLibrary2,
 };
 use windows::Win32::UI::Controls::WM_MOUSELEAVE;
 use windows::Win32::UI::HiDpi::GetDpiForWindow;
-use windows::Win32::UI::Input::KeyboardAndMouse::{TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetKeyState, SetFocus, TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT, VIRTUAL_KEY, VK_DOWN,
+    VK_MENU, VK_RETURN, VK_SPACE,
+};
 use windows::Win32::UI::Shell::SHCreateMemStream;
 use windows::Win32::UI::WindowsAndMessaging::*;
 
+use crate::animation::invalidate_on_next_frame;
+use crate::component::menu::create_svg_document;
+use crate::component::Component;
 use crate::icon::Icon;
+use crate::theme::Tokens;
 use crate::QT;
-use crate::{get_scaling_factor, MouseEvent};
+use crate::{
+    get_parent_background_color, handle_disabled_mouse, is_focus_visible, is_window_of_class,
+    print_client, recover_lost_device, recreate_render_target, render_target_properties,
+    track_input_kind, window_class_name, ButtonHandle, CornerRadius, Dpi, HitTestBehavior,
+    MouseEvent, SizeDips,
+};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Appearance {
     Secondary,
     Primary,
+    /// Looks like a hyperlink: brand-colored text, underlined on hover, with no background or
+    /// border.
+    Transparent,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum IconPosition {
     Before,
     After,
 }
 
-#[derive(Copy, Clone)]
+/// Where the text and icon sit when the button is wider than its content.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    Start,
+    Center,
+    End,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Shape {
     Circular,
     Rounded,
     Square,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Size {
     Small,
     Medium,
     Large,
 }
 
+pub struct Button;
+
+pub struct ButtonOptions {
+    pub text: PCWSTR,
+    pub secondary_text: Option<PCWSTR>,
+    pub appearance: Appearance,
+    pub icon: Option<Icon>,
+    pub icon_position: Option<IconPosition>,
+    pub alignment: Option<Alignment>,
+    pub shape: Shape,
+    pub corner: Option<CornerRadius>,
+    pub hit_test: HitTestBehavior,
+    pub size: Size,
+    pub mouse_event: MouseEvent,
+}
+
+impl Default for ButtonOptions {
+    /// A medium, rounded secondary button with no text or icon.
+    fn default() -> Self {
+        ButtonOptions {
+            text: w!(""),
+            secondary_text: None,
+            appearance: Appearance::Secondary,
+            icon: None,
+            icon_position: None,
+            alignment: None,
+            shape: Shape::Rounded,
+            corner: None,
+            hit_test: HitTestBehavior::Normal,
+            size: Size::Medium,
+            mouse_event: MouseEvent::default(),
+        }
+    }
+}
+
+impl Component for Button {
+    type Options = ButtonOptions;
+
+    fn create(
+        qt: &QT,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: ButtonOptions,
+    ) -> Result<HWND> {
+        qt.create_button(parent_window, x, y, options)
+            .map(|handle| handle.window())
+    }
+}
+
 struct State {
     qt: QT,
     text: PCWSTR,
+    /// A smaller description under the text, which makes this a compound button.
+    secondary_text: Option<PCWSTR>,
     appearance: Appearance,
     icon: Option<Icon>,
     icon_position: Option<IconPosition>,
+    alignment: Option<Alignment>,
     shape: Shape,
+    /// Overrides the rounding of `shape`.
+    corner: Option<CornerRadius>,
+    hit_test: HitTestBehavior,
     size: Size,
     mouse_event: MouseEvent,
+    /// Set for split buttons, which have a dropdown area at the trailing edge.
+    dropdown_event: Option<MouseEvent>,
 }
 
 impl State {
-    fn get_min_width(&self) -> f32 {
+    fn get_corner(&self) -> CornerRadius {
+        let tokens = &self.qt.theme.tokens;
+        self.corner.unwrap_or(match self.shape {
+            Shape::Circular => CornerRadius::Pill,
+            Shape::Rounded => CornerRadius::Dips(tokens.border_radius_medium),
+            Shape::Square => CornerRadius::Dips(tokens.border_radius_none),
+        })
+    }
+
+    unsafe fn get_min_width(&self) -> f32 {
+        if self.is_icon_only() {
+            return self.get_min_height() + self.get_dropdown_width();
+        }
         (match &self.size {
             Size::Small => 96,
             Size::Medium => 96,
             Size::Large => 64,
         }) as f32
+            + self.get_dropdown_width()
+    }
+
+    // The dropdown area of a split button is square, like an icon-only button.
+    unsafe fn get_dropdown_width(&self) -> f32 {
+        if self.dropdown_event.is_some() {
+            self.get_min_height()
+        } else {
+            0f32
+        }
     }
 
     fn get_line_height(&self) -> f32 {
@@ -113,9 +212,19 @@ impl State {
         }
     }
 
+    fn get_secondary_line_height(&self) -> f32 {
+        match self.secondary_text {
+            Some(_) => self.qt.theme.typography_styles.caption1.line_height,
+            None => 0f32,
+        }
+    }
+
     unsafe fn get_min_height(&self) -> f32 {
         let tokens = &self.qt.theme.tokens;
-        self.get_line_height() + self.get_spacing() * 2f32 + tokens.stroke_width_thin * 2f32
+        self.get_line_height()
+            + self.get_secondary_line_height()
+            + self.get_spacing() * 2f32
+            + tokens.stroke_width_thin * 2f32
     }
 
     fn get_desired_icon_size(&self) -> f32 {
@@ -138,22 +247,80 @@ impl State {
     fn has_icon(&self) -> bool {
         self.icon.is_some()
     }
+
+    unsafe fn is_icon_only(&self) -> bool {
+        self.has_icon() && (self.text.is_null() || self.text.is_empty())
+    }
 }
 
 struct Context {
     state: State,
+    /// Owns the text set after creation, which `state.text` then points to.
+    owned_text: Option<HSTRING>,
     icon_svg: Option<ID2D1SvgDocument>,
     text_format: IDWriteTextFormat,
+    secondary_text_format: Option<IDWriteTextFormat>,
+    /// In DIPs, so it survives DPI changes. Cleared when the text changes.
+    text_metrics: Cell<Option<TextMetrics>>,
     render_target: ID2D1HwndRenderTarget,
     stroke_style: ID2D1StrokeStyle,
     animation_manager: IUIAnimationManager2,
-    animation_timer: IUIAnimationTimer,
     transition_library: IUIAnimationTransitionLibrary2,
     background_color_variable: IUIAnimationVariable2,
+    dropdown_background_color_variable: IUIAnimationVariable2,
     border_color_variable: IUIAnimationVariable2,
     text_color_variable: IUIAnimationVariable2,
+    dropdown_svg: Option<ID2D1SvgDocument>,
     mouse_within: bool,
+    mouse_within_dropdown: bool,
     mouse_clicking: bool,
+    is_focused: bool,
+    focus_visible: bool,
+}
+
+impl Context {
+    unsafe fn get_text_metrics(&self) -> Result<TextMetrics> {
+        if let Some(metrics) = self.text_metrics.get() {
+            return Ok(metrics);
+        }
+        let metrics = measure_text(
+            &self.state,
+            &self.text_format,
+            self.secondary_text_format.as_ref(),
+        )?;
+        self.text_metrics.set(Some(metrics));
+        Ok(metrics)
+    }
+
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        (self.icon_svg, self.dropdown_svg) = create_svg_documents(&self.state, &render_target)?;
+        self.render_target = render_target;
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+struct TextMetrics {
+    text: DWRITE_TEXT_METRICS,
+    secondary_text: Option<DWRITE_TEXT_METRICS>,
+}
+
+const CLASS_NAME: &str = "QT_BUTTON";
+
+impl ButtonHandle {
+    /// Changes the button's text from any thread. The button resizes to fit it, as it does for
+    /// `SetWindowTextW` on its own thread.
+    pub fn set_text(&self, text: &str) -> Result<()> {
+        let handle = *self;
+        let text = HSTRING::from(text);
+        self.post_to_ui(Box::new(move || unsafe {
+            // The window may have been destroyed and its handle reused by the time this runs.
+            if is_window_of_class(handle.window(), CLASS_NAME, Some(window_proc)) {
+                _ = SetWindowTextW(handle.window(), &text);
+            }
+        }))
+    }
 }
 
 impl QT {
@@ -162,57 +329,139 @@ impl QT {
         parent_window: HWND,
         x: i32,
         y: i32,
+        options: ButtonOptions,
+    ) -> Result<ButtonHandle> {
+        create(parent_window, x, y, self.button_state(options, None))
+    }
+
+    /// A button with a separate dropdown area at its trailing edge. Clicking the main area fires
+    /// `options.mouse_event`; clicking the dropdown area, or pressing Alt+Down, fires
+    /// `dropdown_event`, which typically opens a menu.
+    pub fn create_split_button(
+        &self,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: ButtonOptions,
+        dropdown_event: MouseEvent,
+    ) -> Result<ButtonHandle> {
+        create(
+            parent_window,
+            x,
+            y,
+            self.button_state(options, Some(dropdown_event)),
+        )
+    }
+
+    fn button_state(&self, options: ButtonOptions, dropdown_event: Option<MouseEvent>) -> State {
+        State {
+            qt: self.clone(),
+            text: options.text,
+            secondary_text: options.secondary_text,
+            appearance: options.appearance,
+            icon: options.icon,
+            icon_position: options.icon_position,
+            alignment: options.alignment,
+            shape: options.shape,
+            corner: options.corner,
+            hit_test: options.hit_test,
+            size: options.size,
+            mouse_event: options.mouse_event,
+            dropdown_event,
+        }
+    }
+
+    /// Measures a button with this content without creating it. The measurement is the one
+    /// `create_button` lays the button out with.
+    pub fn measure_button(
+        &self,
         text: PCWSTR,
-        appearance: &Appearance,
+        size: &Size,
         icon: Option<&Icon>,
-        icon_position: Option<&IconPosition>,
         shape: &Shape,
-        size: &Size,
-        mouse_event: MouseEvent,
-    ) -> Result<HWND> {
-        let class_name: PCWSTR = w!("QT_BUTTON");
+    ) -> Result<SizeDips> {
+        let state = State {
+            qt: self.clone(),
+            text,
+            secondary_text: None,
+            appearance: Appearance::Secondary,
+            icon: icon.cloned(),
+            icon_position: None,
+            alignment: None,
+            shape: *shape,
+            corner: None,
+            hit_test: HitTestBehavior::Normal,
+            size: *size,
+            mouse_event: MouseEvent::default(),
+            dropdown_event: None,
+        };
         unsafe {
-            let window_class = WNDCLASSEXW {
-                cbSize: size_of::<WNDCLASSEXW>() as u32,
-                lpszClassName: class_name,
-                style: CS_CLASSDC,
-                lpfnWndProc: Some(window_proc),
-                hCursor: LoadCursorW(None, IDC_ARROW)?,
-                ..Default::default()
-            };
-            RegisterClassExW(&window_class);
-            let boxed = Box::new(State {
-                qt: self.clone(),
-                text,
-                appearance: *appearance,
-                icon: icon.map(|a| *a),
-                icon_position: icon_position.map(|a| *a),
-                shape: *shape,
-                size: *size,
-                mouse_event,
-            });
-            let scaling_factor = get_scaling_factor(parent_window);
-            CreateWindowExW(
-                WINDOW_EX_STYLE::default(),
-                class_name,
-                w!(""),
-                WS_TABSTOP | WS_VISIBLE | WS_CHILD,
-                x,
-                y,
-                (boxed.as_ref().get_min_width() * scaling_factor) as i32,
-                (boxed.as_ref().get_min_height() * scaling_factor) as i32,
-                Some(parent_window),
-                None,
-                Some(HINSTANCE(
-                    GetWindowLongPtrW(parent_window, GWLP_HINSTANCE) as _
-                )),
-                Some(Box::<State>::into_raw(boxed) as _),
-            )
+            let (text_format, secondary_text_format) = create_text_formats(&state)?;
+            let metrics = measure_text(&state, &text_format, secondary_text_format.as_ref())?;
+            Ok(measure(&state, &metrics))
         }
     }
+
+    /// A medium, rounded secondary button that shows only `icon`. Other looks take a
+    /// `ButtonOptions` with just an icon.
+    pub fn create_icon_button(
+        &self,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        icon: &Icon,
+        mouse_event: MouseEvent,
+    ) -> Result<ButtonHandle> {
+        self.create_button(
+            parent_window,
+            x,
+            y,
+            ButtonOptions {
+                icon: Some(icon.clone()),
+                mouse_event,
+                ..ButtonOptions::default()
+            },
+        )
+    }
 }
 
-unsafe fn set_svg_color(svg: &ID2D1SvgDocument, color: &D2D1_COLOR_F) -> Result<()> {
+fn create(parent_window: HWND, x: i32, y: i32, state: State) -> Result<ButtonHandle> {
+    let class_name = window_class_name(CLASS_NAME, Some(window_proc));
+    unsafe {
+        let window_class = WNDCLASSEXW {
+            cbSize: size_of::<WNDCLASSEXW>() as u32,
+            lpszClassName: PCWSTR(class_name.as_ptr()),
+            style: CS_CLASSDC,
+            lpfnWndProc: Some(window_proc),
+            hCursor: LoadCursorW(None, IDC_ARROW)?,
+            ..Default::default()
+        };
+        RegisterClassExW(&window_class);
+        let text = state.text;
+        let boxed = Box::new(state);
+        let dpi = Dpi::for_window(parent_window);
+        CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            &class_name,
+            // The label is also the window text, so DefWindowProc answers WM_GETTEXT with it.
+            text,
+            WS_TABSTOP | WS_VISIBLE | WS_CHILD,
+            x,
+            y,
+            dpi.to_physical(boxed.as_ref().get_min_width()),
+            dpi.to_physical(boxed.as_ref().get_min_height()),
+            Some(parent_window),
+            None,
+            Some(HINSTANCE(
+                GetWindowLongPtrW(parent_window, GWLP_HINSTANCE) as _
+            )),
+            Some(Box::<State>::into_raw(boxed) as _),
+        )
+        .map(ButtonHandle::new)
+    }
+}
+
+pub(crate) unsafe fn set_svg_color(svg: &ID2D1SvgDocument, color: &D2D1_COLOR_F) -> Result<()> {
     let svg_paint = svg.CreatePaint(D2D1_SVG_PAINT_TYPE_COLOR, Some(color), w!(""))?;
     svg.GetRoot()?
         .GetFirstChild()?
@@ -220,9 +469,10 @@ unsafe fn set_svg_color(svg: &ID2D1SvgDocument, color: &D2D1_COLOR_F) -> Result<
     Ok(())
 }
 
-unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+unsafe fn create_text_formats(
+    state: &State,
+) -> Result<(IDWriteTextFormat, Option<IDWriteTextFormat>)> {
     let tokens = &state.qt.theme.tokens;
-
     let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
     let font_size = match state.size {
         Size::Small => tokens.font_size_base200,
@@ -243,35 +493,42 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
         font_size,
         w!(""),
     )?;
-    text_format.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_CENTER)?;
+    text_format.SetTextAlignment(match state.alignment.unwrap_or(Alignment::Center) {
+        Alignment::Start => DWRITE_TEXT_ALIGNMENT_LEADING,
+        Alignment::Center => DWRITE_TEXT_ALIGNMENT_CENTER,
+        Alignment::End => DWRITE_TEXT_ALIGNMENT_TRAILING,
+    })?;
     text_format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+    let secondary_text_format = match state.secondary_text {
+        Some(_) => {
+            let secondary_text_format = state
+                .qt
+                .theme
+                .typography_styles
+                .caption1
+                .create_text_format(&direct_write_factory)?;
+            secondary_text_format.SetTextAlignment(text_format.GetTextAlignment())?;
+            Some(secondary_text_format)
+        }
+        None => None,
+    };
+    Ok((text_format, secondary_text_format))
+}
 
-    let factory = D2D1CreateFactory::<ID2D1Factory1>(
-        D2D1_FACTORY_TYPE_SINGLE_THREADED,
-        Some(&D2D1_FACTORY_OPTIONS::default()),
-    )?;
-    let dpi = GetDpiForWindow(window);
-    let render_target = factory.CreateHwndRenderTarget(
-        &D2D1_RENDER_TARGET_PROPERTIES {
-            dpiX: dpi as f32,
-            dpiY: dpi as f32,
-            ..Default::default()
-        },
-        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
-            hwnd: window,
-            pixelSize: D2D_SIZE_U {
-                width: state.get_min_width() as u32,
-                height: state.get_min_height() as u32,
-            },
-            presentOptions: Default::default(),
-        },
-    )?;
-    let stroke_style = factory
-        .CreateStrokeStyle(&D2D1_STROKE_STYLE_PROPERTIES1::default(), None)?
-        .cast::<ID2D1StrokeStyle>()?;
-    let svg_document = match state.icon {
+// The icon and the dropdown chevron, in the text color. Both belong to the render target.
+unsafe fn create_svg_documents(
+    state: &State,
+    render_target: &ID2D1HwndRenderTarget,
+) -> Result<(Option<ID2D1SvgDocument>, Option<ID2D1SvgDocument>)> {
+    let tokens = &state.qt.theme.tokens;
+    let color = match state.appearance {
+        Appearance::Primary => &tokens.color_neutral_foreground_on_brand,
+        Appearance::Transparent => &tokens.color_brand_foreground_link,
+        _ => &tokens.color_neutral_foreground1,
+    };
+    let icon_svg = match &state.icon {
         None => None,
-        Some(icon) => match SHCreateMemStream(Some(icon.svg.as_bytes())) {
+        Some(icon) => match SHCreateMemStream(Some(icon.svg_bytes())) {
             None => None,
             Some(svg_stream) => {
                 let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
@@ -282,28 +539,54 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
                         height: icon.size as f32,
                     },
                 )?;
-                let color = match state.appearance {
-                    Appearance::Primary => &tokens.color_neutral_foreground_on_brand,
-                    _ => &tokens.color_neutral_foreground1,
-                };
-                _ = set_svg_color(&svg, &color);
+                _ = set_svg_color(&svg, color);
                 Some(svg)
             }
         },
     };
+    let dropdown_svg = match state.dropdown_event {
+        None => None,
+        Some(_) => {
+            let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
+            let svg = create_svg_document(&device_context5, &Icon::chevron_down_regular())?;
+            _ = set_svg_color(&svg, color);
+            Some(svg)
+        }
+    };
+    Ok((icon_svg, dropdown_svg))
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let tokens = &state.qt.theme.tokens;
+    let (text_format, secondary_text_format) = create_text_formats(&state)?;
+
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        Some(&D2D1_FACTORY_OPTIONS::default()),
+    )?;
+    let dpi = Dpi::for_window(window);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U {
+                width: dpi.to_physical(state.get_min_width()) as u32,
+                height: dpi.to_physical(state.get_min_height()) as u32,
+            },
+            presentOptions: Default::default(),
+        },
+    )?;
+    let stroke_style = factory
+        .CreateStrokeStyle(&D2D1_STROKE_STYLE_PROPERTIES1::default(), None)?
+        .cast::<ID2D1StrokeStyle>()?;
+    let (svg_document, dropdown_svg) = create_svg_documents(&state, &render_target)?;
 
-    let animation_timer: IUIAnimationTimer =
-        CoCreateInstance(&UIAnimationTimer, None, CLSCTX_INPROC_SERVER)?;
     let transition_library: IUIAnimationTransitionLibrary2 =
         CoCreateInstance(&UIAnimationTransitionLibrary2, None, CLSCTX_INPROC_SERVER)?;
-    let animation_manager: IUIAnimationManager2 =
-        CoCreateInstance(&UIAnimationManager2, None, CLSCTX_INPROC_SERVER)?;
-    let timer_update_handler = animation_manager.cast::<IUIAnimationTimerUpdateHandler>()?;
-    animation_timer
-        .SetTimerUpdateHandler(&timer_update_handler, UI_ANIMATION_IDLE_BEHAVIOR_DISABLE)?;
+    let animation_manager = state.qt.animation_manager()?;
     let timer_event_handler: IUIAnimationTimerEventHandler =
         AnimationTimerEventHandler { window }.into();
-    animation_timer.SetTimerEventHandler(&timer_event_handler)?;
+    state.qt.register_animation(window, &timer_event_handler);
     let background_color = match state.appearance {
         Appearance::Primary => &tokens.color_brand_background,
         _ => &tokens.color_neutral_background1,
@@ -313,6 +596,11 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
         background_color.g as f64,
         background_color.b as f64,
     ])?;
+    let dropdown_background_color_variable = animation_manager.CreateAnimationVectorVariable(&[
+        background_color.r as f64,
+        background_color.g as f64,
+        background_color.b as f64,
+    ])?;
     let border_color = &tokens.color_neutral_stroke1;
     let border_color_variable = animation_manager.CreateAnimationVectorVariable(&[
         border_color.r as f64,
@@ -321,6 +609,7 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
     ])?;
     let text_color = match state.appearance {
         Appearance::Primary => &tokens.color_neutral_foreground_on_brand,
+        Appearance::Transparent => &tokens.color_brand_foreground_link,
         _ => &tokens.color_neutral_foreground1,
     };
     let text_color_variable = animation_manager.CreateAnimationVectorVariable(&[
@@ -330,80 +619,163 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
     ])?;
     let context = Context {
         state,
+        owned_text: None,
         text_format,
+        secondary_text_format,
+        text_metrics: Cell::new(None),
         render_target,
         icon_svg: svg_document,
         stroke_style,
         animation_manager,
-        animation_timer,
         transition_library,
         background_color_variable,
+        dropdown_background_color_variable,
         border_color_variable,
         text_color_variable,
+        dropdown_svg,
         mouse_within: false,
+        mouse_within_dropdown: false,
         mouse_clicking: false,
+        is_focused: false,
+        focus_visible: false,
     };
     Ok(context)
 }
 
-unsafe fn layout(window: HWND, context: &Context) -> Result<()> {
-    let state = &context.state;
-    let tokens = &context.state.qt.theme.tokens;
-
+unsafe fn measure_text(
+    state: &State,
+    text_format: &IDWriteTextFormat,
+    secondary_text_format: Option<&IDWriteTextFormat>,
+) -> Result<TextMetrics> {
     let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
     let text_layout = direct_write_factory.CreateTextLayout(
         state.text.as_wide(),
-        &context.text_format,
+        text_format,
         1000f32,
         500f32,
     )?;
     let mut metrics = DWRITE_TEXT_METRICS::default();
     text_layout.GetMetrics(&mut metrics)?;
+    let secondary_metrics = if let (Some(text), Some(text_format)) =
+        (state.secondary_text, secondary_text_format)
+    {
+        let secondary_layout =
+            direct_write_factory.CreateTextLayout(text.as_wide(), text_format, 1000f32, 500f32)?;
+        let mut secondary_metrics = DWRITE_TEXT_METRICS::default();
+        secondary_layout.GetMetrics(&mut secondary_metrics)?;
+        Some(secondary_metrics)
+    } else {
+        None
+    };
+    Ok(TextMetrics {
+        text: metrics,
+        secondary_text: secondary_metrics,
+    })
+}
+
+unsafe fn measure(state: &State, metrics: &TextMetrics) -> SizeDips {
+    let size = measure_content(state, metrics);
+    SizeDips {
+        width: state.get_min_width().max(size.width),
+        ..size
+    }
+}
+
+// The size of the content and its padding, before the minimum width applies.
+unsafe fn measure_content(state: &State, metrics: &TextMetrics) -> SizeDips {
+    let tokens = &state.qt.theme.tokens;
+    let mut text_width = metrics.text.width;
+    if let Some(secondary_metrics) = metrics.secondary_text {
+        text_width = text_width.max(secondary_metrics.width);
+    }
 
-    let scaling_factor = get_scaling_factor(window);
-    let icon_and_space_width = if state.has_icon() {
+    let icon_and_space_width = if state.is_icon_only() {
+        0f32
+    } else if state.has_icon() {
         state.get_desired_icon_spacing() + state.get_desired_icon_size()
     } else {
         0f32
     };
-    let horizontal_padding = state.get_horizontal_padding();
-    let scaled_width = ((state.get_min_width().max(
-        metrics.width
+    let horizontal_padding = if state.is_icon_only() {
+        0f32
+    } else {
+        state.get_horizontal_padding()
+    };
+    SizeDips {
+        width: text_width
             + 2f32 * tokens.stroke_width_thin
             + 2f32 * horizontal_padding
-            + icon_and_space_width,
-    )) * scaling_factor)
-        .ceil() as i32;
-    let scaled_height = ((state.get_line_height() * metrics.lineCount.max(1) as f32
-        + state.get_spacing() * 2f32
-        + tokens.stroke_width_thin * 2f32)
-        * scaling_factor)
-        .ceil() as i32;
+            + icon_and_space_width
+            + state.get_dropdown_width(),
+        height: state.get_line_height() * metrics.text.lineCount.max(1) as f32
+            + state.get_secondary_line_height()
+            + state.get_spacing() * 2f32
+            + tokens.stroke_width_thin * 2f32,
+    }
+}
+
+// A transparent button paints nothing beside its content, so with
+// `HitTestBehavior::TransparentOutsideContent` that space belongs to the parent.
+unsafe fn is_outside_content(window: HWND, context: &Context, l_param: LPARAM) -> Result<bool> {
+    let state = &context.state;
+    if state.hit_test != HitTestBehavior::TransparentOutsideContent
+        || state.appearance != Appearance::Transparent
+    {
+        return Ok(false);
+    }
+    let mut point = POINT {
+        x: l_param.0 as i16 as i32,
+        y: (l_param.0 >> 16) as i16 as i32,
+    };
+    _ = ScreenToClient(window, &mut point);
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    let size = measure_content(state, &context.get_text_metrics()?);
+    let content_width = Dpi::for_window(window)
+        .to_physical_ceil(size.width)
+        .min(rect.right);
+    let left = match state.alignment.unwrap_or(Alignment::Center) {
+        Alignment::Start => 0,
+        Alignment::Center => (rect.right - content_width) / 2,
+        Alignment::End => rect.right - content_width,
+    };
+    Ok(point.x < left || point.x >= left + content_width)
+}
 
+unsafe fn layout(window: HWND, context: &Context) -> Result<()> {
+    let size = measure(&context.state, &context.get_text_metrics()?);
+    let dpi = Dpi::for_window(window);
     SetWindowPos(
         window,
         None,
         0,
         0,
-        scaled_width,
-        scaled_height,
+        dpi.to_physical_ceil(size.width),
+        dpi.to_physical_ceil(size.height),
         SWP_NOMOVE | SWP_NOZORDER,
     )?;
+    on_size(window, context)
+}
+
+// Fits the render target and the window region to the client area, however it was resized.
+unsafe fn on_size(window: HWND, context: &Context) -> Result<()> {
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
     context.render_target.Resize(&D2D_SIZE_U {
-        width: scaled_width as u32,
-        height: scaled_height as u32,
+        width: rect.right as u32,
+        height: rect.bottom as u32,
     })?;
 
-    let corner_diameter = match &state.shape {
-        Shape::Circular => scaled_width.min(scaled_height),
-        Shape::Rounded => (tokens.border_radius_medium * 2f32 * scaling_factor) as i32,
-        Shape::Square => (tokens.border_radius_none * 2f32 * scaling_factor) as i32,
-    };
+    let corner_diameter = context.state.get_corner().to_physical_diameter(
+        Dpi::for_window(window),
+        rect.right,
+        rect.bottom,
+    );
     let region = CreateRoundRectRgn(
         0,
         0,
-        scaled_width + 1,
-        scaled_height + 1,
+        rect.right + 1,
+        rect.bottom + 1,
         corner_diameter,
         corner_diameter,
     );
@@ -422,9 +794,7 @@ impl IUIAnimationTimerEventHandler_Impl for AnimationTimerEventHandler_Impl {
     }
 
     fn OnPostUpdate(&self) -> Result<()> {
-        unsafe {
-            _ = InvalidateRect(Some(self.window), None, false);
-        }
+        invalidate_on_next_frame(self.window);
         Ok(())
     }
 
@@ -433,20 +803,35 @@ impl IUIAnimationTimerEventHandler_Impl for AnimationTimerEventHandler_Impl {
     }
 }
 
+unsafe fn get_text_width(context: &Context, max_width: f32) -> Result<f32> {
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_layout = direct_write_factory.CreateTextLayout(
+        context.state.text.as_wide(),
+        &context.text_format,
+        max_width.max(0f32),
+        500f32,
+    )?;
+    let mut metrics = DWRITE_TEXT_METRICS::default();
+    text_layout.GetMetrics(&mut metrics)?;
+    Ok(metrics.width)
+}
+
 unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
     let state = &context.state;
     let tokens = &state.qt.theme.tokens;
 
     let mut button_rect = RECT::default();
     GetClientRect(window, &mut button_rect)?;
-    let scaling_factor = get_scaling_factor(window);
-    let width = button_rect.right as f32 / scaling_factor;
-    let height = button_rect.bottom as f32 / scaling_factor;
-    let corner_radius = match state.shape {
-        Shape::Circular => width.min(height) / 2f32,
-        Shape::Rounded => tokens.border_radius_medium,
-        Shape::Square => tokens.border_radius_none,
-    };
+    let dpi = Dpi::for_window(window);
+    let width = dpi.to_dip(button_rect.right);
+    let height = dpi.to_dip(button_rect.bottom);
+    let dropdown_width = state.get_dropdown_width();
+    let content_width = width - dropdown_width;
+    let corner_radius = dpi.to_dip(state.get_corner().to_physical_diameter(
+        dpi,
+        button_rect.right,
+        button_rect.bottom,
+    )) / 2f32;
     let rounded_rect = D2D1_ROUNDED_RECT {
         rect: D2D_RECT_F {
             left: 0f32,
@@ -461,11 +846,16 @@ unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
     context
         .background_color_variable
         .GetVectorValue(&mut vector_variable)?;
-    let background_color = D2D1_COLOR_F {
-        r: vector_variable[0] as f32,
-        g: vector_variable[1] as f32,
-        b: vector_variable[2] as f32,
-        a: 1.0,
+    let background_color = if state.appearance == Appearance::Transparent {
+        // Transparent buttons blend into whatever the parent paints behind them.
+        get_parent_background_color(window, &tokens.color_neutral_background1)
+    } else {
+        D2D1_COLOR_F {
+            r: vector_variable[0] as f32,
+            g: vector_variable[1] as f32,
+            b: vector_variable[2] as f32,
+            a: 1.0,
+        }
     };
     let background_brush = context
         .render_target
@@ -473,8 +863,36 @@ unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
     context
         .render_target
         .FillRoundedRectangle(&rounded_rect, &background_brush);
+    if dropdown_width > 0f32 {
+        context
+            .dropdown_background_color_variable
+            .GetVectorValue(&mut vector_variable)?;
+        let dropdown_background_brush = context.render_target.CreateSolidColorBrush(
+            &D2D1_COLOR_F {
+                r: vector_variable[0] as f32,
+                g: vector_variable[1] as f32,
+                b: vector_variable[2] as f32,
+                a: 1.0,
+            },
+            None,
+        )?;
+        // Clipping the whole shape keeps the dropdown area's outer corners rounded.
+        context.render_target.PushAxisAlignedClip(
+            &D2D_RECT_F {
+                left: content_width,
+                top: 0f32,
+                right: width,
+                bottom: height,
+            },
+            D2D1_ANTIALIAS_MODE_ALIASED,
+        );
+        context
+            .render_target
+            .FillRoundedRectangle(&rounded_rect, &dropdown_background_brush);
+        context.render_target.PopAxisAlignedClip();
+    }
 
-    if let Appearance::Primary = state.appearance {
+    if let Appearance::Primary | Appearance::Transparent = state.appearance {
     } else {
         context
             .border_color_variable
@@ -522,11 +940,31 @@ unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
     let horizontal_padding = state.get_horizontal_padding();
     let top = spacing + tokens.stroke_width_thin;
     let left = horizontal_padding + tokens.stroke_width_thin;
-    let right = width - horizontal_padding - tokens.stroke_width_thin;
+    let right = content_width - horizontal_padding - tokens.stroke_width_thin;
     let bottom = height - spacing - tokens.stroke_width_thin;
+    let icon_size = state.get_desired_icon_size();
+    let icon_spacing = state.get_desired_icon_spacing();
+    let icon_position = state.icon_position.unwrap_or(IconPosition::Before);
+    // The icon sits at the edge of the content area, unless that would leave a gap between it and
+    // aligned text.
+    let icon_center = match (icon_position, state.alignment.unwrap_or(Alignment::Center)) {
+        (IconPosition::After, Alignment::Start) => {
+            left + get_text_width(context, right - left - icon_size - icon_spacing)?
+                + icon_spacing
+                + icon_size / 2f32
+        }
+        (IconPosition::Before, Alignment::End) => {
+            right
+                - get_text_width(context, right - left - icon_size - icon_spacing)?
+                - icon_spacing
+                - icon_size / 2f32
+        }
+        (IconPosition::Before, _) => left + icon_size / 2f32,
+        (IconPosition::After, _) => right - icon_size / 2f32,
+    };
     let text_rect = if state.has_icon() {
-        let icon_and_space_width = state.get_desired_icon_size() + state.get_desired_icon_spacing();
-        match state.icon_position.unwrap_or(IconPosition::Before) {
+        let icon_and_space_width = icon_size + icon_spacing;
+        match icon_position {
             IconPosition::Before => D2D_RECT_F {
                 left: left + icon_and_space_width,
                 top,
@@ -548,36 +986,140 @@ unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
             bottom,
         }
     };
-    context.render_target.DrawText(
-        state.text.as_wide(),
-        &context.text_format,
-        &text_rect,
-        &text_brush,
-        D2D1_DRAW_TEXT_OPTIONS_NONE,
-        DWRITE_MEASURING_MODE_NATURAL,
-    );
+    // A compound button keeps the bottom line of the text area for its secondary text.
+    let text_rect = if let (Some(text), Some(text_format)) =
+        (state.secondary_text, &context.secondary_text_format)
+    {
+        let secondary_top = text_rect.bottom - state.get_secondary_line_height();
+        let secondary_color = match state.appearance {
+            Appearance::Primary => &tokens.color_neutral_foreground_on_brand,
+            _ => &tokens.color_neutral_foreground3,
+        };
+        let secondary_brush = context
+            .render_target
+            .CreateSolidColorBrush(secondary_color, None)?;
+        context.render_target.DrawText(
+            text.as_wide(),
+            text_format,
+            &D2D_RECT_F {
+                top: secondary_top,
+                ..text_rect
+            },
+            &secondary_brush,
+            D2D1_DRAW_TEXT_OPTIONS_NONE,
+            DWRITE_MEASURING_MODE_NATURAL,
+        );
+        D2D_RECT_F {
+            bottom: secondary_top,
+            ..text_rect
+        }
+    } else {
+        text_rect
+    };
+    if state.appearance == Appearance::Transparent && context.mouse_within {
+        let direct_write_factory =
+            DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+        let text_layout = direct_write_factory.CreateTextLayout(
+            state.text.as_wide(),
+            &context.text_format,
+            text_rect.right - text_rect.left,
+            text_rect.bottom - text_rect.top,
+        )?;
+        text_layout.SetUnderline(
+            true,
+            DWRITE_TEXT_RANGE {
+                startPosition: 0,
+                length: state.text.len() as u32,
+            },
+        )?;
+        context.render_target.DrawTextLayout(
+            D2D_POINT_2F {
+                x: text_rect.left,
+                y: text_rect.top,
+            },
+            &text_layout,
+            &text_brush,
+            D2D1_DRAW_TEXT_OPTIONS_NONE,
+        );
+    } else {
+        context.render_target.DrawText(
+            state.text.as_wide(),
+            &context.text_format,
+            &text_rect,
+            &text_brush,
+            D2D1_DRAW_TEXT_OPTIONS_NONE,
+            DWRITE_MEASURING_MODE_NATURAL,
+        );
+    }
 
     if state.has_icon() {
         if let Some(svg) = &context.icon_svg {
             let device_context5 = context.render_target.cast::<ID2D1DeviceContext5>()?;
             let viewport_size = svg.GetViewportSize();
-            let desired_size = state.get_desired_icon_size();
-            match state.icon_position.unwrap_or(IconPosition::Before) {
-                IconPosition::Before => {
-                    device_context5.SetTransform(&Matrix3x2::translation(
-                        left + desired_size / 2f32 - viewport_size.width / 2f32,
-                        top / 2f32 + bottom / 2f32 - viewport_size.height / 2f32,
-                    ));
-                }
-                IconPosition::After => device_context5.SetTransform(&Matrix3x2::translation(
-                    right - desired_size / 2f32 - viewport_size.width / 2f32,
+            if state.is_icon_only() {
+                device_context5.SetTransform(&Matrix3x2::translation(
+                    content_width / 2f32 - viewport_size.width / 2f32,
+                    height / 2f32 - viewport_size.height / 2f32,
+                ));
+            } else {
+                device_context5.SetTransform(&Matrix3x2::translation(
+                    icon_center - viewport_size.width / 2f32,
                     top / 2f32 + bottom / 2f32 - viewport_size.height / 2f32,
-                )),
+                ));
             }
             device_context5.DrawSvgDocument(svg);
             device_context5.SetTransform(&Matrix3x2::identity());
         }
     }
+
+    if let Some(svg) = &context.dropdown_svg {
+        let divider_brush = context
+            .render_target
+            .CreateSolidColorBrush(&tokens.color_neutral_stroke1, None)?;
+        context.render_target.DrawLine(
+            D2D_POINT_2F {
+                x: content_width,
+                y: 0f32,
+            },
+            D2D_POINT_2F {
+                x: content_width,
+                y: height,
+            },
+            &divider_brush,
+            tokens.stroke_width_thin,
+            &context.stroke_style,
+        );
+        let device_context5 = context.render_target.cast::<ID2D1DeviceContext5>()?;
+        let viewport_size = svg.GetViewportSize();
+        device_context5.SetTransform(&Matrix3x2::translation(
+            content_width + dropdown_width / 2f32 - viewport_size.width / 2f32,
+            height / 2f32 - viewport_size.height / 2f32,
+        ));
+        device_context5.DrawSvgDocument(svg);
+        device_context5.SetTransform(&Matrix3x2::identity());
+    }
+
+    if context.is_focused && context.focus_visible {
+        let focus_brush = context
+            .render_target
+            .CreateSolidColorBrush(&tokens.color_stroke_focus2, None)?;
+        let focus_width = tokens.stroke_width_thin * 2f32;
+        context.render_target.DrawRoundedRectangle(
+            &D2D1_ROUNDED_RECT {
+                rect: D2D_RECT_F {
+                    left: focus_width * 0.5,
+                    top: focus_width * 0.5,
+                    right: width - focus_width * 0.5,
+                    bottom: height - focus_width * 0.5,
+                },
+                radiusX: corner_radius,
+                radiusY: corner_radius,
+            },
+            &focus_brush,
+            focus_width,
+            &context.stroke_style,
+        );
+    }
     Ok(())
 }
 
@@ -593,17 +1135,20 @@ unsafe fn on_paint(window: HWND, context: &Context) -> Result<()> {
     }
 }
 
-unsafe fn change_color(context: &Context) -> Result<()> {
-    let tokens = &context.state.qt.theme.tokens;
-    let storyboard = context.animation_manager.CreateStoryboard()?;
-
-    let appearance = &context.state.appearance;
-    let background_color = if context.mouse_clicking {
+fn get_background_color(
+    tokens: &Tokens,
+    appearance: Appearance,
+    clicking: bool,
+    within: bool,
+) -> &D2D1_COLOR_F {
+    if appearance == Appearance::Transparent {
+        &tokens.color_neutral_background1
+    } else if clicking {
         match appearance {
             Appearance::Primary => &tokens.color_brand_background_pressed,
             _ => &tokens.color_neutral_background1_pressed,
         }
-    } else if context.mouse_within {
+    } else if within {
         match appearance {
             Appearance::Primary => &tokens.color_brand_background_hover,
             _ => &tokens.color_neutral_background1_hover,
@@ -613,27 +1158,51 @@ unsafe fn change_color(context: &Context) -> Result<()> {
             Appearance::Primary => &tokens.color_brand_background,
             _ => &tokens.color_neutral_background1,
         }
-    };
-    let background_color_transition = context
-        .transition_library
-        .CreateCubicBezierLinearVectorTransition(
-            tokens.duration_faster,
-            &[
-                background_color.r as f64,
-                background_color.g as f64,
-                background_color.b as f64,
-            ],
-            tokens.curve_easy_ease[0],
-            tokens.curve_easy_ease[1],
-            tokens.curve_easy_ease[2],
-            tokens.curve_easy_ease[3],
-        )?;
-    storyboard.AddTransition(
-        &context.background_color_variable,
-        &background_color_transition,
-    )?;
+    }
+}
+
+unsafe fn change_color(window: HWND, context: &Context) -> Result<()> {
+    let tokens = &context.state.qt.theme.tokens;
+    let curves = context.state.qt.animation_curves().easy_ease;
+    let storyboard = context.animation_manager.CreateStoryboard()?;
+
+    let appearance = &context.state.appearance;
+    // The main area and the dropdown area of a split button are hovered and pressed separately.
+    let mouse_within = context.mouse_within && !context.mouse_within_dropdown;
+    let mouse_clicking = context.mouse_clicking && !context.mouse_within_dropdown;
+    let dropdown_within = context.mouse_within && context.mouse_within_dropdown;
+    let dropdown_clicking = context.mouse_clicking && context.mouse_within_dropdown;
+    for (variable, clicking, within) in [
+        (
+            &context.background_color_variable,
+            mouse_clicking,
+            mouse_within,
+        ),
+        (
+            &context.dropdown_background_color_variable,
+            dropdown_clicking,
+            dropdown_within,
+        ),
+    ] {
+        let background_color = get_background_color(tokens, *appearance, clicking, within);
+        let background_color_transition = context
+            .transition_library
+            .CreateCubicBezierLinearVectorTransition(
+                context.state.qt.animation_durations().fast,
+                &[
+                    background_color.r as f64,
+                    background_color.g as f64,
+                    background_color.b as f64,
+                ],
+                curves[0],
+                curves[1],
+                curves[2],
+                curves[3],
+            )?;
+        storyboard.AddTransition(variable, &background_color_transition)?;
+    }
 
-    if let Appearance::Primary = appearance {
+    if let Appearance::Primary | Appearance::Transparent = appearance {
     } else {
         let border_color = if context.mouse_clicking {
             &tokens.color_neutral_stroke1_pressed
@@ -645,26 +1214,35 @@ unsafe fn change_color(context: &Context) -> Result<()> {
         let border_color_transition = context
             .transition_library
             .CreateCubicBezierLinearVectorTransition(
-                tokens.duration_faster,
+                context.state.qt.animation_durations().fast,
                 &[
                     border_color.r as f64,
                     border_color.g as f64,
                     border_color.b as f64,
                 ],
-                tokens.curve_easy_ease[0],
-                tokens.curve_easy_ease[1],
-                tokens.curve_easy_ease[2],
-                tokens.curve_easy_ease[3],
+                curves[0],
+                curves[1],
+                curves[2],
+                curves[3],
             )?;
         storyboard.AddTransition(&context.border_color_variable, &border_color_transition)?;
     }
 
     let text_color = match appearance {
         Appearance::Primary => &tokens.color_neutral_foreground_on_brand,
+        Appearance::Transparent => {
+            if mouse_clicking {
+                &tokens.color_brand_foreground_link_pressed
+            } else if mouse_within {
+                &tokens.color_brand_foreground_link_hover
+            } else {
+                &tokens.color_brand_foreground_link
+            }
+        }
         _ => {
-            if context.mouse_clicking {
+            if mouse_clicking {
                 &tokens.color_neutral_foreground1_pressed
-            } else if context.mouse_within {
+            } else if mouse_within {
                 &tokens.color_neutral_foreground1_hover
             } else {
                 &tokens.color_neutral_foreground1
@@ -674,21 +1252,20 @@ unsafe fn change_color(context: &Context) -> Result<()> {
     let text_color_transition = context
         .transition_library
         .CreateCubicBezierLinearVectorTransition(
-            tokens.duration_faster,
+            context.state.qt.animation_durations().fast,
             &[
                 text_color.r as f64,
                 text_color.g as f64,
                 text_color.b as f64,
             ],
-            tokens.curve_easy_ease[0],
-            tokens.curve_easy_ease[1],
-            tokens.curve_easy_ease[2],
-            tokens.curve_easy_ease[3],
+            curves[0],
+            curves[1],
+            curves[2],
+            curves[3],
         )?;
     storyboard.AddTransition(&context.text_color_variable, &text_color_transition)?;
 
-    let seconds_now = context.animation_timer.GetTime()?;
-    storyboard.Schedule(seconds_now, None)
+    context.state.qt.schedule_storyboard(window, &storyboard)
 }
 
 unsafe fn on_mouse_enter(window: &HWND, context: &Context) -> Result<()> {
@@ -699,27 +1276,55 @@ unsafe fn on_mouse_enter(window: &HWND, context: &Context) -> Result<()> {
         dwHoverTime: 0,
     };
     TrackMouseEvent(&mut tme)?;
-    _ = change_color(context);
+    _ = change_color(*window, context);
     Ok(())
 }
 
-unsafe fn on_mouse_leave(context: &Context) -> Result<()> {
-    _ = change_color(context);
+unsafe fn on_mouse_leave(window: HWND, context: &Context) -> Result<()> {
+    _ = change_color(window, context);
     Ok(())
 }
 
 unsafe fn on_mouse_click(window: &HWND, context: &Context) -> Result<()> {
     (context.state.mouse_event.on_click)(window);
-    _ = change_color(context);
+    _ = change_color(*window, context);
+    Ok(())
+}
+
+unsafe fn on_dropdown(window: &HWND, context: &Context) -> Result<()> {
+    if let Some(dropdown_event) = &context.state.dropdown_event {
+        (dropdown_event.on_click)(window);
+    }
+    _ = change_color(*window, context);
     Ok(())
 }
 
+// Tracks which area of a split button the mouse is over, so each can show its own hover state.
+unsafe fn update_mouse_within_dropdown(window: HWND, context: &mut Context, mouse_x: i32) {
+    let dropdown_width = context.state.get_dropdown_width();
+    if dropdown_width == 0f32 {
+        return;
+    }
+    let mut rect = RECT::default();
+    _ = GetClientRect(window, &mut rect);
+    let dpi = Dpi::for_window(window);
+    let within_dropdown = mouse_x >= rect.right - dpi.to_physical(dropdown_width);
+    if context.mouse_within_dropdown != within_dropdown {
+        context.mouse_within_dropdown = within_dropdown;
+        _ = change_color(window, context);
+    }
+}
+
 extern "system" fn window_proc(
     window: HWND,
     message: u32,
     w_param: WPARAM,
     l_param: LPARAM,
 ) -> LRESULT {
+    if let Some(result) = unsafe { handle_disabled_mouse(window, message) } {
+        return result;
+    }
+    track_input_kind(message);
     match message {
         WM_CREATE => unsafe {
             let cs = l_param.0 as *const CREATESTRUCTW;
@@ -735,25 +1340,49 @@ extern "system" fn window_proc(
                 Err(_) => LRESULT(FALSE.0 as isize),
             }
         },
+        WM_NCHITTEST => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() && is_outside_content(window, &*raw, l_param).unwrap_or(false) {
+                return LRESULT(HTTRANSPARENT as isize);
+            }
+            DefWindowProcW(window, message, w_param, l_param)
+        },
         WM_DESTROY => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            _ = Box::<Context>::from_raw(raw);
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                let context = Box::<Context>::from_raw(raw);
+                context.state.qt.unregister_animation(window);
+            }
             LRESULT(0)
         },
         WM_PAINT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            let context = &*raw;
+            let context = &mut *raw;
             let mut ps = PAINTSTRUCT::default();
             BeginPaint(window, &mut ps);
-            _ = on_paint(window, context);
+            let result = on_paint(window, context);
             _ = EndPaint(window, &ps);
+            recover_lost_device(window, &result, || {
+                context.recreate_device_resources(window)
+            });
             LRESULT(0)
         },
         WM_PRINTCLIENT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &*raw;
-            _ = on_paint(window, context);
-            DefWindowProcW(window, message, w_param, l_param)
+            let dc = HDC(w_param.0 as _);
+            _ = print_client(window, &context.render_target, dc, || {
+                paint(window, context)
+            });
+            LRESULT(0)
+        },
+        WM_SIZE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                _ = on_size(window, &*raw);
+            }
+            LRESULT(0)
         },
         WM_DPICHANGED_BEFOREPARENT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
@@ -761,14 +1390,16 @@ extern "system" fn window_proc(
             _ = layout(window, &context);
             let new_dpi = GetDpiForWindow(window);
             context.render_target.SetDpi(new_dpi as f32, new_dpi as f32);
+            _ = change_color(window, context);
             _ = InvalidateRect(Some(window), None, false);
             LRESULT(0)
         },
         WM_MOUSEMOVE => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            update_mouse_within_dropdown(window, &mut *raw, l_param.0 as i16 as i32);
             let context = &*raw;
-            match context.state.shape {
-                Shape::Square => {
+            match context.state.get_corner() {
+                CornerRadius::Dips(radius) if radius <= 0f32 => {
                     if !(*raw).mouse_within {
                         (*raw).mouse_within = true;
                         let _ = on_mouse_enter(&window, context);
@@ -778,8 +1409,11 @@ extern "system" fn window_proc(
                     let mouse_x = l_param.0 as i16 as i32;
                     let mouse_y = (l_param.0 >> 16) as i16 as i32;
                     let region = CreateRectRgn(0, 0, 0, 0);
-                    GetWindowRgn(window, region);
-                    if PtInRegion(region, mouse_x, mouse_y).into() {
+                    // Before the first layout there is no region yet, and the whole window counts.
+                    let is_inside = GetWindowRgn(window, region) == RGN_ERROR
+                        || PtInRegion(region, mouse_x, mouse_y).as_bool();
+                    _ = DeleteObject(region.into());
+                    if is_inside {
                         if !(*raw).mouse_within {
                             (*raw).mouse_within = true;
                             let _ = on_mouse_enter(&window, context);
@@ -788,10 +1422,9 @@ extern "system" fn window_proc(
                         if (*raw).mouse_within {
                             (*raw).mouse_within = false;
                             (*raw).mouse_clicking = false;
-                            let _ = on_mouse_leave(context);
+                            let _ = on_mouse_leave(window, context);
                         }
                     }
-                    _ = DeleteObject(region.into());
                 }
             }
             LRESULT(0)
@@ -801,23 +1434,339 @@ extern "system" fn window_proc(
             let context = &*raw;
             (*raw).mouse_within = false;
             (*raw).mouse_clicking = false;
-            let _ = on_mouse_leave(context);
+            let _ = on_mouse_leave(window, context);
             LRESULT(0)
         },
         WM_LBUTTONDOWN => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &*raw;
             (*raw).mouse_clicking = true;
-            let _ = change_color(context);
+            _ = SetFocus(Some(window));
+            let _ = change_color(window, context);
             LRESULT(0)
         },
+        WM_SETTEXT => unsafe {
+            let result = DefWindowProcW(window, message, w_param, l_param);
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return result;
+            }
+            let context = &mut *raw;
+            let text = HSTRING::from_wide(PCWSTR(l_param.0 as *const u16).as_wide());
+            context.state.text = PCWSTR(text.as_ptr());
+            context.owned_text = Some(text);
+            context.text_metrics.set(None);
+            _ = layout(window, context);
+            _ = InvalidateRect(Some(window), None, false);
+            result
+        },
+        WM_SETFOCUS => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            // The focus ring is only for keyboard users.
+            (*raw).is_focused = true;
+            (*raw).focus_visible = is_focus_visible();
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_KILLFOCUS => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            (*raw).is_focused = false;
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_KEYDOWN => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            match VIRTUAL_KEY(w_param.0 as u16) {
+                VK_RETURN => {
+                    (*raw).focus_visible = true;
+                    let _ = on_mouse_click(&window, context);
+                }
+                VK_SPACE if !context.mouse_clicking => {
+                    (*raw).focus_visible = true;
+                    (*raw).mouse_clicking = true;
+                    let _ = change_color(window, context);
+                }
+                _ => return DefWindowProcW(window, message, w_param, l_param),
+            }
+            LRESULT(0)
+        },
+        WM_KEYUP => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            if VIRTUAL_KEY(w_param.0 as u16) == VK_SPACE && context.mouse_clicking {
+                (*raw).mouse_clicking = false;
+                let _ = on_mouse_click(&window, context);
+                return LRESULT(0);
+            }
+            DefWindowProcW(window, message, w_param, l_param)
+        },
+        WM_SETCURSOR => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null()
+                && (*raw).state.appearance == Appearance::Transparent
+                && (l_param.0 & 0xffff) as u32 == HTCLIENT
+            {
+                if let Ok(cursor) = LoadCursorW(None, IDC_HAND) {
+                    SetCursor(Some(cursor));
+                    return LRESULT(TRUE.0 as isize);
+                }
+            }
+            DefWindowProcW(window, message, w_param, l_param)
+        },
         WM_LBUTTONUP => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &*raw;
             (*raw).mouse_clicking = false;
-            let _ = on_mouse_click(&window, context);
+            if context.mouse_within_dropdown {
+                let _ = on_dropdown(&window, context);
+            } else {
+                let _ = on_mouse_click(&window, context);
+            }
             LRESULT(0)
         },
+        WM_SYSKEYDOWN => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            // Alt+Down opens the dropdown, as it does for a combo box.
+            if context.state.dropdown_event.is_some()
+                && VIRTUAL_KEY(w_param.0 as u16) == VK_DOWN
+                && GetKeyState(VK_MENU.0 as i32) < 0
+            {
+                (*raw).focus_visible = true;
+                let _ = on_dropdown(&window, context);
+                return LRESULT(0);
+            }
+            DefWindowProcW(window, message, w_param, l_param)
+        },
         _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use windows::Win32::Graphics::Gdi::GetUpdateRect;
+    use windows::Win32::UI::Input::KeyboardAndMouse::EnableWindow;
+
+    use super::*;
+    use crate::animation::{AnimationMode, TokenOverrides};
+    use crate::force_device_lost;
+    use crate::test_support::{
+        click, client_size, is_near, pump_messages, rgb, window_at, window_text, Capture, Host,
+    };
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn window_text_is_the_label() {
+        let host = Host::new(300, 100);
+        let window = host.create::<Button>(
+            10,
+            10,
+            ButtonOptions {
+                text: w!("Save"),
+                ..ButtonOptions::default()
+            },
+        );
+        assert_eq!(window_text(window), "Save");
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn external_resize_paints_the_new_area() {
+        let host = Host::new(400, 200);
+        let window = host.create::<Button>(10, 10, ButtonOptions::default());
+        unsafe {
+            SetWindowPos(window, None, 0, 0, 300, 80, SWP_NOMOVE | SWP_NOZORDER).unwrap();
+        }
+        pump_messages();
+        let context = unsafe { &*(GetWindowLongPtrW(window, GWLP_USERDATA) as *const Context) };
+        let size = unsafe { context.render_target.GetPixelSize() };
+        assert_eq!((size.width, size.height), (300, 80));
+        // Right of the centered text, in what used to be outside the button.
+        let background = rgb(&host.qt.theme.tokens.color_neutral_background1);
+        let capture = Capture::of(window);
+        assert!(is_near(capture.pixel(280, 40), background, 0x08));
+        assert_eq!(window_at(window, 280, 40), window);
+        assert_eq!(window_at(window, 150, 75), window);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn hover_snaps_with_zero_durations() {
+        let host = Host::new(300, 100);
+        host.qt.set_animation_mode(AnimationMode::Manual).unwrap();
+        host.qt
+            .override_tokens(&TokenOverrides::default().scale_durations(0.0));
+        let window = host.create::<Button>(10, 10, ButtonOptions::default());
+        let (_, height) = client_size(window);
+        unsafe {
+            SendMessageW(
+                window,
+                WM_MOUSEMOVE,
+                None,
+                Some(LPARAM(((height / 2) << 16 | 6) as isize)),
+            );
+        }
+        host.qt.tick_animations(1.0 / 60.0).unwrap();
+        pump_messages();
+        let hover = rgb(&host.qt.theme.tokens.color_neutral_background1_hover);
+        assert!(is_near(
+            Capture::of(window).pixel(6, height / 2),
+            hover,
+            0x04
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn paint_after_lost_device_succeeds() {
+        let host = Host::new(300, 100);
+        let window = host.create::<Button>(10, 10, ButtonOptions::default());
+        pump_messages();
+        let context = unsafe { &*(GetWindowLongPtrW(window, GWLP_USERDATA) as *const Context) };
+        let lost_render_target = context.render_target.clone();
+        force_device_lost();
+        unsafe {
+            _ = InvalidateRect(Some(window), None, false);
+        }
+        // The first paint counts as lost and queues the second, which draws with a new target.
+        pump_messages();
+        assert_ne!(context.render_target, lost_render_target);
+        assert!(unsafe { on_paint(window, context) }.is_ok());
+        assert!(!unsafe { GetUpdateRect(window, None, false) }.as_bool());
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn click_on_disabled_button_reaches_parent() {
+        let clicked = Rc::new(Cell::new(0));
+        let host = Host::new(300, 100);
+        let window = host.create::<Button>(
+            10,
+            10,
+            ButtonOptions {
+                text: w!("Disabled"),
+                mouse_event: MouseEvent {
+                    on_click: Box::new({
+                        let clicked = clicked.clone();
+                        move |_| clicked.set(clicked.get() + 1)
+                    }),
+                },
+                ..ButtonOptions::default()
+            },
+        );
+        let (width, height) = client_size(window);
+        unsafe {
+            _ = EnableWindow(window, false);
+        }
+        assert_eq!(window_at(window, width / 2, height / 2), host.window);
+        let before = host.clicks();
+        click(window, width / 2, height / 2);
+        assert_eq!(host.clicks(), before + 1);
+        assert_eq!(clicked.get(), 0);
+
+        unsafe {
+            _ = EnableWindow(window, true);
+        }
+        click(window, width / 2, height / 2);
+        assert_eq!(host.clicks(), before + 1);
+        assert_eq!(clicked.get(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn disabled_button_shows_the_arrow_cursor() {
+        let host = Host::new(300, 100);
+        let window = host.create::<Button>(10, 10, ButtonOptions::default());
+        unsafe {
+            _ = EnableWindow(window, false);
+            SendMessageW(
+                window,
+                WM_SETCURSOR,
+                Some(WPARAM(window.0 as usize)),
+                Some(LPARAM(HTCLIENT as isize)),
+            );
+            assert_eq!(GetCursor(), LoadCursorW(None, IDC_ARROW).unwrap());
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn corner_pixel_of_rounded_button_reaches_parent() {
+        let host = Host::new(300, 100);
+        let window = host.create::<Button>(
+            10,
+            10,
+            ButtonOptions {
+                text: w!("Corner"),
+                shape: Shape::Circular,
+                ..ButtonOptions::default()
+            },
+        );
+        let (width, height) = client_size(window);
+        assert_eq!(window_at(window, 0, 0), host.window);
+        assert_eq!(window_at(window, width - 1, height - 1), host.window);
+        assert_eq!(window_at(window, width / 2, height / 2), window);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn space_beside_transparent_content_reaches_parent() {
+        let host = Host::new(300, 100);
+        let options = |hit_test| ButtonOptions {
+            text: w!("A"),
+            appearance: Appearance::Transparent,
+            alignment: Some(Alignment::Start),
+            hit_test,
+            ..ButtonOptions::default()
+        };
+        // The minimum width leaves empty space after the short start-aligned text.
+        let transparent =
+            host.create::<Button>(10, 10, options(HitTestBehavior::TransparentOutsideContent));
+        let (width, height) = client_size(transparent);
+        assert_eq!(window_at(transparent, width - 4, height / 2), host.window);
+        assert_eq!(window_at(transparent, 4, height / 2), transparent);
+
+        let normal = host.create::<Button>(10, 60, options(HitTestBehavior::Normal));
+        assert_eq!(window_at(normal, width - 4, height / 2), normal);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn measurement_matches_created_size() {
+        let host = Host::new(600, 200);
+        let icon = Icon::calendar_month_regular();
+        let cases = [
+            (w!("OK"), Size::Medium, None, Shape::Rounded),
+            (
+                w!("A label longer than the minimum width"),
+                Size::Small,
+                None,
+                Shape::Square,
+            ),
+            (w!("Pick a date"), Size::Large, Some(&icon), Shape::Circular),
+            (w!(""), Size::Medium, Some(&icon), Shape::Rounded),
+        ];
+        let dpi = Dpi::for_window(host.window);
+        for (text, size, icon, shape) in cases {
+            let measured = host.qt.measure_button(text, &size, icon, &shape).unwrap();
+            let options = ButtonOptions {
+                text,
+                size,
+                icon: icon.cloned(),
+                shape,
+                ..ButtonOptions::default()
+            };
+            let window = host.create::<Button>(0, 0, options);
+            assert_eq!(
+                client_size(window),
+                (
+                    dpi.to_physical_ceil(measured.width),
+                    dpi.to_physical_ceil(measured.height)
+                )
+            );
+        }
+    }
+}
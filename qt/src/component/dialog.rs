@@ -1,54 +1,190 @@
-use std::mem::size_of;
+use std::fmt::{self, Display, Formatter};
+use std::mem::{replace, size_of};
 
 use windows::core::*;
 use windows::Win32::Foundation::*;
-use windows::Win32::Graphics::Direct2D::Common::{D2D_RECT_F, D2D_SIZE_U};
+use windows::Win32::Graphics::Direct2D::Common::{D2D_POINT_2F, D2D_RECT_F, D2D_SIZE_U};
 use windows::Win32::Graphics::Direct2D::{
     D2D1CreateFactory, ID2D1Factory1, ID2D1HwndRenderTarget, D2D1_DRAW_TEXT_OPTIONS_NONE,
     D2D1_FACTORY_OPTIONS, D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_HWND_RENDER_TARGET_PROPERTIES,
-    D2D1_RENDER_TARGET_PROPERTIES,
 };
 use windows::Win32::Graphics::DirectWrite::{
-    DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, DWRITE_FACTORY_TYPE_SHARED,
-    DWRITE_MEASURING_MODE_NATURAL, DWRITE_TEXT_METRICS,
+    DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, IDWriteTextLayout,
+    DWRITE_FACTORY_TYPE_SHARED, DWRITE_HIT_TEST_METRICS, DWRITE_MEASURING_MODE_NATURAL,
+    DWRITE_TEXT_METRICS, DWRITE_TEXT_RANGE,
 };
-use windows::Win32::Graphics::Gdi::{BeginPaint, EndPaint, InvalidateRect, PAINTSTRUCT};
-use windows::Win32::UI::HiDpi::{AdjustWindowRectExForDpi, GetDpiForWindow};
+use windows::Win32::Graphics::Dwm::{
+    DWMWCP_DONOTROUND, DWMWCP_ROUND, DWMWCP_ROUNDSMALL, DWM_WINDOW_CORNER_PREFERENCE,
+};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, ClientToScreen, EndPaint, GetStockObject, InvalidateRect, ScreenToClient,
+    BLACK_BRUSH, HBRUSH, HDC, PAINTSTRUCT,
+};
+use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+use windows::Win32::UI::Animation::{
+    IUIAnimationTimerEventHandler, IUIAnimationTimerEventHandler_Impl,
+    IUIAnimationTransitionLibrary2, IUIAnimationVariable2, UIAnimationTransitionLibrary2,
+};
+use windows::Win32::UI::HiDpi::AdjustWindowRectExForDpi;
 use windows::Win32::UI::Input::KeyboardAndMouse::{EnableWindow, SetActiveWindow};
 use windows::Win32::UI::WindowsAndMessaging::*;
 use windows_version::OsVersion;
 
-use crate::component::button;
-use crate::{get_scaling_factor, MouseEvent, QT};
+use crate::component::button::{self, ButtonOptions};
+use crate::shadow::{self, Elevation};
+use crate::theme::Tokens;
+use crate::{
+    apply_corner_preference, apply_title_bar_theme, print_client, recover_lost_device,
+    recreate_render_target, render_target_properties, window_class_name, CornerRadius, Dpi,
+    MouseEvent, SizeDips, QT,
+};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DialogResult {
     OK,
     Cancel,
     Close,
 }
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum ModelType {
     Modal,
     Alert,
 }
 
+impl Display for DialogResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DialogResult::OK => "OK",
+            DialogResult::Cancel => "Cancel",
+            DialogResult::Close => "Close",
+        })
+    }
+}
+
+pub enum ContentSegment {
+    Text(PCWSTR),
+    Link(PCWSTR, MouseEvent),
+}
+
+#[derive(Default)]
+pub struct DialogOptions {
+    /// Lets the user resize the dialog, though never below what its content needs.
+    pub resizable: bool,
+    pub max_width: Option<f32>,
+    pub max_height: Option<f32>,
+    /// Overrides the rounded corners of the dialog. DWM rounds the window itself and only knows
+    /// a few radii, so the nearest one is used, and nothing is rounded before Windows 11.
+    pub corner: Option<CornerRadius>,
+}
+
 struct State {
     qt: QT,
     title: PCWSTR,
-    content: PCWSTR,
+    content: Vec<ContentSegment>,
+    options: DialogOptions,
+    is_alert: bool,
 }
 
 struct Context {
     state: State,
     result: DialogResult,
     title_text_format: IDWriteTextFormat,
-    content_text_format: IDWriteTextFormat,
+    content_text_layout: IDWriteTextLayout,
+    links: Vec<(DWRITE_TEXT_RANGE, MouseEvent)>,
     render_target: ID2D1HwndRenderTarget,
     ok_button: HWND,
     cancel_button: HWND,
+    shadow: HWND,
+    /// The radius DWM rounds the window with, in DIPs, which the shadow follows.
+    corner_radius: f32,
+}
+
+impl Context {
+    // The text brush is created on every paint, but the links keep the brush they were given.
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        apply_link_brush(
+            &render_target,
+            &self.state.qt.theme.tokens,
+            &self.content_text_layout,
+            &self.links,
+        )?;
+        self.render_target = render_target;
+        Ok(())
+    }
+}
+
+// Links are drawn with a brush of the render target attached to their text ranges.
+unsafe fn apply_link_brush(
+    render_target: &ID2D1HwndRenderTarget,
+    tokens: &Tokens,
+    content_text_layout: &IDWriteTextLayout,
+    links: &[(DWRITE_TEXT_RANGE, MouseEvent)],
+) -> Result<()> {
+    let link_brush =
+        render_target.CreateSolidColorBrush(&tokens.color_brand_foreground_link, None)?;
+    for (range, _) in links {
+        content_text_layout.SetDrawingEffect(&link_brush, *range)?;
+    }
+    Ok(())
 }
+
+const SCRIM_OPACITY: f64 = 0.4;
+const DRAG_AREA_HEIGHT: f32 = 32f32;
+const MAX_WIDTH: f32 = 600f32;
+
+struct ScrimContext {
+    qt: QT,
+    opacity_variable: IUIAnimationVariable2,
+}
+
 impl QT {
+    /// Measures the client area of a dialog with this title and content without opening it. The
+    /// content wraps so that the dialog is never wider than `max_width`; dialogs use 600 DIPs.
+    pub fn measure_dialog(
+        &self,
+        title: PCWSTR,
+        content: PCWSTR,
+        max_width: f32,
+    ) -> Result<SizeDips> {
+        unsafe {
+            let direct_write_factory =
+                DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+            let typography_styles = &self.theme.typography_styles;
+            let title_text_format = typography_styles
+                .subtitle1
+                .create_text_format(&direct_write_factory)?;
+            let content_text_layout = direct_write_factory.CreateTextLayout(
+                content.as_wide(),
+                &typography_styles
+                    .body1
+                    .create_text_format(&direct_write_factory)?,
+                max_width,
+                1000f32,
+            )?;
+            let ok_button_size = self.measure_button(
+                w!("OK"),
+                &button::Size::Medium,
+                None,
+                &button::Shape::Rounded,
+            )?;
+            let cancel_button_size = self.measure_button(
+                w!("Cancel"),
+                &button::Size::Medium,
+                None,
+                &button::Shape::Rounded,
+            )?;
+            measure(
+                title,
+                &title_text_format,
+                &content_text_layout,
+                max_width,
+                ok_button_size.height.max(cancel_button_size.height),
+            )
+        }
+    }
+
     pub fn open_dialog(
         &self,
         parent_window: HWND,
@@ -56,37 +192,79 @@ impl QT {
         content: PCWSTR,
         modal_type: &ModelType,
     ) -> Result<DialogResult> {
-        let class_name: PCWSTR = w!("QT_DIALOG");
+        self.open_dialog_with_content(
+            parent_window,
+            title,
+            vec![ContentSegment::Text(content)],
+            modal_type,
+        )
+    }
+
+    pub fn open_dialog_with_content(
+        &self,
+        parent_window: HWND,
+        title: PCWSTR,
+        content: Vec<ContentSegment>,
+        modal_type: &ModelType,
+    ) -> Result<DialogResult> {
+        self.open_dialog_with_options(
+            parent_window,
+            title,
+            content,
+            modal_type,
+            DialogOptions::default(),
+        )
+    }
+
+    pub fn open_dialog_with_options(
+        &self,
+        parent_window: HWND,
+        title: PCWSTR,
+        content: Vec<ContentSegment>,
+        modal_type: &ModelType,
+        options: DialogOptions,
+    ) -> Result<DialogResult> {
+        let class_name = window_class_name("QT_DIALOG", Some(window_proc));
         unsafe {
             let window_class = WNDCLASSEXW {
                 cbSize: size_of::<WNDCLASSEXW>() as u32,
-                lpszClassName: class_name,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
                 style: CS_OWNDC,
                 lpfnWndProc: Some(window_proc),
                 hCursor: LoadCursorW(None, IDC_ARROW)?,
                 ..Default::default()
             };
             RegisterClassExW(&window_class);
-            let scaling_factor = get_scaling_factor(parent_window);
+            let dpi = Dpi::for_window(parent_window);
             _ = EnableWindow(parent_window, false);
+            let scrim = if IsIconic(parent_window).as_bool() {
+                None
+            } else {
+                create_scrim(self, parent_window).ok()
+            };
+            let mut window_style = match modal_type {
+                ModelType::Modal => WS_OVERLAPPED | WS_CAPTION | WS_SYSMENU,
+                ModelType::Alert => WS_OVERLAPPED | WS_DLGFRAME,
+            };
+            if options.resizable {
+                window_style |= WS_THICKFRAME;
+            }
             let boxed = Box::new(State {
                 qt: self.clone(),
                 title,
                 content,
+                options,
+                is_alert: *modal_type == ModelType::Alert,
             });
-            let window_style = match modal_type {
-                ModelType::Modal => WS_OVERLAPPED | WS_CAPTION | WS_SYSMENU,
-                ModelType::Alert => WS_OVERLAPPED | WS_DLGFRAME,
-            };
             let window = CreateWindowExW(
                 WINDOW_EX_STYLE::default(),
-                class_name,
+                &class_name,
                 title,
                 window_style,
                 CW_USEDEFAULT,
                 CW_USEDEFAULT,
-                (600f32 * scaling_factor) as i32,
-                (400f32 * scaling_factor) as i32,
+                dpi.to_physical(600f32),
+                dpi.to_physical(400f32),
                 Some(parent_window),
                 None,
                 Some(HINSTANCE(
@@ -96,6 +274,20 @@ impl QT {
             )?;
 
             _ = ShowWindow(window, SW_SHOW);
+            if let Some(scrim) = scrim {
+                // The dialog's shadow falls on the scrim rather than being dimmed by it.
+                let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+                let above = if raw.is_null() { window } else { (*raw).shadow };
+                _ = SetWindowPos(
+                    scrim,
+                    Some(above),
+                    0,
+                    0,
+                    0,
+                    0,
+                    SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+                );
+            }
 
             let mut message = MSG::default();
             let mut result = DialogResult::Cancel;
@@ -110,6 +302,12 @@ impl QT {
                 if !IsWindow(Some(window)).as_bool() {
                     break;
                 }
+                if let Some(scrim) = scrim {
+                    _ = sync_scrim(scrim, parent_window);
+                }
+            }
+            if let Some(scrim) = scrim {
+                _ = DestroyWindow(scrim);
             }
             _ = EnableWindow(parent_window, true);
             _ = SetActiveWindow(parent_window);
@@ -118,8 +316,195 @@ impl QT {
     }
 }
 
-unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+unsafe fn get_scrim_rect(parent_window: HWND) -> Result<RECT> {
+    let mut rect = RECT::default();
+    GetClientRect(parent_window, &mut rect)?;
+    let mut origin = POINT::default();
+    _ = ClientToScreen(parent_window, &mut origin);
+    Ok(RECT {
+        left: origin.x,
+        top: origin.y,
+        right: origin.x + rect.right,
+        bottom: origin.y + rect.bottom,
+    })
+}
+
+unsafe fn create_scrim(qt: &QT, parent_window: HWND) -> Result<HWND> {
+    let class_name = window_class_name("QT_DIALOG_SCRIM", Some(scrim_window_proc));
+    let window_class = WNDCLASSEXW {
+        cbSize: size_of::<WNDCLASSEXW>() as u32,
+        lpszClassName: PCWSTR(class_name.as_ptr()),
+        lpfnWndProc: Some(scrim_window_proc),
+        hCursor: LoadCursorW(None, IDC_ARROW)?,
+        hbrBackground: HBRUSH(GetStockObject(BLACK_BRUSH).0),
+        ..Default::default()
+    };
+    RegisterClassExW(&window_class);
+    let rect = get_scrim_rect(parent_window)?;
+    let boxed = Box::new(qt.clone());
+    let window = CreateWindowExW(
+        WS_EX_LAYERED | WS_EX_NOACTIVATE | WS_EX_TOOLWINDOW,
+        &class_name,
+        w!(""),
+        WS_POPUP,
+        rect.left,
+        rect.top,
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+        Some(parent_window),
+        None,
+        Some(HINSTANCE(
+            GetWindowLongPtrW(parent_window, GWLP_HINSTANCE) as _
+        )),
+        Some(Box::<QT>::into_raw(boxed) as _),
+    )?;
+    _ = ShowWindow(window, SW_SHOWNOACTIVATE);
+    Ok(window)
+}
+
+unsafe fn sync_scrim(scrim: HWND, parent_window: HWND) -> Result<()> {
+    if IsIconic(parent_window).as_bool() {
+        _ = ShowWindow(scrim, SW_HIDE);
+        return Ok(());
+    }
+    if !IsWindowVisible(scrim).as_bool() {
+        _ = ShowWindow(scrim, SW_SHOWNOACTIVATE);
+    }
+    let rect = get_scrim_rect(parent_window)?;
+    let mut scrim_rect = RECT::default();
+    GetWindowRect(scrim, &mut scrim_rect)?;
+    if scrim_rect != rect {
+        SetWindowPos(
+            scrim,
+            None,
+            rect.left,
+            rect.top,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_NOZORDER | SWP_NOACTIVATE,
+        )?;
+    }
+    Ok(())
+}
+
+#[implement(IUIAnimationTimerEventHandler)]
+struct ScrimAnimationTimerEventHandler {
+    window: HWND,
+}
+
+impl IUIAnimationTimerEventHandler_Impl for ScrimAnimationTimerEventHandler_Impl {
+    fn OnPreUpdate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn OnPostUpdate(&self) -> Result<()> {
+        unsafe {
+            let raw = GetWindowLongPtrW(self.window, GWLP_USERDATA) as *mut ScrimContext;
+            if raw.is_null() {
+                return Ok(());
+            }
+            let context = &*raw;
+            let opacity = context.opacity_variable.GetValue()?;
+            SetLayeredWindowAttributes(self.window, COLORREF(0), (opacity * 255.0) as u8, LWA_ALPHA)
+        }
+    }
+
+    fn OnRenderingTooSlow(&self, _frames_per_second: u32) -> Result<()> {
+        Ok(())
+    }
+}
+
+unsafe fn on_scrim_create(window: HWND, qt: QT) -> Result<ScrimContext> {
+    let curves = qt.animation_curves().easy_ease;
+    SetLayeredWindowAttributes(window, COLORREF(0), 0, LWA_ALPHA)?;
+    let transition_library: IUIAnimationTransitionLibrary2 =
+        CoCreateInstance(&UIAnimationTransitionLibrary2, None, CLSCTX_INPROC_SERVER)?;
+    let animation_manager = qt.animation_manager()?;
+    let timer_event_handler: IUIAnimationTimerEventHandler =
+        ScrimAnimationTimerEventHandler { window }.into();
+    qt.register_animation(window, &timer_event_handler);
+    let opacity_variable = animation_manager.CreateAnimationVariable(0.0)?;
+    let transition = transition_library.CreateCubicBezierLinearTransition(
+        qt.animation_durations().fast,
+        SCRIM_OPACITY,
+        curves[0],
+        curves[1],
+        curves[2],
+        curves[3],
+    )?;
+    qt.schedule_transition(window, &opacity_variable, &transition)?;
+    Ok(ScrimContext {
+        qt,
+        opacity_variable,
+    })
+}
+
+extern "system" fn scrim_window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut QT;
+            let qt = Box::<QT>::from_raw(raw);
+            match on_scrim_create(window, *qt) {
+                Ok(context) => {
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(
+                        window,
+                        GWLP_USERDATA,
+                        Box::<ScrimContext>::into_raw(boxed) as _,
+                    );
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(-1),
+            }
+        },
+        WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as isize),
+        WM_DESTROY => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut ScrimContext;
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                let context = Box::<ScrimContext>::from_raw(raw);
+                context.qt.unregister_animation(window);
+            }
+            LRESULT(0)
+        },
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
+
+fn get_corner_preference(corner: Option<CornerRadius>) -> DWM_WINDOW_CORNER_PREFERENCE {
+    match corner {
+        None | Some(CornerRadius::Pill) => DWMWCP_ROUND,
+        Some(CornerRadius::Dips(radius)) if radius <= 0f32 => DWMWCP_DONOTROUND,
+        // Small corners have a radius of 4 DIPs and normal ones of 8.
+        Some(CornerRadius::Dips(radius)) if radius < 6f32 => DWMWCP_ROUNDSMALL,
+        Some(CornerRadius::Dips(_)) => DWMWCP_ROUND,
+    }
+}
+
+fn get_corner_radius(preference: DWM_WINDOW_CORNER_PREFERENCE) -> f32 {
+    match preference {
+        DWMWCP_DONOTROUND => 0f32,
+        DWMWCP_ROUNDSMALL => 4f32,
+        _ => 8f32,
+    }
+}
+
+unsafe fn on_create(window: HWND, mut state: State) -> Result<Context> {
     let qt = &state.qt;
+    apply_title_bar_theme(window, &qt.theme);
+    let corner_preference = get_corner_preference(state.options.corner);
+    // Windows 10 cannot round the window, so its shadow stays square too.
+    let corner_radius = if apply_corner_preference(window, corner_preference) {
+        get_corner_radius(corner_preference)
+    } else {
+        0f32
+    };
     let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
     let title_typo = &qt.theme.typography_styles.subtitle1;
     let title_text_format = title_typo.create_text_format(&direct_write_factory)?;
@@ -130,13 +515,9 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
         D2D1_FACTORY_TYPE_SINGLE_THREADED,
         Some(&D2D1_FACTORY_OPTIONS::default()),
     )?;
-    let dpi = GetDpiForWindow(window);
+    let dpi = Dpi::for_window(window);
     let render_target = factory.CreateHwndRenderTarget(
-        &D2D1_RENDER_TARGET_PROPERTIES {
-            dpiX: dpi as f32,
-            dpiY: dpi as f32,
-            ..Default::default()
-        },
+        &render_target_properties(dpi),
         &D2D1_HWND_RENDER_TARGET_PROPERTIES {
             hwnd: window,
             pixelSize: D2D_SIZE_U {
@@ -147,117 +528,160 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
         },
     )?;
 
-    let ok_button = qt.create_button(
-        window,
-        0,
-        0,
-        w!("OK"),
-        &button::Appearance::Primary,
-        None,
-        None,
-        &button::Shape::Rounded,
-        &button::Size::Medium,
-        MouseEvent {
-            on_click: Box::new(move |_| {
-                let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-                (*raw).result = DialogResult::OK;
-                _ = PostMessageW(Some(window), WM_USER, WPARAM(0), LPARAM(0));
-            }),
-        },
+    let mut content_text = Vec::<u16>::new();
+    let mut links = Vec::new();
+    for segment in std::mem::take(&mut state.content) {
+        match segment {
+            ContentSegment::Text(text) => content_text.extend_from_slice(text.as_wide()),
+            ContentSegment::Link(text, event) => {
+                let range = DWRITE_TEXT_RANGE {
+                    startPosition: content_text.len() as u32,
+                    length: text.len() as u32,
+                };
+                content_text.extend_from_slice(text.as_wide());
+                links.push((range, event));
+            }
+        }
+    }
+    let content_text_layout = direct_write_factory.CreateTextLayout(
+        &content_text,
+        &content_text_format,
+        MAX_WIDTH - 24f32 - 24f32,
+        1000f32,
     )?;
-    let cancel_button = qt.create_button(
-        window,
-        0,
-        0,
-        w!("Cancel"),
-        &button::Appearance::Secondary,
-        None,
-        None,
-        &button::Shape::Rounded,
-        &button::Size::Medium,
-        MouseEvent {
-            on_click: Box::new(move |_| {
-                let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-                (*raw).result = DialogResult::Cancel;
-                _ = PostMessageW(Some(window), WM_USER, WPARAM(0), LPARAM(0));
-            }),
-        },
+    for (range, _) in &links {
+        content_text_layout.SetUnderline(true, *range)?;
+    }
+    apply_link_brush(
+        &render_target,
+        &state.qt.theme.tokens,
+        &content_text_layout,
+        &links,
     )?;
+
+    let qt = &state.qt;
+    let ok_button = qt
+        .create_button(
+            window,
+            0,
+            0,
+            ButtonOptions {
+                text: w!("OK"),
+                appearance: button::Appearance::Primary,
+                mouse_event: MouseEvent {
+                    on_click: Box::new(move |_| {
+                        let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+                        (*raw).result = DialogResult::OK;
+                        _ = PostMessageW(Some(window), WM_USER, WPARAM(0), LPARAM(0));
+                    }),
+                },
+                ..ButtonOptions::default()
+            },
+        )?
+        .window();
+    let cancel_button = qt
+        .create_button(
+            window,
+            0,
+            0,
+            ButtonOptions {
+                text: w!("Cancel"),
+                appearance: button::Appearance::Secondary,
+                mouse_event: MouseEvent {
+                    on_click: Box::new(move |_| {
+                        let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+                        (*raw).result = DialogResult::Cancel;
+                        _ = PostMessageW(Some(window), WM_USER, WPARAM(0), LPARAM(0));
+                    }),
+                },
+                ..ButtonOptions::default()
+            },
+        )?
+        .window();
+    let shadow = shadow::create(GetWindow(window, GW_OWNER)?)?;
     Ok(Context {
         state,
         title_text_format,
-        content_text_format,
+        content_text_layout,
+        links,
         render_target,
         result: DialogResult::Close,
         ok_button,
         cancel_button,
+        shadow,
+        corner_radius,
     })
 }
 
-unsafe fn layout(window: HWND, context: &Context) -> Result<()> {
-    let scaling_factor = get_scaling_factor(window);
+unsafe fn get_button_sizes(context: &Context) -> Result<(SIZE, SIZE)> {
+    let mut ok_rect = RECT::default();
+    GetClientRect(context.ok_button, &mut ok_rect)?;
+    let mut cancel_rect = RECT::default();
+    GetClientRect(context.cancel_button, &mut cancel_rect)?;
+    Ok((
+        SIZE {
+            cx: ok_rect.right,
+            cy: ok_rect.bottom,
+        },
+        SIZE {
+            cx: cancel_rect.right,
+            cy: cancel_rect.bottom,
+        },
+    ))
+}
 
-    let mut button_rect = RECT::default();
-    GetClientRect(context.cancel_button, &mut button_rect)?;
-    let cancel_button_width = button_rect.right - button_rect.left;
-    let cancel_button_height = button_rect.bottom - button_rect.top;
-    GetClientRect(context.ok_button, &mut button_rect)?;
-    let ok_button_width = button_rect.right - button_rect.left;
-    let ok_button_height = button_rect.bottom - button_rect.top;
+unsafe fn adjust_window_rect(window: HWND, rect: &mut RECT, dpi: Dpi) -> Result<()> {
+    let style = WINDOW_STYLE(GetWindowLongPtrW(window, GWL_STYLE) as u32);
+    let ex_style = WINDOW_EX_STYLE(GetWindowLongPtrW(window, GWL_EXSTYLE) as u32);
+    if OsVersion::current() >= OsVersion::new(10, 0, 0, 14393) {
+        AdjustWindowRectExForDpi(rect, style, false, ex_style, dpi.0)
+    } else {
+        AdjustWindowRectEx(rect, style, false, ex_style)
+    }
+}
 
+// The client area that fits the title, the content and a row of buttons this tall.
+unsafe fn measure(
+    title: PCWSTR,
+    title_text_format: &IDWriteTextFormat,
+    content_text_layout: &IDWriteTextLayout,
+    max_width: f32,
+    button_height: f32,
+) -> Result<SizeDips> {
     let surface_padding = 24f32;
     let gap = 8f32;
 
-    let state = &context.state;
-    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
-    let title_text_layout = direct_write_factory.CreateTextLayout(
-        state.title.as_wide(),
-        &context.title_text_format,
-        600f32 - 24f32 - 24f32,
-        1000f32,
-    )?;
-    let mut title_metrics = DWRITE_TEXT_METRICS::default();
-    title_text_layout.GetMetrics(&mut title_metrics)?;
-    let content_text_layout = direct_write_factory.CreateTextLayout(
-        state.content.as_wide(),
-        &context.content_text_format,
-        600f32 - 24f32 - 24f32,
-        1000f32,
-    )?;
+    let title_metrics = get_title_metrics(title, title_text_format, max_width)?;
     let mut content_metrics = DWRITE_TEXT_METRICS::default();
+    content_text_layout.SetMaxWidth(max_width - surface_padding * 2f32)?;
     content_text_layout.GetMetrics(&mut content_metrics)?;
 
-    let scaled_width = (((surface_padding * 2f32 + title_metrics.width)
-        .max(surface_padding * 2f32 + content_metrics.width)
-        .min(600f32))
-        * scaling_factor)
-        .ceil() as i32;
     let buttons_top = surface_padding + title_metrics.height + gap + content_metrics.height + gap;
-    let scaled_height = ((buttons_top + surface_padding) * scaling_factor).ceil() as i32
-        + ok_button_height.max(cancel_button_height);
+    Ok(SizeDips {
+        width: (surface_padding * 2f32 + title_metrics.width)
+            .max(surface_padding * 2f32 + content_metrics.width)
+            .min(max_width),
+        height: buttons_top + surface_padding + button_height,
+    })
+}
+
+unsafe fn layout(window: HWND, context: &Context, dpi: Dpi) -> Result<()> {
+    let (ok_button_size, cancel_button_size) = get_button_sizes(context)?;
+    let size = measure(
+        context.state.title,
+        &context.title_text_format,
+        &context.content_text_layout,
+        MAX_WIDTH,
+        dpi.to_dip(ok_button_size.cy.max(cancel_button_size.cy)),
+    )?;
 
     let mut rect = RECT {
         left: 0,
         top: 0,
-        right: scaled_width,
-        bottom: scaled_height,
+        right: dpi.to_physical_ceil(size.width),
+        bottom: dpi.to_physical_ceil(size.height),
     };
-    if OsVersion::current() >= OsVersion::new(10, 0, 0, 14393) {
-        AdjustWindowRectExForDpi(
-            &mut rect,
-            WINDOW_STYLE(GetWindowLongPtrW(window, GWL_STYLE) as u32),
-            false,
-            WINDOW_EX_STYLE(GetWindowLongPtrW(window, GWL_EXSTYLE) as u32),
-            GetDpiForWindow(window),
-        )?;
-    } else {
-        AdjustWindowRectEx(
-            &mut rect,
-            WINDOW_STYLE(GetWindowLongPtrW(window, GWL_STYLE) as u32),
-            false,
-            WINDOW_EX_STYLE(GetWindowLongPtrW(window, GWL_EXSTYLE) as u32),
-        )?;
-    }
+    adjust_window_rect(window, &mut rect, dpi)?;
     let window_width = rect.right - rect.left;
     let window_height = rect.bottom - rect.top;
     let parent_window = GetAncestor(window, GA_PARENT);
@@ -271,27 +695,87 @@ unsafe fn layout(window: HWND, context: &Context) -> Result<()> {
         window_height,
         SWP_NOZORDER | SWP_NOMOVE,
     )?;
+    arrange(window, context, dpi)
+}
+
+// Fits the content to the current client size and pins the buttons to the bottom right corner.
+unsafe fn arrange(window: HWND, context: &Context, dpi: Dpi) -> Result<()> {
+    let (ok_button_size, cancel_button_size) = get_button_sizes(context)?;
+    let mut client_rect = RECT::default();
+    GetClientRect(window, &mut client_rect)?;
+    context
+        .content_text_layout
+        .SetMaxWidth((dpi.to_dip(client_rect.right) - 48f32).max(0f32))?;
     context.render_target.Resize(&D2D_SIZE_U {
-        width: scaled_width as u32,
-        height: scaled_height as u32,
+        width: client_rect.right as u32,
+        height: client_rect.bottom as u32,
     })?;
+    let buttons_top =
+        client_rect.bottom - dpi.to_physical(24f32) - ok_button_size.cy.max(cancel_button_size.cy);
     MoveWindow(
         context.cancel_button,
-        scaled_width - (cancel_button_width + (24f32 * scaling_factor) as i32),
-        (buttons_top * scaling_factor) as i32,
-        cancel_button_width,
-        cancel_button_height,
+        client_rect.right - (cancel_button_size.cx + dpi.to_physical(24f32)),
+        buttons_top,
+        cancel_button_size.cx,
+        cancel_button_size.cy,
         false,
     )?;
     MoveWindow(
         context.ok_button,
-        scaled_width - (cancel_button_width + ok_button_width + (32f32 * scaling_factor) as i32),
-        (buttons_top * scaling_factor) as i32,
-        ok_button_width,
-        ok_button_height,
+        client_rect.right - (cancel_button_size.cx + ok_button_size.cx + dpi.to_physical(32f32)),
+        buttons_top,
+        ok_button_size.cx,
+        ok_button_size.cy,
         false,
     )?;
+    Ok(())
+}
 
+unsafe fn on_get_min_max_info(
+    window: HWND,
+    context: &Context,
+    info: &mut MINMAXINFO,
+) -> Result<()> {
+    let dpi = Dpi::for_window(window);
+    let (ok_button_size, cancel_button_size) = get_button_sizes(context)?;
+    let mut content_metrics = DWRITE_TEXT_METRICS::default();
+    context
+        .content_text_layout
+        .GetMetrics(&mut content_metrics)?;
+    // The content is measured at the current width, so narrowing the dialog raises its minimum
+    // height as the text wraps onto more lines.
+    let mut min_rect = RECT {
+        left: 0,
+        top: 0,
+        right: ok_button_size.cx + cancel_button_size.cx + dpi.to_physical(24f32 + 8f32 + 24f32),
+        bottom: dpi.to_physical_ceil(
+            get_content_top(window, context)? + content_metrics.height + 8f32 + 24f32,
+        ) + ok_button_size.cy.max(cancel_button_size.cy),
+    };
+    adjust_window_rect(window, &mut min_rect, dpi)?;
+    info.ptMinTrackSize = POINT {
+        x: min_rect.right - min_rect.left,
+        y: min_rect.bottom - min_rect.top,
+    };
+
+    let options = &context.state.options;
+    if options.max_width.is_some() || options.max_height.is_some() {
+        let mut max_rect = RECT {
+            left: 0,
+            top: 0,
+            right: options.max_width.map_or(0, |width| dpi.to_physical(width)),
+            bottom: options
+                .max_height
+                .map_or(0, |height| dpi.to_physical(height)),
+        };
+        adjust_window_rect(window, &mut max_rect, dpi)?;
+        if options.max_width.is_some() {
+            info.ptMaxTrackSize.x = (max_rect.right - max_rect.left).max(info.ptMinTrackSize.x);
+        }
+        if options.max_height.is_some() {
+            info.ptMaxTrackSize.y = (max_rect.bottom - max_rect.top).max(info.ptMinTrackSize.y);
+        }
+    }
     Ok(())
 }
 
@@ -300,9 +784,12 @@ unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
     let tokens = &state.qt.theme.tokens;
     let mut window_rect = RECT::default();
     GetClientRect(window, &mut window_rect)?;
-    let scaling_factor = get_scaling_factor(window);
-    let width = (window_rect.right - window_rect.left) as f32 / scaling_factor;
-    let height = (window_rect.bottom - window_rect.top) as f32 / scaling_factor;
+    let dpi = Dpi::for_window(window);
+    let width = dpi.to_dip(window_rect.right - window_rect.left);
+    let height = dpi.to_dip(window_rect.bottom - window_rect.top);
+    context
+        .render_target
+        .Clear(Some(&tokens.color_neutral_background1));
     let text_brush = context
         .render_target
         .CreateSolidColorBrush(&tokens.color_neutral_foreground1, None)?;
@@ -320,39 +807,85 @@ unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
         DWRITE_MEASURING_MODE_NATURAL,
     );
 
-    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
-    let title_text_layout = direct_write_factory.CreateTextLayout(
-        state.title.as_wide(),
-        &context.title_text_format,
-        width - 24f32 - 24f32,
-        height - 24f32 - 24f32,
-    )?;
-    let mut title_metrics = DWRITE_TEXT_METRICS::default();
-    title_text_layout.GetMetrics(&mut title_metrics)?;
-    context.render_target.DrawText(
-        state.content.as_wide(),
-        &context.content_text_format,
-        &D2D_RECT_F {
-            left: 24f32,
-            top: 24f32 + title_metrics.height + 8f32,
-            right: width - 24f32,
-            bottom: height - 24f32,
+    context.render_target.DrawTextLayout(
+        D2D_POINT_2F {
+            x: 24f32,
+            y: get_content_top(window, context)?,
         },
+        &context.content_text_layout,
         &text_brush,
         D2D1_DRAW_TEXT_OPTIONS_NONE,
-        DWRITE_MEASURING_MODE_NATURAL,
     );
     Ok(())
 }
 
+unsafe fn get_title_metrics(
+    title: PCWSTR,
+    title_text_format: &IDWriteTextFormat,
+    max_width: f32,
+) -> Result<DWRITE_TEXT_METRICS> {
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let title_text_layout = direct_write_factory.CreateTextLayout(
+        title.as_wide(),
+        title_text_format,
+        (max_width - 24f32 - 24f32).max(0f32),
+        1000f32,
+    )?;
+    let mut title_metrics = DWRITE_TEXT_METRICS::default();
+    title_text_layout.GetMetrics(&mut title_metrics)?;
+    Ok(title_metrics)
+}
+
+// The title wraps at the current client width, as it is painted, rather than at the width the
+// dialog was first measured with.
+unsafe fn get_content_top(window: HWND, context: &Context) -> Result<f32> {
+    let mut client_rect = RECT::default();
+    GetClientRect(window, &mut client_rect)?;
+    let width = Dpi::for_window(window).to_dip(client_rect.right);
+    let title_metrics = get_title_metrics(context.state.title, &context.title_text_format, width)?;
+    Ok(24f32 + title_metrics.height + 8f32)
+}
+
+unsafe fn hit_test_link(window: HWND, context: &Context, point: POINT) -> Result<Option<usize>> {
+    let dpi = Dpi::for_window(window);
+    let mut is_trailing_hit = FALSE;
+    let mut is_inside = FALSE;
+    let mut metrics = DWRITE_HIT_TEST_METRICS::default();
+    context.content_text_layout.HitTestPoint(
+        dpi.to_dip(point.x) - 24f32,
+        dpi.to_dip(point.y) - get_content_top(window, context)?,
+        &mut is_trailing_hit,
+        &mut is_inside,
+        &mut metrics,
+    )?;
+    if !is_inside.as_bool() {
+        return Ok(None);
+    }
+    Ok(context.links.iter().position(|(range, _)| {
+        metrics.textPosition >= range.startPosition
+            && metrics.textPosition < range.startPosition + range.length
+    }))
+}
+
+// The handler may destroy the dialog and with it the context that holds the handler, so it runs
+// taken out of the context and is put back only if the dialog still exists.
+unsafe fn on_link_click(window: HWND, index: usize) {
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    let Some(context) = raw.as_mut() else {
+        return;
+    };
+    let on_click = replace(&mut context.links[index].1.on_click, Box::new(|_window| {}));
+    on_click(&window);
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    if let Some(context) = raw.as_mut() {
+        context.links[index].1.on_click = on_click;
+    }
+}
+
 unsafe fn on_paint(window: HWND, context: &Context) -> Result<()> {
     let mut ps = PAINTSTRUCT::default();
     BeginPaint(window, &mut ps);
     context.render_target.BeginDraw();
-    context.render_target.Clear(Some(
-        &context.state.qt.theme.tokens.color_neutral_background1,
-    ));
-
     let result = paint(window, context).and(context.render_target.EndDraw(None, None));
     _ = EndPaint(window, &ps);
     result
@@ -371,7 +904,7 @@ extern "system" fn window_proc(
             let state = Box::<State>::from_raw(raw);
             match on_create(window, *state) {
                 Ok(context) => {
-                    _ = layout(window, &context);
+                    _ = layout(window, &context, Dpi::for_window(window));
                     let boxed = Box::new(context);
                     SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
                     DefWindowProcW(window, message, w_param, l_param)
@@ -380,25 +913,136 @@ extern "system" fn window_proc(
             }
         },
         WM_PAINT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let result = on_paint(window, context);
+            recover_lost_device(window, &result, || {
+                context.recreate_device_resources(window)
+            });
+            // on_paint has validated the window, and DefWindowProc would also validate the
+            // repaint queued after a lost device.
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &*raw;
-            _ = on_paint(window, context);
-            DefWindowProcW(window, message, w_param, l_param)
+            let dc = HDC(w_param.0 as _);
+            _ = print_client(window, &context.render_target, dc, || {
+                paint(window, context)
+            });
+            LRESULT(0)
         },
         WM_GETDPISCALEDSIZE => LRESULT(TRUE.0 as isize),
         WM_DPICHANGED => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &*raw;
-            let new_dpi_x = w_param.0 as i16 as f32;
-            let new_dpi_y = (w_param.0 >> 16) as i16 as f32;
-            context.render_target.SetDpi(new_dpi_x, new_dpi_y);
-            _ = layout(window, &context);
+            let dpi = Dpi(w_param.0 as u16 as u32);
+            context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+            _ = layout(window, &context, dpi);
             _ = InvalidateRect(Some(window), None, false);
             LRESULT(TRUE.0 as isize)
         },
         WM_DESTROY => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            _ = Box::<Context>::from_raw(raw);
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                let context = Box::<Context>::from_raw(raw);
+                _ = DestroyWindow(context.shadow);
+            }
+            LRESULT(0)
+        },
+        // The shadow follows the dialog as it is shown, moved, resized and hidden.
+        WM_WINDOWPOSCHANGED => unsafe {
+            let result = DefWindowProcW(window, message, w_param, l_param);
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                let context = &*raw;
+                if IsWindowVisible(window).as_bool() && !IsIconic(window).as_bool() {
+                    _ = shadow::show_beneath(
+                        context.shadow,
+                        window,
+                        &context.state.qt.theme.tokens,
+                        Elevation::Shadow8,
+                        context.corner_radius,
+                    );
+                } else {
+                    shadow::hide(context.shadow);
+                }
+            }
+            result
+        },
+        WM_SIZE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                _ = arrange(window, &*raw, Dpi::for_window(window));
+                _ = InvalidateRect(Some(window), None, false);
+            }
+            LRESULT(0)
+        },
+        WM_GETMINMAXINFO => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() && (*raw).state.options.resizable {
+                let info = &mut *(l_param.0 as *mut MINMAXINFO);
+                _ = on_get_min_max_info(window, &*raw, info);
+                return LRESULT(0);
+            }
+            DefWindowProcW(window, message, w_param, l_param)
+        },
+        WM_NCHITTEST => unsafe {
+            let result = DefWindowProcW(window, message, w_param, l_param);
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() || !(*raw).state.is_alert || result.0 != HTCLIENT as isize {
+                return result;
+            }
+            // Alerts have no title bar, so the header area stands in for one.
+            let mut point = POINT {
+                x: l_param.0 as i16 as i32,
+                y: (l_param.0 >> 16) as i16 as i32,
+            };
+            _ = ScreenToClient(window, &mut point);
+            if point.y < Dpi::for_window(window).to_physical(DRAG_AREA_HEIGHT) {
+                LRESULT(HTCAPTION as isize)
+            } else {
+                result
+            }
+        },
+        WM_LBUTTONUP => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return LRESULT(0);
+            }
+            let point = POINT {
+                x: l_param.0 as i16 as i32,
+                y: (l_param.0 >> 16) as i16 as i32,
+            };
+            if let Ok(Some(index)) = hit_test_link(window, &*raw, point) {
+                on_link_click(window, index);
+            }
+            LRESULT(0)
+        },
+        WM_SETCURSOR => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let mut point = POINT::default();
+            if !raw.is_null()
+                && (l_param.0 & 0xffff) as u32 == HTCLIENT
+                && GetCursorPos(&mut point).is_ok()
+                && ScreenToClient(window, &mut point).as_bool()
+                && matches!(hit_test_link(window, &*raw, point), Ok(Some(_)))
+            {
+                if let Ok(cursor) = LoadCursorW(None, IDC_HAND) {
+                    SetCursor(Some(cursor));
+                    return LRESULT(TRUE.0 as isize);
+                }
+            }
+            DefWindowProcW(window, message, w_param, l_param)
+        },
+        // Alt+F4 and the close button dismiss the dialog the same way as its Cancel button.
+        WM_CLOSE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                (*raw).result = DialogResult::Cancel;
+            }
+            _ = PostMessageW(Some(window), WM_USER, WPARAM(0), LPARAM(0));
             LRESULT(0)
         },
         WM_USER => unsafe {
@@ -408,3 +1052,155 @@ extern "system" fn window_proc(
         _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use windows::Win32::Graphics::Gdi::{CreateCompatibleDC, DeleteDC, UpdateWindow};
+
+    use super::*;
+    use crate::force_device_lost;
+    use crate::test_support::Host;
+
+    thread_local! {
+        static PRINTED_AFTER_RECOVERY: Cell<Option<bool>> = const { Cell::new(None) };
+        static LINK_CLICKS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn find_dialog() -> Option<HWND> {
+        let class_name = window_class_name("QT_DIALOG", Some(window_proc));
+        unsafe { FindWindowW(&class_name, PCWSTR::null()).ok() }
+    }
+
+    // Runs inside the dialog's message loop: loses the device on a paint, then draws the dialog
+    // again and closes it.
+    unsafe extern "system" fn lose_device_and_print(
+        _window: HWND,
+        _message: u32,
+        id: usize,
+        _time: u32,
+    ) {
+        let Some(window) = find_dialog() else {
+            return;
+        };
+        _ = KillTimer(None, id);
+        _ = InvalidateRect(Some(window), None, false);
+        force_device_lost();
+        _ = UpdateWindow(window);
+        let context = &*(GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context);
+        let dc = CreateCompatibleDC(None);
+        let result = print_client(window, &context.render_target, dc, || {
+            paint(window, context)
+        });
+        _ = DeleteDC(dc);
+        PRINTED_AFTER_RECOVERY.set(Some(result.is_ok()));
+        _ = PostMessageW(Some(window), WM_CLOSE, WPARAM(0), LPARAM(0));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn links_draw_after_a_lost_device() {
+        let host = Host::new(400, 300);
+        unsafe {
+            SetTimer(None, 0, 10, Some(Some(lose_device_and_print)));
+        }
+        let result = host
+            .qt
+            .open_dialog_with_content(
+                host.window,
+                w!("Title"),
+                vec![
+                    ContentSegment::Text(w!("Read the ")),
+                    ContentSegment::Link(w!("docs"), MouseEvent::default()),
+                ],
+                &ModelType::Modal,
+            )
+            .unwrap();
+        assert_eq!(result, DialogResult::Cancel);
+        assert_eq!(PRINTED_AFTER_RECOVERY.get(), Some(true));
+    }
+
+    #[test]
+    fn corner_preference_picks_the_nearest_radius() {
+        assert_eq!(get_corner_preference(None), DWMWCP_ROUND);
+        assert_eq!(
+            get_corner_preference(Some(CornerRadius::Pill)),
+            DWMWCP_ROUND
+        );
+        assert_eq!(
+            get_corner_preference(Some(CornerRadius::Dips(0f32))),
+            DWMWCP_DONOTROUND
+        );
+        assert_eq!(
+            get_corner_preference(Some(CornerRadius::Dips(2f32))),
+            DWMWCP_ROUNDSMALL
+        );
+        assert_eq!(
+            get_corner_preference(Some(CornerRadius::Dips(8f32))),
+            DWMWCP_ROUND
+        );
+    }
+
+    #[test]
+    fn shadow_follows_the_corner_preference() {
+        assert_eq!(get_corner_radius(DWMWCP_DONOTROUND), 0f32);
+        assert_eq!(get_corner_radius(DWMWCP_ROUNDSMALL), 4f32);
+        assert_eq!(get_corner_radius(DWMWCP_ROUND), 8f32);
+    }
+
+    // Runs inside the dialog's message loop: clicks the middle of the first link.
+    unsafe extern "system" fn click_link(_window: HWND, _message: u32, id: usize, _time: u32) {
+        let Some(window) = find_dialog() else {
+            return;
+        };
+        _ = KillTimer(None, id);
+        let context = &*(GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context);
+        let range = context.links[0].0;
+        let (mut x, mut y) = (0f32, 0f32);
+        let mut metrics = DWRITE_HIT_TEST_METRICS::default();
+        context
+            .content_text_layout
+            .HitTestTextPosition(range.startPosition, false, &mut x, &mut y, &mut metrics)
+            .unwrap();
+        let dpi = Dpi::for_window(window);
+        let point_x = dpi.to_physical(24f32 + x + 1f32);
+        let point_y =
+            dpi.to_physical(get_content_top(window, context).unwrap() + y + metrics.height / 2f32);
+        SendMessageW(
+            window,
+            WM_LBUTTONUP,
+            None,
+            Some(LPARAM((point_y << 16 | point_x) as isize)),
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn link_may_destroy_its_dialog() {
+        let host = Host::new(400, 300);
+        unsafe {
+            SetTimer(None, 0, 10, Some(Some(click_link)));
+        }
+        let result = host
+            .qt
+            .open_dialog_with_content(
+                host.window,
+                w!("Title"),
+                vec![ContentSegment::Link(
+                    w!("Close this dialog"),
+                    MouseEvent {
+                        on_click: Box::new(|window| {
+                            LINK_CLICKS.set(LINK_CLICKS.get() + 1);
+                            _ = unsafe { DestroyWindow(*window) };
+                        }),
+                    },
+                )],
+                &ModelType::Modal,
+            )
+            .unwrap();
+        assert_eq!(LINK_CLICKS.get(), 1);
+        assert_eq!(result, DialogResult::Cancel);
+        assert!(find_dialog().is_none());
+    }
+}
@@ -0,0 +1,570 @@
+use std::mem::{replace, size_of};
+
+use windows::core::*;
+use windows::Foundation::Numerics::Matrix3x2;
+use windows::Win32::Foundation::{
+    FALSE, HINSTANCE, HWND, LPARAM, LRESULT, POINT, RECT, TRUE, WPARAM,
+};
+use windows::Win32::Graphics::Direct2D::Common::{D2D_POINT_2F, D2D_SIZE_U};
+use windows::Win32::Graphics::Direct2D::{
+    D2D1CreateFactory, ID2D1DeviceContext5, ID2D1Factory1, ID2D1HwndRenderTarget, ID2D1SvgDocument,
+    D2D1_DRAW_TEXT_OPTIONS_NONE, D2D1_FACTORY_OPTIONS, D2D1_FACTORY_TYPE_SINGLE_THREADED,
+    D2D1_HWND_RENDER_TARGET_PROPERTIES,
+};
+use windows::Win32::Graphics::DirectWrite::{
+    DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, IDWriteTextLayout,
+    DWRITE_FACTORY_TYPE_SHARED, DWRITE_TEXT_METRICS, DWRITE_TEXT_RANGE,
+};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, CreateRoundRectRgn, EndPaint, InvalidateRect, PtInRect, ScreenToClient,
+    SetWindowRgn, HDC, PAINTSTRUCT,
+};
+use windows::Win32::UI::Controls::WM_MOUSELEAVE;
+use windows::Win32::UI::Input::KeyboardAndMouse::{TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT};
+use windows::Win32::UI::Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::component::button::set_svg_color;
+use crate::component::menu::create_svg_document;
+use crate::component::tooltip::{get_work_area, HoverAction, HoverTimer};
+use crate::icon::Icon;
+use crate::{
+    print_client, recover_lost_device, recreate_render_target, render_target_properties,
+    window_class_name, CornerRadius, Dpi, MouseEvent, QT,
+};
+
+const MAX_WIDTH: f32 = 320f32;
+const ICON_SIZE: f32 = 20f32;
+const SHOW_DELAY: u32 = 900;
+
+/// A link at the bottom of a hover card, such as "Learn more".
+pub struct HoverCardLink {
+    pub text: PCWSTR,
+    pub event: MouseEvent,
+}
+
+struct State {
+    qt: QT,
+    target: HWND,
+    title: PCWSTR,
+    body: PCWSTR,
+    icon: Option<Icon>,
+    link: Option<HoverCardLink>,
+    /// Overrides the theme's large border radius.
+    corner: Option<CornerRadius>,
+}
+
+struct Context {
+    state: State,
+    render_target: ID2D1HwndRenderTarget,
+    title_format: IDWriteTextFormat,
+    body_format: IDWriteTextFormat,
+    icon_svg: Option<ID2D1SvgDocument>,
+    title_layout: Option<IDWriteTextLayout>,
+    body_layout: Option<IDWriteTextLayout>,
+    link_layout: Option<IDWriteTextLayout>,
+    text_left: f32,
+    body_top: f32,
+    link_top: f32,
+    hover_timer: HoverTimer,
+}
+
+impl Context {
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        self.icon_svg = create_icon_svg(&render_target, &self.state)?;
+        self.render_target = render_target;
+        Ok(())
+    }
+}
+
+impl QT {
+    /// Shows a card with a title, a body, and optionally an icon and a link once the cursor has
+    /// rested on `target` for a while. The card stays open while the cursor is over the target or
+    /// the card, and is destroyed along with the target.
+    pub fn attach_hover_card(
+        &self,
+        target: HWND,
+        title: PCWSTR,
+        body: PCWSTR,
+        icon: Option<&Icon>,
+        link: Option<HoverCardLink>,
+        corner: Option<CornerRadius>,
+    ) -> Result<HWND> {
+        let class_name = window_class_name("QT_HOVER_CARD", Some(window_proc));
+        unsafe {
+            let window_class = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                style: CS_DROPSHADOW | CS_SAVEBITS,
+                lpfnWndProc: Some(window_proc),
+                hCursor: LoadCursorW(None, IDC_ARROW)?,
+                ..Default::default()
+            };
+            RegisterClassExW(&window_class);
+            let boxed = Box::new(State {
+                qt: self.clone(),
+                target,
+                title,
+                body,
+                icon: icon.cloned(),
+                link,
+                corner,
+            });
+            let owner = GetAncestor(target, GA_ROOT);
+            let window = CreateWindowExW(
+                WS_EX_NOACTIVATE | WS_EX_TOOLWINDOW,
+                &class_name,
+                w!(""),
+                WS_POPUP,
+                0,
+                0,
+                0,
+                0,
+                Some(owner),
+                None,
+                Some(HINSTANCE(GetWindowLongPtrW(owner, GWLP_HINSTANCE) as _)),
+                Some(Box::<State>::into_raw(boxed) as _),
+            )?;
+            // The target's own window procedure is left alone; the card only watches its messages.
+            if !SetWindowSubclass(
+                target,
+                Some(target_subclass_proc),
+                window.0 as usize,
+                window.0 as usize,
+            )
+            .as_bool()
+            {
+                _ = DestroyWindow(window);
+                return Err(Error::from_win32());
+            }
+            Ok(window)
+        }
+    }
+}
+
+unsafe fn get_context<'a>(window: HWND) -> Option<&'a mut Context> {
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    if raw.is_null() {
+        None
+    } else {
+        Some(&mut *raw)
+    }
+}
+
+extern "system" fn target_subclass_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+    id: usize,
+    card: usize,
+) -> LRESULT {
+    let card = HWND(card as _);
+    unsafe {
+        match message {
+            WM_MOUSEMOVE => {
+                if let Some(context) = get_context(card) {
+                    if !context.hover_timer.is_pending() && !IsWindowVisible(card).as_bool() {
+                        context.hover_timer.schedule();
+                        let mut event = TRACKMOUSEEVENT {
+                            cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
+                            dwFlags: TME_LEAVE,
+                            hwndTrack: window,
+                            dwHoverTime: 0,
+                        };
+                        _ = TrackMouseEvent(&mut event);
+                    }
+                }
+            }
+            // Leaving before the delay cancels the card; once it is shown, polling decides.
+            WM_MOUSELEAVE => {
+                if let Some(context) = get_context(card) {
+                    if context.hover_timer.is_pending() {
+                        context.hover_timer.cancel();
+                    }
+                }
+            }
+            WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_KEYDOWN => {
+                if let Some(context) = get_context(card) {
+                    hide(card, context);
+                }
+            }
+            WM_NCDESTROY => {
+                _ = RemoveWindowSubclass(window, Some(target_subclass_proc), id);
+                _ = DestroyWindow(card);
+            }
+            _ => {}
+        }
+        DefSubclassProc(window, message, w_param, l_param)
+    }
+}
+
+unsafe fn is_cursor_over(window: HWND) -> bool {
+    let mut point = POINT::default();
+    let mut rect = RECT::default();
+    GetCursorPos(&mut point).is_ok()
+        && IsWindowVisible(window).as_bool()
+        && GetWindowRect(window, &mut rect).is_ok()
+        && PtInRect(&rect, point).as_bool()
+}
+
+unsafe fn show(window: HWND, context: &mut Context) -> Result<()> {
+    let state = &context.state;
+    let tokens = &state.qt.theme.tokens;
+    let padding = tokens.spacing_horizontal_m;
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_left = match state.icon {
+        Some(_) => padding + ICON_SIZE + tokens.spacing_horizontal_s,
+        None => padding,
+    };
+    let max_text_width = MAX_WIDTH - text_left - padding;
+    let create_layout = |text: PCWSTR, format: &IDWriteTextFormat| {
+        direct_write_factory.CreateTextLayout(text.as_wide(), format, max_text_width, f32::MAX)
+    };
+    let title_layout = create_layout(state.title, &context.title_format)?;
+    let body_layout = create_layout(state.body, &context.body_format)?;
+    let link_layout = match &state.link {
+        Some(link) => {
+            let link_layout = create_layout(link.text, &context.body_format)?;
+            link_layout.SetUnderline(
+                true,
+                DWRITE_TEXT_RANGE {
+                    startPosition: 0,
+                    length: link.text.len() as u32,
+                },
+            )?;
+            Some(link_layout)
+        }
+        None => None,
+    };
+
+    let mut title_metrics = DWRITE_TEXT_METRICS::default();
+    title_layout.GetMetrics(&mut title_metrics)?;
+    let mut body_metrics = DWRITE_TEXT_METRICS::default();
+    body_layout.GetMetrics(&mut body_metrics)?;
+    let mut link_metrics = DWRITE_TEXT_METRICS::default();
+    if let Some(link_layout) = &link_layout {
+        link_layout.GetMetrics(&mut link_metrics)?;
+    }
+    let body_top = padding + title_metrics.height + tokens.spacing_horizontal_xs;
+    let link_top = body_top + body_metrics.height + tokens.spacing_horizontal_s;
+    let content_bottom = match link_layout {
+        Some(_) => link_top + link_metrics.height,
+        None => body_top + body_metrics.height,
+    };
+    let content_width = title_metrics
+        .width
+        .max(body_metrics.width)
+        .max(link_metrics.width);
+
+    let dpi = Dpi::for_window(state.target);
+    let width = dpi.to_physical_ceil(text_left + content_width + padding);
+    let height = dpi.to_physical_ceil(content_bottom.max(padding + ICON_SIZE) + padding);
+
+    // Below the target, or above it when there is no room below.
+    let mut target_rect = RECT::default();
+    GetWindowRect(state.target, &mut target_rect)?;
+    let work_area = get_work_area(&target_rect);
+    let gap = dpi.to_physical(tokens.spacing_horizontal_xs);
+    let mut y = target_rect.bottom + gap;
+    if y + height > work_area.bottom {
+        y = (target_rect.top - gap - height).max(work_area.top);
+    }
+    let x = target_rect
+        .left
+        .min(work_area.right - width)
+        .max(work_area.left);
+
+    context.title_layout = Some(title_layout);
+    context.body_layout = Some(body_layout);
+    context.link_layout = link_layout;
+    context.text_left = text_left;
+    context.body_top = body_top;
+    context.link_top = link_top;
+
+    SetWindowPos(
+        window,
+        Some(HWND_TOP),
+        x,
+        y,
+        width,
+        height,
+        SWP_NOACTIVATE | SWP_SHOWWINDOW,
+    )?;
+    context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+    context.render_target.Resize(&D2D_SIZE_U {
+        width: width as u32,
+        height: height as u32,
+    })?;
+    let corner_diameter = context
+        .state
+        .corner
+        .unwrap_or(CornerRadius::Dips(tokens.border_radius_large))
+        .to_physical_diameter(dpi, width, height);
+    let region = CreateRoundRectRgn(
+        0,
+        0,
+        width + 1,
+        height + 1,
+        corner_diameter,
+        corner_diameter,
+    );
+    SetWindowRgn(window, Some(region), true);
+    context.hover_timer.start_polling();
+    _ = InvalidateRect(Some(window), None, false);
+    Ok(())
+}
+
+unsafe fn hide(window: HWND, context: &mut Context) {
+    _ = ShowWindow(window, SW_HIDE);
+    context.hover_timer.cancel();
+}
+
+unsafe fn on_timer(window: HWND, context: &mut Context, id: usize) -> Result<()> {
+    let target = context.state.target;
+    match context
+        .hover_timer
+        .on_timer(id, || is_cursor_over(target) || is_cursor_over(window))
+    {
+        Some(HoverAction::Show) if is_cursor_over(target) => show(window, context),
+        Some(HoverAction::Dismiss) => {
+            hide(window, context);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Runs the link's callback with no borrow of the context held, since the callback may destroy the
+// target and so the card.
+unsafe fn on_link_click(window: HWND) {
+    let Some(context) = get_context(window) else {
+        return;
+    };
+    let target = context.state.target;
+    let Some(link) = &mut context.state.link else {
+        return;
+    };
+    let on_click = replace(&mut link.event.on_click, Box::new(|_window| {}));
+    on_click(&target);
+    if let Some(HoverCardLink { event, .. }) =
+        get_context(window).and_then(|context| context.state.link.as_mut())
+    {
+        event.on_click = on_click;
+    }
+}
+
+unsafe fn hit_test_link(window: HWND, context: &Context, point: POINT) -> bool {
+    let Some(link_layout) = &context.link_layout else {
+        return false;
+    };
+    let dpi = Dpi::for_window(window);
+    let x = dpi.to_dip(point.x) - context.text_left;
+    let y = dpi.to_dip(point.y) - context.link_top;
+    let mut metrics = DWRITE_TEXT_METRICS::default();
+    link_layout.GetMetrics(&mut metrics).is_ok()
+        && x >= metrics.left
+        && x < metrics.left + metrics.width
+        && y >= 0f32
+        && y < metrics.height
+}
+
+unsafe fn create_icon_svg(
+    render_target: &ID2D1HwndRenderTarget,
+    state: &State,
+) -> Result<Option<ID2D1SvgDocument>> {
+    let Some(icon) = &state.icon else {
+        return Ok(None);
+    };
+    let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
+    let svg = create_svg_document(&device_context5, icon)?;
+    set_svg_color(&svg, &state.qt.theme.tokens.color_neutral_foreground1)?;
+    Ok(Some(svg))
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        Some(&D2D1_FACTORY_OPTIONS::default()),
+    )?;
+    let dpi = Dpi::for_window(state.target);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U::default(),
+            presentOptions: Default::default(),
+        },
+    )?;
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let typography_styles = &state.qt.theme.typography_styles;
+    let title_format = typography_styles
+        .subtitle2
+        .create_text_format(&direct_write_factory)?;
+    let body_format = typography_styles
+        .body1
+        .create_text_format(&direct_write_factory)?;
+    let icon_svg = create_icon_svg(&render_target, &state)?;
+    Ok(Context {
+        state,
+        render_target,
+        title_format,
+        body_format,
+        icon_svg,
+        title_layout: None,
+        body_layout: None,
+        link_layout: None,
+        text_left: 0f32,
+        body_top: 0f32,
+        link_top: 0f32,
+        hover_timer: HoverTimer::new(window, SHOW_DELAY),
+    })
+}
+
+unsafe fn paint(context: &Context) -> Result<()> {
+    let tokens = &context.state.qt.theme.tokens;
+    let padding = tokens.spacing_horizontal_m;
+    let render_target = &context.render_target;
+    render_target.Clear(Some(&tokens.color_neutral_background1));
+    if let Some(svg) = &context.icon_svg {
+        let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
+        device_context5.SetTransform(&Matrix3x2::translation(padding, padding));
+        device_context5.DrawSvgDocument(svg);
+        device_context5.SetTransform(&Matrix3x2::identity());
+    }
+    let text_brush =
+        render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground1, None)?;
+    for (layout, top) in [
+        (&context.title_layout, padding),
+        (&context.body_layout, context.body_top),
+    ] {
+        if let Some(layout) = layout {
+            render_target.DrawTextLayout(
+                D2D_POINT_2F {
+                    x: context.text_left,
+                    y: top,
+                },
+                layout,
+                &text_brush,
+                D2D1_DRAW_TEXT_OPTIONS_NONE,
+            );
+        }
+    }
+    if let Some(link_layout) = &context.link_layout {
+        let link_brush =
+            render_target.CreateSolidColorBrush(&tokens.color_brand_foreground_link, None)?;
+        render_target.DrawTextLayout(
+            D2D_POINT_2F {
+                x: context.text_left,
+                y: context.link_top,
+            },
+            link_layout,
+            &link_brush,
+            D2D1_DRAW_TEXT_OPTIONS_NONE,
+        );
+    }
+    Ok(())
+}
+
+unsafe fn on_paint(window: HWND, context: &Context) -> Result<()> {
+    let mut ps = PAINTSTRUCT::default();
+    BeginPaint(window, &mut ps);
+    context.render_target.BeginDraw();
+    let result = paint(context).and(context.render_target.EndDraw(None, None));
+    _ = EndPaint(window, &ps);
+    result
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut State;
+            let state = Box::<State>::from_raw(raw);
+            match on_create(window, *state) {
+                Ok(context) => {
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(FALSE.0 as isize),
+            }
+        },
+        WM_DESTROY => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                let context = Box::<Context>::from_raw(raw);
+                _ = RemoveWindowSubclass(
+                    context.state.target,
+                    Some(target_subclass_proc),
+                    window.0 as usize,
+                );
+            }
+            LRESULT(0)
+        },
+        WM_TIMER => unsafe {
+            if let Some(context) = get_context(window) {
+                _ = on_timer(window, context, w_param.0);
+            }
+            LRESULT(0)
+        },
+        WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as isize),
+        WM_SETCURSOR => unsafe {
+            let mut point = POINT::default();
+            if let Some(context) = get_context(window) {
+                if GetCursorPos(&mut point).is_ok()
+                    && ScreenToClient(window, &mut point).as_bool()
+                    && hit_test_link(window, context, point)
+                {
+                    if let Ok(cursor) = LoadCursorW(None, IDC_HAND) {
+                        SetCursor(Some(cursor));
+                        return LRESULT(TRUE.0 as isize);
+                    }
+                }
+            }
+            DefWindowProcW(window, message, w_param, l_param)
+        },
+        WM_LBUTTONUP => unsafe {
+            let point = POINT {
+                x: l_param.0 as i16 as i32,
+                y: (l_param.0 >> 16) as i16 as i32,
+            };
+            let is_link_click = match get_context(window) {
+                Some(context) if hit_test_link(window, context, point) => {
+                    hide(window, context);
+                    true
+                }
+                _ => false,
+            };
+            if is_link_click {
+                on_link_click(window);
+            }
+            LRESULT(0)
+        },
+        WM_PAINT => unsafe {
+            if let Some(context) = get_context(window) {
+                let result = on_paint(window, context);
+                recover_lost_device(window, &result, || {
+                    context.recreate_device_resources(window)
+                });
+            }
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
+            if let Some(context) = get_context(window) {
+                let dc = HDC(w_param.0 as _);
+                _ = print_client(window, &context.render_target, dc, || paint(context));
+            }
+            LRESULT(0)
+        },
+        WM_ERASEBKGND => LRESULT(1),
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
@@ -1,58 +1,104 @@
+use std::collections::VecDeque;
 use std::ffi::c_void;
-use std::mem::{size_of, swap};
-use std::ptr::{null, null_mut};
+use std::fmt::{self, Display, Formatter};
+use std::mem::{replace, size_of, swap, take, ManuallyDrop};
+use std::ptr::{null, null_mut, write_volatile};
 use std::slice::from_raw_parts_mut;
+use std::sync::atomic::{compiler_fence, Ordering};
 
 use windows::core::*;
 use windows::Win32::Foundation::{
-    COLORREF, FALSE, HANDLE, HGLOBAL, HINSTANCE, HWND, LPARAM, LRESULT, POINT, RECT, SIZE, TRUE,
-    WPARAM,
+    BOOL, COLORREF, DRAGDROP_S_CANCEL, DRAGDROP_S_DROP, DRAGDROP_S_USEDEFAULTCURSORS, E_INVALIDARG,
+    FALSE, HANDLE, HGLOBAL, HINSTANCE, HWND, LPARAM, LRESULT, POINT, POINTL, RECT, SIZE, S_OK,
+    TRUE, WPARAM,
 };
 use windows::Win32::Globalization::ScriptStringAnalyse;
 use windows::Win32::Globalization::{
-    lstrcpynW, lstrlenW, u_memcpy, ScriptBreak, ScriptStringCPtoX, ScriptStringFree,
-    ScriptStringOut, ScriptStringXtoCP, ScriptString_pSize, SCRIPT_ANALYSIS, SCRIPT_LOGATTR,
-    SCRIPT_UNDEFINED, SSA_FALLBACK, SSA_GLYPHS, SSA_LINK, SSA_PASSWORD,
+    lstrcpynW, lstrlenW, u_memcpy, GetLocaleInfoEx, GetLocaleInfoW, GetNumberFormatEx,
+    MultiByteToWideChar, ScriptBreak, ScriptStringCPtoX, ScriptStringFree, ScriptStringOut,
+    ScriptStringXtoCP, ScriptString_pSize, CP_ACP, CP_OEMCP, LOCALE_IDEFAULTANSICODEPAGE,
+    LOCALE_IDEFAULTCODEPAGE, LOCALE_ILZERO, LOCALE_INEGNUMBER, LOCALE_RETURN_NUMBER,
+    LOCALE_SDECIMAL, LOCALE_SGROUPING, LOCALE_SNEGATIVESIGN, LOCALE_STHOUSAND, MB_PRECOMPOSED,
+    NUMBERFMTW, SCRIPT_ANALYSIS, SCRIPT_LOGATTR, SCRIPT_UNDEFINED, SSA_FALLBACK, SSA_GLYPHS,
+    SSA_LINK, SSA_PASSWORD,
+};
+use windows::Win32::Graphics::Direct2D::Common::{D2D1_COLOR_F, D2D_SIZE_F};
+use windows::Win32::Graphics::Direct2D::{
+    D2D1CreateFactory, ID2D1DCRenderTarget, ID2D1DeviceContext5, ID2D1Factory1, ID2D1SvgDocument,
+    D2D1_FACTORY_OPTIONS, D2D1_FACTORY_TYPE_SINGLE_THREADED,
 };
-use windows::Win32::Graphics::Direct2D::Common::D2D1_COLOR_F;
 use windows::Win32::Graphics::Gdi::{
-    AngleArc, BeginPaint, BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, CreateFontW,
-    CreatePen, CreateRoundRectRgn, CreateSolidBrush, DeleteDC, DeleteObject, EndPaint, FillRect,
-    GetBkColor, GetBkMode, GetClipBox, GetDC, GetObjectW, GetSysColor, GetTextColor,
-    GetTextExtentPoint32W, GetTextMetricsW, InflateRect, IntersectRect, InvalidateRect,
-    MapWindowPoints, MoveToEx, PatBlt, RedrawWindow, ReleaseDC, SelectObject, SetBkColor,
+    AngleArc, BeginPaint, BitBlt, ClientToScreen, CombineRgn, CreateCompatibleBitmap,
+    CreateCompatibleDC, CreateFontW, CreatePen, CreateRectRgn, CreateRoundRectRgn,
+    CreateSolidBrush, DeleteDC, DeleteObject, EndPaint, ExtSelectClipRgn, FillRect, GetBkColor,
+    GetBkMode, GetClipBox, GetDC, GetObjectW, GetSysColor, GetTextColor, GetTextExtentPoint32W,
+    GetTextMetricsW, InflateRect, IntersectRect, InvalidateRect, LineTo, MapWindowPoints, MoveToEx,
+    PatBlt, RedrawWindow, ReleaseDC, RestoreDC, SaveDC, ScreenToClient, SelectObject, SetBkColor,
     SetBkMode, SetTextColor, SetWindowRgn, TextOutW, BACKGROUND_MODE, CLEARTYPE_QUALITY,
     CLIP_DEFAULT_PRECIS, COLOR_GRAYTEXT, COLOR_HIGHLIGHT, COLOR_HIGHLIGHTTEXT, DEFAULT_CHARSET,
-    ETO_OPTIONS, FF_SWISS, HBRUSH, HDC, HFONT, HPEN, LOGFONTW, OPAQUE, OUT_OUTLINE_PRECIS,
-    PAINTSTRUCT, PATCOPY, PS_SOLID, RDW_INVALIDATE, SRCCOPY, TEXTMETRICW, VARIABLE_PITCH,
+    ETO_OPAQUE, ETO_OPTIONS, FF_SWISS, HBITMAP, HBRUSH, HDC, HFONT, HGDIOBJ, HPEN, LOGFONTW,
+    OPAQUE, OUT_OUTLINE_PRECIS, PAINTSTRUCT, PATCOPY, PS_SOLID, RDW_INVALIDATE, RGN_AND, RGN_OR,
+    SRCCOPY, TEXTMETRICW, VARIABLE_PITCH,
+};
+use windows::Win32::System::Com::{
+    CoCreateInstance, IDataObject, CLSCTX_INPROC_SERVER, DVASPECT_CONTENT, FORMATETC, STGMEDIUM,
+    STGMEDIUM_0, TYMED_HGLOBAL,
 };
-use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
 use windows::Win32::System::DataExchange::{
-    CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, SetClipboardData,
+    AddClipboardFormatListener, CloseClipboard, EmptyClipboard, GetClipboardData,
+    IsClipboardFormatAvailable, OpenClipboard, RemoveClipboardFormatListener, SetClipboardData,
+};
+use windows::Win32::System::Memory::{
+    GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+};
+use windows::Win32::System::Ole::{
+    DoDragDrop, IDropSource, IDropSource_Impl, IDropTarget, IDropTarget_Impl, RegisterDragDrop,
+    ReleaseStgMedium, RevokeDragDrop, CF_LOCALE, CF_OEMTEXT, CF_TEXT, CF_UNICODETEXT,
+    CLIPBOARD_FORMAT, DROPEFFECT, DROPEFFECT_COPY, DROPEFFECT_MOVE, DROPEFFECT_NONE,
+};
+use windows::Win32::System::SystemServices::{
+    MK_CONTROL, MK_LBUTTON, MK_SHIFT, MODIFIERKEYS_FLAGS,
 };
-use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
-use windows::Win32::System::Ole::CF_UNICODETEXT;
-use windows::Win32::System::SystemServices::MK_SHIFT;
 use windows::Win32::UI::Animation::{
-    IUIAnimationManager2, IUIAnimationTimer, IUIAnimationTimerEventHandler,
-    IUIAnimationTimerEventHandler_Impl, IUIAnimationTimerUpdateHandler,
-    IUIAnimationTransitionLibrary2, IUIAnimationVariable2, UIAnimationManager2, UIAnimationTimer,
-    UIAnimationTransitionLibrary2, UI_ANIMATION_IDLE_BEHAVIOR_DISABLE,
+    IUIAnimationManager2, IUIAnimationTimerEventHandler, IUIAnimationTimerEventHandler_Impl,
+    IUIAnimationTransitionLibrary2, IUIAnimationVariable2, UIAnimationTransitionLibrary2,
+};
+use windows::Win32::UI::Controls::{
+    SetScrollInfo, EM_GETFIRSTVISIBLELINE, EM_LINESCROLL, EM_SCROLLCARET, EM_SETREADONLY,
+    WORD_BREAK_ACTION,
 };
-use windows::Win32::UI::Controls::{SetScrollInfo, WORD_BREAK_ACTION};
 use windows::Win32::UI::Controls::{WB_ISDELIMITER, WB_LEFT, WB_RIGHT};
 use windows::Win32::UI::Input::Ime::{
-    ImmGetContext, ImmReleaseContext, ImmSetCompositionFontW, ImmSetCompositionWindow, CFS_RECT,
-    COMPOSITIONFORM, IMECHARPOSITION, IMR_QUERYCHARPOSITION,
+    ImmGetCompositionStringW, ImmGetContext, ImmReleaseContext, ImmSetCompositionFontW,
+    ImmSetCompositionWindow, ATTR_TARGET_CONVERTED, ATTR_TARGET_NOTCONVERTED, CFS_RECT,
+    COMPOSITIONFORM, GCS_COMPATTR, GCS_COMPSTR, GCS_CURSORPOS, GCS_RESULTSTR, HIMC,
+    IMECHARPOSITION, IME_COMPOSITION_STRING, IMR_QUERYCHARPOSITION,
 };
 use windows::Win32::UI::Input::KeyboardAndMouse::{
-    GetCapture, GetKeyState, ReleaseCapture, SetCapture, SetFocus, VK_BACK, VK_CONTROL, VK_DELETE,
-    VK_END, VK_HOME, VK_INSERT, VK_LEFT, VK_MENU, VK_RIGHT, VK_SHIFT,
+    DragDetect, GetCapture, GetKeyState, ReleaseCapture, SetCapture, SetFocus, VK_BACK, VK_CONTROL,
+    VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE, VK_HOME, VK_INSERT, VK_LEFT, VK_MENU, VK_RETURN,
+    VK_RIGHT, VK_SHIFT, VK_TAB, VK_UP,
+};
+use windows::Win32::UI::Shell::{
+    DefSubclassProc, RemoveWindowSubclass, SHCreateDataObject, SetWindowSubclass,
 };
 use windows::Win32::UI::WindowsAndMessaging::*;
 
-use crate::theme::TypographyStyle;
-use crate::{get_scaling_factor, QT};
+use crate::component::button::set_svg_color;
+use crate::component::menu::create_svg_document;
+use crate::component::suggestion_list::{self, WM_ACCEPT_SUGGESTION};
+use crate::component::Component;
+use crate::icon::Icon;
+use crate::theme::{convert_to_color_ref, is_high_contrast, TypographyStyle};
+use crate::{
+    handle_disabled_mouse, is_enabled, is_focus_visible, is_window_of_class, recover_lost_device,
+    render_target_properties, track_input_kind, window_class_name, CornerRadius, Dpi, InputHandle,
+    QT,
+};
+
+const CLASS_NAME: &str = "QT_INPUT";
+const SUGGESTION_TIMER_ID: usize = 1;
+const SUGGESTION_DELAY: u32 = 150;
 
 macro_rules! order_usize {
     ($x:expr, $y:expr) => {{
@@ -61,44 +107,176 @@ macro_rules! order_usize {
         }
     }};
 }
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Size {
     Small,
     Medium,
     Large,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Appearance {
     Outline,
     FilledLighter,
     FilledDarker,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Type {
     Number,
     Text,
+    /// Text is masked, cannot be copied, dragged or undone, and is withheld from `WM_GETTEXT`
+    /// unless [`allow_get_text`] is called, and must never be exposed through UI Automation.
+    /// The field is still visible to screen capture; set
+    /// `SetWindowDisplayAffinity` on the top-level window if that matters.
     Password,
 }
 
+/// The validation status shown as a glyph at the trailing edge of the input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Warning,
+    Error,
+}
+
+/// How a number input displays its value while it does not have focus, e.g. "1,234.50 €". The
+/// separators follow the user's locale. Focus strips the formatting back to the editable digits,
+/// which keep every decimal of the value and the locale's decimal separator.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NumberFormat {
+    /// Groups the integer digits, e.g. into thousands.
+    pub grouping: bool,
+    /// Rounds to this many decimals, or shows as many as the value has.
+    pub decimals: Option<u8>,
+    pub prefix: Option<PCWSTR>,
+    pub suffix: Option<PCWSTR>,
+}
+
+// A `NumberFormat` with its affixes copied, since they are used for as long as the input lives.
+struct DisplayFormat {
+    grouping: bool,
+    decimals: Option<u8>,
+    prefix: String,
+    suffix: String,
+}
+
+impl DisplayFormat {
+    unsafe fn new(format: &NumberFormat) -> Self {
+        let copy = |affix: Option<PCWSTR>| {
+            affix
+                .map(|affix| String::from_utf16_lossy(affix.as_wide()))
+                .unwrap_or_default()
+        };
+        DisplayFormat {
+            grouping: format.grouping,
+            decimals: format.decimals,
+            prefix: copy(format.prefix),
+            suffix: copy(format.suffix),
+        }
+    }
+}
+
+pub type QueryCallback = Box<dyn Fn(&str) -> Vec<String>>;
+pub type CommitCallback = Box<dyn Fn(&HWND, &str)>;
+pub type CommitValueCallback = Box<dyn Fn(&HWND, f64)>;
+
+pub struct InputEvent {
+    /// Returns the suggestions to offer for the current text; leave as `None` to disable them.
+    pub on_query: Option<QueryCallback>,
+    /// Fired with the text when Enter is pressed or a suggestion is accepted, and when focus
+    /// leaves an input whose text changed since it was last committed or focused. Never fired by
+    /// password inputs, which keep no copy of their text. It is fired once the message that
+    /// committed has been handled, so it may change the input's text or destroy the input.
+    pub on_commit: CommitCallback,
+    /// Fired after `on_commit` by number inputs whose text is a number, with its value however it
+    /// is displayed.
+    pub on_commit_value: CommitValueCallback,
+}
+
+impl Default for InputEvent {
+    fn default() -> Self {
+        InputEvent {
+            on_query: None,
+            on_commit: Box::new(|_window, _text| {}),
+            on_commit_value: Box::new(|_window, _value| {}),
+        }
+    }
+}
+
+pub struct Input;
+
+pub struct InputOptions {
+    pub width: i32,
+    pub size: Size,
+    pub appearance: Appearance,
+    pub corner: Option<CornerRadius>,
+    pub default_value: Option<PCWSTR>,
+    pub input_type: Type,
+    pub number_format: Option<NumberFormat>,
+    pub placeholder: Option<PCWSTR>,
+    pub history_size: Option<usize>,
+    pub revert_on_escape: bool,
+    pub event: InputEvent,
+}
+
+impl Component for Input {
+    type Options = InputOptions;
+
+    fn create(qt: &QT, parent_window: HWND, x: i32, y: i32, options: InputOptions) -> Result<HWND> {
+        qt.create_input(parent_window, x, y, options)
+            .map(|handle| handle.window())
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Type::Number => "number",
+            Type::Text => "text",
+            Type::Password => "password",
+        })
+    }
+}
+
 pub struct State {
     qt: QT,
     width: f32,
     size: Size,
     appearance: Appearance,
+    /// Overrides the theme's medium border radius.
+    corner: Option<CornerRadius>,
     default_value: Option<PCWSTR>,
     input_type: Type,
-    placeholder: Option<PCWSTR>,
+    number_format: Option<DisplayFormat>,
+    /// Copied, since it is painted for as long as the input is empty.
+    placeholder: Option<HSTRING>,
+    history_size: Option<usize>,
+    /// Escape reverts to the committed value, or clears the field if there is nothing to revert.
+    /// Password inputs have no committed value, so Escape always clears them.
+    revert_on_escape: bool,
+    event: InputEvent,
+}
+
+fn get_field_height(size: &Size) -> f32 {
+    match size {
+        Size::Small => 24f32,
+        Size::Medium => 32f32,
+        Size::Large => 40f32,
+    }
 }
 
 impl State {
     fn get_field_height(&self) -> f32 {
-        match self.size {
-            Size::Small => 24f32,
-            Size::Medium => 32f32,
-            Size::Large => 40f32,
-        }
+        get_field_height(&self.size)
+    }
+
+    // Shared by the window region and the painted border, so that they agree at every DPI.
+    fn get_corner_diameter(&self, dpi: Dpi, rect: &RECT) -> i32 {
+        let tokens = &self.qt.theme.tokens;
+        self.corner
+            .unwrap_or(CornerRadius::Dips(tokens.border_radius_medium))
+            .to_physical_diameter(dpi, rect.right - rect.left, rect.bottom - rect.top)
     }
 
     fn get_horizontal_padding(&self) -> f32 {
@@ -110,6 +288,14 @@ impl State {
         }
     }
 
+    fn get_status_icon_size(&self) -> f32 {
+        match self.size {
+            Size::Small => 16f32,
+            Size::Medium => 20f32,
+            Size::Large => 24f32,
+        }
+    }
+
     fn get_typography_style(&self) -> &TypographyStyle {
         let typography_styles = &self.qt.theme.typography_styles;
         match self.size {
@@ -139,18 +325,32 @@ impl StringBuffer {
             return;
         }
 
+        self.reserve(size + 1 - self.0.len());
         self.0.resize(size + 1, 0);
     }
 
+    // Grow into a fresh allocation so the old one can be wiped instead of being left to realloc.
+    fn reserve(&mut self, additional: usize) {
+        if self.0.capacity() - self.0.len() >= additional {
+            return;
+        }
+        let mut vec = Vec::<u16>::with_capacity(self.0.len() + additional);
+        vec.extend_from_slice(&self.0);
+        swap(&mut self.0, &mut vec);
+        wipe(&mut vec);
+    }
+
     fn empty(&mut self) {
         if self.0.len() > 32 {
-            self.0 = vec![0];
+            let mut vec = replace(&mut self.0, vec![0]);
+            wipe(&mut vec);
         } else {
-            self.0[0] = 0;
+            wipe(&mut self.0);
         }
     }
 
     fn insert_at(&mut self, at: usize, to_insert: &[u16]) {
+        self.reserve(to_insert.len());
         self.0.splice(at..at, to_insert.iter().cloned());
     }
 
@@ -174,10 +374,26 @@ impl StringBuffer {
     }
 }
 
+impl Drop for StringBuffer {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+// Zero the whole allocation, including the spare capacity left behind by removals.
+fn wipe(vec: &mut Vec<u16>) {
+    for a in vec.iter_mut() {
+        unsafe { write_volatile(a, 0) };
+    }
+    for a in vec.spare_capacity_mut() {
+        unsafe { write_volatile(a.as_mut_ptr(), 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
 pub struct Context {
     state: State,
     animation_manager: IUIAnimationManager2,
-    animation_timer: IUIAnimationTimer,
     transition_library: IUIAnimationTransitionLibrary2,
     bottom_focus_border: IUIAnimationVariable2,
     cached_text_length: Option<usize>,
@@ -190,23 +406,61 @@ pub struct Context {
     selection_end: usize,
     is_captured: bool,
     is_focused: bool,
+    focus_visible: bool,
+    is_overwrite: bool,
+    allow_get_text: bool,
+    is_drag_source: bool,
+    drop_position: Option<usize>,
+    composition_start: usize,
+    composition_length: usize,
+    composition_attributes: Vec<u8>,
+    suggestion_list: Option<HWND>,
+    is_accepting_suggestion: bool,
+    history: Option<VecDeque<String>>,
+    history_index: Option<usize>,
+    history_draft: String,
+    is_recalling_history: bool,
+    committed_text: String,
+    // Commits whose callbacks have not run yet, with the value of a number input's text.
+    pending_commits: VecDeque<(String, Option<f64>)>,
+    // The value behind a formatted number and the text it was formatted to, so that focus restores
+    // the digits the display rounded away.
+    formatted_number: Option<(f64, String)>,
+    status: Option<Status>,
+    status_svg: Option<ID2D1SvgDocument>,
+    status_render_target: ID2D1DCRenderTarget,
     format_rect: RECT,
     font: HFONT,
     background_color: COLORREF,
     background_color_brush: HBRUSH,
     border_pen: HPEN,
     border_pen_focused: HPEN,
+    border_pen_focus_ring: HPEN,
     border_bottom_pen: HPEN,
     border_bottom_color_focused_brush: HBRUSH,
+    caret_bitmap: HBITMAP,
+    caret_width: i32,
     text_color: COLORREF,
     line_height: i32,
     char_width: i32,
     text_width: i32,
     log_attribute: Vec<SCRIPT_LOGATTR>,
     ssa: *mut c_void,
+    // Set by edits, which leave scrolling to the caret and the scroll range to the next paint, so
+    // a burst of them shapes the text once.
+    is_layout_pending: bool,
 }
 
 impl Context {
+    // Only the status icon is drawn with Direct2D, through a DC render target. Setting the status
+    // again recreates its SVG and invalidates the input.
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let factory = self.status_render_target.GetFactory()?;
+        self.status_render_target =
+            factory.CreateDCRenderTarget(&render_target_properties(Dpi::for_window(window)))?;
+        on_set_status(window, self, self.status)
+    }
+
     unsafe fn get_text_length(&mut self) -> usize {
         match self.cached_text_length {
             None => {
@@ -217,6 +471,11 @@ impl Context {
             Some(text_length) => text_length,
         }
     }
+    unsafe fn get_text(&mut self) -> String {
+        let length = self.get_text_length();
+        String::from_utf16_lossy(&self.buffer.0[..length])
+    }
+
     unsafe fn invalidate_uniscribe_data(&mut self) -> Result<()> {
         if !self.ssa.is_null() {
             ScriptStringFree(&mut self.ssa)?;
@@ -238,48 +497,68 @@ impl Context {
 }
 
 impl QT {
+    /// The height in DIPs of an input of this size, which does not depend on its content.
+    pub fn measure_input(&self, size: &Size) -> f32 {
+        get_field_height(size)
+    }
+
+    /// `default_value`, `placeholder` and the affixes of `number_format` only need to be valid for
+    /// the duration of this call: the default value is inserted while the window is created and
+    /// the others are copied. `number_format` requires [`Type::Number`].
     pub fn create_input(
         &self,
         parent_window: HWND,
         x: i32,
         y: i32,
-        width: i32,
-        size: &Size,
-        appearance: &Appearance,
-        default_value: Option<PCWSTR>,
-        input_type: &Type,
-        placeholder: Option<PCWSTR>,
-    ) -> Result<HWND> {
-        let class_name: PCWSTR = w!("QT_INPUT");
+        options: InputOptions,
+    ) -> Result<InputHandle> {
+        // Up and Down recall history, so it is only offered on plain text fields.
+        if options.history_size.is_some() && options.input_type != Type::Text {
+            return Err(E_INVALIDARG.into());
+        }
+        if options.number_format.is_some() && options.input_type != Type::Number {
+            return Err(E_INVALIDARG.into());
+        }
+        let class_name = window_class_name(CLASS_NAME, Some(window_proc));
         unsafe {
             let window_class = WNDCLASSEXW {
                 cbSize: size_of::<WNDCLASSEXW>() as u32,
-                lpszClassName: class_name,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
                 style: CS_CLASSDC | CS_DBLCLKS,
                 lpfnWndProc: Some(window_proc),
                 hCursor: LoadCursorW(None, IDC_IBEAM)?,
                 ..Default::default()
             };
             RegisterClassExW(&window_class);
-            let scaling_factor = get_scaling_factor(parent_window);
+            let dpi = Dpi::for_window(parent_window);
             let boxed = Box::new(State {
                 qt: self.clone(),
-                width: width as f32 / scaling_factor,
-                size: *size,
-                appearance: *appearance,
-                default_value,
-                input_type: *input_type,
-                placeholder,
+                width: dpi.to_dip(options.width),
+                size: options.size,
+                appearance: options.appearance,
+                corner: options.corner,
+                default_value: options.default_value,
+                input_type: options.input_type,
+                number_format: options
+                    .number_format
+                    .as_ref()
+                    .map(|format| DisplayFormat::new(format)),
+                placeholder: options
+                    .placeholder
+                    .map(|placeholder| HSTRING::from_wide(placeholder.as_wide())),
+                history_size: options.history_size,
+                revert_on_escape: options.revert_on_escape,
+                event: options.event,
             });
             CreateWindowExW(
                 WINDOW_EX_STYLE::default(),
-                class_name,
+                &class_name,
                 w!(""),
                 WS_TABSTOP | WS_VISIBLE | WS_CHILD,
                 x,
                 y,
-                (boxed.width * scaling_factor) as i32,
-                (boxed.get_field_height() * scaling_factor) as i32,
+                dpi.to_physical(boxed.width),
+                dpi.to_physical(boxed.get_field_height()),
                 Some(parent_window),
                 None,
                 Some(HINSTANCE(
@@ -287,6 +566,7 @@ impl QT {
                 )),
                 Some(Box::<State>::into_raw(boxed) as _),
             )
+            .map(InputHandle::new)
         }
     }
 }
@@ -396,9 +676,9 @@ unsafe fn replace_selection(
     replace: &[u16],
     honor_limit: bool,
 ) -> Result<()> {
+    let can_undo = can_undo && context.state.input_type != Type::Password;
     let mut start = context.selection_start;
     let mut end = context.selection_end;
-    context.invalidate_uniscribe_data()?;
     let mut replace_length = replace.len();
     if start == end && replace_length == 0 {
         return Ok(());
@@ -434,8 +714,6 @@ unsafe fn replace_selection(
     }
 
     let fw = context.format_rect.right - context.format_rect.left;
-    context.invalidate_uniscribe_data()?;
-    calculate_line_width(window, context)?;
     if honor_limit && context.text_width > fw {
         while (context.text_width > fw) && start + replace_length >= start {
             context.buffer.remove_at(start + replace_length - 1);
@@ -501,18 +779,43 @@ unsafe fn replace_selection(
         }
     }
 
+    // The whole line is invalidated, so the caret moves without measuring the old selection.
     start = start + replace.len();
-    set_selection(window, context, Some(start), Some(start))?;
+    context.selection_start = start;
+    context.selection_end = start;
     _ = InvalidateRect(Some(window), Some(&context.format_rect), false);
+    context.is_layout_pending = true;
 
-    scroll_caret(window, context)?;
-    update_scroll_info(window, context);
+    notify_parent(window, EN_CHANGE);
 
-    context.invalidate_uniscribe_data()?;
+    if !context.is_recalling_history {
+        context.history_index = None;
+    }
+    if context.suggestion_list.is_some() && context.is_focused && !context.is_accepting_suggestion {
+        SetTimer(Some(window), SUGGESTION_TIMER_ID, SUGGESTION_DELAY, None);
+    }
 
     Ok(())
 }
 
+unsafe fn notify_parent(window: HWND, code: u32) {
+    if let Ok(parent) = GetParent(window) {
+        let id = GetDlgCtrlID(window) as usize & 0xffff;
+        SendMessageW(
+            parent,
+            WM_COMMAND,
+            Some(WPARAM(((code as usize & 0xffff) << 16) | id)),
+            Some(LPARAM(window.0 as isize)),
+        );
+    }
+}
+
+// How many times this thread shaped input text, so tests can check that edits reuse the analysis.
+#[cfg(test)]
+thread_local! {
+    static ANALYSES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 unsafe fn update_uniscribe_data(
     window: HWND,
     context: &mut Context,
@@ -563,15 +866,42 @@ unsafe fn update_uniscribe_data(
         }
 
         SelectObject(udc, old_font);
-        if dc.map(|x| x == udc).unwrap_or(false) {
+        if dc.is_none() {
             ReleaseDC(Some(window), udc);
         }
+        #[cfg(test)]
+        ANALYSES.set(ANALYSES.get() + 1);
     }
     Ok(context.ssa)
 }
 
+unsafe fn get_caret_width(window: HWND, context: &mut Context, position: usize) -> i32 {
+    if !context.is_overwrite {
+        return Dpi::for_window(window).to_physical(1.0);
+    }
+    let length = context.get_text_length();
+    if position < length {
+        if let Ok(ssa) = update_uniscribe_data(window, context, None) {
+            if !ssa.is_null() {
+                if let (Ok(leading), Ok(trailing)) = (
+                    ScriptStringCPtoX(ssa, position as i32, false),
+                    ScriptStringCPtoX(ssa, position as i32, true),
+                ) {
+                    return (trailing - leading).abs().max(1);
+                }
+            }
+        }
+    }
+    context.char_width
+}
+
 unsafe fn set_caret_position(window: HWND, context: &mut Context, position: usize) -> Result<()> {
     if context.is_focused {
+        let width = get_caret_width(window, context, position);
+        if width != context.caret_width {
+            create_caret(window, context, width)?;
+            ShowCaret(Some(window))?;
+        }
         let res = position_from_char(window, context, position)?;
         SetCaretPos(res.x, res.y)?;
         update_imm_composition_window(window, context, res.x, res.y);
@@ -579,6 +909,16 @@ unsafe fn set_caret_position(window: HWND, context: &mut Context, position: usiz
     Ok(())
 }
 
+// Shapes the text edited since the last paint.
+unsafe fn update_layout(window: HWND, context: &mut Context) -> Result<()> {
+    if context.is_layout_pending {
+        context.is_layout_pending = false;
+        scroll_caret(window, context)?;
+        update_scroll_info(window, context);
+    }
+    Ok(())
+}
+
 unsafe fn scroll_caret(window: HWND, context: &mut Context) -> Result<()> {
     let mut x = position_from_char(window, context, context.selection_end)?.x;
     let format_width = context.format_rect.right - context.format_rect.left;
@@ -610,30 +950,279 @@ unsafe fn scroll_caret(window: HWND, context: &mut Context) -> Result<()> {
     Ok(())
 }
 
+// The range and position are in pixels, matching `scroll_offset_pixels`.
 unsafe fn update_scroll_info(window: HWND, context: &mut Context) {
+    let text_width = get_text_pixel_width(window, context).unwrap_or(0);
+    let offset = get_scroll_offset_pixels(window, context).unwrap_or(0);
     let si = SCROLLINFO {
         cbSize: size_of::<SCROLLINFO>() as u32,
         fMask: SIF_PAGE | SIF_POS | SIF_RANGE | SIF_DISABLENOSCROLL,
         nMin: 0,
-        nMax: context.text_width - 1,
+        nMax: text_width - 1,
         nPage: (context.format_rect.right - context.format_rect.left) as u32,
-        nPos: context.x_offset as i32,
-        nTrackPos: context.x_offset as i32,
+        nPos: offset,
+        nTrackPos: offset,
     };
     SetScrollInfo(window, SB_HORZ, &si, true);
 }
 
-unsafe fn set_text(window: HWND, context: &mut Context, text: PCWSTR) -> Result<()> {
+unsafe fn get_text_pixel_width(window: HWND, context: &mut Context) -> Result<i32> {
+    let ssa = update_uniscribe_data(window, context, None)?;
+    if ssa.is_null() {
+        Ok(0)
+    } else {
+        Ok((*ScriptString_pSize(ssa)).cx)
+    }
+}
+
+unsafe fn get_scroll_offset_pixels(window: HWND, context: &mut Context) -> Result<i32> {
+    Ok(context.format_rect.left - position_from_char(window, context, 0)?.x)
+}
+
+unsafe fn set_scroll_offset(window: HWND, context: &mut Context, offset: usize) -> Result<()> {
+    let offset = offset.min(context.get_text_length());
+    if context.x_offset != offset {
+        context.x_offset = offset;
+        _ = InvalidateRect(Some(window), Some(&context.format_rect), true);
+        if context.is_focused {
+            set_caret_position(window, context, context.selection_end)?;
+        }
+        update_scroll_info(window, context);
+    }
+    Ok(())
+}
+
+unsafe fn scroll_to_pixels(window: HWND, context: &mut Context, pixels: i32) -> Result<()> {
+    let ssa = update_uniscribe_data(window, context, None)?;
+    if ssa.is_null() || pixels <= 0 {
+        return set_scroll_offset(window, context, 0);
+    }
+    let mut index = 0;
+    let mut trailing = 0;
+    ScriptStringXtoCP(ssa, pixels, &mut index, &mut trailing)?;
+    set_scroll_offset(window, context, (index + trailing).max(0) as usize)
+}
+
+// With `preserve_position`, the caret and scroll offset survive as long as they fall within the
+// prefix shared with the old text, and a caret at the end of the old text follows it to the new
+// end, so appending keeps the tail in view.
+unsafe fn set_text(
+    window: HWND,
+    context: &mut Context,
+    text: PCWSTR,
+    preserve_position: bool,
+) -> Result<()> {
+    let old_length = context.get_text_length();
+    let new_text = text.as_wide();
+    let prefix_length = context.buffer.0[..old_length]
+        .iter()
+        .zip(new_text)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let caret = context.selection_end;
+    let x_offset = context.x_offset;
+
     set_selection(window, context, Some(0), None)?;
-    replace_selection(window, context, false, text.as_wide(), false)?;
-    context.x_offset = 0;
-    set_selection(window, context, Some(0), Some(0))?;
+    replace_selection(window, context, false, new_text, false)?;
+    if preserve_position {
+        let caret = if caret == old_length && prefix_length == old_length {
+            new_text.len()
+        } else {
+            caret.min(prefix_length)
+        };
+        context.x_offset = x_offset.min(prefix_length);
+        set_selection(window, context, Some(caret), Some(caret))?;
+    } else {
+        context.x_offset = 0;
+        set_selection(window, context, Some(0), Some(0))?;
+    }
     scroll_caret(window, context)?;
     update_scroll_info(window, context);
-    context.invalidate_uniscribe_data()?;
     Ok(())
 }
 
+unsafe fn locale_text(lc_type: u32) -> String {
+    let mut buffer = [0u16; 16];
+    let length = GetLocaleInfoEx(PCWSTR::null(), lc_type, Some(&mut buffer));
+    String::from_utf16_lossy(&buffer[..(length - 1).max(0) as usize])
+}
+
+unsafe fn locale_number(lc_type: u32) -> u32 {
+    let mut number = 0u32;
+    GetLocaleInfoEx(
+        PCWSTR::null(),
+        lc_type | LOCALE_RETURN_NUMBER,
+        Some(from_raw_parts_mut(
+            &mut number as *mut u32 as *mut u16,
+            size_of::<u32>() / size_of::<u16>(),
+        )),
+    );
+    number
+}
+
+// The separators the user's locale writes numbers with.
+struct NumberLocale {
+    decimal: String,
+    thousand: String,
+    negative_sign: String,
+}
+
+impl NumberLocale {
+    unsafe fn current() -> Self {
+        NumberLocale {
+            decimal: locale_text(LOCALE_SDECIMAL),
+            thousand: locale_text(LOCALE_STHOUSAND),
+            negative_sign: locale_text(LOCALE_SNEGATIVESIGN),
+        }
+    }
+}
+
+// A value rounded to the format's decimals with '.' as the decimal point, which is what
+// `GetNumberFormatEx` expects.
+fn raw_number(value: f64, format: &DisplayFormat) -> String {
+    match format.decimals {
+        Some(decimals) => format!("{:.*}", decimals as usize, value),
+        None => value.to_string(),
+    }
+}
+
+// The editable form of a value: every digit it has, ungrouped, with the locale's decimal
+// separator and negative sign.
+fn editable_number(value: f64, locale: &NumberLocale) -> String {
+    let text = value.to_string();
+    let text = match text.strip_prefix('-') {
+        Some(magnitude) => format!("{}{}", locale.negative_sign, magnitude),
+        None => text,
+    };
+    text.replace('.', &locale.decimal)
+}
+
+// Reads the editable form. It has no grouping, so '.' is taken as a decimal point as well, e.g.
+// in text written by the application or copied from elsewhere.
+fn parse_editable_number(text: &str, locale: &NumberLocale) -> Option<f64> {
+    let mut text = text.trim().to_string();
+    for (separator, replacement) in [(&locale.decimal, "."), (&locale.negative_sign, "-")] {
+        if !separator.is_empty() {
+            text = text.replace(separator.as_str(), replacement);
+        }
+    }
+    text.parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+// Converts `LOCALE_SGROUPING` to `NUMBERFMTW.Grouping`: "3;0" repeats groups of three and is 3,
+// "3;2;0" is 32, and "3", which groups only once, is 30.
+fn number_grouping(locale_grouping: &str) -> u32 {
+    let digits: String = locale_grouping
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect();
+    match digits.strip_suffix('0') {
+        Some(repeated) => repeated.parse().unwrap_or(0),
+        None => digits.parse::<u32>().map_or(0, |digits| digits * 10),
+    }
+}
+
+unsafe fn format_number(value: f64, format: &DisplayFormat) -> Option<String> {
+    let raw = HSTRING::from(raw_number(value, format));
+    let decimals = raw
+        .to_string()
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len());
+    let grouping = if format.grouping {
+        number_grouping(&locale_text(LOCALE_SGROUPING))
+    } else {
+        0
+    };
+    let mut decimal_separator: Vec<u16> = HSTRING::from(locale_text(LOCALE_SDECIMAL)).to_vec();
+    decimal_separator.push(0);
+    let mut thousand_separator: Vec<u16> = HSTRING::from(locale_text(LOCALE_STHOUSAND)).to_vec();
+    thousand_separator.push(0);
+    let number_format = NUMBERFMTW {
+        NumDigits: decimals as u32,
+        LeadingZero: locale_number(LOCALE_ILZERO),
+        Grouping: grouping,
+        lpDecimalSep: PWSTR(decimal_separator.as_mut_ptr()),
+        lpThousandSep: PWSTR(thousand_separator.as_mut_ptr()),
+        NegativeOrder: locale_number(LOCALE_INEGNUMBER),
+    };
+    let length = GetNumberFormatEx(PCWSTR::null(), 0, &raw, Some(&number_format), None);
+    if length <= 0 {
+        return None;
+    }
+    let mut buffer = vec![0u16; length as usize];
+    GetNumberFormatEx(
+        PCWSTR::null(),
+        0,
+        &raw,
+        Some(&number_format),
+        Some(&mut buffer),
+    );
+    let number = String::from_utf16_lossy(&buffer[..length as usize - 1]);
+    Some(format!("{}{}{}", format.prefix, number, format.suffix))
+}
+
+// Reads a number as the user's locale displays it, with or without the format's prefix and
+// suffix.
+fn parse_number(text: &str, format: &DisplayFormat, locale: &NumberLocale) -> Option<f64> {
+    let text = text.trim();
+    let text = text.strip_prefix(format.prefix.as_str()).unwrap_or(text);
+    let text = text.strip_suffix(format.suffix.as_str()).unwrap_or(text);
+    let mut text = text.to_string();
+    for (separator, replacement) in [
+        (&locale.thousand, ""),
+        (&locale.decimal, "."),
+        (&locale.negative_sign, "-"),
+    ] {
+        if !separator.is_empty() {
+            text = text.replace(separator.as_str(), replacement);
+        }
+    }
+    text.retain(|a| !a.is_whitespace());
+    // Negative numbers may also be written "(1.5)" or "1.5-".
+    if let Some(number) = text
+        .strip_prefix('(')
+        .and_then(|text| text.strip_suffix(')'))
+        .or_else(|| text.strip_suffix('-'))
+    {
+        text = format!("-{}", number);
+    }
+    text.parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+unsafe fn apply_number_format(window: HWND, context: &mut Context) -> Result<()> {
+    let text = context.get_text();
+    let Some(format) = &context.state.number_format else {
+        return Ok(());
+    };
+    let Some(value) = parse_editable_number(&text, &NumberLocale::current()) else {
+        return Ok(());
+    };
+    let Some(text) = format_number(value, format) else {
+        return Ok(());
+    };
+    context.formatted_number = Some((value, text.clone()));
+    let text = HSTRING::from(text);
+    set_text(window, context, PCWSTR(text.as_ptr()), false)
+}
+
+unsafe fn strip_number_format(window: HWND, context: &mut Context) -> Result<()> {
+    let text = context.get_text();
+    let Some(format) = &context.state.number_format else {
+        return Ok(());
+    };
+    let locale = NumberLocale::current();
+    let value = match context.formatted_number.take() {
+        // Unchanged since it was formatted, so the value has every digit the display rounded.
+        Some((value, formatted)) if formatted == text => value,
+        _ => match parse_number(&text, format, &locale) {
+            Some(value) => value,
+            None => return Ok(()),
+        },
+    };
+    let text = HSTRING::from(editable_number(value, &locale));
+    set_text(window, context, PCWSTR(text.as_ptr()), false)
+}
+
 unsafe fn adjust_format_rect(window: HWND, context: &mut Context) -> Result<()> {
     context.format_rect.right = context
         .format_rect
@@ -646,8 +1235,8 @@ unsafe fn adjust_format_rect(window: HWND, context: &mut Context) -> Result<()>
     context.format_rect.bottom = context.format_rect.top + context.line_height;
     let mut client_rect = RECT::default();
     GetClientRect(window, &mut client_rect)?;
-    let scaling_factor = get_scaling_factor(window);
-    let border_bottom_width = (2.0 * scaling_factor) as i32;
+    let dpi = Dpi::for_window(window);
+    let border_bottom_width = dpi.to_physical(2.0);
     context.format_rect.bottom = context
         .format_rect
         .bottom
@@ -656,10 +1245,9 @@ unsafe fn adjust_format_rect(window: HWND, context: &mut Context) -> Result<()>
 }
 
 unsafe fn set_rect_np(window: HWND, context: &mut Context) -> Result<()> {
-    let scaling_factor = get_scaling_factor(window);
+    let dpi = Dpi::for_window(window);
     GetClientRect(window, &mut context.format_rect)?;
-    let corner_diameter =
-        (context.state.qt.theme.tokens.border_radius_medium * scaling_factor * 2f32) as i32;
+    let corner_diameter = context.state.get_corner_diameter(dpi, &context.format_rect);
     let region = CreateRoundRectRgn(
         0,
         0,
@@ -669,15 +1257,21 @@ unsafe fn set_rect_np(window: HWND, context: &mut Context) -> Result<()> {
         corner_diameter,
     );
     SetWindowRgn(window, Some(region), true);
-    let border_width = (1.0 * scaling_factor) as i32;
+    let border_width = dpi.to_physical(1.0);
     _ = InflateRect(&mut context.format_rect, -border_width, 0);
     if context.format_rect.bottom - context.format_rect.top > context.line_height + 2 * border_width
     {
         _ = InflateRect(&mut context.format_rect, 0, -border_width);
     }
-    let horizontal_padding = (context.state.get_horizontal_padding() * scaling_factor) as i32;
+    let horizontal_padding = dpi.to_physical(context.state.get_horizontal_padding());
     context.format_rect.left = context.format_rect.left + horizontal_padding;
     context.format_rect.right = context.format_rect.right - horizontal_padding;
+    if context.status.is_some() {
+        context.format_rect.right -= dpi.to_physical(
+            context.state.get_status_icon_size()
+                + context.state.qt.theme.tokens.spacing_horizontal_xs,
+        );
+    }
     adjust_format_rect(window, context)
 }
 
@@ -829,19 +1423,28 @@ unsafe fn move_backward(window: HWND, context: &mut Context, extend: bool) -> Re
     scroll_caret(window, context)?;
     Ok(())
 }
-fn convert_to_color_ref(from: &D2D1_COLOR_F) -> COLORREF {
-    let r = (from.r * 255.0) as u32;
-    let g = (from.g * 255.0) as u32;
-    let b = (from.b * 255.0) as u32;
-    COLORREF(b << 16 | g << 8 | r)
+// GDI centers wide pens on the path, and the border path runs along the client edge, so the outer
+// half of the pen falls outside the window region. The pen is twice the border width so that
+// exactly one border width remains visible inside the region at every scaling factor.
+unsafe fn create_border_pen(dpi: Dpi, color: &D2D1_COLOR_F) -> HPEN {
+    CreatePen(
+        PS_SOLID,
+        dpi.to_physical(1.0) * 2,
+        convert_to_color_ref(color),
+    )
+}
+
+unsafe fn create_focus_ring_pen(dpi: Dpi, color: &D2D1_COLOR_F) -> HPEN {
+    CreatePen(
+        PS_SOLID,
+        dpi.to_physical(2.0) * 2,
+        convert_to_color_ref(color),
+    )
 }
 
-unsafe fn create_font_from_typography_style(
-    typography_style: &TypographyStyle,
-    scaling_factor: f32,
-) -> HFONT {
+unsafe fn create_font_from_typography_style(typography_style: &TypographyStyle, dpi: Dpi) -> HFONT {
     CreateFontW(
-        (typography_style.line_height * scaling_factor) as i32,
+        dpi.to_physical(typography_style.line_height),
         0,                                      // Width of the font (0 for default)
         0,                                      // Angle of escapement
         0,                                      // Orientation angle
@@ -872,9 +1475,9 @@ impl IUIAnimationTimerEventHandler_Impl for AnimationTimerEventHandler_Impl {
         unsafe {
             let mut rc = RECT::default();
             GetClientRect(self.window, &mut rc)?;
-            let scaling_factor = get_scaling_factor(self.window);
-            let border_width = (1.0 * scaling_factor) as i32;
-            let border_bottom_width = (2.0 * scaling_factor) as i32;
+            let dpi = Dpi::for_window(self.window);
+            let border_width = dpi.to_physical(1.0);
+            let border_bottom_width = dpi.to_physical(2.0);
             _ = InvalidateRect(
                 Some(self.window),
                 Some(&RECT {
@@ -896,9 +1499,9 @@ impl IUIAnimationTimerEventHandler_Impl for AnimationTimerEventHandler_Impl {
 
 unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
     let tokens = &state.qt.theme.tokens;
-    let scaling_factor = get_scaling_factor(window);
+    let dpi = Dpi::for_window(window);
     let typography_style = state.get_typography_style();
-    let font = create_font_from_typography_style(typography_style, scaling_factor);
+    let font = create_font_from_typography_style(typography_style, dpi);
     let dc = GetDC(Some(window));
     let old_font = SelectObject(dc, font.into());
     let mut tm = TEXTMETRICW::default();
@@ -907,45 +1510,33 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
     }
     SelectObject(dc, old_font);
     ReleaseDC(Some(window), dc);
-    let animation_timer: IUIAnimationTimer =
-        CoCreateInstance(&UIAnimationTimer, None, CLSCTX_INPROC_SERVER)?;
     let transition_library: IUIAnimationTransitionLibrary2 =
         CoCreateInstance(&UIAnimationTransitionLibrary2, None, CLSCTX_INPROC_SERVER)?;
-    let animation_manager: IUIAnimationManager2 =
-        CoCreateInstance(&UIAnimationManager2, None, CLSCTX_INPROC_SERVER)?;
-    let timer_update_handler = animation_manager.cast::<IUIAnimationTimerUpdateHandler>()?;
-    animation_timer
-        .SetTimerUpdateHandler(&timer_update_handler, UI_ANIMATION_IDLE_BEHAVIOR_DISABLE)?;
+    let animation_manager = state.qt.animation_manager()?;
     let timer_event_handler: IUIAnimationTimerEventHandler =
         AnimationTimerEventHandler { window }.into();
-    animation_timer.SetTimerEventHandler(&timer_event_handler)?;
+    state.qt.register_animation(window, &timer_event_handler);
     let bottom_focus_border = animation_manager.CreateAnimationVariable(0.0)?;
     let background_color = match state.appearance {
         Appearance::Outline => convert_to_color_ref(&tokens.color_neutral_background1),
         Appearance::FilledLighter => convert_to_color_ref(&tokens.color_neutral_background1),
         Appearance::FilledDarker => convert_to_color_ref(&tokens.color_neutral_background3),
     };
-    let border_pen = CreatePen(
-        PS_SOLID,
-        (1.0 * scaling_factor * 2f32) as i32,
-        convert_to_color_ref(&tokens.color_neutral_stroke1),
-    );
-    let border_pen_focused = CreatePen(
-        PS_SOLID,
-        (1.0 * scaling_factor * 2f32) as i32,
-        convert_to_color_ref(&tokens.color_neutral_stroke1_pressed),
-    );
-    let border_bottom_pen = CreatePen(
-        PS_SOLID,
-        (1.0 * scaling_factor * 2f32) as i32,
-        convert_to_color_ref(&tokens.color_neutral_stroke_accessible),
-    );
+    let border_pen = create_border_pen(dpi, &tokens.color_neutral_stroke1);
+    let border_pen_focused = create_border_pen(dpi, &tokens.color_neutral_stroke1_pressed);
+    let border_pen_focus_ring = create_focus_ring_pen(dpi, &tokens.color_stroke_focus2);
+    let border_bottom_pen = create_border_pen(dpi, &tokens.color_neutral_stroke_accessible);
     let border_bottom_focused_color = convert_to_color_ref(&tokens.color_compound_brand_stroke);
     let text_color = convert_to_color_ref(&tokens.color_neutral_foreground1);
+    let history = state.history_size.map(VecDeque::with_capacity);
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        Some(&D2D1_FACTORY_OPTIONS::default()),
+    )?;
+    let status_render_target = factory.CreateDCRenderTarget(&render_target_properties(dpi))?;
     Ok(Context {
         state,
         animation_manager,
-        animation_timer,
         transition_library,
         bottom_focus_border,
         cached_text_length: None,
@@ -958,24 +1549,58 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
         selection_end: 0,
         is_captured: false,
         is_focused: false,
+        focus_visible: false,
+        is_overwrite: false,
+        allow_get_text: false,
+        is_drag_source: false,
+        drop_position: None,
+        composition_start: 0,
+        composition_length: 0,
+        composition_attributes: Vec::new(),
+        suggestion_list: None,
+        is_accepting_suggestion: false,
+        history,
+        history_index: None,
+        history_draft: String::new(),
+        is_recalling_history: false,
+        committed_text: String::new(),
+        pending_commits: VecDeque::new(),
+        formatted_number: None,
+        status: None,
+        status_svg: None,
+        status_render_target,
         format_rect: RECT::default(),
         font,
         background_color,
         background_color_brush: CreateSolidBrush(background_color),
         border_pen,
         border_pen_focused,
+        border_pen_focus_ring,
         border_bottom_pen,
         border_bottom_color_focused_brush: CreateSolidBrush(border_bottom_focused_color),
+        caret_bitmap: HBITMAP::default(),
+        caret_width: 0,
         text_color,
         line_height: tm.tmHeight,
         char_width: tm.tmAveCharWidth,
         text_width: 0,
         log_attribute: Vec::new(),
         ssa: null_mut(),
+        is_layout_pending: false,
     })
 }
 
+/// Whether the text may change. As with a native edit control, `EM_SETREADONLY` toggles the
+/// `ES_READONLY` style.
+unsafe fn is_editable(window: HWND) -> bool {
+    is_enabled(window) && GetWindowLongW(window, GWL_STYLE) & ES_READONLY == 0
+}
+
 unsafe fn on_char(window: HWND, context: &mut Context, char: u16) -> Result<()> {
+    // Copying is the only shortcut that leaves the text alone.
+    if char != 0x03 && !is_editable(window) {
+        return Ok(());
+    }
     let control = GetKeyState(VK_CONTROL.0 as i32) < 0;
     const BACK: u16 = VK_BACK.0;
     match char {
@@ -1013,23 +1638,41 @@ unsafe fn on_char(window: HWND, context: &mut Context, char: u16) -> Result<()>
             SendMessageW(window, WM_UNDO, None, None);
         }
         _ => {
-            if let Type::Number = context.state.input_type {
-            } else {
-                if char >= ' ' as u16 && char != 127 {
-                    replace_selection(window, context, true, &[char], true)?;
+            let accepted = match context.state.input_type {
+                Type::Number => {
+                    (char >= '0' as u16 && char <= '9' as u16)
+                        || char == '.' as u16
+                        || char == '-' as u16
+                }
+                _ => char >= ' ' as u16 && char != 127,
+            };
+            if accepted {
+                let length = context.get_text_length();
+                let position = context.selection_end;
+                if context.is_overwrite
+                    && context.selection_start == position
+                    && position < length
+                    && !(0xdc00..0xe000).contains(&char)
+                {
+                    // Select the character being overwritten so the replacement can be undone.
+                    let high_surrogate = (0xd800..0xdc00).contains(&context.buffer.0[position]);
+                    let end = if high_surrogate && position + 1 < length {
+                        position + 2
+                    } else {
+                        position + 1
+                    };
+                    set_selection(window, context, Some(position), Some(end))?;
                 }
+                replace_selection(window, context, true, &[char], true)?;
             }
         }
     }
     Ok(())
 }
 
-unsafe fn on_copy(window: HWND, context: &mut Context) -> Result<()> {
+unsafe fn copy_selection_to_global(context: &Context) -> Result<HGLOBAL> {
     let start = context.selection_start.min(context.selection_end);
     let end = context.selection_start.max(context.selection_end);
-    if end == start {
-        return Ok(());
-    }
     let length = end - start;
     let hdst = GlobalAlloc(GMEM_MOVEABLE, (length + 1) * size_of::<u16>())?;
     let dst = GlobalLock(hdst);
@@ -1040,81 +1683,305 @@ unsafe fn on_copy(window: HWND, context: &mut Context) -> Result<()> {
     );
     *(dst as *mut u16).offset(length as isize) = 0;
     GlobalUnlock(hdst).or_else(|error| error.code().ok())?;
+    Ok(hdst)
+}
+
+// Returns whether anything was put on the clipboard.
+unsafe fn on_copy(window: HWND, context: &mut Context) -> Result<bool> {
+    if context.selection_start == context.selection_end
+        || context.state.input_type == Type::Password
+    {
+        return Ok(false);
+    }
+    let hdst = copy_selection_to_global(context)?;
     OpenClipboard(Some(window))?;
     EmptyClipboard()?;
     SetClipboardData(CF_UNICODETEXT.0 as u32, Some(HANDLE(hdst.0 as _)))?;
     CloseClipboard()?;
-    Ok(())
+    Ok(true)
 }
 
 unsafe fn on_cut(window: HWND, context: &mut Context) -> Result<()> {
-    on_copy(window, context)?;
+    if !is_editable(window) {
+        return Ok(());
+    }
+    _ = on_copy(window, context)?;
     clear(window, context)?;
     Ok(())
 }
 
-unsafe fn on_paste(window: HWND, context: &mut Context) -> Result<()> {
-    OpenClipboard(Some(window))?;
-    let hsrc = GetClipboardData(CF_UNICODETEXT.0 as u32)?;
-    if !hsrc.is_invalid() {
-        let src = GlobalLock(HGLOBAL(hsrc.0 as _));
-        let string = PCWSTR::from_raw(src as _);
-        let mut len = lstrlenW(string) as usize;
-        if let Some(position) = string.as_wide().iter().position(|a| *a == '\n' as u16) {
-            len = position;
-            if len > 0 && string.as_wide()[len - 1] == '\r' as u16 {
-                len = len - 1;
-            }
+/// Lets a password input answer `WM_GETTEXT`. This is a function rather than a message so that
+/// only the owning process can opt in.
+pub fn allow_get_text(window: HWND, allow: bool) {
+    unsafe {
+        if let Some(context) = get_input_context(window) {
+            context.allow_get_text = allow;
         }
-        replace_selection(window, context, true, &string.as_wide()[..len], true)?;
-        GlobalUnlock(HGLOBAL(hsrc.0 as _)).or_else(|error| error.code().ok())?;
-    } else {
-        if let Type::Password = context.state.input_type {
-            replace_selection(window, context, true, &[], true)?;
+    }
+}
+
+/// Replaces the text. With `preserve_position`, the caret and scroll position are kept when the new
+/// text shares a prefix with the old one.
+pub fn set_text_preserving(window: HWND, text: PCWSTR, preserve_position: bool) {
+    unsafe {
+        if let Some(context) = get_input_context(window) {
+            _ = set_text(window, context, text, preserve_position);
         }
     }
-    CloseClipboard()?;
-    Ok(())
 }
 
-unsafe fn on_undo(window: HWND, context: &mut Context) -> Result<()> {
-    let text = context.undo_buffer.clone();
-    set_selection(
-        window,
-        context,
-        Some(context.undo_position),
-        Some(context.undo_position + context.undo_insert_count),
-    )?;
-    context.undo_buffer.empty();
-    replace_selection(window, context, true, text.as_wcs().as_wide(), true)?;
-    set_selection(
-        window,
-        context,
-        Some(context.undo_position),
-        Some(context.undo_position + context.undo_insert_count),
-    )?;
-    scroll_caret(window, context)?;
-    Ok(())
+const WM_SET_STATUS: u32 = WM_USER + 2;
+
+/// Shows a validation glyph, tinted with the matching status color, at the trailing edge of the
+/// input, or removes it with `None`. Does nothing if `window` is not an input.
+pub fn set_status(window: HWND, status: Option<Status>) {
+    // Other components use the same private message numbers for their own messages.
+    if !is_window_of_class(window, CLASS_NAME, Some(window_proc)) {
+        return;
+    }
+    let w_param = match status {
+        None => 0,
+        Some(Status::Success) => 1,
+        Some(Status::Warning) => 2,
+        Some(Status::Error) => 3,
+    };
+    unsafe {
+        SendMessageW(window, WM_SET_STATUS, Some(WPARAM(w_param)), None);
+    }
 }
 
-unsafe fn on_key_down(window: HWND, context: &mut Context, key: i32) -> Result<()> {
-    if GetKeyState(VK_MENU.0 as i32) < 0 {
-        return Ok(());
+impl InputHandle {
+    /// Like [`set_status`], but may be called from any thread.
+    pub fn set_status(&self, status: Option<Status>) -> Result<()> {
+        let handle = *self;
+        self.post_to_ui(Box::new(move || set_status(handle.window(), status)))
     }
 
-    let shift = GetKeyState(VK_SHIFT.0 as i32) < 0;
-    let control = GetKeyState(VK_CONTROL.0 as i32) < 0;
+    /// Like [`set_text_preserving`] without preserving the position, but may be called from any
+    /// thread.
+    pub fn set_text(&self, text: &str) -> Result<()> {
+        let handle = *self;
+        let text = HSTRING::from(text);
+        self.post_to_ui(Box::new(move || {
+            set_text_preserving(handle.window(), PCWSTR(text.as_ptr()), false)
+        }))
+    }
+}
 
-    const LEFT: i32 = VK_LEFT.0 as i32;
-    const RIGHT: i32 = VK_RIGHT.0 as i32;
-    const HOME: i32 = VK_HOME.0 as i32;
-    const END: i32 = VK_END.0 as i32;
-    const DELETE: i32 = VK_DELETE.0 as i32;
-    const INSERT: i32 = VK_INSERT.0 as i32;
-    const A: i32 = 'A' as i32;
-    match key {
-        LEFT => {
-            move_backward(window, context, shift)?;
+/// Returns the index of the first visible character, like `EM_GETFIRSTVISIBLELINE` does for a
+/// single-line edit control.
+pub fn first_visible_char(window: HWND) -> usize {
+    unsafe { SendMessageW(window, EM_GETFIRSTVISIBLELINE, None, None).0 as usize }
+}
+
+pub fn set_first_visible_char(window: HWND, index: usize) {
+    unsafe {
+        if let Some(context) = get_input_context(window) {
+            _ = set_scroll_offset(window, context, index);
+        }
+    }
+}
+
+/// Returns how far the text is scrolled, in physical pixels.
+pub fn scroll_offset_pixels(window: HWND) -> i32 {
+    unsafe {
+        match get_input_context(window) {
+            Some(context) => get_scroll_offset_pixels(window, context).unwrap_or(0),
+            None => 0,
+        }
+    }
+}
+
+/// Scrolls to the character at `pixels`, so the resulting offset is rounded to a character.
+pub fn set_scroll_offset_pixels(window: HWND, pixels: i32) {
+    unsafe {
+        if let Some(context) = get_input_context(window) {
+            _ = scroll_to_pixels(window, context, pixels);
+        }
+    }
+}
+
+pub fn scroll_to_caret(window: HWND) {
+    unsafe {
+        SendMessageW(window, EM_SCROLLCARET, None, None);
+    }
+}
+
+fn is_text_withheld(context: &Context) -> bool {
+    context.state.input_type == Type::Password && !context.allow_get_text
+}
+
+/// The `WM_COMMAND` notification code an input sends its parent whenever the clipboard changes,
+/// so that UI like a Paste button can follow [`can_paste`]. It is above the codes Windows uses for
+/// edit controls.
+pub const EN_CLIPBOARDUPDATE: u32 = 0x8000;
+
+/// Whether the clipboard holds text an input can paste, e.g. to enable Paste in the menu a parent
+/// opens for `WM_CONTEXTMENU`. It is cheap enough to call each time the menu opens.
+pub fn can_paste() -> bool {
+    unsafe {
+        [CF_UNICODETEXT, CF_TEXT, CF_OEMTEXT]
+            .iter()
+            .any(|format| IsClipboardFormatAvailable(format.0 as u32).is_ok())
+    }
+}
+
+unsafe fn get_clipboard_code_page(format: CLIPBOARD_FORMAT) -> u32 {
+    let (lc_type, fallback) = if format == CF_OEMTEXT {
+        (LOCALE_IDEFAULTCODEPAGE, CP_OEMCP)
+    } else {
+        (LOCALE_IDEFAULTANSICODEPAGE, CP_ACP)
+    };
+    let Ok(handle) = GetClipboardData(CF_LOCALE.0 as u32) else {
+        return fallback;
+    };
+    let locale = GlobalLock(HGLOBAL(handle.0 as _)) as *const u32;
+    if locale.is_null() {
+        return fallback;
+    }
+    let mut code_page = 0u32;
+    let length = GetLocaleInfoW(
+        *locale,
+        lc_type | LOCALE_RETURN_NUMBER,
+        Some(from_raw_parts_mut(
+            &mut code_page as *mut u32 as *mut u16,
+            size_of::<u32>() / size_of::<u16>(),
+        )),
+    );
+    _ = GlobalUnlock(HGLOBAL(handle.0 as _));
+    if length == 0 {
+        fallback
+    } else {
+        code_page
+    }
+}
+
+unsafe fn get_clipboard_text() -> Option<Vec<u16>> {
+    if let Ok(handle) = GetClipboardData(CF_UNICODETEXT.0 as u32) {
+        let src = GlobalLock(HGLOBAL(handle.0 as _));
+        if !src.is_null() {
+            let text = PCWSTR::from_raw(src as _).as_wide().to_vec();
+            _ = GlobalUnlock(HGLOBAL(handle.0 as _));
+            return Some(text);
+        }
+    }
+    for format in [CF_TEXT, CF_OEMTEXT] {
+        let Ok(handle) = GetClipboardData(format.0 as u32) else {
+            continue;
+        };
+        let src = GlobalLock(HGLOBAL(handle.0 as _)) as *const u8;
+        if src.is_null() {
+            continue;
+        }
+        let size = GlobalSize(HGLOBAL(handle.0 as _));
+        let bytes = std::slice::from_raw_parts(src, size);
+        let bytes = &bytes[..bytes.iter().position(|a| *a == 0).unwrap_or(size)];
+        let code_page = get_clipboard_code_page(format);
+        let length = MultiByteToWideChar(code_page, MB_PRECOMPOSED, bytes, None);
+        let mut text = vec![0u16; length.max(0) as usize];
+        MultiByteToWideChar(code_page, MB_PRECOMPOSED, bytes, Some(&mut text));
+        _ = GlobalUnlock(HGLOBAL(handle.0 as _));
+        return Some(text);
+    }
+    None
+}
+
+fn first_line(text: &[u16]) -> &[u16] {
+    let mut len = text.len();
+    if let Some(position) = text.iter().position(|a| *a == '\n' as u16) {
+        len = position;
+        if len > 0 && text[len - 1] == '\r' as u16 {
+            len = len - 1;
+        }
+    }
+    &text[..len]
+}
+
+unsafe fn on_paste(window: HWND, context: &mut Context) -> Result<()> {
+    if !is_editable(window) {
+        return Ok(());
+    }
+    OpenClipboard(Some(window))?;
+    let text = get_clipboard_text();
+    CloseClipboard()?;
+    if let Some(text) = text {
+        // A formatted number, e.g. "1 234,50 €", is pasted as its editable digits. Text that
+        // already reads as an editable number is kept as one, so a '.' copied from an editable
+        // field stays a decimal point even where the locale groups with it.
+        let digits = context.state.number_format.as_ref().and_then(|format| {
+            let locale = NumberLocale::current();
+            let text = String::from_utf16_lossy(first_line(&text));
+            parse_editable_number(&text, &locale)
+                .or_else(|| parse_number(&text, format, &locale))
+                .map(|value| editable_number(value, &locale))
+        });
+        if let Some(digits) = digits {
+            let digits: Vec<u16> = digits.encode_utf16().collect();
+            replace_selection(window, context, true, &digits, true)?;
+            return Ok(());
+        }
+        replace_selection(window, context, true, first_line(&text), true)?;
+    } else {
+        if let Type::Password = context.state.input_type {
+            replace_selection(window, context, true, &[], true)?;
+        }
+    }
+    Ok(())
+}
+
+unsafe fn on_undo(window: HWND, context: &mut Context) -> Result<()> {
+    if !is_editable(window) {
+        return Ok(());
+    }
+    let text = context.undo_buffer.clone();
+    set_selection(
+        window,
+        context,
+        Some(context.undo_position),
+        Some(context.undo_position + context.undo_insert_count),
+    )?;
+    context.undo_buffer.empty();
+    replace_selection(window, context, true, text.as_wcs().as_wide(), true)?;
+    set_selection(
+        window,
+        context,
+        Some(context.undo_position),
+        Some(context.undo_position + context.undo_insert_count),
+    )?;
+    scroll_caret(window, context)?;
+    Ok(())
+}
+
+unsafe fn on_key_down(window: HWND, context: &mut Context, key: i32) -> Result<()> {
+    if GetKeyState(VK_MENU.0 as i32) < 0 {
+        return Ok(());
+    }
+
+    if let Some(list) = context.suggestion_list {
+        if suggestion_list::is_visible(list) && on_suggestion_key_down(window, context, list, key)?
+        {
+            return Ok(());
+        }
+    }
+    if context.history.is_some() && on_history_key_down(window, context, key)? {
+        return Ok(());
+    }
+
+    let shift = GetKeyState(VK_SHIFT.0 as i32) < 0;
+    let control = GetKeyState(VK_CONTROL.0 as i32) < 0;
+
+    const LEFT: i32 = VK_LEFT.0 as i32;
+    const RIGHT: i32 = VK_RIGHT.0 as i32;
+    const HOME: i32 = VK_HOME.0 as i32;
+    const END: i32 = VK_END.0 as i32;
+    const DELETE: i32 = VK_DELETE.0 as i32;
+    const INSERT: i32 = VK_INSERT.0 as i32;
+    const RETURN: i32 = VK_RETURN.0 as i32;
+    const ESCAPE: i32 = VK_ESCAPE.0 as i32;
+    const A: i32 = 'A' as i32;
+    match key {
+        LEFT => {
+            move_backward(window, context, shift)?;
         }
         RIGHT => {
             move_forward(window, context, shift)?;
@@ -1122,7 +1989,7 @@ unsafe fn on_key_down(window: HWND, context: &mut Context, key: i32) -> Result<(
         HOME => move_home(window, context, shift)?,
         END => move_end(window, context, shift)?,
         DELETE => {
-            if !(shift && control) {
+            if !(shift && control) && is_editable(window) {
                 if context.selection_start != context.selection_end {
                     if shift {
                         on_cut(window, context)?;
@@ -1146,7 +2013,10 @@ unsafe fn on_key_down(window: HWND, context: &mut Context, key: i32) -> Result<(
             if shift {
                 on_paste(window, context)?;
             } else if control {
-                on_copy(window, context)?;
+                _ = on_copy(window, context)?;
+            } else {
+                context.is_overwrite = !context.is_overwrite;
+                set_caret_position(window, context, context.selection_end)?;
             }
         }
         A => {
@@ -1155,13 +2025,217 @@ unsafe fn on_key_down(window: HWND, context: &mut Context, key: i32) -> Result<(
                 set_selection(window, context, Some(0), Some(length))?;
             }
         }
+        RETURN if context.state.input_type != Type::Password => {
+            let text = context.get_text();
+            commit(window, context, text);
+        }
+        ESCAPE if context.state.revert_on_escape => revert(window, context)?,
         _ => {}
     }
     Ok(())
 }
 
+unsafe fn revert(window: HWND, context: &mut Context) -> Result<()> {
+    if !is_editable(window) {
+        return Ok(());
+    }
+    let text = if context.get_text() != context.committed_text {
+        HSTRING::from(&context.committed_text)
+    } else {
+        HSTRING::new()
+    };
+    context.history_index = None;
+    context.history_draft.clear();
+    let length = context.get_text_length();
+    set_selection(window, context, Some(0), Some(length))?;
+    replace_selection(window, context, true, &text, false)?;
+    move_end(window, context, false)
+}
+
+unsafe fn on_suggestion_key_down(
+    window: HWND,
+    context: &mut Context,
+    list: HWND,
+    key: i32,
+) -> Result<bool> {
+    const UP: i32 = VK_UP.0 as i32;
+    const DOWN: i32 = VK_DOWN.0 as i32;
+    const ESCAPE: i32 = VK_ESCAPE.0 as i32;
+    const RETURN: i32 = VK_RETURN.0 as i32;
+    match key {
+        UP => suggestion_list::move_selection(list, -1),
+        DOWN => suggestion_list::move_selection(list, 1),
+        ESCAPE => suggestion_list::hide(list),
+        RETURN => match suggestion_list::get_selected_index(list) {
+            Some(index) => accept_suggestion(window, context, index)?,
+            None => return Ok(false),
+        },
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+unsafe fn on_history_key_down(window: HWND, context: &mut Context, key: i32) -> Result<bool> {
+    const UP: i32 = VK_UP.0 as i32;
+    const DOWN: i32 = VK_DOWN.0 as i32;
+    const RETURN: i32 = VK_RETURN.0 as i32;
+    let count = context.history.as_ref().map_or(0, |history| history.len());
+    match (key, context.history_index) {
+        (UP, None) if count > 0 => {
+            context.history_draft = context.get_text();
+            recall_history(window, context, Some(count - 1))?;
+        }
+        (UP, Some(index)) if index > 0 => recall_history(window, context, Some(index - 1))?,
+        (DOWN, Some(index)) if index + 1 < count => {
+            recall_history(window, context, Some(index + 1))?
+        }
+        // Stepping past the newest entry brings back the text that was being typed.
+        (DOWN, Some(_)) => recall_history(window, context, None)?,
+        (UP | DOWN, _) => {}
+        (RETURN, _) => commit_history(window, context),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+unsafe fn recall_history(window: HWND, context: &mut Context, index: Option<usize>) -> Result<()> {
+    if !is_editable(window) {
+        return Ok(());
+    }
+    let text = match index {
+        Some(index) => match context
+            .history
+            .as_ref()
+            .and_then(|history| history.get(index))
+        {
+            Some(text) => HSTRING::from(text),
+            None => return Ok(()),
+        },
+        None => HSTRING::from(take(&mut context.history_draft)),
+    };
+    context.history_index = index;
+    context.is_recalling_history = true;
+    let result = set_text(window, context, PCWSTR(text.as_ptr()), false)
+        .and_then(|_| move_end(window, context, false));
+    context.is_recalling_history = false;
+    result
+}
+
+unsafe fn commit_history(window: HWND, context: &mut Context) {
+    let text = context.get_text();
+    let capacity = context.state.history_size.unwrap_or(0);
+    if let Some(history) = &mut context.history {
+        if !text.is_empty() && capacity > 0 && history.back() != Some(&text) {
+            if history.len() == capacity {
+                history.pop_front();
+            }
+            history.push_back(text.clone());
+        }
+    }
+    context.history_index = None;
+    context.history_draft.clear();
+    commit(window, context, text);
+}
+
+// Commit callbacks run from a posted message, with no borrow of the context, since they may set
+// the text of the input or destroy it.
+const WM_COMMIT: u32 = WM_USER + 3;
+
+unsafe fn commit(window: HWND, context: &mut Context, text: String) {
+    if context.state.input_type == Type::Password {
+        return;
+    }
+    let value = if context.state.input_type == Type::Number {
+        // Commits happen while focused, so the text is in its editable form.
+        match context.state.number_format {
+            Some(_) => parse_editable_number(&text, &NumberLocale::current()),
+            None => text.trim().parse::<f64>().ok(),
+        }
+    } else {
+        None
+    };
+    context.committed_text = text.clone();
+    context.pending_commits.push_back((text, value));
+    _ = PostMessageW(Some(window), WM_COMMIT, WPARAM(0), LPARAM(0));
+}
+
+fn fire_commit(window: HWND, event: &InputEvent, text: &str, value: Option<f64>) {
+    (event.on_commit)(&window, text);
+    if let Some(value) = value {
+        (event.on_commit_value)(&window, value);
+    }
+}
+
+// The callbacks are moved out of the context while they run, and only put back if the input
+// survived.
+unsafe fn on_commit(window: HWND) {
+    let Some(context) = get_input_context(window) else {
+        return;
+    };
+    let Some((text, value)) = context.pending_commits.pop_front() else {
+        return;
+    };
+    let event = InputEvent {
+        on_query: None,
+        on_commit: replace(
+            &mut context.state.event.on_commit,
+            Box::new(|_window, _text| {}),
+        ),
+        on_commit_value: replace(
+            &mut context.state.event.on_commit_value,
+            Box::new(|_window, _value| {}),
+        ),
+    };
+    fire_commit(window, &event, &text, value);
+    if let Some(context) = get_input_context(window) {
+        context.state.event.on_commit = event.on_commit;
+        context.state.event.on_commit_value = event.on_commit_value;
+    }
+}
+
+/// Replaces the history of an input created with a history size, oldest entry first. Only the
+/// newest entries that fit are kept.
+pub fn set_history(window: HWND, history: Vec<String>) {
+    unsafe {
+        let Some(context) = get_input_context(window) else {
+            return;
+        };
+        let capacity = context.state.history_size.unwrap_or(0);
+        if let Some(current) = &mut context.history {
+            let skip = history.len().saturating_sub(capacity);
+            *current = history.into_iter().skip(skip).collect();
+            context.history_index = None;
+        }
+    }
+}
+
+/// Returns the committed values of an input created with a history size, oldest entry first.
+pub fn history(window: HWND) -> Vec<String> {
+    unsafe {
+        let Some(context) = get_input_context(window) else {
+            return Vec::new();
+        };
+        match &context.history {
+            Some(history) => history.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
 unsafe fn on_kill_focus(window: HWND, context: &mut Context) -> Result<()> {
     context.is_focused = false;
+    if context.state.input_type != Type::Password {
+        let text = context.get_text();
+        if text != context.committed_text {
+            commit(window, context, text);
+        }
+    }
+    apply_number_format(window, context)?;
+    context.is_overwrite = false;
+    if let Some(list) = context.suggestion_list {
+        _ = KillTimer(Some(window), SUGGESTION_TIMER_ID);
+        suggestion_list::hide(list);
+    }
     DestroyCaret()?;
     invalidate_text(
         window,
@@ -1170,9 +2244,67 @@ unsafe fn on_kill_focus(window: HWND, context: &mut Context) -> Result<()> {
         context.selection_end,
     )?;
     _ = RedrawWindow(Some(window), None, None, RDW_INVALIDATE);
+    notify_parent(window, EN_KILLFOCUS);
+    Ok(())
+}
+
+unsafe fn update_suggestions(context: &mut Context) -> Result<()> {
+    let Some(list) = context.suggestion_list else {
+        return Ok(());
+    };
+    let text = context.get_text();
+    let Some(on_query) = &context.state.event.on_query else {
+        return Ok(());
+    };
+    let suggestions = if text.is_empty() {
+        Vec::new()
+    } else {
+        on_query(&text)
+    };
+    if suggestions.is_empty() {
+        suggestion_list::hide(list);
+        Ok(())
+    } else {
+        suggestion_list::show(list, suggestions)
+    }
+}
+
+unsafe fn accept_suggestion(window: HWND, context: &mut Context, index: usize) -> Result<()> {
+    let Some(list) = context.suggestion_list else {
+        return Ok(());
+    };
+    if !is_editable(window) {
+        return Ok(());
+    }
+    let Some(item) = suggestion_list::get_item(list, index) else {
+        return Ok(());
+    };
+    suggestion_list::hide(list);
+    context.is_accepting_suggestion = true;
+    let result = set_text(window, context, PCWSTR(item.as_ptr()), false)
+        .and_then(|_| move_end(window, context, false));
+    context.is_accepting_suggestion = false;
+    result?;
+    commit(window, context, item.to_string_lossy());
     Ok(())
 }
 
+// The list is a separate top-level window, so it has to follow the root window around.
+unsafe extern "system" fn root_subclass_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+    _id: usize,
+    list: usize,
+) -> LRESULT {
+    let list = HWND(list as _);
+    if message == WM_WINDOWPOSCHANGED && suggestion_list::is_visible(list) {
+        _ = suggestion_list::reposition(list);
+    }
+    DefSubclassProc(window, message, w_param, l_param)
+}
+
 unsafe fn word_break_proc(
     context: &mut Context,
     mut index: usize,
@@ -1253,21 +2385,40 @@ unsafe fn on_double_click(window: HWND, context: &mut Context) -> Result<()> {
     Ok(())
 }
 
+/// Returns whether a drag of the selection should start, which the caller does with
+/// [`start_drag`] once it no longer borrows the context.
 unsafe fn on_left_button_down(
     window: HWND,
     context: &mut Context,
     keys: u32,
     mut x: i32,
     mut y: i32,
-) -> Result<()> {
-    context.is_captured = true;
-    SetCapture(window);
+) -> Result<bool> {
     x = x
         .max(context.format_rect.left)
         .min(context.format_rect.right - 1);
     y = y
         .max(context.format_rect.top)
         .min(context.format_rect.bottom - 1);
+    if (keys & MK_SHIFT.0) == 0
+        && context.state.input_type != Type::Password
+        && is_in_selection(window, context, x)?
+    {
+        if !context.is_focused {
+            SetFocus(Some(window))?;
+        }
+        let mut point = POINT { x, y };
+        _ = ClientToScreen(window, &mut point);
+        if DragDetect(window, point).as_bool() {
+            return Ok(true);
+        }
+        let position = char_from_position(window, context, POINT { x, y })?;
+        set_selection(window, context, Some(position), Some(position))?;
+        scroll_caret(window, context)?;
+        return Ok(false);
+    }
+    context.is_captured = true;
+    SetCapture(window);
     let end = char_from_position(window, context, POINT { x, y })?;
     let start = if (keys & MK_SHIFT.0) != 0 {
         context.selection_start
@@ -1279,7 +2430,7 @@ unsafe fn on_left_button_down(
     if !context.is_focused {
         SetFocus(Some(window))?;
     }
-    Ok(())
+    Ok(false)
 }
 
 unsafe fn on_left_button_up(window: HWND, context: &mut Context) -> Result<()> {
@@ -1304,6 +2455,287 @@ unsafe fn on_mouse_move(window: HWND, context: &mut Context, x: i32, y: i32) ->
     Ok(())
 }
 
+unsafe fn is_in_selection(window: HWND, context: &mut Context, x: i32) -> Result<bool> {
+    let start = context.selection_start.min(context.selection_end);
+    let end = context.selection_start.max(context.selection_end);
+    if start == end {
+        return Ok(false);
+    }
+    let left = position_from_char(window, context, start)?.x;
+    let right = position_from_char(window, context, end)?.x;
+    Ok(x >= left && x < right)
+}
+
+fn get_text_format() -> FORMATETC {
+    FORMATETC {
+        cfFormat: CF_UNICODETEXT.0,
+        ptd: null_mut(),
+        dwAspect: DVASPECT_CONTENT.0,
+        lindex: -1,
+        tymed: TYMED_HGLOBAL.0 as u32,
+    }
+}
+
+// Takes no context: the modal drag loop re-enters the window, and the input's own drop target
+// uses the context, so it is fetched again once the loop returns.
+unsafe fn start_drag(window: HWND) -> Result<()> {
+    let Some(context) = get_context(window) else {
+        return Ok(());
+    };
+    let data_object: IDataObject = SHCreateDataObject(None, None, None)?;
+    let medium = STGMEDIUM {
+        tymed: TYMED_HGLOBAL.0 as u32,
+        u: STGMEDIUM_0 {
+            hGlobal: copy_selection_to_global(context)?,
+        },
+        pUnkForRelease: ManuallyDrop::new(None),
+    };
+    data_object.SetData(&get_text_format(), &medium, true)?;
+    let drop_source: IDropSource = DropSource.into();
+    let mut effect = DROPEFFECT_NONE;
+    context.is_drag_source = true;
+    let result = DoDragDrop(
+        &data_object,
+        &drop_source,
+        DROPEFFECT_COPY | DROPEFFECT_MOVE,
+        &mut effect,
+    );
+    let Some(context) = get_context(window) else {
+        return Ok(());
+    };
+    // A move within this input is completed by the drop target, which resets the flag.
+    if result == DRAGDROP_S_DROP && effect == DROPEFFECT_MOVE && context.is_drag_source {
+        clear(window, context)?;
+    }
+    context.is_drag_source = false;
+    Ok(())
+}
+
+fn get_drop_effect(keys: MODIFIERKEYS_FLAGS, allowed: DROPEFFECT) -> DROPEFFECT {
+    if (keys.0 & MK_CONTROL.0) == 0 && (allowed.0 & DROPEFFECT_MOVE.0) != 0 {
+        DROPEFFECT_MOVE
+    } else if (allowed.0 & DROPEFFECT_COPY.0) != 0 {
+        DROPEFFECT_COPY
+    } else {
+        DROPEFFECT_NONE
+    }
+}
+
+unsafe fn on_drag_enter(
+    window: HWND,
+    context: &mut Context,
+    data_object: &IDataObject,
+    point: &POINTL,
+) -> Result<()> {
+    if !is_editable(window) || data_object.QueryGetData(&get_text_format()) != S_OK {
+        context.drop_position = None;
+        return Ok(());
+    }
+    if !context.is_focused {
+        let width = Dpi::for_window(window).to_physical(1.0);
+        create_caret(window, context, width)?;
+        ShowCaret(Some(window))?;
+    }
+    on_drag_over(window, context, point)
+}
+
+unsafe fn on_drag_over(window: HWND, context: &mut Context, point: &POINTL) -> Result<()> {
+    let mut point = POINT {
+        x: point.x,
+        y: point.y,
+    };
+    _ = ScreenToClient(window, &mut point);
+    let x = point
+        .x
+        .max(context.format_rect.left)
+        .min(context.format_rect.right - 1);
+    let position = char_from_position(
+        window,
+        context,
+        POINT {
+            x,
+            y: context.format_rect.top,
+        },
+    )?;
+    context.drop_position = Some(position);
+    let caret = position_from_char(window, context, position)?;
+    SetCaretPos(caret.x, caret.y)
+}
+
+unsafe fn on_drag_leave(window: HWND, context: &mut Context) -> Result<()> {
+    if context.drop_position.take().is_some() {
+        if context.is_focused {
+            set_caret_position(window, context, context.selection_end)?;
+        } else {
+            DestroyCaret()?;
+        }
+    }
+    Ok(())
+}
+
+unsafe fn on_drop(
+    window: HWND,
+    context: &mut Context,
+    data_object: &IDataObject,
+    effect: DROPEFFECT,
+) -> Result<DROPEFFECT> {
+    let Some(mut position) = context.drop_position else {
+        return Ok(DROPEFFECT_NONE);
+    };
+    on_drag_leave(window, context)?;
+    let mut medium = data_object.GetData(&get_text_format())?;
+    let src = GlobalLock(medium.u.hGlobal);
+    let text = if src.is_null() {
+        None
+    } else {
+        let text = PCWSTR::from_raw(src as _).as_wide().to_vec();
+        _ = GlobalUnlock(medium.u.hGlobal);
+        Some(text)
+    };
+    ReleaseStgMedium(&mut medium);
+    let Some(text) = text else {
+        return Ok(DROPEFFECT_NONE);
+    };
+
+    if context.is_drag_source {
+        let start = context.selection_start.min(context.selection_end);
+        let end = context.selection_start.max(context.selection_end);
+        if position >= start && position <= end {
+            return Ok(DROPEFFECT_NONE);
+        }
+        if effect == DROPEFFECT_MOVE {
+            clear(window, context)?;
+            if position > end {
+                position -= end - start;
+            }
+            context.is_drag_source = false;
+        }
+    }
+    _ = SetFocus(Some(window));
+    set_selection(window, context, Some(position), Some(position))?;
+    replace_selection(window, context, true, first_line(&text), true)?;
+    Ok(effect)
+}
+
+// For public functions that take any window: anything other than an input is ignored, since its
+// GWLP_USERDATA holds something else.
+unsafe fn get_input_context<'a>(window: HWND) -> Option<&'a mut Context> {
+    if is_window_of_class(window, CLASS_NAME, Some(window_proc)) {
+        get_context(window)
+    } else {
+        None
+    }
+}
+
+unsafe fn get_context<'a>(window: HWND) -> Option<&'a mut Context> {
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    if raw.is_null() {
+        None
+    } else {
+        Some(&mut *raw)
+    }
+}
+
+#[implement(IDropTarget)]
+struct DropTarget {
+    window: HWND,
+}
+
+impl IDropTarget_Impl for DropTarget_Impl {
+    fn DragEnter(
+        &self,
+        pdataobj: Ref<IDataObject>,
+        grfkeystate: MODIFIERKEYS_FLAGS,
+        pt: &POINTL,
+        pdweffect: *mut DROPEFFECT,
+    ) -> Result<()> {
+        unsafe {
+            let Some(context) = get_context(self.window) else {
+                *pdweffect = DROPEFFECT_NONE;
+                return Ok(());
+            };
+            _ = on_drag_enter(self.window, context, pdataobj.ok()?, pt);
+            *pdweffect = match context.drop_position {
+                Some(_) => get_drop_effect(grfkeystate, *pdweffect),
+                None => DROPEFFECT_NONE,
+            };
+        }
+        Ok(())
+    }
+
+    fn DragOver(
+        &self,
+        grfkeystate: MODIFIERKEYS_FLAGS,
+        pt: &POINTL,
+        pdweffect: *mut DROPEFFECT,
+    ) -> Result<()> {
+        unsafe {
+            let Some(context) = get_context(self.window) else {
+                *pdweffect = DROPEFFECT_NONE;
+                return Ok(());
+            };
+            *pdweffect = match context.drop_position {
+                Some(_) => {
+                    _ = on_drag_over(self.window, context, pt);
+                    get_drop_effect(grfkeystate, *pdweffect)
+                }
+                None => DROPEFFECT_NONE,
+            };
+        }
+        Ok(())
+    }
+
+    fn DragLeave(&self) -> Result<()> {
+        unsafe {
+            if let Some(context) = get_context(self.window) {
+                _ = on_drag_leave(self.window, context);
+            }
+        }
+        Ok(())
+    }
+
+    fn Drop(
+        &self,
+        pdataobj: Ref<IDataObject>,
+        grfkeystate: MODIFIERKEYS_FLAGS,
+        pt: &POINTL,
+        pdweffect: *mut DROPEFFECT,
+    ) -> Result<()> {
+        unsafe {
+            let Some(context) = get_context(self.window) else {
+                *pdweffect = DROPEFFECT_NONE;
+                return Ok(());
+            };
+            if context.drop_position.is_some() {
+                _ = on_drag_over(self.window, context, pt);
+            }
+            let effect = get_drop_effect(grfkeystate, *pdweffect);
+            *pdweffect =
+                on_drop(self.window, context, pdataobj.ok()?, effect).unwrap_or(DROPEFFECT_NONE);
+        }
+        Ok(())
+    }
+}
+
+#[implement(IDropSource)]
+struct DropSource;
+
+impl IDropSource_Impl for DropSource_Impl {
+    fn QueryContinueDrag(&self, fescapepressed: BOOL, grfkeystate: MODIFIERKEYS_FLAGS) -> HRESULT {
+        if fescapepressed.as_bool() {
+            DRAGDROP_S_CANCEL
+        } else if (grfkeystate.0 & MK_LBUTTON.0) == 0 {
+            DRAGDROP_S_DROP
+        } else {
+            S_OK
+        }
+    }
+
+    fn GiveFeedback(&self, _dweffect: DROPEFFECT) -> HRESULT {
+        DRAGDROP_S_USEDEFAULTCURSORS
+    }
+}
+
 unsafe fn paint_text(
     context: &Context,
     dc: HDC,
@@ -1321,8 +2753,9 @@ unsafe fn paint_text(
     let bk_color = GetBkColor(dc);
     let text_color = GetTextColor(dc);
     if rev {
-        SetBkColor(dc, COLORREF(GetSysColor(COLOR_HIGHLIGHT)));
-        SetTextColor(dc, COLORREF(GetSysColor(COLOR_HIGHLIGHTTEXT)));
+        let (background, foreground) = get_selection_colors(context);
+        SetBkColor(dc, background);
+        SetTextColor(dc, foreground);
         SetBkMode(dc, OPAQUE);
     }
 
@@ -1333,7 +2766,7 @@ unsafe fn paint_text(
         &context.buffer.as_wcs().as_wide()[col..col + count],
     );
     let mut size = SIZE::default();
-    if GetTextExtentPoint32W(
+    if !GetTextExtentPoint32W(
         dc,
         &context.buffer.as_wcs().as_wide()[col..col + count],
         &mut size,
@@ -1351,21 +2784,112 @@ unsafe fn paint_text(
     Ok(size.cx)
 }
 
-unsafe fn paint_line(window: HWND, context: &mut Context, dc: HDC, rev: bool) -> Result<()> {
-    let ssa = update_uniscribe_data(window, context, Some(dc))?;
+fn get_selection_colors(context: &Context) -> (COLORREF, COLORREF) {
+    if is_high_contrast() {
+        unsafe {
+            return (
+                COLORREF(GetSysColor(COLOR_HIGHLIGHT)),
+                COLORREF(GetSysColor(COLOR_HIGHLIGHTTEXT)),
+            );
+        }
+    }
+    let tokens = &context.state.qt.theme.tokens;
+    if context.is_focused {
+        (
+            convert_to_color_ref(&tokens.color_compound_brand_background),
+            convert_to_color_ref(&tokens.color_neutral_foreground_on_brand),
+        )
+    } else {
+        (
+            convert_to_color_ref(&tokens.color_neutral_background1_selected),
+            convert_to_color_ref(&tokens.color_neutral_foreground1),
+        )
+    }
+}
+
+// Joins the horizontal spans of single characters into the runs they form on screen, from left
+// to right.
+fn merge_runs(mut spans: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+    spans.sort_unstable();
+    let mut runs: Vec<(i32, i32)> = Vec::new();
+    for (left, right) in spans {
+        match runs.last_mut() {
+            Some(run) if left <= run.1 => run.1 = run.1.max(right),
+            _ => runs.push((left, right)),
+        }
+    }
+    runs
+}
+
+// The visual runs, relative to the start of the line, that show the logical range `start..end`.
+// In BiDi text one logical range can land in several separate runs.
+unsafe fn get_selection_runs(
+    ssa: *mut c_void,
+    start: usize,
+    end: usize,
+) -> Result<Vec<(i32, i32)>> {
+    let spans = (start..end)
+        .map(|index| {
+            let leading = ScriptStringCPtoX(ssa, index as i32, false)?;
+            let trailing = ScriptStringCPtoX(ssa, index as i32, true)?;
+            Ok((leading.min(trailing), leading.max(trailing)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(merge_runs(spans))
+}
+
+// ScriptStringOut can only highlight with the system colors, so the selected glyphs are drawn a
+// second time with the selection colors, clipped to where they landed on screen.
+unsafe fn paint_selection(
+    context: &Context,
+    dc: HDC,
+    ssa: *mut c_void,
+    x: i32,
+    y: i32,
+    start: usize,
+    end: usize,
+) -> Result<()> {
+    let runs = get_selection_runs(ssa, start, end)?;
+    let region = CreateRectRgn(0, 0, 0, 0);
+    for (left, right) in runs {
+        let run_region = CreateRectRgn(
+            x + left,
+            context.format_rect.top,
+            x + right,
+            context.format_rect.bottom,
+        );
+        CombineRgn(Some(region), Some(region), Some(run_region), RGN_OR);
+        _ = DeleteObject(run_region.into());
+    }
+
+    let saved = SaveDC(dc);
+    ExtSelectClipRgn(dc, Some(region), RGN_AND);
+    let (background, foreground) = get_selection_colors(context);
+    SetBkColor(dc, background);
+    SetTextColor(dc, foreground);
+    let result = ScriptStringOut(
+        ssa,
+        x,
+        y,
+        ETO_OPAQUE,
+        Some(&context.format_rect),
+        0,
+        0,
+        false,
+    );
+    _ = RestoreDC(dc, saved);
+    _ = DeleteObject(region.into());
+    result
+}
+
+unsafe fn paint_line(window: HWND, context: &mut Context, dc: HDC) -> Result<()> {
+    let ssa = update_uniscribe_data(window, context, Some(dc)).unwrap_or(null_mut());
     let pos = position_from_char(window, context, 0)?;
     let mut x = pos.x;
     let y = pos.y;
-    let mut ll = 0;
-    let mut start = 0;
-    let mut end = 0;
-    if rev {
-        ll = context.get_text_length();
-        start = context.selection_start.min(context.selection_end);
-        end = context.selection_start.max(context.selection_end);
-        start = ll.min(start);
-        end = ll.min(end);
-    }
+    let ll = context.get_text_length();
+    let start = ll.min(context.selection_start.min(context.selection_end));
+    let end = ll.min(context.selection_start.max(context.selection_end));
 
     if !ssa.is_null() {
         ScriptStringOut(
@@ -1374,26 +2898,68 @@ unsafe fn paint_line(window: HWND, context: &mut Context, dc: HDC, rev: bool) ->
             y,
             ETO_OPTIONS::default(),
             Some(&context.format_rect),
-            start as i32,
-            end as i32,
+            0,
+            0,
             false,
         )?;
-    } else if rev && start == end && context.is_focused {
+        if start < end {
+            paint_selection(context, dc, ssa, x, y, start, end)?;
+        }
+    } else if is_plain_ascii(context, ll) {
+        // Uniscribe is unavailable; plain ASCII can still be drawn correctly with GDI.
         x = x + paint_text(context, dc, x, y, 0, start, false)?;
         x = x + paint_text(context, dc, x, y, start, end - start, true)?;
         paint_text(context, dc, x, y, end, ll - end, false)?;
-    } else {
-        paint_text(context, dc, x, y, 0, ll, false)?;
+    }
+    if context.composition_length > 0 {
+        paint_composition(window, context, dc)?;
     }
     Ok(())
 }
 
+fn is_plain_ascii(context: &Context, length: usize) -> bool {
+    if let Type::Password = context.state.input_type {
+        return false;
+    }
+    context.buffer.0[..length].iter().all(|a| *a < 0x80)
+}
+
+// An off-screen DC with a bitmap selected into it, both freed when it goes out of scope.
+struct MemoryDC {
+    dc: HDC,
+    bitmap: HBITMAP,
+    old_bitmap: HGDIOBJ,
+}
+
+impl MemoryDC {
+    unsafe fn new(dc: HDC, width: i32, height: i32) -> Self {
+        let mem_dc = CreateCompatibleDC(Some(dc));
+        let bitmap = CreateCompatibleBitmap(dc, width, height);
+        let old_bitmap = SelectObject(mem_dc, bitmap.into());
+        MemoryDC {
+            dc: mem_dc,
+            bitmap,
+            old_bitmap,
+        }
+    }
+}
+
+impl Drop for MemoryDC {
+    fn drop(&mut self) {
+        unsafe {
+            // A bitmap cannot be deleted while it is still selected into a DC.
+            SelectObject(self.dc, self.old_bitmap);
+            _ = DeleteObject(self.bitmap.into());
+            _ = DeleteDC(self.dc);
+        }
+    }
+}
+
 unsafe fn on_paint(window: HWND, context: &mut Context, dc: HDC, full_draw: bool) -> Result<()> {
-    let rev = context.is_focused;
     let mut rc_rgn = RECT::default();
     GetClipBox(dc, &mut rc_rgn);
 
-    let scaling_factor = get_scaling_factor(window);
+    let dpi = Dpi::for_window(window);
     let mut rc = RECT::default();
     GetClientRect(window, &mut rc)?;
 
@@ -1405,20 +2971,19 @@ unsafe fn on_paint(window: HWND, context: &mut Context, dc: HDC, full_draw: bool
         let old_font = SelectObject(dc, context.font.into());
         SetBkColor(dc, context.background_color);
         if context.get_text_length() == 0 {
-            if let Some(placeholder) = context.state.placeholder {
+            if let Some(placeholder) = &context.state.placeholder {
                 SetTextColor(dc, COLORREF(GetSysColor(COLOR_GRAYTEXT)));
                 _ = TextOutW(
                     dc,
                     context.format_rect.left,
                     context.format_rect.top,
-                    &placeholder.as_wide(),
+                    placeholder,
                 );
             }
         } else {
             SetTextColor(dc, context.text_color);
-            context.invalidate_uniscribe_data()?;
             update_uniscribe_data(window, context, Some(dc))?;
-            paint_line(window, context, dc, rev)?;
+            paint_line(window, context, dc)?;
         }
         SelectObject(dc, old_font);
 
@@ -1444,10 +3009,11 @@ unsafe fn on_paint(window: HWND, context: &mut Context, dc: HDC, full_draw: bool
         );
     }
 
-    let border_width = (1.0 * scaling_factor) as i32;
-    let border_bottom_width = (2.0 * scaling_factor) as i32;
+    let border_width = dpi.to_physical(1.0);
+    let border_bottom_width = dpi.to_physical(2.0);
 
-    let tokens = &context.state.qt.theme.tokens;
+    // Keyboard focus draws a ring along the whole edge, in every appearance.
+    let show_focus_ring = context.is_focused && context.focus_visible;
     let need_draw_border = (IntersectRect(
         &mut rc_intersect,
         &rc_rgn,
@@ -1481,21 +3047,23 @@ unsafe fn on_paint(window: HWND, context: &mut Context, dc: HDC, full_draw: bool
             },
         )
         .as_bool())
-        && match context.state.appearance {
+        && (match context.state.appearance {
             Appearance::Outline => true,
             _ => false,
-        };
+        } || show_focus_ring);
     if need_draw_border || full_draw {
         SelectObject(
             dc,
-            if context.is_focused {
+            if show_focus_ring {
+                context.border_pen_focus_ring
+            } else if context.is_focused {
                 context.border_pen_focused
             } else {
                 context.border_pen
             }
             .into(),
         );
-        let radius = (tokens.border_radius_medium * scaling_factor) as i32;
+        let radius = context.state.get_corner_diameter(dpi, &rc) / 2;
         _ = MoveToEx(dc, rc.right - radius, rc.top, None).as_bool()
             && AngleArc(
                 dc,
@@ -1550,7 +3118,7 @@ unsafe fn on_paint(window: HWND, context: &mut Context, dc: HDC, full_draw: bool
     {
         SelectObject(dc, context.border_bottom_pen.into());
 
-        let radius = (tokens.border_radius_medium * scaling_factor) as i32;
+        let radius = context.state.get_corner_diameter(dpi, &rc) / 2;
 
         _ = MoveToEx(dc, radius, rc.bottom, None).as_bool()
             && AngleArc(dc, radius, rc.bottom - radius, radius as u32, 270.0, -45.0).as_bool();
@@ -1580,45 +3148,273 @@ unsafe fn on_paint(window: HWND, context: &mut Context, dc: HDC, full_draw: bool
         }
     }
 
+    if context.status_svg.is_some() {
+        let status_rect = get_status_icon_rect(window, context)?;
+        if IntersectRect(&mut rc_intersect, &rc_rgn, &status_rect).as_bool() || full_draw {
+            paint_status_icon(context, dc, &status_rect)?;
+        }
+    }
+
+    Ok(())
+}
+
+// The status icon sits inside the trailing padding, centered above the bottom border.
+unsafe fn get_status_icon_rect(window: HWND, context: &Context) -> Result<RECT> {
+    let dpi = Dpi::for_window(window);
+    let mut rc = RECT::default();
+    GetClientRect(window, &mut rc)?;
+    let size = dpi.to_physical(context.state.get_status_icon_size());
+    let right =
+        rc.right - dpi.to_physical(1.0) - dpi.to_physical(context.state.get_horizontal_padding());
+    let top = (rc.bottom - size) / 2;
+    Ok(RECT {
+        left: right - size,
+        top,
+        right,
+        bottom: top + size,
+    })
+}
+
+// The input paints with GDI, so the SVG glyph is drawn through a DC render target bound to the
+// icon's rectangle on top of what GDI has already painted there.
+unsafe fn paint_status_icon(context: &Context, dc: HDC, rect: &RECT) -> Result<()> {
+    let Some(svg) = &context.status_svg else {
+        return Ok(());
+    };
+    let render_target = &context.status_render_target;
+    render_target.BindDC(dc, rect)?;
+    let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
+    render_target.BeginDraw();
+    device_context5.DrawSvgDocument(svg);
+    render_target.EndDraw(None, None)
+}
+
+unsafe fn on_set_status(window: HWND, context: &mut Context, status: Option<Status>) -> Result<()> {
+    context.status = status;
+    context.status_svg = match status {
+        None => None,
+        Some(status) => {
+            let tokens = &context.state.qt.theme.tokens;
+            let (icon, color) = match status {
+                Status::Success => (
+                    Icon::checkmark_circle_filled(),
+                    &tokens.color_status_success_foreground1,
+                ),
+                Status::Warning => (
+                    Icon::warning_filled(),
+                    &tokens.color_status_warning_foreground1,
+                ),
+                Status::Error => (
+                    Icon::error_circle_filled(),
+                    &tokens.color_status_danger_foreground1,
+                ),
+            };
+            let dpi = Dpi::for_window(window);
+            let render_target = &context.status_render_target;
+            render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+            let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
+            let svg = create_svg_document(&device_context5, &icon)?;
+            let size = context.state.get_status_icon_size();
+            svg.SetViewportSize(D2D_SIZE_F {
+                width: size,
+                height: size,
+            })?;
+            set_svg_color(&svg, color)?;
+            Some(svg)
+        }
+    };
+    set_rect_np(window, context)?;
+    _ = InvalidateRect(Some(window), None, true);
+    Ok(())
+}
+
+unsafe fn set_focus(window: HWND, context: &mut Context) -> Result<()> {
+    context.is_focused = true;
+    context.focus_visible = is_focus_visible();
+    strip_number_format(window, context)?;
+    if context.state.input_type != Type::Password {
+        context.committed_text = context.get_text();
+    }
+    invalidate_text(
+        window,
+        context,
+        context.selection_start,
+        context.selection_end,
+    )?;
+    let width = get_caret_width(window, context, context.selection_end);
+    create_caret(window, context, width)?;
+    set_caret_position(window, context, context.selection_end)?;
+    ShowCaret(Some(window))?;
+    _ = RedrawWindow(Some(window), None, None, RDW_INVALIDATE);
+    let curves = context.state.qt.animation_curves().decelerate_mid;
+    let transition = context
+        .transition_library
+        .CreateCubicBezierLinearTransition(
+            context.state.qt.animation_durations().gentle,
+            1.0,
+            curves[0],
+            curves[1],
+            curves[2],
+            curves[3],
+        )?;
+    context.bottom_focus_border = context.animation_manager.CreateAnimationVariable(0.0)?;
+    context
+        .state
+        .qt
+        .schedule_transition(window, &context.bottom_focus_border, &transition)?;
+    Ok(())
+}
+
+unsafe fn create_caret(window: HWND, context: &mut Context, width: i32) -> Result<()> {
+    let dc = GetDC(Some(window));
+    let mem_dc = CreateCompatibleDC(Some(dc));
+    let bitmap = CreateCompatibleBitmap(dc, width, context.line_height);
+    ReleaseDC(Some(window), dc);
+    let old_bitmap = SelectObject(mem_dc, bitmap.into());
+    // The caret is XOR-ed onto the window, so this shows the text color over the background.
+    let brush = CreateSolidBrush(COLORREF(context.text_color.0 ^ context.background_color.0));
+    FillRect(
+        mem_dc,
+        &RECT {
+            left: 0,
+            top: 0,
+            right: width,
+            bottom: context.line_height,
+        },
+        brush,
+    );
+    _ = DeleteObject(brush.into());
+    SelectObject(mem_dc, old_bitmap);
+    _ = DeleteDC(mem_dc);
+    CreateCaret(window, Some(bitmap), width, context.line_height)?;
+    if !context.caret_bitmap.is_invalid() {
+        _ = DeleteObject(context.caret_bitmap.into());
+    }
+    context.caret_bitmap = bitmap;
+    context.caret_width = width;
+    Ok(())
+}
+
+unsafe fn get_composition_data<T: Copy + Default>(
+    himc: HIMC,
+    index: IME_COMPOSITION_STRING,
+) -> Vec<T> {
+    let size = ImmGetCompositionStringW(himc, index, None, 0);
+    if size <= 0 {
+        return Vec::new();
+    }
+    let mut buffer = vec![T::default(); size as usize / size_of::<T>()];
+    ImmGetCompositionStringW(himc, index, Some(buffer.as_mut_ptr() as _), size as u32);
+    buffer
+}
+
+unsafe fn replace_composition(
+    window: HWND,
+    context: &mut Context,
+    text: &[u16],
+    can_undo: bool,
+) -> Result<()> {
+    if context.composition_length == 0 {
+        context.composition_start = context.selection_start.min(context.selection_end);
+    } else {
+        set_selection(
+            window,
+            context,
+            Some(context.composition_start),
+            Some(context.composition_start + context.composition_length),
+        )?;
+    }
+    replace_selection(window, context, can_undo, text, true)?;
+    context.composition_length = context.selection_end - context.composition_start;
+    Ok(())
+}
+
+unsafe fn on_ime_composition(
+    window: HWND,
+    context: &mut Context,
+    flags: IME_COMPOSITION_STRING,
+) -> Result<()> {
+    if !is_editable(window) {
+        return Ok(());
+    }
+    let himc = ImmGetContext(window);
+    if himc.is_invalid() {
+        return Ok(());
+    }
+    if (flags.0 & GCS_RESULTSTR.0) != 0 {
+        let result = get_composition_data::<u16>(himc, GCS_RESULTSTR);
+        replace_composition(window, context, &result, true)?;
+        context.composition_length = 0;
+        context.composition_attributes.clear();
+    }
+    if (flags.0 & GCS_COMPSTR.0) != 0 {
+        let composition = get_composition_data::<u16>(himc, GCS_COMPSTR);
+        replace_composition(window, context, &composition, false)?;
+        context.composition_attributes = if (flags.0 & GCS_COMPATTR.0) != 0 {
+            get_composition_data::<u8>(himc, GCS_COMPATTR)
+        } else {
+            Vec::new()
+        };
+        let cursor = if (flags.0 & GCS_CURSORPOS.0) != 0 {
+            ImmGetCompositionStringW(himc, GCS_CURSORPOS, None, 0).max(0) as usize
+        } else {
+            context.composition_length
+        };
+        let caret = context.composition_start + cursor.min(context.composition_length);
+        set_selection(window, context, Some(caret), Some(caret))?;
+        set_caret_position(window, context, caret)?;
+    }
+    _ = ImmReleaseContext(window, himc);
     Ok(())
 }
 
-unsafe fn set_focus(window: HWND, context: &mut Context) -> Result<()> {
-    context.is_focused = true;
-    invalidate_text(
-        window,
-        context,
-        context.selection_start,
-        context.selection_end,
-    )?;
-    let scaling_factor = get_scaling_factor(window);
-    CreateCaret(
-        window,
-        None,
-        (1.0 * scaling_factor) as i32,
-        context.line_height,
-    )?;
-    set_caret_position(window, context, context.selection_end)?;
-    ShowCaret(Some(window))?;
-    _ = RedrawWindow(Some(window), None, None, RDW_INVALIDATE);
+unsafe fn on_ime_end_composition(window: HWND, context: &mut Context) -> Result<()> {
+    let start = context.composition_start;
+    let end = start + context.composition_length;
+    context.composition_length = 0;
+    context.composition_attributes.clear();
+    invalidate_text(window, context, start, end)
+}
+
+unsafe fn paint_composition(window: HWND, context: &mut Context, dc: HDC) -> Result<()> {
+    let dpi = Dpi::for_window(window);
     let tokens = &context.state.qt.theme.tokens;
-    let transition = context
-        .transition_library
-        .CreateCubicBezierLinearTransition(
-            tokens.duration_normal,
-            1.0,
-            tokens.curve_decelerate_mid[0],
-            tokens.curve_decelerate_mid[1],
-            tokens.curve_decelerate_mid[2],
-            tokens.curve_decelerate_mid[3],
-        )?;
-    let seconds_now = context.animation_timer.GetTime()?;
-    context.bottom_focus_border = context.animation_manager.CreateAnimationVariable(0.0)?;
-    context.animation_manager.ScheduleTransition(
-        &context.bottom_focus_border,
-        &transition,
-        seconds_now,
-    )?;
+    let clause_pen = CreatePen(
+        PS_SOLID,
+        dpi.to_physical(1.0),
+        convert_to_color_ref(&tokens.color_neutral_foreground1),
+    );
+    let target_clause_pen = CreatePen(
+        PS_SOLID,
+        dpi.to_physical(2.0),
+        convert_to_color_ref(&tokens.color_compound_brand_stroke),
+    );
+    let old_pen = SelectObject(dc, clause_pen.into());
+    let gap = dpi.to_physical(1.0);
+    let y = context.format_rect.top + context.line_height - dpi.to_physical(1.0);
+    let mut start = 0;
+    while start < context.composition_length {
+        let attribute = context.composition_attributes.get(start).copied();
+        let mut end = start + 1;
+        while end < context.composition_length
+            && context.composition_attributes.get(end).copied() == attribute
+        {
+            end += 1;
+        }
+        let left = position_from_char(window, context, context.composition_start + start)?.x;
+        let right = position_from_char(window, context, context.composition_start + end)?.x;
+        let pen = match attribute.map(u32::from) {
+            Some(ATTR_TARGET_CONVERTED) | Some(ATTR_TARGET_NOTCONVERTED) => target_clause_pen,
+            _ => clause_pen,
+        };
+        SelectObject(dc, pen.into());
+        _ = MoveToEx(dc, left + gap, y, None);
+        _ = LineTo(dc, right - gap, y);
+        start = end;
+    }
+    SelectObject(dc, old_pen);
+    _ = DeleteObject(clause_pen.into());
+    _ = DeleteObject(target_clause_pen.into());
     Ok(())
 }
 
@@ -1651,6 +3447,10 @@ extern "system" fn window_proc(
     w_param: WPARAM,
     l_param: LPARAM,
 ) -> LRESULT {
+    if let Some(result) = unsafe { handle_disabled_mouse(window, message) } {
+        return result;
+    }
+    track_input_kind(message);
     match message {
         WM_CREATE => unsafe {
             let cs = l_param.0 as *const CREATESTRUCTW;
@@ -1661,10 +3461,27 @@ extern "system" fn window_proc(
                 if let Some(default_text) = context.state.default_value {
                     replace_selection(window, &mut context, false, default_text.as_wide(), false)?;
                 }
+                apply_number_format(window, &mut context)?;
                 Ok(context)
             }) {
                 Ok(mut context) => {
                     update_scroll_info(window, &mut context);
+                    _ = AddClipboardFormatListener(window);
+                    let drop_target: IDropTarget = DropTarget { window }.into();
+                    _ = RegisterDragDrop(window, &drop_target);
+                    if context.state.event.on_query.is_some()
+                        && context.state.input_type != Type::Password
+                    {
+                        if let Ok(list) = suggestion_list::create(&context.state.qt, window) {
+                            context.suggestion_list = Some(list);
+                            _ = SetWindowSubclass(
+                                GetAncestor(window, GA_ROOT),
+                                Some(root_subclass_proc),
+                                window.0 as usize,
+                                list.0 as usize,
+                            );
+                        }
+                    }
                     let boxed = Box::new(context);
                     SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
                     LRESULT(TRUE.0 as isize)
@@ -1673,15 +3490,37 @@ extern "system" fn window_proc(
             }
         },
         WM_DESTROY => unsafe {
+            _ = RemoveClipboardFormatListener(window);
+            _ = RevokeDragDrop(window);
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            let mut context = Box::<Context>::from_raw(raw);
-            _ = context.invalidate_uniscribe_data();
-            _ = DeleteObject(context.font.into());
-            _ = DeleteObject(context.background_color_brush.into());
-            _ = DeleteObject(context.border_pen.into());
-            _ = DeleteObject(context.border_pen_focused.into());
-            _ = DeleteObject(context.border_bottom_pen.into());
-            _ = DeleteObject(context.border_bottom_color_focused_brush.into());
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                let mut context = Box::<Context>::from_raw(raw);
+                // Commits still queued, e.g. from losing focus to the destruction, are not lost.
+                for (text, value) in take(&mut context.pending_commits) {
+                    fire_commit(window, &context.state.event, &text, value);
+                }
+                context.state.qt.unregister_animation(window);
+                if let Some(list) = context.suggestion_list {
+                    _ = RemoveWindowSubclass(
+                        GetAncestor(window, GA_ROOT),
+                        Some(root_subclass_proc),
+                        window.0 as usize,
+                    );
+                    _ = DestroyWindow(list);
+                }
+                _ = context.invalidate_uniscribe_data();
+                _ = DeleteObject(context.font.into());
+                _ = DeleteObject(context.background_color_brush.into());
+                _ = DeleteObject(context.border_pen.into());
+                _ = DeleteObject(context.border_pen_focused.into());
+                _ = DeleteObject(context.border_pen_focus_ring.into());
+                _ = DeleteObject(context.border_bottom_pen.into());
+                _ = DeleteObject(context.border_bottom_color_focused_brush.into());
+                if !context.caret_bitmap.is_invalid() {
+                    _ = DeleteObject(context.caret_bitmap.into());
+                }
+            }
             LRESULT(0)
         },
         WM_CHAR => unsafe {
@@ -1715,16 +3554,30 @@ extern "system" fn window_proc(
         WM_CLEAR => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
-            _ = clear(window, context);
+            if is_editable(window) {
+                _ = clear(window, context);
+            }
             LRESULT::default()
         },
-        WM_CONTEXTMENU => LRESULT::default(),
+        EM_SETREADONLY => unsafe {
+            let style = GetWindowLongW(window, GWL_STYLE);
+            let style = if w_param.0 != 0 {
+                style | ES_READONLY
+            } else {
+                style & !ES_READONLY
+            };
+            SetWindowLongW(window, GWL_STYLE, style);
+            LRESULT(1)
+        },
+        // Passed on to the parent. When it comes from the keyboard, -1, -1 places the menu at the
+        // input's caret.
+        WM_CONTEXTMENU => unsafe { DefWindowProcW(window, message, w_param, l_param) },
         WM_COPY => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
             match on_copy(window, context) {
-                Ok(_) => LRESULT(1),
-                Err(_) => LRESULT(0),
+                Ok(true) => LRESULT(1),
+                Ok(false) | Err(_) => LRESULT(0),
             }
         },
         WM_CUT => unsafe {
@@ -1744,23 +3597,81 @@ extern "system" fn window_proc(
         WM_GETTEXT => unsafe {
             let max_length = w_param.0;
             let dest = l_param.0 as *mut u16;
+            // There is no room even for the terminator.
+            if max_length == 0 || dest.is_null() {
+                return LRESULT(0);
+            }
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &*raw;
+            if is_text_withheld(context) {
+                *dest = 0;
+                return LRESULT(0);
+            }
             let source = context.buffer.as_wcs();
             lstrcpynW(from_raw_parts_mut(dest, max_length), source);
+            // WM_GETTEXT returns the number of characters copied, not counting the terminator.
+            // The copy may have been truncated, so this is measured on the destination.
             LRESULT(lstrlenW(PCWSTR(dest)) as isize)
         },
         WM_GETTEXTLENGTH => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
+            if is_text_withheld(context) {
+                return LRESULT(0);
+            }
             LRESULT(context.get_text_length() as isize)
         },
+        WM_GETDLGCODE => LRESULT((DLGC_WANTCHARS | DLGC_WANTARROWS) as isize),
+        // The input has no use for Ctrl+Tab, so it goes to the parent, e.g. to leave the field.
+        WM_KEYDOWN
+            if w_param.0 == VK_TAB.0 as usize
+                && unsafe { GetKeyState(VK_CONTROL.0 as i32) } < 0 =>
+        unsafe {
+            match GetParent(window) {
+                Ok(parent) => SendMessageW(parent, message, Some(w_param), Some(l_param)),
+                Err(_) => LRESULT(0),
+            }
+        },
         WM_KEYDOWN => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
             _ = on_key_down(window, context, w_param.0 as i32);
             LRESULT(0)
         },
+        WM_TIMER if w_param.0 == SUGGESTION_TIMER_ID => unsafe {
+            _ = KillTimer(Some(window), SUGGESTION_TIMER_ID);
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            _ = update_suggestions(context);
+            LRESULT(0)
+        },
+        WM_ACCEPT_SUGGESTION => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            _ = accept_suggestion(window, context, w_param.0);
+            LRESULT(0)
+        },
+        WM_COMMIT => unsafe {
+            on_commit(window);
+            LRESULT(0)
+        },
+        WM_SET_STATUS => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let status = match w_param.0 {
+                1 => Some(Status::Success),
+                2 => Some(Status::Warning),
+                3 => Some(Status::Error),
+                _ => None,
+            };
+            _ = on_set_status(window, context, status);
+            LRESULT(0)
+        },
+        WM_CLIPBOARDUPDATE => unsafe {
+            notify_parent(window, EN_CLIPBOARDUPDATE);
+            LRESULT(0)
+        },
+        WM_SYSKEYDOWN => unsafe { DefWindowProcW(window, message, w_param, l_param) },
         WM_KILLFOCUS => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
@@ -1778,7 +3689,10 @@ extern "system" fn window_proc(
             let context = &mut *raw;
             let mouse_x = l_param.0 as i16 as i32;
             let mouse_y = (l_param.0 >> 16) as i16 as i32;
-            _ = on_left_button_down(window, context, w_param.0 as u32, mouse_x, mouse_y);
+            let keys = w_param.0 as u32;
+            if let Ok(true) = on_left_button_down(window, context, keys, mouse_x, mouse_y) {
+                _ = start_drag(window);
+            }
             LRESULT(0)
         },
         WM_LBUTTONUP => unsafe {
@@ -1798,26 +3712,31 @@ extern "system" fn window_proc(
         WM_PAINT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
+            // Scrolling to the caret may widen the region this paint covers.
+            _ = update_layout(window, context);
             let mut rc = RECT::default();
             if GetClientRect(window, &mut rc).is_ok() {
                 let mut ps = PAINTSTRUCT::default();
                 let dc = BeginPaint(window, &mut ps);
-                let mem_dc = CreateCompatibleDC(Some(dc));
-                let bit_map = CreateCompatibleBitmap(dc, rc.right, rc.bottom);
-                SelectObject(mem_dc, bit_map.into());
-                _ = on_paint(window, context, mem_dc, false).and(BitBlt(
-                    dc,
-                    ps.rcPaint.left,
-                    ps.rcPaint.top,
-                    ps.rcPaint.right - ps.rcPaint.left,
-                    ps.rcPaint.bottom - ps.rcPaint.top,
-                    Some(mem_dc),
-                    ps.rcPaint.left,
-                    ps.rcPaint.top,
-                    SRCCOPY,
-                ));
-                _ = DeleteObject(bit_map.into());
-                _ = DeleteDC(mem_dc);
+                {
+                    let mem_dc = MemoryDC::new(dc, rc.right, rc.bottom);
+                    let result = on_paint(window, context, mem_dc.dc, false).and_then(|_| {
+                        BitBlt(
+                            dc,
+                            ps.rcPaint.left,
+                            ps.rcPaint.top,
+                            ps.rcPaint.right - ps.rcPaint.left,
+                            ps.rcPaint.bottom - ps.rcPaint.top,
+                            Some(mem_dc.dc),
+                            ps.rcPaint.left,
+                            ps.rcPaint.top,
+                            SRCCOPY,
+                        )
+                    });
+                    recover_lost_device(window, &result, || {
+                        context.recreate_device_resources(window)
+                    });
+                }
                 _ = EndPaint(window, &ps);
             }
             LRESULT(0)
@@ -1826,6 +3745,7 @@ extern "system" fn window_proc(
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
             let dc = HDC(w_param.0 as _);
+            _ = update_layout(window, context);
             _ = on_paint(window, context, dc, true);
             LRESULT(0)
         },
@@ -1836,6 +3756,24 @@ extern "system" fn window_proc(
             _ = on_paste(window, context);
             LRESULT::default()
         },
+        EM_GETFIRSTVISIBLELINE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            _ = update_layout(window, context);
+            LRESULT(context.x_offset as isize)
+        },
+        EM_LINESCROLL => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            _ = update_layout(window, context);
+            let offset = context.x_offset.saturating_add_signed(w_param.0 as isize);
+            LRESULT(set_scroll_offset(window, context, offset).is_ok() as isize)
+        },
+        EM_SCROLLCARET => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            _ = scroll_caret(window, &mut *raw);
+            LRESULT(0)
+        },
         WM_SETFOCUS => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
@@ -1845,7 +3783,7 @@ extern "system" fn window_proc(
         WM_SETTEXT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
-            _ = set_text(window, context, PCWSTR(l_param.0 as *const u16));
+            _ = set_text(window, context, PCWSTR(l_param.0 as *const u16), false);
             LRESULT(TRUE.0 as isize)
         },
         WM_IME_SETCONTEXT => unsafe {
@@ -1860,11 +3798,23 @@ extern "system" fn window_proc(
             }
             LRESULT::default()
         },
+        WM_IME_STARTCOMPOSITION => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            context.composition_length = 0;
+            LRESULT(0)
+        },
         WM_IME_COMPOSITION => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
-            _ = replace_selection(window, context, true, &[], true);
-            DefWindowProcW(window, message, w_param, l_param)
+            _ = on_ime_composition(window, context, IME_COMPOSITION_STRING(l_param.0 as u32));
+            LRESULT(0)
+        },
+        WM_IME_ENDCOMPOSITION => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            _ = on_ime_end_composition(window, context);
+            LRESULT(0)
         },
         WM_IME_SELECT => LRESULT::default(),
         WM_IME_REQUEST => unsafe {
@@ -1908,24 +3858,41 @@ extern "system" fn window_proc(
                 _ => DefWindowProcW(window, message, w_param, l_param),
             }
         },
+        WM_SIZE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                let context = &mut *raw;
+                // Keeps the width across DPI changes when the input is resized after creation.
+                let width = (l_param.0 & 0xffff) as i32;
+                context.state.width = Dpi::for_window(window).to_dip(width);
+                if set_rect_np(window, context).is_ok() {
+                    _ = scroll_caret(window, context);
+                    update_scroll_info(window, context);
+                    _ = InvalidateRect(Some(window), None, true);
+                }
+            }
+            LRESULT(0)
+        },
         WM_DPICHANGED_BEFOREPARENT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &mut *raw;
-            let scaling_factor = get_scaling_factor(window);
+            let dpi = Dpi::for_window(window);
             if SetWindowPos(
                 window,
                 None,
                 0,
                 0,
-                (context.state.width * scaling_factor) as i32,
-                (context.state.get_field_height() * scaling_factor) as i32,
+                dpi.to_physical(context.state.width),
+                dpi.to_physical(context.state.get_field_height()),
                 SWP_NOMOVE | SWP_NOZORDER,
             )
             .is_ok()
             {
+                // The analysis was shaped with the old font.
+                _ = context.invalidate_uniscribe_data();
                 let tokens = &context.state.qt.theme.tokens;
                 let typography_style = context.state.get_typography_style();
-                let font = create_font_from_typography_style(typography_style, scaling_factor);
+                let font = create_font_from_typography_style(typography_style, dpi);
                 let dc = GetDC(Some(window));
                 let old_font = SelectObject(dc, font.into());
                 let mut tm = TEXTMETRICW::default();
@@ -1935,28 +3902,536 @@ extern "system" fn window_proc(
                 }
                 SelectObject(dc, old_font);
                 ReleaseDC(Some(window), dc);
+                _ = DeleteObject(context.font.into());
                 context.font = font;
-                context.border_pen = CreatePen(
-                    PS_SOLID,
-                    (1.0 * scaling_factor * 2f32) as i32,
-                    convert_to_color_ref(&tokens.color_neutral_stroke1),
-                );
-                context.border_pen_focused = CreatePen(
-                    PS_SOLID,
-                    (1.0 * scaling_factor * 2f32) as i32,
-                    convert_to_color_ref(&tokens.color_neutral_stroke1_pressed),
-                );
-                context.border_bottom_pen = CreatePen(
-                    PS_SOLID,
-                    (1.0 * scaling_factor * 2f32) as i32,
-                    convert_to_color_ref(&tokens.color_neutral_stroke_accessible),
-                );
-                if set_rect_np(window, context).is_ok() {
-                    _ = InvalidateRect(Some(window), None, true);
-                }
+                _ = DeleteObject(context.border_pen.into());
+                context.border_pen = create_border_pen(dpi, &tokens.color_neutral_stroke1);
+                _ = DeleteObject(context.border_pen_focused.into());
+                context.border_pen_focused =
+                    create_border_pen(dpi, &tokens.color_neutral_stroke1_pressed);
+                _ = DeleteObject(context.border_pen_focus_ring.into());
+                context.border_pen_focus_ring =
+                    create_focus_ring_pen(dpi, &tokens.color_stroke_focus2);
+                _ = DeleteObject(context.border_bottom_pen.into());
+                context.border_bottom_pen =
+                    create_border_pen(dpi, &tokens.color_neutral_stroke_accessible);
+                // Recreates the status icon for the new DPI; this also resets the format rect.
+                _ = on_set_status(window, context, context.status);
             }
             LRESULT(0)
         },
         _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{client_size, pump_messages, window_at, window_text, Capture, Host};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    fn en_us() -> NumberLocale {
+        NumberLocale {
+            decimal: ".".to_string(),
+            thousand: ",".to_string(),
+            negative_sign: "-".to_string(),
+        }
+    }
+
+    fn de_de() -> NumberLocale {
+        NumberLocale {
+            decimal: ",".to_string(),
+            thousand: ".".to_string(),
+            negative_sign: "-".to_string(),
+        }
+    }
+
+    fn display_format(decimals: Option<u8>) -> DisplayFormat {
+        DisplayFormat {
+            grouping: true,
+            decimals,
+            prefix: String::new(),
+            suffix: " €".to_string(),
+        }
+    }
+
+    #[test]
+    fn field_heights_scale_with_dpi() {
+        let sizes = [Size::Small, Size::Medium, Size::Large];
+        let mut previous = [0; 3];
+        for dpi in [96, 120, 144, 192] {
+            let heights = sizes.map(|size| Dpi(dpi).to_physical(get_field_height(&size)));
+            for (height, size) in heights.iter().zip(sizes) {
+                let exact = get_field_height(&size) * dpi as f32 / 96f32;
+                assert!(
+                    (*height as f32 - exact).abs() <= 1f32,
+                    "{size:?} at {dpi} DPI"
+                );
+            }
+            assert!(heights.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(heights
+                .iter()
+                .zip(previous)
+                .all(|(height, previous)| *height > previous));
+            previous = heights;
+        }
+    }
+
+    #[test]
+    fn parse_number_reads_locale_display() {
+        let format = display_format(Some(2));
+        assert_eq!(parse_number("1,234.50 €", &format, &en_us()), Some(1234.5));
+        assert_eq!(parse_number("1.234,50 €", &format, &de_de()), Some(1234.5));
+        assert_eq!(parse_number("  42 ", &format, &en_us()), Some(42f64));
+    }
+
+    #[test]
+    fn parse_number_reads_negative_forms() {
+        let format = display_format(None);
+        assert_eq!(parse_number("-1,5", &format, &de_de()), Some(-1.5));
+        assert_eq!(parse_number("(1.5)", &format, &en_us()), Some(-1.5));
+        assert_eq!(parse_number("1.5-", &format, &en_us()), Some(-1.5));
+    }
+
+    #[test]
+    fn parse_number_rejects_garbage() {
+        let format = display_format(None);
+        assert_eq!(parse_number("", &format, &en_us()), None);
+        assert_eq!(parse_number("abc", &format, &en_us()), None);
+        assert_eq!(parse_number("inf", &format, &en_us()), None);
+    }
+
+    #[test]
+    fn raw_number_rounds_to_decimals_with_a_point() {
+        assert_eq!(raw_number(1.234, &display_format(Some(2))), "1.23");
+        assert_eq!(raw_number(1.5, &display_format(Some(0))), "2");
+        assert_eq!(raw_number(1.234, &display_format(None)), "1.234");
+    }
+
+    #[test]
+    fn editable_number_keeps_every_decimal() {
+        assert_eq!(editable_number(1.234, &en_us()), "1.234");
+        assert_eq!(editable_number(1234.5, &de_de()), "1234,5");
+        assert_eq!(editable_number(-0.5, &de_de()), "-0,5");
+    }
+
+    #[test]
+    fn editable_number_round_trips() {
+        for locale in [en_us(), de_de()] {
+            for value in [0f64, 1.234, -1234.5, 1e-7] {
+                let text = editable_number(value, &locale);
+                assert_eq!(parse_editable_number(&text, &locale), Some(value));
+            }
+        }
+    }
+
+    #[test]
+    fn parse_editable_number_takes_a_point_as_decimal() {
+        // Pasting "1234.5" must not become 12345 where '.' groups thousands.
+        assert_eq!(parse_editable_number("1234.5", &de_de()), Some(1234.5));
+        assert_eq!(parse_editable_number("1234,5", &de_de()), Some(1234.5));
+        assert_eq!(parse_editable_number("1.234,5", &de_de()), None);
+        assert_eq!(parse_editable_number("1,234.5", &en_us()), None);
+    }
+
+    #[test]
+    fn number_grouping_converts_locale_grouping() {
+        assert_eq!(number_grouping("3;0"), 3);
+        assert_eq!(number_grouping("3;2;0"), 32);
+        assert_eq!(number_grouping("3"), 30);
+        assert_eq!(number_grouping("0;0"), 0);
+        assert_eq!(number_grouping(""), 0);
+    }
+
+    fn text_input(default_value: PCWSTR, event: InputEvent) -> InputOptions {
+        InputOptions {
+            width: 200,
+            size: Size::Medium,
+            appearance: Appearance::Outline,
+            corner: None,
+            default_value: Some(default_value),
+            input_type: Type::Text,
+            number_format: None,
+            placeholder: None,
+            history_size: Some(4),
+            revert_on_escape: true,
+            event,
+        }
+    }
+
+    // A read-only input with history and a suggestion for its text, so that every path that
+    // replaces the text has something to replace it with.
+    fn create_read_only(host: &Host) -> HWND {
+        let event = InputEvent {
+            on_query: Some(Box::new(|_text| vec!["apricot".to_string()])),
+            ..Default::default()
+        };
+        let window = host.create::<Input>(0, 0, text_input(w!("apple"), event));
+        set_history(window, vec!["banana".to_string()]);
+        unsafe {
+            SendMessageW(window, EM_SETREADONLY, Some(WPARAM(1)), None);
+        }
+        window
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn focus_restores_digits_the_display_rounded() {
+        let host = Host::new(300, 100);
+        let options = InputOptions {
+            input_type: Type::Number,
+            number_format: Some(NumberFormat {
+                decimals: Some(2),
+                ..Default::default()
+            }),
+            history_size: None,
+            ..text_input(w!("1.234"), InputEvent::default())
+        };
+        let window = host.create::<Input>(0, 0, options);
+        let locale = unsafe { NumberLocale::current() };
+        assert_eq!(window_text(window), format!("1{}23", locale.decimal));
+        unsafe {
+            SendMessageW(window, WM_SETFOCUS, None, None);
+        }
+        assert_eq!(window_text(window), editable_number(1.234, &locale));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn corner_pixel_of_pill_input_reaches_parent() {
+        let host = Host::new(300, 100);
+        let options = InputOptions {
+            corner: Some(CornerRadius::Pill),
+            ..text_input(w!(""), InputEvent::default())
+        };
+        let window = host.create::<Input>(10, 10, options);
+        let (width, height) = client_size(window);
+        assert_eq!(window_at(window, 0, 0), host.window);
+        assert_eq!(window_at(window, width - 1, 0), host.window);
+        assert_eq!(window_at(window, width / 2, height / 2), window);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn measurement_matches_created_height() {
+        let host = Host::new(300, 200);
+        let dpi = Dpi::for_window(host.window);
+        for size in [Size::Small, Size::Medium, Size::Large] {
+            let options = InputOptions {
+                size,
+                ..text_input(w!(""), InputEvent::default())
+            };
+            let window = host.create::<Input>(10, 10, options);
+            let height = dpi.to_physical(host.qt.measure_input(&size));
+            assert_eq!(client_size(window), (dpi.to_physical(200f32), height));
+        }
+    }
+
+    fn type_text(window: HWND, text: &str) {
+        for char in text.encode_utf16() {
+            unsafe { SendMessageW(window, WM_CHAR, Some(WPARAM(char as usize)), None) };
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn typed_characters_are_shaped_once_at_paint() {
+        let host = Host::new(300, 100);
+        let window = host.create::<Input>(10, 10, text_input(w!(""), InputEvent::default()));
+        pump_messages();
+        ANALYSES.set(0);
+        type_text(window, &"x".repeat(5000));
+        assert_eq!(ANALYSES.get(), 0);
+        pump_messages();
+        assert_eq!(ANALYSES.get(), 1);
+        // The caret was scrolled into view by the paint.
+        let context = unsafe { get_input_context(window).unwrap() };
+        assert!(context.x_offset > 0);
+        assert!(!context.is_layout_pending);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn selection_rects_match_after_cached_edits() {
+        let host = Host::new(300, 100);
+        let typed = host.create::<Input>(10, 10, text_input(w!(""), InputEvent::default()));
+        type_text(typed, "Hello, wörld");
+        let fresh = host.create::<Input>(
+            10,
+            50,
+            text_input(w!("Hello, wörld"), InputEvent::default()),
+        );
+        pump_messages();
+        for (start, end) in [(0, 5), (3, 9), (7, 12), (0, 12)] {
+            unsafe {
+                let cached = get_single_line_rect(
+                    typed,
+                    get_input_context(typed).unwrap(),
+                    start,
+                    Some(end),
+                )
+                .unwrap();
+                let expected = get_single_line_rect(
+                    fresh,
+                    get_input_context(fresh).unwrap(),
+                    start,
+                    Some(end),
+                )
+                .unwrap();
+                assert_eq!((cached.left, cached.right), (expected.left, expected.right));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn external_resize_widens_the_text_area() {
+        let host = Host::new(400, 100);
+        let window = host.create::<Input>(10, 10, text_input(w!(""), InputEvent::default()));
+        let (width, height) = client_size(window);
+        let format_rect = |window| unsafe { get_input_context(window).unwrap().format_rect };
+        let before = format_rect(window);
+        unsafe {
+            SetWindowPos(
+                window,
+                None,
+                0,
+                0,
+                width + 100,
+                height,
+                SWP_NOMOVE | SWP_NOZORDER,
+            )
+            .unwrap();
+        }
+        let after = format_rect(window);
+        assert_eq!(after.left, before.left);
+        assert_eq!(after.right, before.right + 100);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn caret_stays_visible_when_narrowed() {
+        let host = Host::new(400, 100);
+        let text = w!("A sentence that is far too long for a narrow input");
+        let window = host.create::<Input>(10, 10, text_input(text, InputEvent::default()));
+        let (width, height) = client_size(window);
+        unsafe {
+            let length = get_input_context(window).unwrap().get_text_length();
+            for caret in [length, length / 2, 0] {
+                let context = get_input_context(window).unwrap();
+                set_selection(window, context, Some(caret), Some(caret)).unwrap();
+                for new_width in [width / 3, width / 5, width] {
+                    SetWindowPos(
+                        window,
+                        None,
+                        0,
+                        0,
+                        new_width,
+                        height,
+                        SWP_NOMOVE | SWP_NOZORDER,
+                    )
+                    .unwrap();
+                    let context = get_input_context(window).unwrap();
+                    let x = position_from_char(window, context, caret).unwrap().x;
+                    assert!(x >= context.format_rect.left && x <= context.format_rect.right);
+                    let dpi = Dpi::for_window(window);
+                    assert_eq!(dpi.to_physical(context.state.width), new_width);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn merge_runs_joins_touching_spans() {
+        let spans = vec![(30, 40), (0, 10), (10, 20), (35, 50), (60, 70)];
+        assert_eq!(merge_runs(spans), [(0, 20), (30, 50), (60, 70)]);
+        assert_eq!(merge_runs(Vec::new()), []);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn arabic_selection_in_ltr_text_highlights_only_selected_glyphs() {
+        let host = Host::new(400, 100);
+        // "abc مرحبا def": the Arabic word runs right to left inside the left-to-right line.
+        let text = w!("abc \u{645}\u{631}\u{62D}\u{628}\u{627} def");
+        let window = host.create::<Input>(10, 10, text_input(text, InputEvent::default()));
+        unsafe {
+            let context = get_input_context(window).unwrap();
+            // "c", the space and the first two Arabic letters, which sit at the word's right end.
+            set_selection(window, context, Some(2), Some(6)).unwrap();
+            let context = get_input_context(window).unwrap();
+            let x = position_from_char(window, context, 0).unwrap().x;
+            let runs = get_selection_runs(context.ssa, 2, 6).unwrap();
+            assert_eq!(runs.len(), 2);
+
+            let to_pixel = |color: COLORREF| {
+                let [r, g, b, _] = color.0.to_le_bytes();
+                u32::from_be_bytes([0, r, g, b])
+            };
+            let selected = to_pixel(get_selection_colors(context).0);
+            let capture = Capture::of(window);
+            // Above the glyphs only the opaque selection fill shows.
+            let y = context.format_rect.top + 1;
+            for (left, right) in &runs {
+                assert_eq!(capture.pixel(x + (left + right) / 2, y), selected);
+            }
+            // The unselected Arabic letters between the two runs keep the normal background.
+            assert_ne!(capture.pixel(x + (runs[0].1 + runs[1].0) / 2, y), selected);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn read_only_input_ignores_ime_result() {
+        let host = Host::new(300, 100);
+        let window = create_read_only(&host);
+        unsafe {
+            // An unguarded composition would replace the selection, even with an empty result.
+            let context = get_input_context(window).unwrap();
+            let length = context.get_text_length();
+            set_selection(window, context, Some(0), Some(length)).unwrap();
+            SendMessageW(
+                window,
+                WM_IME_COMPOSITION,
+                None,
+                Some(LPARAM(GCS_RESULTSTR.0 as isize)),
+            );
+        }
+        assert_eq!(window_text(window), "apple");
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn read_only_input_ignores_revert() {
+        let host = Host::new(300, 100);
+        let window = create_read_only(&host);
+        unsafe {
+            SendMessageW(window, WM_KEYDOWN, Some(WPARAM(VK_ESCAPE.0 as usize)), None);
+        }
+        assert_eq!(window_text(window), "apple");
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn read_only_input_ignores_history() {
+        let host = Host::new(300, 100);
+        let window = create_read_only(&host);
+        unsafe {
+            SendMessageW(window, WM_KEYDOWN, Some(WPARAM(VK_UP.0 as usize)), None);
+        }
+        assert_eq!(window_text(window), "apple");
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn read_only_input_ignores_suggestion() {
+        let host = Host::new(300, 100);
+        let window = create_read_only(&host);
+        unsafe {
+            update_suggestions(get_input_context(window).unwrap()).unwrap();
+            SendMessageW(window, WM_ACCEPT_SUGGESTION, Some(WPARAM(0)), None);
+        }
+        assert_eq!(window_text(window), "apple");
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn read_only_input_ignores_undo() {
+        let host = Host::new(300, 100);
+        let window = create_read_only(&host);
+        unsafe {
+            SendMessageW(window, WM_UNDO, None, None);
+        }
+        assert_eq!(window_text(window), "apple");
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn clipboard_changes_reach_the_parent() {
+        let host = Host::new(300, 100);
+        let window = host.create::<Input>(10, 10, text_input(w!("pear"), InputEvent::default()));
+        pump_messages();
+        unsafe {
+            let context = get_input_context(window).unwrap();
+            context.selection_start = 0;
+            context.selection_end = 4;
+            SendMessageW(window, WM_COPY, None, None);
+        }
+        // Clipboard listeners are told asynchronously.
+        for _ in 0..20 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            pump_messages();
+        }
+        assert!(host.notifications().contains(&EN_CLIPBOARDUPDATE));
+        assert!(can_paste());
+    }
+
+    fn commit_with(host: &Host, on_commit: CommitCallback) -> HWND {
+        let event = InputEvent {
+            on_commit,
+            ..Default::default()
+        };
+        let window = host.create::<Input>(10, 10, text_input(w!(""), event));
+        type_text(window, "typed");
+        window
+    }
+
+    fn press_enter(window: HWND) {
+        unsafe {
+            SendMessageW(window, WM_KEYDOWN, Some(WPARAM(VK_RETURN.0 as usize)), None);
+        }
+        pump_messages();
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn commit_handler_may_set_the_text() {
+        let host = Host::new(300, 100);
+        let window = commit_with(
+            &host,
+            Box::new(|window, _text| set_text_preserving(*window, w!("normalized"), false)),
+        );
+        press_enter(window);
+        assert_eq!(window_text(window), "normalized");
+        let context = unsafe { get_input_context(window).unwrap() };
+        assert_eq!(context.committed_text, "typed");
+        assert!(context.pending_commits.is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn commit_handler_may_destroy_the_input() {
+        let host = Host::new(300, 100);
+        let commits = Rc::new(Cell::new(0));
+        let counted = commits.clone();
+        let window = commit_with(
+            &host,
+            Box::new(move |window, _text| {
+                counted.set(counted.get() + 1);
+                _ = unsafe { DestroyWindow(*window) };
+            }),
+        );
+        press_enter(window);
+        assert_eq!(commits.get(), 1);
+        assert!(!unsafe { IsWindow(Some(window)) }.as_bool());
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn destroying_the_input_fires_queued_commits() {
+        let host = Host::new(300, 100);
+        let commits = Rc::new(RefCell::new(Vec::new()));
+        let recorded = commits.clone();
+        let window = commit_with(
+            &host,
+            Box::new(move |_window, text| recorded.borrow_mut().push(text.to_string())),
+        );
+        unsafe {
+            SendMessageW(window, WM_KEYDOWN, Some(WPARAM(VK_RETURN.0 as usize)), None);
+            DestroyWindow(window).unwrap();
+        }
+        pump_messages();
+        assert_eq!(*commits.borrow(), ["typed"]);
+    }
+}
@@ -1,11 +1,13 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Debug, Formatter};
 use std::mem::size_of;
 use std::rc::Rc;
 
 use windows::core::*;
 use windows::Foundation::Numerics::Matrix3x2;
 use windows::Win32::Foundation::{
-    ERROR_INVALID_WINDOW_HANDLE, FALSE, HINSTANCE, HWND, LPARAM, LRESULT, POINT, RECT, TRUE, WPARAM,
+    ERROR_BUSY, ERROR_INVALID_WINDOW_HANDLE, E_INVALIDARG, FALSE, HINSTANCE, HWND, LPARAM, LRESULT,
+    POINT, RECT, TRUE, WPARAM,
 };
 use windows::Win32::Graphics::Direct2D::Common::{
     D2D_POINT_2F, D2D_RECT_F, D2D_SIZE_F, D2D_SIZE_U,
@@ -13,54 +15,125 @@ use windows::Win32::Graphics::Direct2D::Common::{
 use windows::Win32::Graphics::Direct2D::{
     D2D1CreateFactory, ID2D1DeviceContext5, ID2D1Factory1, ID2D1HwndRenderTarget,
     ID2D1SolidColorBrush, ID2D1SvgDocument, D2D1_DRAW_TEXT_OPTIONS_NONE, D2D1_FACTORY_OPTIONS,
-    D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_HWND_RENDER_TARGET_PROPERTIES,
-    D2D1_RENDER_TARGET_PROPERTIES, D2D1_ROUNDED_RECT,
+    D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_HWND_RENDER_TARGET_PROPERTIES, D2D1_ROUNDED_RECT,
 };
 use windows::Win32::Graphics::DirectWrite::{
     DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, DWRITE_FACTORY_TYPE_SHARED,
     DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL, DWRITE_MEASURING_MODE_NATURAL,
-    DWRITE_TEXT_METRICS,
+    DWRITE_TEXT_ALIGNMENT_TRAILING, DWRITE_TEXT_METRICS,
 };
+use windows::Win32::Graphics::Dwm::DWMWCP_ROUNDSMALL;
 use windows::Win32::Graphics::Gdi::{
-    BeginPaint, ClientToScreen, CreateRoundRectRgn, EndPaint, GetMonitorInfoW, MonitorFromPoint,
-    OffsetRect, PtInRect, RedrawWindow, SetRect, SetRectEmpty, SetWindowRgn, MONITORINFO,
-    MONITOR_DEFAULTTONEAREST, PAINTSTRUCT, RDW_INVALIDATE, RDW_NOCHILDREN,
+    BeginPaint, ClientToScreen, CreateRoundRectRgn, EndPaint, GetMonitorInfoW, InvalidateRect,
+    MonitorFromPoint, OffsetRect, PtInRect, RedrawWindow, SetRect, SetRectEmpty, SetWindowRgn, HDC,
+    MONITORINFO, MONITOR_DEFAULTTONEAREST, PAINTSTRUCT, RDW_INVALIDATE, RDW_NOCHILDREN,
 };
-use windows::Win32::UI::HiDpi::GetDpiForWindow;
+use windows::Win32::UI::Accessibility::UiaReturnRawElementProvider;
 use windows::Win32::UI::Input::KeyboardAndMouse::{
-    ReleaseCapture, SetCapture, VIRTUAL_KEY, VK_DOWN, VK_END, VK_ESCAPE, VK_F10, VK_HOME, VK_LEFT,
-    VK_MENU, VK_RIGHT, VK_UP,
+    GetCapture, GetFocus, ReleaseCapture, SetCapture, VIRTUAL_KEY, VK_DOWN, VK_END, VK_ESCAPE,
+    VK_F10, VK_HOME, VK_LEFT, VK_MENU, VK_RIGHT, VK_UP,
 };
 use windows::Win32::UI::Shell::SHCreateMemStream;
 use windows::Win32::UI::WindowsAndMessaging::*;
 
+use crate::component::button::set_svg_color;
+use crate::component::tooltip::{self, HoverAction, HoverTimer};
 use crate::icon::Icon;
-use crate::{get_scaling_factor, QT};
+use crate::theme::Tokens;
+use crate::type_ahead::TypeAhead;
+use crate::{
+    apply_corner_preference, copy_window_text, print_client, recover_lost_device,
+    recreate_render_target, render_target_properties, window_class_name, Dpi, QT,
+};
+
+mod automation;
 
 pub enum MenuInfo {
+    /// `text` may end with a tab and a shortcut to display, as in `"Copy\tCtrl+C"`. Shortcuts are
+    /// only shown; the application still handles the keys.
     MenuItem {
         text: PCWSTR,
         command_id: u32,
         disabled: bool,
+        disabled_reason: Option<PCWSTR>,
     },
     SubMenu {
         menu_list: Vec<MenuInfo>,
         text: PCWSTR,
     },
+    /// A submenu whose items are built each time it opens, e.g. a list of recent files. `build`
+    /// runs on the UI thread, inside the menu's tracking loop, so it should return promptly.
+    DynamicSubMenu {
+        build: Box<dyn Fn() -> Vec<MenuInfo>>,
+        text: PCWSTR,
+    },
+    /// A toggleable item drawn with a checkbox. Choosing it sends the command like any other item;
+    /// the check state only changes through [`MenuHandle::set_item_checked`].
+    CheckItem {
+        text: PCWSTR,
+        command_id: u32,
+        checked: bool,
+        disabled: bool,
+    },
     MenuDivider,
 }
 
+impl Debug for MenuInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MenuInfo::MenuItem {
+                text,
+                command_id,
+                disabled,
+                disabled_reason,
+            } => f
+                .debug_struct("MenuItem")
+                .field("text", text)
+                .field("command_id", command_id)
+                .field("disabled", disabled)
+                .field("disabled_reason", disabled_reason)
+                .finish(),
+            MenuInfo::SubMenu { menu_list, text } => f
+                .debug_struct("SubMenu")
+                .field("menu_list", menu_list)
+                .field("text", text)
+                .finish(),
+            MenuInfo::DynamicSubMenu { text, .. } => f
+                .debug_struct("DynamicSubMenu")
+                .field("text", text)
+                .finish_non_exhaustive(),
+            MenuInfo::CheckItem {
+                text,
+                command_id,
+                checked,
+                disabled,
+            } => f
+                .debug_struct("CheckItem")
+                .field("text", text)
+                .field("command_id", command_id)
+                .field("checked", checked)
+                .field("disabled", disabled)
+                .finish(),
+            MenuInfo::MenuDivider => f.write_str("MenuDivider"),
+        }
+    }
+}
+
 enum MenuItem {
     MenuItem {
         text: PCWSTR,
         id: u32,
         rect: RECT,
         disabled: bool,
+        disabled_reason: Option<PCWSTR>,
+        checked: bool,
+        checkable: bool,
     },
     SubMenu {
         sub_menu: Rc<RefCell<Menu>>,
         text: PCWSTR,
         rect: RECT,
+        build: Option<Box<dyn Fn() -> Vec<MenuInfo>>>,
     },
     MenuDivider {
         rect: RECT,
@@ -74,6 +147,9 @@ struct Menu {
     menu_list_rect: RECT,
     is_scrolling: bool,
     scroll_position: i32,
+    has_check_column: bool,
+    /// Where the shortcuts of all items start, relative to the menu list, if any item has one.
+    shortcut_column_offset: Option<i32>,
 }
 
 pub struct Context {
@@ -82,11 +158,71 @@ pub struct Context {
     owning_window: HWND,
     render_target: ID2D1HwndRenderTarget,
     text_format: IDWriteTextFormat,
+    resources: DeviceResources,
+}
+
+// Everything drawn with that belongs to the render target, so it is recreated with it.
+struct DeviceResources {
     text_brush: ID2D1SolidColorBrush,
     text_focused_brush: ID2D1SolidColorBrush,
     text_disabled_brush: ID2D1SolidColorBrush,
     sub_menu_indicator_svg: ID2D1SvgDocument,
     sub_menu_indicator_focused_svg: ID2D1SvgDocument,
+    checkmark_svg: ID2D1SvgDocument,
+    check_box_brush: ID2D1SolidColorBrush,
+    check_box_checked_brush: ID2D1SolidColorBrush,
+    check_box_checkmark_svg: ID2D1SvgDocument,
+}
+
+impl DeviceResources {
+    unsafe fn new(render_target: &ID2D1HwndRenderTarget, tokens: &Tokens) -> Result<Self> {
+        let text_brush =
+            render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground2, None)?;
+        let text_focused_brush =
+            render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground1_hover, None)?;
+        let text_disabled_brush =
+            render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground_disabled, None)?;
+        let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
+        let sub_menu_indicator_svg =
+            create_svg_document(&device_context5, &Icon::chevron_right_regular())?;
+        let sub_menu_indicator_focused_svg =
+            create_svg_document(&device_context5, &Icon::chevron_right_filled())?;
+        let checkmark_svg = create_svg_document(&device_context5, &Icon::checkmark_regular())?;
+        let check_box_brush =
+            render_target.CreateSolidColorBrush(&tokens.color_neutral_stroke1, None)?;
+        let check_box_checked_brush =
+            render_target.CreateSolidColorBrush(&tokens.color_compound_brand_background, None)?;
+        let check_box_checkmark_svg =
+            create_svg_document(&device_context5, &Icon::checkmark_regular())?;
+        check_box_checkmark_svg.SetViewportSize(D2D_SIZE_F {
+            width: CHECK_BOX_SIZE,
+            height: CHECK_BOX_SIZE,
+        })?;
+        set_svg_color(
+            &check_box_checkmark_svg,
+            &tokens.color_neutral_foreground_on_brand,
+        )?;
+        Ok(DeviceResources {
+            text_brush,
+            text_focused_brush,
+            text_disabled_brush,
+            sub_menu_indicator_svg,
+            sub_menu_indicator_focused_svg,
+            checkmark_svg,
+            check_box_brush,
+            check_box_checked_brush,
+            check_box_checkmark_svg,
+        })
+    }
+}
+
+impl Context {
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        self.resources = DeviceResources::new(&render_target, &self.qt.theme.tokens)?;
+        self.render_target = render_target;
+        Ok(())
+    }
 }
 
 fn convert_menu_info_list_to_menu(menu_info_list: Vec<MenuInfo>) -> Menu {
@@ -97,11 +233,29 @@ fn convert_menu_info_list_to_menu(menu_info_list: Vec<MenuInfo>) -> Menu {
                 text,
                 command_id,
                 disabled,
+                disabled_reason,
+            } => MenuItem::MenuItem {
+                text,
+                id: command_id,
+                rect: RECT::default(),
+                disabled,
+                disabled_reason,
+                checked: false,
+                checkable: false,
+            },
+            MenuInfo::CheckItem {
+                text,
+                command_id,
+                checked,
+                disabled,
             } => MenuItem::MenuItem {
                 text,
                 id: command_id,
                 rect: RECT::default(),
                 disabled,
+                disabled_reason: None,
+                checked,
+                checkable: true,
             },
             MenuInfo::SubMenu { menu_list, text } => {
                 let sub_menu = convert_menu_info_list_to_menu(menu_list);
@@ -109,8 +263,15 @@ fn convert_menu_info_list_to_menu(menu_info_list: Vec<MenuInfo>) -> Menu {
                     sub_menu: Rc::new(RefCell::new(sub_menu)),
                     text,
                     rect: RECT::default(),
+                    build: None,
                 }
             }
+            MenuInfo::DynamicSubMenu { build, text } => MenuItem::SubMenu {
+                sub_menu: Rc::new(RefCell::new(convert_menu_info_list_to_menu(Vec::new()))),
+                text,
+                rect: RECT::default(),
+                build: Some(build),
+            },
             MenuInfo::MenuDivider => MenuItem::MenuDivider {
                 rect: RECT::default(),
             },
@@ -123,12 +284,141 @@ fn convert_menu_info_list_to_menu(menu_info_list: Vec<MenuInfo>) -> Menu {
         menu_list_rect: RECT::default(),
         is_scrolling: false,
         scroll_position: 0,
+        has_check_column: false,
+        shortcut_column_offset: None,
     }
 }
 
-const CLASS_NAME: PCWSTR = w!("QT_MENU");
+/// A menu that keeps its items between openings, so that an application can hold on to one and
+/// update its entries instead of rebuilding it every time. Items are addressed by command id;
+/// every item with that id, in any submenu, is updated. Changes to an open menu are drawn right
+/// away, but item sizes and the room for check marks are only worked out when a popup opens.
+#[derive(Clone)]
+pub struct MenuHandle {
+    menu: Rc<RefCell<Menu>>,
+}
+
+impl MenuHandle {
+    pub fn new(menu_list: Vec<MenuInfo>) -> Self {
+        MenuHandle {
+            menu: Rc::new(RefCell::new(convert_menu_info_list_to_menu(menu_list))),
+        }
+    }
+
+    pub fn set_item_disabled(&self, command_id: u32, disabled: bool) -> Result<()> {
+        self.update_item(command_id, |item| {
+            if let MenuItem::MenuItem { disabled: d, .. } = item {
+                *d = disabled;
+            }
+        })
+    }
+
+    pub fn set_item_checked(&self, command_id: u32, checked: bool) -> Result<()> {
+        self.update_item(command_id, |item| {
+            if let MenuItem::MenuItem { checked: c, .. } = item {
+                *c = checked;
+            }
+        })
+    }
+
+    /// `text` must stay valid for as long as the handle is alive.
+    pub fn set_item_text(&self, command_id: u32, text: PCWSTR) -> Result<()> {
+        self.update_item(command_id, |item| {
+            if let MenuItem::MenuItem { text: t, .. } = item {
+                *t = text;
+            }
+        })
+    }
+
+    fn update_item(&self, command_id: u32, mut update: impl FnMut(&mut MenuItem)) -> Result<()> {
+        if update_menu_items(&self.menu, command_id, &mut update)? {
+            Ok(())
+        } else {
+            Err(Error::from(E_INVALIDARG))
+        }
+    }
+}
+
+// Applies `update` to every item with `command_id` in `menu` and its submenus, and redraws the ones
+// that are on screen. Returns whether any item matched.
+fn update_menu_items(
+    menu: &Rc<RefCell<Menu>>,
+    command_id: u32,
+    update: &mut dyn FnMut(&mut MenuItem),
+) -> Result<bool> {
+    let mut menu = menu.try_borrow_mut().map_err(|_| Error::from(ERROR_BUSY))?;
+    let mut found = false;
+    for index in 0..menu.items.len() {
+        match &menu.items[index] {
+            MenuItem::MenuItem { id, .. } if *id == command_id => {
+                update(&mut menu.items[index]);
+                invalidate_item(&menu, index);
+                found = true;
+            }
+            MenuItem::SubMenu { sub_menu, .. } => {
+                found |= update_menu_items(sub_menu, command_id, update)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(found)
+}
+
+fn invalidate_item(menu: &Menu, index: usize) {
+    let Some(window) = menu.window else {
+        return;
+    };
+    let (MenuItem::MenuItem { rect, .. }
+    | MenuItem::SubMenu { rect, .. }
+    | MenuItem::MenuDivider { rect }) = &menu.items[index];
+    let rect = adjust_menu_item_rect(menu, rect);
+    let dpi = Dpi::for_window(window);
+    let rect = RECT {
+        left: dpi.to_physical(rect.left as f32),
+        top: dpi.to_physical(rect.top as f32),
+        right: dpi.to_physical(rect.right as f32),
+        bottom: dpi.to_physical(rect.bottom as f32),
+    };
+    unsafe {
+        _ = InvalidateRect(Some(window), Some(&rect), false);
+    }
+}
+
+thread_local! {
+    static IS_TRACKING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Marks a menu as being tracked on this thread for as long as it is alive. The tracking loop owns
+// the mouse capture and the message pump, so a second one cannot run inside it.
+struct TrackingGuard;
+
+impl TrackingGuard {
+    fn enter() -> Result<Self> {
+        if IS_TRACKING.replace(true) {
+            Err(Error::from(ERROR_BUSY))
+        } else {
+            Ok(TrackingGuard)
+        }
+    }
+}
+
+impl Drop for TrackingGuard {
+    fn drop(&mut self) {
+        IS_TRACKING.set(false);
+    }
+}
+
+fn get_class_name() -> HSTRING {
+    window_class_name("QT_MENU", Some(window_proc))
+}
 
 impl QT {
+    /// Opens a menu at `x`, `y` in screen coordinates and returns once it closes. `WM_CONTEXTMENU`
+    /// reports those directly, or -1, -1 when the menu was invoked from the keyboard, in which case
+    /// the menu is opened like [`QT::open_context_menu`] for the focused window. Commands are
+    /// posted to `parent_window`, so their handlers run after the menu is gone and may open
+    /// another one. Opening a menu while one is already open on this thread, e.g. from a message
+    /// dispatched by its loop, fails with `ERROR_BUSY`.
     pub unsafe fn open_menu(
         &self,
         parent_window: HWND,
@@ -136,9 +426,66 @@ impl QT {
         x: i32,
         y: i32,
     ) -> Result<()> {
+        self.open_menu_handle(parent_window, &MenuHandle::new(menu_list), x, y)
+    }
+
+    /// Opens a menu kept in `menu`, like [`QT::open_menu`]. The handle keeps its items, including
+    /// any changes made through it, for the next opening.
+    ///
+    /// # Safety
+    /// `parent_window` must belong to the calling thread.
+    pub unsafe fn open_menu_handle(
+        &self,
+        parent_window: HWND,
+        menu: &MenuHandle,
+        x: i32,
+        y: i32,
+    ) -> Result<()> {
+        if (x, y) == (-1, -1) {
+            let focused_window = GetFocus();
+            let anchor = get_keyboard_position(if focused_window.is_invalid() {
+                parent_window
+            } else {
+                focused_window
+            })?;
+            self.open(parent_window, menu, anchor.x, anchor.y, true)
+        } else {
+            self.open(parent_window, menu, x, y, false)
+        }
+    }
+
+    /// Opens a menu invoked from the keyboard at `anchor` in screen coordinates, with its first
+    /// enabled item focused so that the arrow keys work right away.
+    ///
+    /// # Safety
+    /// `parent_window` must belong to the calling thread.
+    pub unsafe fn open_menu_for_keyboard(
+        &self,
+        parent_window: HWND,
+        menu_list: Vec<MenuInfo>,
+        anchor: POINT,
+    ) -> Result<()> {
+        self.open(
+            parent_window,
+            &MenuHandle::new(menu_list),
+            anchor.x,
+            anchor.y,
+            true,
+        )
+    }
+
+    unsafe fn open(
+        &self,
+        parent_window: HWND,
+        menu: &MenuHandle,
+        x: i32,
+        y: i32,
+        from_keyboard: bool,
+    ) -> Result<()> {
+        let class_name = get_class_name();
         let window_class = WNDCLASSEXW {
             cbSize: size_of::<WNDCLASSEXW>() as u32,
-            lpszClassName: CLASS_NAME,
+            lpszClassName: PCWSTR(class_name.as_ptr()),
             style: CS_DROPSHADOW | CS_SAVEBITS | CS_DBLCLKS,
             lpfnWndProc: Some(window_proc),
             hCursor: LoadCursorW(None, IDC_ARROW)?,
@@ -148,20 +495,66 @@ impl QT {
         if !IsWindow(Some(parent_window)).as_bool() {
             return Err(Error::from(ERROR_INVALID_WINDOW_HANDLE));
         }
-        let menu = Rc::new(RefCell::new(convert_menu_info_list_to_menu(menu_list)));
-        init_popup(self.clone(), parent_window, menu.clone(), x, y, 0, 0)?;
+        let _tracking = TrackingGuard::enter()?;
+        let menu = menu.menu.clone();
+        init_popup(self.clone(), parent_window, menu.clone(), x, y, None)?;
+        if from_keyboard {
+            select_first_enabled(&mut menu.borrow_mut());
+        }
         init_tracking(parent_window)?;
-        track_menu(menu.clone(), 0, 0, parent_window).and(exit_tracking(parent_window))?;
+        // WM_EXITMENULOOP is sent synchronously on every exit path, including cancellation.
+        let result = track_menu(menu.clone(), 0, 0, parent_window);
+        exit_tracking(parent_window)?;
+        result?;
         Ok(())
     }
+
+    /// Opens a menu for a keyboard-invoked context menu, such as Shift+F10 or the Menu key. It
+    /// is placed at the caret when `focused_window` owns one, and below the window otherwise.
+    ///
+    /// # Safety
+    /// `focused_window` must belong to the calling thread.
+    pub unsafe fn open_context_menu(
+        &self,
+        focused_window: HWND,
+        menu_list: Vec<MenuInfo>,
+    ) -> Result<()> {
+        let anchor = get_keyboard_position(focused_window)?;
+        self.open_menu_for_keyboard(GetAncestor(focused_window, GA_ROOT), menu_list, anchor)
+    }
+}
+
+// Screen position for a menu invoked from the keyboard: below the caret when the window owns it,
+// and below the window otherwise.
+unsafe fn get_keyboard_position(focused_window: HWND) -> Result<POINT> {
+    let mut info = GUITHREADINFO {
+        cbSize: size_of::<GUITHREADINFO>() as u32,
+        ..Default::default()
+    };
+    // The caret belongs to the window's own thread, which need not be the foreground one.
+    let thread_id = GetWindowThreadProcessId(focused_window, None);
+    if GetGUIThreadInfo(thread_id, &mut info).is_ok() && info.hwndCaret == focused_window {
+        let mut point = POINT {
+            x: info.rcCaret.left,
+            y: info.rcCaret.bottom,
+        };
+        _ = ClientToScreen(focused_window, &mut point);
+        Ok(point)
+    } else {
+        let mut rect = RECT::default();
+        GetWindowRect(focused_window, &mut rect)?;
+        Ok(POINT {
+            x: rect.left,
+            y: rect.bottom,
+        })
+    }
 }
 
 pub struct CreateParams {
     qt: QT,
     menu: Rc<RefCell<Menu>>,
     owning_window: HWND,
-    x_anchor: i32,
-    y_anchor: i32,
+    anchor: Option<RECT>,
 }
 
 unsafe fn init_popup(
@@ -170,19 +563,17 @@ unsafe fn init_popup(
     menu: Rc<RefCell<Menu>>,
     x: i32,
     y: i32,
-    x_anchor: i32,
-    y_anchor: i32,
+    anchor: Option<RECT>,
 ) -> Result<()> {
     let boxed = Box::new(CreateParams {
         qt,
         menu: menu.clone(),
         owning_window,
-        x_anchor,
-        y_anchor,
+        anchor,
     });
     let window = CreateWindowExW(
         WINDOW_EX_STYLE::default(),
-        CLASS_NAME,
+        &get_class_name(),
         w!(""),
         WS_POPUP,
         x,
@@ -222,6 +613,9 @@ struct Tracker {
     top_menu: Rc<RefCell<Menu>>,
     owning_window: HWND,
     point: POINT,
+    tooltip: Option<HWND>,
+    tooltip_point: POINT,
+    tooltip_timer: HoverTimer,
 }
 
 fn menu_from_point(root: Rc<RefCell<Menu>>, point: &POINT) -> Option<Rc<RefCell<Menu>>> {
@@ -242,6 +636,22 @@ fn menu_from_point(root: Rc<RefCell<Menu>>, point: &POINT) -> Option<Rc<RefCell<
     result
 }
 
+// Converts an item rectangle, in DIPs relative to the list, to physical screen coordinates. Each
+// edge is scaled on its own so that neighbouring popups agree on where the item is.
+unsafe fn get_item_screen_rect(menu: &Menu, rect: &RECT) -> Option<RECT> {
+    let window = menu.window?;
+    let mut window_rect = RECT::default();
+    GetWindowRect(window, &mut window_rect).ok()?;
+    let dpi = Dpi::for_window(window);
+    let item_rect = adjust_menu_item_rect(menu, rect);
+    Some(RECT {
+        left: window_rect.left + dpi.to_physical(item_rect.left as f32),
+        top: window_rect.top + dpi.to_physical(item_rect.top as f32),
+        right: window_rect.left + dpi.to_physical(item_rect.right as f32),
+        bottom: window_rect.top + dpi.to_physical(item_rect.bottom as f32),
+    })
+}
+
 fn adjust_menu_item_rect(menu: &Menu, rect: &RECT) -> RECT {
     let scroll_offset = if menu.is_scrolling {
         menu.scroll_position
@@ -282,9 +692,9 @@ fn find_item_by_coordinates(menu: &Menu, point: &mut POINT) -> HitTest {
             point.x -= rect.left;
             point.y -= rect.top;
 
-            let scaling_factor = get_scaling_factor(window);
-            point.x = (point.x as f32 / scaling_factor) as i32;
-            point.y = (point.y as f32 / scaling_factor) as i32;
+            let dpi = Dpi::for_window(window);
+            point.x = dpi.to_dip(point.x) as i32;
+            point.y = dpi.to_dip(point.y) as i32;
 
             if !PtInRect(&menu.menu_list_rect, *point).as_bool() {
                 if !menu.is_scrolling
@@ -410,14 +820,35 @@ unsafe fn menu_mouse_move(
     Ok(true)
 }
 
+// The popup reads as the text of its focused item, for tools that identify windows by their text.
+unsafe fn get_focused_item_text(context: &Context) -> Vec<u16> {
+    let Ok(menu) = context.menu.try_borrow() else {
+        return Vec::new();
+    };
+    match menu.focused_item_index.map(|index| &menu.items[index]) {
+        Some(MenuItem::MenuItem { text, .. } | MenuItem::SubMenu { text, .. }) => {
+            split_shortcut(text.as_wide()).0.to_vec()
+        }
+        _ => Vec::new(),
+    }
+}
+
 fn select_item(menu: &mut Menu, index: Option<usize>) {
     if menu.focused_item_index == index {
         return;
     }
     menu.focused_item_index = index;
     unsafe {
-        if menu.window.is_some() {
-            _ = RedrawWindow(menu.window, None, None, RDW_INVALIDATE | RDW_NOCHILDREN);
+        if let Some(window) = menu.window {
+            _ = RedrawWindow(Some(window), None, None, RDW_INVALIDATE | RDW_NOCHILDREN);
+            if let Some(index) = index {
+                _ = PostMessageW(
+                    Some(window),
+                    automation::WM_UIA_FOCUS_CHANGED,
+                    WPARAM(index),
+                    LPARAM(0),
+                );
+            }
         }
     }
 }
@@ -460,6 +891,27 @@ fn select_first(menu: &mut Menu) {
     }
 }
 
+// Like `select_first_enabled`, type-ahead only lands on items that can be invoked.
+fn get_type_ahead_text(item: &MenuItem) -> Option<PCWSTR> {
+    match item {
+        MenuItem::MenuItem { disabled: true, .. } | MenuItem::MenuDivider { .. } => None,
+        MenuItem::MenuItem { text, .. } | MenuItem::SubMenu { text, .. } => Some(*text),
+    }
+}
+
+// Falls back to the first item of any kind when every item is disabled.
+fn select_first_enabled(menu: &mut Menu) {
+    let index = menu.items.iter().position(|item| match item {
+        MenuItem::MenuItem { disabled, .. } => !disabled,
+        MenuItem::SubMenu { .. } => true,
+        MenuItem::MenuDivider { .. } => false,
+    });
+    match index {
+        Some(index) => select_item(menu, Some(index)),
+        None => select_first(menu),
+    }
+}
+
 fn select_last(menu: &mut Menu) {
     let mut item_index = menu.items.len() as isize - 1;
     while item_index >= 0 {
@@ -493,7 +945,12 @@ fn menu_key_left(mt: &mut Tracker) -> Result<()> {
 }
 
 unsafe fn menu_key_right(context: &Context, mt: &mut Tracker) -> Result<()> {
-    mt.current_menu = show_sub_popup(&context.qt, mt.owning_window, mt.current_menu.clone())?;
+    let sub_menu = show_sub_popup(&context.qt, mt.owning_window, mt.current_menu.clone())?;
+    // A submenu opened from the keyboard starts on its first item, as native menus do.
+    if !Rc::ptr_eq(&sub_menu, &mt.current_menu) {
+        select_first(&mut sub_menu.borrow_mut());
+    }
+    mt.current_menu = sub_menu;
     Ok(())
 }
 
@@ -536,6 +993,12 @@ fn menu_key_escape(mt: &mut Tracker) -> Result<bool> {
 const MENU_MARGIN: i32 = 4;
 const MENU_BORDER_WIDTH: i32 = 1;
 const MENU_LIST_GAP: i32 = 2;
+const MENU_DIVIDER_PADDING: i32 = 2;
+// Wide enough for the 20px checkmark; the 12px checkbox of check items is centered in it.
+const CHECK_COLUMN_WIDTH: i32 = 4 + 20;
+const CHECK_BOX_SIZE: f32 = 12f32;
+const SHORTCUT_GAP: i32 = 24;
+const TOOLTIP_DELAY: u32 = 600;
 
 #[derive(PartialEq)]
 enum ExecutionResult {
@@ -556,29 +1019,21 @@ unsafe fn show_sub_popup(
             if let MenuItem::SubMenu {
                 sub_menu,
                 rect: item_rect,
+                build,
                 ..
             } = item
             {
-                if let Some(window) = menu.window {
-                    let item_rect = adjust_menu_item_rect(&menu, &item_rect);
-                    let mut rect = RECT::default();
-                    GetWindowRect(window, &mut rect)?;
-                    let scaling_factor = get_scaling_factor(window);
-                    rect.left +=
-                        ((item_rect.right - MENU_BORDER_WIDTH) as f32 * scaling_factor) as i32;
-                    rect.top += (item_rect.top as f32 * scaling_factor) as i32;
-                    rect.right = ((item_rect.left - item_rect.right + MENU_BORDER_WIDTH) as f32
-                        * scaling_factor) as i32;
-                    rect.bottom = ((item_rect.top - item_rect.bottom - 2 * MENU_MARGIN) as f32
-                        * scaling_factor) as i32;
+                if let Some(anchor) = get_item_screen_rect(&menu, item_rect) {
+                    if let Some(build) = build {
+                        *sub_menu.borrow_mut() = convert_menu_info_list_to_menu(build());
+                    }
                     init_popup(
                         qt.clone(),
                         owning_window,
                         sub_menu.clone(),
-                        rect.left,
-                        rect.top,
-                        rect.right,
-                        rect.bottom,
+                        anchor.right,
+                        anchor.top,
+                        Some(anchor),
                     )?;
                     return Ok(sub_menu.clone());
                 }
@@ -591,7 +1046,10 @@ unsafe fn show_sub_popup(
 fn hide_sub_popups(menu: &mut Menu) -> Result<()> {
     if let Some(focused_item_index) = menu.focused_item_index {
         let item = &menu.items[focused_item_index];
-        if let MenuItem::SubMenu { sub_menu, .. } = item {
+        if let MenuItem::SubMenu {
+            sub_menu, build, ..
+        } = item
+        {
             let mut sub_menu = sub_menu.borrow_mut();
             hide_sub_popups(&mut sub_menu)?;
             select_item(&mut sub_menu, None);
@@ -599,6 +1057,10 @@ fn hide_sub_popups(menu: &mut Menu) -> Result<()> {
                 unsafe { DestroyWindow(sub_menu_window)? };
                 sub_menu.window = None;
             }
+            // Built items are dropped so that the next open queries them again.
+            if build.is_some() {
+                sub_menu.items.clear();
+            }
         }
     }
     Ok(())
@@ -636,6 +1098,61 @@ unsafe fn execute_focused_item(
     }
 }
 
+// Returns the screen rectangle of the disabled item under `point` and why it is disabled.
+unsafe fn find_disabled_reason(menu: &Menu, point: &POINT) -> Option<(RECT, PCWSTR)> {
+    let mut item_point = *point;
+    let HitTest::Item(index) = find_item_by_coordinates(menu, &mut item_point) else {
+        return None;
+    };
+    let MenuItem::MenuItem {
+        rect,
+        disabled: true,
+        disabled_reason: Some(reason),
+        ..
+    } = &menu.items[index]
+    else {
+        return None;
+    };
+    Some((get_item_screen_rect(menu, rect)?, *reason))
+}
+
+unsafe fn hide_tooltip(mt: &mut Tracker) {
+    mt.tooltip_timer.cancel();
+    if let Some(tooltip) = mt.tooltip {
+        tooltip::hide(tooltip);
+    }
+}
+
+// Any movement dismisses the tooltip; resting on a disabled item with a reason brings it back.
+unsafe fn tooltip_mouse_move(mt: &mut Tracker, menu: Option<&Rc<RefCell<Menu>>>) {
+    if mt.point == mt.tooltip_point {
+        return;
+    }
+    mt.tooltip_point = mt.point;
+    hide_tooltip(mt);
+    if menu.is_some_and(|menu| find_disabled_reason(&menu.borrow(), &mt.point).is_some()) {
+        mt.tooltip_timer.schedule();
+    }
+}
+
+unsafe fn show_tooltip(context: &Context, mt: &mut Tracker) -> Result<()> {
+    let Some(menu) = menu_from_point(mt.top_menu.clone(), &mt.point) else {
+        return Ok(());
+    };
+    let Some((rect, reason)) = find_disabled_reason(&menu.borrow(), &mt.point) else {
+        return Ok(());
+    };
+    let tooltip = match mt.tooltip {
+        Some(tooltip) => tooltip,
+        None => {
+            let tooltip = tooltip::create(&context.qt, context.owning_window)?;
+            mt.tooltip = Some(tooltip);
+            tooltip
+        }
+    };
+    tooltip::show(tooltip, reason, &rect)
+}
+
 unsafe fn track_menu(menu: Rc<RefCell<Menu>>, x: i32, y: i32, owning_window: HWND) -> Result<bool> {
     let window = {
         let menu = menu.borrow();
@@ -651,10 +1168,14 @@ unsafe fn track_menu(menu: Rc<RefCell<Menu>>, x: i32, y: i32, owning_window: HWN
         top_menu: menu.clone(),
         owning_window,
         point: POINT { x, y },
+        tooltip: None,
+        tooltip_point: POINT { x, y },
+        tooltip_timer: HoverTimer::new(window, TOOLTIP_DELAY),
     };
     let mut exit_menu = false;
     let mut enter_idle_sent = false;
     let mut execution_result = ExecutionResult::NoExecuted;
+    let mut type_ahead = TypeAhead::new();
     while !exit_menu {
         let mut msg = MSG::default();
         loop {
@@ -683,7 +1204,8 @@ unsafe fn track_menu(menu: Rc<RefCell<Menu>>, x: i32, y: i32, owning_window: HWN
         }
 
         if msg.message == WM_CANCELMODE {
-            _ = PeekMessageW(&mut msg, None, msg.message, msg.message, PM_REMOVE);
+            // Drain every queued WM_CANCELMODE, not only the one peeked above.
+            while PeekMessageW(&mut msg, None, WM_CANCELMODE, WM_CANCELMODE, PM_REMOVE).as_bool() {}
             break;
         }
 
@@ -692,6 +1214,17 @@ unsafe fn track_menu(menu: Rc<RefCell<Menu>>, x: i32, y: i32, owning_window: HWN
             enter_idle_sent = false;
         }
 
+        // The tooltip is dismissed by the next mouse move or key, so it never polls.
+        if msg.message == WM_TIMER
+            && msg.hwnd == window
+            && mt.tooltip_timer.on_timer(msg.wParam.0, || true) == Some(HoverAction::Show)
+        {
+            _ = PeekMessageW(&mut msg, None, WM_TIMER, WM_TIMER, PM_REMOVE);
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            _ = show_tooltip(&*raw, &mut mt);
+            continue;
+        }
+
         let mut remove_message = false;
         if msg.message >= WM_MOUSEFIRST && msg.message <= WM_MOUSELAST {
             mt.point.x = msg.lParam.0 as i16 as i32;
@@ -726,6 +1259,7 @@ unsafe fn track_menu(menu: Rc<RefCell<Menu>>, x: i32, y: i32, owning_window: HWN
                     None => exit_menu = false,
                 },
                 WM_MOUSEMOVE => {
+                    tooltip_mouse_move(&mut mt, menu_from_point_result.as_ref());
                     if let Some(menu_from_point) = menu_from_point_result {
                         let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
                         let context = &*raw;
@@ -736,6 +1270,7 @@ unsafe fn track_menu(menu: Rc<RefCell<Menu>>, x: i32, y: i32, owning_window: HWN
             }
         } else if msg.message >= WM_KEYFIRST && msg.message <= WM_KEYLAST {
             remove_message = true;
+            hide_tooltip(&mut mt);
             match msg.message {
                 WM_KEYDOWN | WM_SYSKEYDOWN => match VIRTUAL_KEY(msg.wParam.0 as u16) {
                     VK_MENU | VK_F10 => {
@@ -768,6 +1303,19 @@ unsafe fn track_menu(menu: Rc<RefCell<Menu>>, x: i32, y: i32, owning_window: HWN
                         let _ = TranslateMessage(&mut msg);
                     }
                 },
+                WM_CHAR if msg.wParam.0 >= ' ' as usize => {
+                    let mut menu = mt.current_menu.borrow_mut();
+                    let index = type_ahead.search(
+                        msg.wParam.0 as u16,
+                        msg.time,
+                        menu.items.len(),
+                        menu.focused_item_index,
+                        |index| get_type_ahead_text(&menu.items[index]),
+                    );
+                    if index.is_some() {
+                        select_item(&mut menu, index);
+                    }
+                }
                 _ => {}
             }
         } else {
@@ -786,7 +1334,14 @@ unsafe fn track_menu(menu: Rc<RefCell<Menu>>, x: i32, y: i32, owning_window: HWN
         }
     }
 
-    ReleaseCapture()?;
+    mt.tooltip_timer.cancel();
+    if let Some(tooltip) = mt.tooltip {
+        _ = DestroyWindow(tooltip);
+    }
+    // Capture may already belong to another window, which took it and so ended the loop.
+    if GetCapture() == window {
+        ReleaseCapture()?;
+    }
     if IsWindow(Some(mt.owning_window)).as_bool() {
         {
             let mut top_menu = mt.top_menu.borrow_mut();
@@ -816,6 +1371,34 @@ unsafe fn exit_tracking(owning_window: HWND) -> Result<()> {
     Ok(())
 }
 
+/// Splits an item's text into its label and the shortcut after the first tab, if there is one.
+pub(crate) fn split_shortcut(text: &[u16]) -> (&[u16], Option<&[u16]>) {
+    match text.iter().position(|&c| c == '\t' as u16) {
+        Some(index) => (&text[..index], Some(&text[index + 1..])),
+        None => (text, None),
+    }
+}
+
+unsafe fn measure_text(
+    text: &[u16],
+    text_format: &IDWriteTextFormat,
+) -> Result<DWRITE_TEXT_METRICS> {
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_layout = direct_write_factory.CreateTextLayout(text, text_format, 290f32, 500f32)?;
+    let mut metrics = DWRITE_TEXT_METRICS::default();
+    text_layout.GetMetrics(&mut metrics)?;
+    Ok(metrics)
+}
+
+// The middle of the divider's stroke, which sits between equal paddings.
+fn get_divider_line_y(rect: &RECT, stroke_width: f32) -> f32 {
+    (rect.top + MENU_DIVIDER_PADDING) as f32 + stroke_width / 2.0
+}
+
+fn get_divider_height(stroke_width: f32) -> i32 {
+    2 * MENU_DIVIDER_PADDING + stroke_width.ceil() as i32
+}
+
 unsafe fn calc_menu_item_size(
     qt: &QT,
     menu_item: &mut MenuItem,
@@ -827,23 +1410,14 @@ unsafe fn calc_menu_item_size(
     match menu_item {
         MenuItem::MenuItem { rect, text, .. } | MenuItem::SubMenu { rect, text, .. } => {
             SetRect(rect, org_x, org_y, org_x, org_y);
-            let direct_write_factory =
-                DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
-            let text_layout = direct_write_factory.CreateTextLayout(
-                text.as_wide(),
-                text_format,
-                290f32,
-                500f32,
-            )?;
-            let mut metrics = DWRITE_TEXT_METRICS::default();
-            text_layout.GetMetrics(&mut metrics)?;
+            let metrics = measure_text(split_shortcut(text.as_wide()).0, text_format)?;
             rect.right += metrics.width.ceil() as i32 + 2 * tokens.spacing_vertical_s_nudge as i32;
             rect.bottom +=
                 (metrics.height.ceil() as i32 + 2 * tokens.spacing_vertical_s_nudge as i32).max(32);
         }
         MenuItem::MenuDivider { rect } => {
             SetRect(rect, org_x, org_y, org_x, org_y);
-            rect.bottom += 4 + tokens.stroke_width_thin as i32;
+            rect.bottom += get_divider_height(tokens.stroke_width_thin);
         }
     }
     if let MenuItem::SubMenu { rect, .. } = menu_item {
@@ -868,8 +1442,33 @@ unsafe fn get_text_format(qt: &QT) -> Result<IDWriteTextFormat> {
 
 unsafe fn calc_popup_menu_size(qt: &QT, menu: &mut Menu, max_height: i32) -> Result<(i32, i32)> {
     SetRectEmpty(&mut menu.menu_list_rect);
+    menu.has_check_column = menu.items.iter().any(|item| {
+        matches!(
+            item,
+            MenuItem::MenuItem { checked: true, .. }
+                | MenuItem::MenuItem {
+                    checkable: true,
+                    ..
+                }
+        )
+    });
+    let check_column_width = if menu.has_check_column {
+        CHECK_COLUMN_WIDTH
+    } else {
+        0
+    };
     let mut start = 0;
     let text_format = get_text_format(qt)?;
+    // Shortcuts line up in one column, as wide as the widest of them.
+    let mut max_shortcut_width = None;
+    for item in &menu.items {
+        if let MenuItem::MenuItem { text, .. } = item {
+            if let Some(shortcut) = split_shortcut(text.as_wide()).1 {
+                let width = measure_text(shortcut, &text_format)?.width.ceil() as i32;
+                max_shortcut_width = Some(max_shortcut_width.unwrap_or(0).max(width));
+            }
+        }
+    }
     while start < menu.items.len() {
         let org_x = menu.menu_list_rect.right;
         let mut org_y = menu.menu_list_rect.top;
@@ -878,19 +1477,22 @@ unsafe fn calc_popup_menu_size(qt: &QT, menu: &mut Menu, max_height: i32) -> Res
         while i < menu.items.len() {
             let item = &mut menu.items[i];
             calc_menu_item_size(qt, item, org_x, org_y, &text_format)?;
-            let desired_width = match item {
-                MenuItem::MenuItem { rect, .. }
-                | MenuItem::SubMenu { rect, .. }
-                | MenuItem::MenuDivider { rect } => rect.right,
-            };
-            let desired_height = match item {
+            if let MenuItem::MenuItem { rect, .. } | MenuItem::SubMenu { rect, .. } = item {
+                rect.right += check_column_width;
+            }
+            if let (MenuItem::MenuItem { rect, .. }, Some(width)) = (&mut *item, max_shortcut_width)
+            {
+                rect.right += SHORTCUT_GAP + width;
+            }
+            let item_rect = match item {
                 MenuItem::MenuItem { rect, .. }
                 | MenuItem::SubMenu { rect, .. }
-                | MenuItem::MenuDivider { rect } => rect.bottom,
+                | MenuItem::MenuDivider { rect } => *rect,
             };
 
-            menu.menu_list_rect.right = menu.menu_list_rect.right.max(desired_width);
-            org_y = desired_height + MENU_LIST_GAP;
+            // Every item starts exactly where the previous one ended plus the gap, whatever its kind
+            menu.menu_list_rect.right = menu.menu_list_rect.right.max(item_rect.right);
+            org_y += (item_rect.bottom - item_rect.top) + MENU_LIST_GAP;
 
             i = i + 1;
         }
@@ -907,6 +1509,9 @@ unsafe fn calc_popup_menu_size(qt: &QT, menu: &mut Menu, max_height: i32) -> Res
         }
         menu.menu_list_rect.bottom = menu.menu_list_rect.bottom.max(org_y);
     }
+    let spacing = qt.theme.tokens.spacing_vertical_s_nudge as i32;
+    menu.shortcut_column_offset =
+        max_shortcut_width.map(|width| menu.menu_list_rect.right - width - spacing);
 
     OffsetRect(
         &mut menu.menu_list_rect,
@@ -931,8 +1536,7 @@ unsafe fn show_popup(
     menu: &mut Menu,
     x: i32,
     y: i32,
-    x_anchor: i32,
-    y_anchor: i32,
+    anchor: Option<RECT>,
 ) -> Result<()> {
     menu.focused_item_index = None;
     let pt = POINT { x, y };
@@ -942,54 +1546,90 @@ unsafe fn show_popup(
         ..Default::default()
     };
     GetMonitorInfoW(monitor, &mut info);
-    let max_height = info.rcWork.bottom - info.rcWork.top;
+    let dpi = Dpi::for_window(window);
+    let max_height = dpi.to_dip(info.rcWork.bottom - info.rcWork.top) as i32;
     let (width, height) = calc_popup_menu_size(qt, menu, max_height)?;
-    let mut x = x;
-    if x + width > info.rcWork.right {
-        if x_anchor != 0 && x >= width - x_anchor {
-            x = x - width - x_anchor;
-        }
-        if x + width > info.rcWork.right {
-            x = info.rcWork.right - width;
-        }
-    }
-    if x < info.rcWork.left {
-        x = info.rcWork.left;
-    }
-    let mut y = y;
-    if y + height > info.rcWork.bottom {
-        if y_anchor != 0 && y >= height + y_anchor {
-            y -= height + y_anchor;
-        }
-        if y + height > info.rcWork.bottom {
-            y = info.rcWork.bottom - height;
-        }
-    }
-    if y < info.rcWork.top {
-        y = info.rcWork.top;
-    }
-    let scaling_factor = get_scaling_factor(window);
-    let scaled_width = (width as f32 * scaling_factor) as i32;
-    let scaled_height = (height as f32 * scaling_factor) as i32;
+    let scaled_width = dpi.to_physical(width as f32);
+    let scaled_height = dpi.to_physical(height as f32);
+    let position = place_popup(
+        &info.rcWork,
+        scaled_width,
+        scaled_height,
+        POINT { x, y },
+        anchor.as_ref(),
+        dpi.to_physical(MENU_BORDER_WIDTH as f32),
+    );
     SetWindowPos(
         window,
         Some(HWND_TOPMOST),
-        x,
-        y,
+        position.x,
+        position.y,
         scaled_width,
         scaled_height,
         SWP_SHOWWINDOW | SWP_NOACTIVATE,
     )?;
-    let corner_diameter = (qt.theme.tokens.border_radius_medium * 2f32 * scaling_factor) as i32;
+    set_popup_region(qt, window, scaled_width, scaled_height);
+    Ok(())
+}
+
+// Keeps a popup of the given physical size inside `work`. A submenu opens to the right of its
+// `anchor` item, overlapping it by `overlap`, and flips to the left of it when the right side has
+// no room. Near the bottom it lines up with the item's bottom edge instead of its top.
+fn place_popup(
+    work: &RECT,
+    width: i32,
+    height: i32,
+    point: POINT,
+    anchor: Option<&RECT>,
+    overlap: i32,
+) -> POINT {
+    let mut x = point.x;
+    let mut y = point.y;
+    if let Some(anchor) = anchor {
+        x = anchor.right - overlap;
+        y = anchor.top;
+        let flipped_x = anchor.left + overlap - width;
+        if x + width > work.right && flipped_x >= work.left {
+            x = flipped_x;
+        }
+        let flipped_y = anchor.bottom - height;
+        if y + height > work.bottom && flipped_y >= work.top {
+            y = flipped_y;
+        }
+    }
+    POINT {
+        x: x.min(work.right - width).max(work.left),
+        y: y.min(work.bottom - height).max(work.top),
+    }
+}
+
+unsafe fn set_popup_region(qt: &QT, window: HWND, width: i32, height: i32) {
+    // DWM's small radius matches `border_radius_medium`, and a region would square it off again.
+    if apply_corner_preference(window, DWMWCP_ROUNDSMALL) {
+        return;
+    }
+    let dpi = Dpi::for_window(window);
+    let corner_diameter = dpi.to_physical(qt.theme.tokens.border_radius_medium * 2f32);
     let region = CreateRoundRectRgn(
         0,
         0,
-        scaled_width + 1,
-        scaled_height + 1,
+        width + 1,
+        height + 1,
         corner_diameter,
         corner_diameter,
     );
     SetWindowRgn(window, Some(region), false);
+}
+
+unsafe fn on_size(window: HWND, context: &Context) -> Result<()> {
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    context.render_target.Resize(&D2D_SIZE_U {
+        width: rect.right as u32,
+        height: rect.bottom as u32,
+    })?;
+    set_popup_region(&context.qt, window, rect.right, rect.bottom);
+    _ = InvalidateRect(Some(window), None, false);
     Ok(())
 }
 
@@ -1034,33 +1674,88 @@ unsafe fn draw_menu_item(
                 .FillRoundedRectangle(&rounded_rect, &focused_brush);
         }
     }
+    let text_left = if menu.has_check_column {
+        (rect.left + CHECK_COLUMN_WIDTH) as f32 + tokens.spacing_vertical_s_nudge
+    } else {
+        rect.left as f32 + tokens.spacing_vertical_s_nudge
+    };
     match menu_item {
-        MenuItem::MenuItem { text, disabled, .. } => {
+        MenuItem::MenuItem {
+            text,
+            disabled,
+            checked,
+            checkable,
+            ..
+        } => {
+            if *checkable {
+                draw_check_box(context, &rect, *checked)?;
+            } else if *checked {
+                let device_context5 = context.render_target.cast::<ID2D1DeviceContext5>()?;
+                device_context5.SetTransform(&Matrix3x2::translation(
+                    rect.left as f32 + tokens.spacing_vertical_s_nudge,
+                    rect.top as f32 + tokens.spacing_vertical_s_nudge,
+                ));
+                device_context5.DrawSvgDocument(&context.resources.checkmark_svg);
+                device_context5.SetTransform(&Matrix3x2::identity());
+            }
+            let (label, shortcut) = split_shortcut(text.as_wide());
+            // The offset is relative to the menu list, like the item rects before adjusting.
+            let shortcut_left = menu
+                .shortcut_column_offset
+                .map(|offset| (menu.menu_list_rect.left + offset) as f32);
             let text_rect = D2D_RECT_F {
-                left: rect.left as f32 + tokens.spacing_vertical_s_nudge,
+                left: text_left,
                 top: rect.top as f32 + tokens.spacing_vertical_s_nudge,
-                right: rect.right as f32 - tokens.spacing_vertical_s_nudge,
+                right: shortcut_left.unwrap_or(rect.right as f32) - tokens.spacing_vertical_s_nudge,
                 bottom: rect.bottom as f32 - tokens.spacing_vertical_s_nudge,
             };
             let text_brush = if *disabled {
-                &context.text_disabled_brush
+                &context.resources.text_disabled_brush
             } else if focused {
-                &context.text_focused_brush
+                &context.resources.text_focused_brush
             } else {
-                &context.text_brush
+                &context.resources.text_brush
             };
             context.render_target.DrawText(
-                text.as_wide(),
+                label,
                 &context.text_format,
                 &text_rect,
                 text_brush,
                 D2D1_DRAW_TEXT_OPTIONS_NONE,
                 DWRITE_MEASURING_MODE_NATURAL,
             );
+            if let (Some(shortcut), Some(shortcut_left)) = (shortcut, shortcut_left) {
+                let direct_write_factory =
+                    DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+                let right = rect.right as f32 - tokens.spacing_vertical_s_nudge;
+                let shortcut_layout = direct_write_factory.CreateTextLayout(
+                    shortcut,
+                    &context.text_format,
+                    (right - shortcut_left).max(0f32),
+                    text_rect.bottom - text_rect.top,
+                )?;
+                shortcut_layout.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_TRAILING)?;
+                let shortcut_brush = if *disabled {
+                    context.resources.text_disabled_brush.clone()
+                } else {
+                    context
+                        .render_target
+                        .CreateSolidColorBrush(&tokens.color_neutral_foreground3, None)?
+                };
+                context.render_target.DrawTextLayout(
+                    D2D_POINT_2F {
+                        x: shortcut_left,
+                        y: text_rect.top,
+                    },
+                    &shortcut_layout,
+                    &shortcut_brush,
+                    D2D1_DRAW_TEXT_OPTIONS_NONE,
+                );
+            }
         }
         MenuItem::SubMenu { text, .. } => {
             let text_rect = D2D_RECT_F {
-                left: rect.left as f32 + tokens.spacing_vertical_s_nudge,
+                left: text_left,
                 top: rect.top as f32 + tokens.spacing_vertical_s_nudge,
                 right: (rect.right - 4 - 20) as f32 - tokens.spacing_vertical_s_nudge,
                 bottom: rect.bottom as f32 - tokens.spacing_vertical_s_nudge,
@@ -1069,7 +1764,7 @@ unsafe fn draw_menu_item(
                 text.as_wide(),
                 &context.text_format,
                 &text_rect,
-                &context.text_brush,
+                &context.resources.text_brush,
                 D2D1_DRAW_TEXT_OPTIONS_NONE,
                 DWRITE_MEASURING_MODE_NATURAL,
             );
@@ -1079,21 +1774,22 @@ unsafe fn draw_menu_item(
                 rect.top as f32 + tokens.spacing_vertical_s_nudge,
             ));
             let svg = if focused {
-                &context.sub_menu_indicator_focused_svg
+                &context.resources.sub_menu_indicator_focused_svg
             } else {
-                &context.sub_menu_indicator_svg
+                &context.resources.sub_menu_indicator_svg
             };
             device_context5.DrawSvgDocument(svg);
             device_context5.SetTransform(&Matrix3x2::identity());
         }
         MenuItem::MenuDivider { .. } => {
+            let y = get_divider_line_y(&rect, tokens.stroke_width_thin);
             let start = D2D_POINT_2F {
                 x: (rect.left - MENU_MARGIN) as f32,
-                y: rect.top as f32 + 2.0,
+                y,
             };
             let end = D2D_POINT_2F {
                 x: (rect.right + MENU_MARGIN) as f32,
-                y: rect.top as f32 + 2.0,
+                y,
             };
             let divider_brush = context
                 .render_target
@@ -1115,9 +1811,8 @@ unsafe fn draw_scroll_arrows(window: HWND, context: &Context) -> Result<()> {
     Ok(())
 }
 
-unsafe fn draw_popup_menu(window: HWND, context: &Context) -> Result<()> {
+unsafe fn paint_popup_menu(window: HWND, context: &Context) -> Result<()> {
     let tokens = &context.qt.theme.tokens;
-    context.render_target.BeginDraw();
     context
         .render_target
         .Clear(Some(&tokens.color_neutral_background1));
@@ -1128,37 +1823,89 @@ unsafe fn draw_popup_menu(window: HWND, context: &Context) -> Result<()> {
     if menu.is_scrolling {
         draw_scroll_arrows(window, context)?;
     }
-    context.render_target.EndDraw(None, None)?;
     Ok(())
 }
 
+unsafe fn draw_popup_menu(window: HWND, context: &Context) -> Result<()> {
+    context.render_target.BeginDraw();
+    let result = paint_popup_menu(window, context);
+    context.render_target.EndDraw(None, None).and(result)
+}
+
+pub(crate) unsafe fn draw_check_box(context: &Context, rect: &RECT, checked: bool) -> Result<()> {
+    let tokens = &context.qt.theme.tokens;
+    let left = rect.left as f32
+        + tokens.spacing_vertical_s_nudge
+        + (CHECK_COLUMN_WIDTH as f32 - tokens.spacing_vertical_s_nudge - CHECK_BOX_SIZE) / 2f32;
+    let top = rect.top as f32 + ((rect.bottom - rect.top) as f32 - CHECK_BOX_SIZE) / 2f32;
+    let box_rect = D2D1_ROUNDED_RECT {
+        rect: D2D_RECT_F {
+            left,
+            top,
+            right: left + CHECK_BOX_SIZE,
+            bottom: top + CHECK_BOX_SIZE,
+        },
+        radiusX: tokens.border_radius_small,
+        radiusY: tokens.border_radius_small,
+    };
+    if !checked {
+        let inset = tokens.stroke_width_thin / 2f32;
+        let outline_rect = D2D1_ROUNDED_RECT {
+            rect: D2D_RECT_F {
+                left: left + inset,
+                top: top + inset,
+                right: left + CHECK_BOX_SIZE - inset,
+                bottom: top + CHECK_BOX_SIZE - inset,
+            },
+            ..box_rect
+        };
+        context.render_target.DrawRoundedRectangle(
+            &outline_rect,
+            &context.resources.check_box_brush,
+            tokens.stroke_width_thin,
+            None,
+        );
+        return Ok(());
+    }
+    context
+        .render_target
+        .FillRoundedRectangle(&box_rect, &context.resources.check_box_checked_brush);
+    let device_context5 = context.render_target.cast::<ID2D1DeviceContext5>()?;
+    device_context5.SetTransform(&Matrix3x2::translation(left, top));
+    device_context5.DrawSvgDocument(&context.resources.check_box_checkmark_svg);
+    device_context5.SetTransform(&Matrix3x2::identity());
+    Ok(())
+}
+
+pub(crate) unsafe fn create_svg_document(
+    device_context5: &ID2D1DeviceContext5,
+    icon: &Icon,
+) -> Result<ID2D1SvgDocument> {
+    let size = D2D_SIZE_F {
+        width: icon.size as f32,
+        height: icon.size as f32,
+    };
+    match SHCreateMemStream(Some(icon.svg_bytes())) {
+        None => device_context5.CreateSvgDocument(None, size),
+        Some(svg_stream) => device_context5.CreateSvgDocument(&svg_stream, size),
+    }
+}
+
 unsafe fn on_create(window: HWND, params: CreateParams, x: i32, y: i32) -> Result<Context> {
     {
         let mut menu = params.menu.borrow_mut();
-        show_popup(
-            &params.qt,
-            window,
-            &mut menu,
-            x,
-            y,
-            params.x_anchor,
-            params.y_anchor,
-        )?;
+        show_popup(&params.qt, window, &mut menu, x, y, params.anchor)?;
     }
 
     let mut client_rect = RECT::default();
     GetClientRect(window, &mut client_rect)?;
-    let dpi = GetDpiForWindow(window);
+    let dpi = Dpi::for_window(window);
     let factory = D2D1CreateFactory::<ID2D1Factory1>(
         D2D1_FACTORY_TYPE_SINGLE_THREADED,
         Some(&D2D1_FACTORY_OPTIONS::default()),
     )?;
     let render_target = factory.CreateHwndRenderTarget(
-        &D2D1_RENDER_TARGET_PROPERTIES {
-            dpiX: dpi as f32,
-            dpiY: dpi as f32,
-            ..Default::default()
-        },
+        &render_target_properties(dpi),
         &D2D1_HWND_RENDER_TARGET_PROPERTIES {
             hwnd: window,
             pixelSize: D2D_SIZE_U {
@@ -1169,61 +1916,14 @@ unsafe fn on_create(window: HWND, params: CreateParams, x: i32, y: i32) -> Resul
         },
     )?;
     let text_format = get_text_format(&params.qt)?;
-    let tokens = &params.qt.theme.tokens;
-    let text_brush =
-        render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground2, None)?;
-    let text_focused_brush =
-        render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground1_hover, None)?;
-    let text_disabled_brush =
-        render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground_disabled, None)?;
-    let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
-    let sub_menu_indicator_icon = Icon::chevron_right_regular();
-    let sub_menu_indicator_svg =
-        match SHCreateMemStream(Some(sub_menu_indicator_icon.svg.as_bytes())) {
-            None => device_context5.CreateSvgDocument(
-                None,
-                D2D_SIZE_F {
-                    width: sub_menu_indicator_icon.size as f32,
-                    height: sub_menu_indicator_icon.size as f32,
-                },
-            )?,
-            Some(svg_stream) => device_context5.CreateSvgDocument(
-                &svg_stream,
-                D2D_SIZE_F {
-                    width: sub_menu_indicator_icon.size as f32,
-                    height: sub_menu_indicator_icon.size as f32,
-                },
-            )?,
-        };
-    let sub_menu_indicator_focused_icon = Icon::chevron_right_filled();
-    let sub_menu_indicator_focused_svg =
-        match SHCreateMemStream(Some(sub_menu_indicator_focused_icon.svg.as_bytes())) {
-            None => device_context5.CreateSvgDocument(
-                None,
-                D2D_SIZE_F {
-                    width: sub_menu_indicator_focused_icon.size as f32,
-                    height: sub_menu_indicator_focused_icon.size as f32,
-                },
-            )?,
-            Some(svg_stream) => device_context5.CreateSvgDocument(
-                &svg_stream,
-                D2D_SIZE_F {
-                    width: sub_menu_indicator_focused_icon.size as f32,
-                    height: sub_menu_indicator_focused_icon.size as f32,
-                },
-            )?,
-        };
+    let resources = DeviceResources::new(&render_target, &params.qt.theme.tokens)?;
     Ok(Context {
         qt: params.qt,
         menu: params.menu,
         owning_window: params.owning_window,
         render_target,
         text_format,
-        text_brush,
-        text_focused_brush,
-        text_disabled_brush,
-        sub_menu_indicator_svg,
-        sub_menu_indicator_focused_svg,
+        resources,
     })
 }
 
@@ -1242,33 +1942,309 @@ extern "system" fn window_proc(
                 Ok(context) => {
                     let boxed = Box::new(context);
                     SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    _ = PostMessageW(
+                        Some(window),
+                        automation::WM_UIA_MENU_OPENED,
+                        WPARAM(0),
+                        LPARAM(0),
+                    );
                     LRESULT(TRUE.0 as isize)
                 }
                 Err(_) => LRESULT(FALSE.0 as isize),
             }
         },
         WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as isize),
+        WM_CAPTURECHANGED => unsafe {
+            // Another window took the capture, so the tracking loop would no longer see the mouse.
+            if l_param.0 != 0 && l_param.0 != window.0 as isize {
+                _ = PostMessageW(Some(window), WM_CANCELMODE, WPARAM(0), LPARAM(0));
+            }
+            LRESULT(0)
+        },
         WM_PAINT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            let context = &*raw;
+            let context = &mut *raw;
             let mut ps = PAINTSTRUCT::default();
             BeginPaint(window, &mut ps);
-            _ = draw_popup_menu(window, context);
+            let result = draw_popup_menu(window, context);
             _ = EndPaint(window, &ps);
+            recover_lost_device(window, &result, || {
+                context.recreate_device_resources(window)
+            });
             LRESULT(0)
         },
         WM_PRINTCLIENT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &*raw;
-            _ = draw_popup_menu(window, context);
+            let dc = HDC(w_param.0 as _);
+            _ = print_client(window, &context.render_target, dc, || {
+                paint_popup_menu(window, context)
+            });
             LRESULT(0)
         },
         WM_ERASEBKGND => LRESULT(1),
+        WM_SIZE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                _ = on_size(window, &*raw);
+            }
+            LRESULT(0)
+        },
+        WM_GETTEXT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return DefWindowProcW(window, message, w_param, l_param);
+            }
+            copy_window_text(&get_focused_item_text(&*raw), w_param, l_param)
+        },
+        WM_GETTEXTLENGTH => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return DefWindowProcW(window, message, w_param, l_param);
+            }
+            LRESULT(get_focused_item_text(&*raw).len() as isize)
+        },
+        WM_GETOBJECT => unsafe {
+            automation::return_provider(window, w_param, l_param)
+                .unwrap_or_else(|| DefWindowProcW(window, message, w_param, l_param))
+        },
+        automation::WM_UIA_MENU_OPENED => unsafe {
+            automation::raise_menu_opened(window);
+            LRESULT(0)
+        },
+        automation::WM_UIA_FOCUS_CHANGED => unsafe {
+            automation::raise_focus_changed(window, w_param.0);
+            LRESULT(0)
+        },
         WM_DESTROY => unsafe {
+            // Tells UI Automation that the providers handed out for this window are gone.
+            UiaReturnRawElementProvider(window, WPARAM(0), LPARAM(0), None);
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            _ = Box::<Context>::from_raw(raw);
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                _ = Box::<Context>::from_raw(raw);
+            }
             LRESULT(0)
         },
         _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{window_text, Host};
+
+    const WORK: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 1000,
+        bottom: 800,
+    };
+
+    fn anchor(left: i32, top: i32) -> RECT {
+        RECT {
+            left,
+            top,
+            right: left + 200,
+            bottom: top + 30,
+        }
+    }
+
+    fn item(text: PCWSTR, disabled: bool) -> MenuItem {
+        MenuItem::MenuItem {
+            text,
+            id: 0,
+            rect: RECT::default(),
+            disabled,
+            disabled_reason: None,
+            checked: false,
+            checkable: false,
+        }
+    }
+
+    #[test]
+    fn type_ahead_only_matches_enabled_items() {
+        assert!(get_type_ahead_text(&item(w!("Copy"), false)).is_some());
+        assert!(get_type_ahead_text(&item(w!("Cut"), true)).is_none());
+        let divider = MenuItem::MenuDivider {
+            rect: RECT::default(),
+        };
+        assert!(get_type_ahead_text(&divider).is_none());
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs Windows")]
+    fn type_ahead_skips_disabled_items() {
+        let items = [
+            item(w!("Copy"), false),
+            item(w!("Cut"), true),
+            item(w!("Clear"), false),
+        ];
+        let mut type_ahead = TypeAhead::new();
+        let text_at = |index: usize| get_type_ahead_text(&items[index]);
+        assert_eq!(type_ahead.search('c' as u16, 0, 3, None, text_at), Some(0));
+        assert_eq!(
+            type_ahead.search('c' as u16, 10, 3, Some(0), text_at),
+            Some(2)
+        );
+        assert_eq!(
+            type_ahead.search('u' as u16, 5000, 3, Some(2), text_at),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn window_text_is_the_focused_item_without_its_shortcut() {
+        let host = Host::new(300, 100);
+        let handle = MenuHandle::new(vec![MenuInfo::MenuItem {
+            text: w!("Copy\tCtrl+C"),
+            command_id: 1,
+            disabled: false,
+            disabled_reason: None,
+        }]);
+        unsafe {
+            init_popup(
+                host.qt.clone(),
+                host.window,
+                handle.menu.clone(),
+                10,
+                10,
+                None,
+            )
+        }
+        .unwrap();
+        let window = handle.menu.borrow().window.unwrap();
+        assert_eq!(window_text(window), "");
+        select_item(&mut handle.menu.borrow_mut(), Some(0));
+        assert_eq!(window_text(window), "Copy");
+        unsafe { DestroyWindow(window) }.unwrap();
+    }
+
+    fn rect_of(item: &MenuItem) -> RECT {
+        match item {
+            MenuItem::MenuItem { rect, .. }
+            | MenuItem::SubMenu { rect, .. }
+            | MenuItem::MenuDivider { rect } => *rect,
+        }
+    }
+
+    #[test]
+    fn divider_line_is_centered() {
+        for stroke_width in [1f32, 2f32] {
+            let rect = RECT {
+                left: 0,
+                top: 40,
+                right: 100,
+                bottom: 40 + get_divider_height(stroke_width),
+            };
+            let y = get_divider_line_y(&rect, stroke_width);
+            assert_eq!(y - rect.top as f32, rect.bottom as f32 - y);
+        }
+    }
+
+    #[test]
+    fn divider_height_fits_a_fractional_stroke() {
+        assert_eq!(get_divider_height(1f32), 5);
+        assert_eq!(get_divider_height(1.5f32), 6);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs Windows")]
+    fn items_and_dividers_stack_with_even_gaps() {
+        let qt = QT::default();
+        let command = |text, command_id| MenuInfo::MenuItem {
+            text,
+            command_id,
+            disabled: false,
+            disabled_reason: None,
+        };
+        let mut menu = convert_menu_info_list_to_menu(vec![
+            command(w!("Cut"), 1),
+            MenuInfo::MenuDivider,
+            MenuInfo::MenuDivider,
+            command(w!("Paste"), 2),
+        ]);
+        let (width, height) = unsafe { calc_popup_menu_size(&qt, &mut menu, i32::MAX) }.unwrap();
+        let rects: Vec<RECT> = menu.items.iter().map(rect_of).collect();
+
+        assert_eq!(rects[0].top, 0);
+        for pair in rects.windows(2) {
+            assert_eq!(pair[1].top, pair[0].bottom + MENU_LIST_GAP);
+            assert_eq!((pair[1].left, pair[1].right), (pair[0].left, pair[0].right));
+        }
+        let divider_height = get_divider_height(qt.theme.tokens.stroke_width_thin);
+        assert_eq!(rects[1].bottom - rects[1].top, divider_height);
+        assert_eq!(rects[2].bottom - rects[2].top, divider_height);
+        assert_eq!(
+            rects[0].bottom - rects[0].top,
+            rects[3].bottom - rects[3].top
+        );
+
+        let frame = MENU_BORDER_WIDTH + MENU_MARGIN;
+        assert_eq!(height, rects[3].bottom + 2 * frame);
+        assert_eq!(width, rects[3].right + 2 * frame);
+        assert!(!menu.is_scrolling);
+    }
+
+    #[test]
+    fn context_menu_opens_at_point() {
+        let position = place_popup(&WORK, 200, 300, POINT { x: 100, y: 50 }, None, 4);
+        assert_eq!((position.x, position.y), (100, 50));
+    }
+
+    #[test]
+    fn context_menu_is_clamped_into_work_area() {
+        let position = place_popup(&WORK, 200, 300, POINT { x: 900, y: 700 }, None, 4);
+        assert_eq!((position.x, position.y), (800, 500));
+        let position = place_popup(&WORK, 200, 300, POINT { x: -50, y: -20 }, None, 4);
+        assert_eq!((position.x, position.y), (0, 0));
+    }
+
+    #[test]
+    fn submenu_opens_right_of_item() {
+        let item = anchor(100, 100);
+        let position = place_popup(&WORK, 200, 300, POINT::default(), Some(&item), 4);
+        assert_eq!((position.x, position.y), (296, 100));
+    }
+
+    #[test]
+    fn submenu_flips_left_without_room_on_the_right() {
+        let item = anchor(700, 100);
+        let position = place_popup(&WORK, 200, 300, POINT::default(), Some(&item), 4);
+        assert_eq!((position.x, position.y), (504, 100));
+    }
+
+    #[test]
+    fn submenu_lines_up_with_item_bottom_near_work_area_bottom() {
+        let item = anchor(100, 700);
+        let position = place_popup(&WORK, 200, 300, POINT::default(), Some(&item), 4);
+        assert_eq!((position.x, position.y), (296, 430));
+    }
+
+    #[test]
+    fn submenu_without_room_on_either_side_is_clamped() {
+        let item = RECT {
+            left: 50,
+            top: 100,
+            right: 950,
+            bottom: 130,
+        };
+        let position = place_popup(&WORK, 200, 300, POINT::default(), Some(&item), 4);
+        assert_eq!((position.x, position.y), (800, 100));
+    }
+
+    #[test]
+    fn work_area_offset_is_respected() {
+        let work = RECT {
+            left: -1920,
+            top: 0,
+            right: 0,
+            bottom: 1080,
+        };
+        let item = anchor(-250, 1060);
+        let position = place_popup(&work, 200, 300, POINT::default(), Some(&item), 4);
+        assert_eq!((position.x, position.y), (-446, 780));
+    }
+}
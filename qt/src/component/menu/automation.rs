@@ -0,0 +1,466 @@
+// UI Automation names its constants in mixed case, and they are matched on throughout.
+#![allow(non_upper_case_globals)]
+
+use std::cell::RefCell;
+use std::mem::ManuallyDrop;
+use std::rc::Rc;
+
+use windows::core::*;
+use windows::Win32::Foundation::{
+    E_OUTOFMEMORY, HWND, LPARAM, LRESULT, POINT, RECT, VARIANT_BOOL, VARIANT_FALSE, VARIANT_TRUE,
+    WPARAM,
+};
+use windows::Win32::System::Com::SAFEARRAY;
+use windows::Win32::System::Ole::{SafeArrayCreateVector, SafeArrayPutElement};
+use windows::Win32::System::Variant::{
+    VARENUM, VARIANT, VARIANT_0, VARIANT_0_0, VARIANT_0_0_0, VT_BOOL, VT_BSTR, VT_I4,
+};
+use windows::Win32::UI::Accessibility::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::{VK_LEFT, VK_RIGHT};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use super::{
+    find_item_by_coordinates, get_item_screen_rect, select_item, split_shortcut, Context, HitTest,
+    Menu, MenuItem,
+};
+
+// Posted to a popup so that UI Automation events are raised outside the tracking loop's borrows.
+pub(super) const WM_UIA_MENU_OPENED: u32 = WM_USER + 1;
+pub(super) const WM_UIA_FOCUS_CHANGED: u32 = WM_USER + 2;
+
+fn element_not_available() -> Error {
+    Error::from(HRESULT(UIA_E_ELEMENTNOTAVAILABLE as i32))
+}
+
+unsafe fn get_menu(window: HWND) -> Result<Rc<RefCell<Menu>>> {
+    if !IsWindow(Some(window)).as_bool() {
+        return Err(element_not_available());
+    }
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *const Context;
+    if raw.is_null() {
+        Err(element_not_available())
+    } else {
+        Ok((*raw).menu.clone())
+    }
+}
+
+unsafe fn get_owning_window(window: HWND) -> Result<HWND> {
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *const Context;
+    if raw.is_null() {
+        Err(element_not_available())
+    } else {
+        Ok((*raw).owning_window)
+    }
+}
+
+fn variant(vt: VARENUM, value: VARIANT_0_0_0) -> VARIANT {
+    VARIANT {
+        Anonymous: VARIANT_0 {
+            Anonymous: ManuallyDrop::new(VARIANT_0_0 {
+                vt,
+                wReserved1: 0,
+                wReserved2: 0,
+                wReserved3: 0,
+                Anonymous: value,
+            }),
+        },
+    }
+}
+
+fn variant_i32(value: i32) -> VARIANT {
+    variant(VT_I4, VARIANT_0_0_0 { lVal: value })
+}
+
+fn variant_bool(value: bool) -> VARIANT {
+    let value: VARIANT_BOOL = if value { VARIANT_TRUE } else { VARIANT_FALSE };
+    variant(VT_BOOL, VARIANT_0_0_0 { boolVal: value })
+}
+
+fn variant_string(value: BSTR) -> VARIANT {
+    variant(
+        VT_BSTR,
+        VARIANT_0_0_0 {
+            bstrVal: ManuallyDrop::new(value),
+        },
+    )
+}
+
+fn to_uia_rect(rect: &RECT) -> UiaRect {
+    UiaRect {
+        left: rect.left as f64,
+        top: rect.top as f64,
+        width: (rect.right - rect.left) as f64,
+        height: (rect.bottom - rect.top) as f64,
+    }
+}
+
+fn item_rect(item: &MenuItem) -> &RECT {
+    match item {
+        MenuItem::MenuItem { rect, .. }
+        | MenuItem::SubMenu { rect, .. }
+        | MenuItem::MenuDivider { rect } => rect,
+    }
+}
+
+pub(super) unsafe fn return_provider(
+    window: HWND,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> Option<LRESULT> {
+    if l_param.0 as i32 != UiaRootObjectId {
+        return None;
+    }
+    let provider: IRawElementProviderSimple = MenuProvider { window }.into();
+    Some(UiaReturnRawElementProvider(
+        window, w_param, l_param, &provider,
+    ))
+}
+
+pub(super) unsafe fn raise_menu_opened(window: HWND) {
+    if UiaClientsAreListening().as_bool() {
+        let provider: IRawElementProviderSimple = MenuProvider { window }.into();
+        _ = UiaRaiseAutomationEvent(&provider, UIA_MenuOpenedEventId);
+    }
+}
+
+pub(super) unsafe fn raise_focus_changed(window: HWND, index: usize) {
+    if UiaClientsAreListening().as_bool() {
+        let provider: IRawElementProviderSimple = MenuItemProvider { window, index }.into();
+        _ = UiaRaiseAutomationEvent(&provider, UIA_AutomationFocusChangedEventId);
+    }
+}
+
+// The popup window itself, exposed as a menu whose children are its items.
+#[implement(
+    IRawElementProviderSimple,
+    IRawElementProviderFragment,
+    IRawElementProviderFragmentRoot
+)]
+struct MenuProvider {
+    window: HWND,
+}
+
+impl IRawElementProviderSimple_Impl for MenuProvider_Impl {
+    fn ProviderOptions(&self) -> Result<ProviderOptions> {
+        Ok(ProviderOptions_ServerSideProvider | ProviderOptions_UseComThreading)
+    }
+
+    fn GetPatternProvider(&self, _pattern_id: UIA_PATTERN_ID) -> Result<IUnknown> {
+        Err(Error::empty())
+    }
+
+    fn GetPropertyValue(&self, property_id: UIA_PROPERTY_ID) -> Result<VARIANT> {
+        Ok(match property_id {
+            UIA_ControlTypePropertyId => variant_i32(UIA_MenuControlTypeId.0),
+            _ => VARIANT::default(),
+        })
+    }
+
+    fn HostRawElementProvider(&self) -> Result<IRawElementProviderSimple> {
+        unsafe { UiaHostProviderFromHwnd(self.window) }
+    }
+}
+
+impl IRawElementProviderFragment_Impl for MenuProvider_Impl {
+    fn Navigate(&self, direction: NavigateDirection) -> Result<IRawElementProviderFragment> {
+        let menu = unsafe { get_menu(self.window)? };
+        let menu = menu.try_borrow().map_err(|_| element_not_available())?;
+        let index = match direction {
+            NavigateDirection_FirstChild => 0,
+            NavigateDirection_LastChild => menu.items.len().wrapping_sub(1),
+            _ => return Err(Error::empty()),
+        };
+        if index < menu.items.len() {
+            Ok(MenuItemProvider {
+                window: self.window,
+                index,
+            }
+            .into())
+        } else {
+            Err(Error::empty())
+        }
+    }
+
+    fn GetRuntimeId(&self) -> Result<*mut SAFEARRAY> {
+        // Root elements take their runtime id from the host window.
+        Ok(std::ptr::null_mut())
+    }
+
+    fn BoundingRectangle(&self) -> Result<UiaRect> {
+        let mut rect = RECT::default();
+        unsafe { GetWindowRect(self.window, &mut rect)? };
+        Ok(to_uia_rect(&rect))
+    }
+
+    fn GetEmbeddedFragmentRoots(&self) -> Result<*mut SAFEARRAY> {
+        Ok(std::ptr::null_mut())
+    }
+
+    fn SetFocus(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn FragmentRoot(&self) -> Result<IRawElementProviderFragmentRoot> {
+        Ok(MenuProvider {
+            window: self.window,
+        }
+        .into())
+    }
+}
+
+impl IRawElementProviderFragmentRoot_Impl for MenuProvider_Impl {
+    fn ElementProviderFromPoint(&self, x: f64, y: f64) -> Result<IRawElementProviderFragment> {
+        let menu = unsafe { get_menu(self.window)? };
+        let menu = menu.try_borrow().map_err(|_| element_not_available())?;
+        let mut point = POINT {
+            x: x as i32,
+            y: y as i32,
+        };
+        match find_item_by_coordinates(&menu, &mut point) {
+            HitTest::Item(index) => Ok(MenuItemProvider {
+                window: self.window,
+                index,
+            }
+            .into()),
+            _ => Ok(MenuProvider {
+                window: self.window,
+            }
+            .into()),
+        }
+    }
+
+    fn GetFocus(&self) -> Result<IRawElementProviderFragment> {
+        let menu = unsafe { get_menu(self.window)? };
+        let menu = menu.try_borrow().map_err(|_| element_not_available())?;
+        match menu.focused_item_index {
+            Some(index) => Ok(MenuItemProvider {
+                window: self.window,
+                index,
+            }
+            .into()),
+            None => Err(Error::empty()),
+        }
+    }
+}
+
+// An item of the popup, addressed by its index so that it never outlives the menu it reads from.
+#[implement(
+    IRawElementProviderSimple,
+    IRawElementProviderFragment,
+    IInvokeProvider,
+    IExpandCollapseProvider
+)]
+struct MenuItemProvider {
+    window: HWND,
+    index: usize,
+}
+
+impl MenuItemProvider {
+    fn with_item<T>(&self, f: impl FnOnce(&Menu, &MenuItem) -> Result<T>) -> Result<T> {
+        let menu = unsafe { get_menu(self.window)? };
+        let menu = menu.try_borrow().map_err(|_| element_not_available())?;
+        let item = menu
+            .items
+            .get(self.index)
+            .ok_or_else(element_not_available)?;
+        f(&menu, item)
+    }
+
+    // Focuses this item and replays `key` through the tracking loop, as if it was pressed.
+    fn select_and_press(&self, key: u16) -> Result<()> {
+        let menu = unsafe { get_menu(self.window)? };
+        let mut menu = menu.try_borrow_mut().map_err(|_| element_not_available())?;
+        select_item(&mut menu, Some(self.index));
+        unsafe {
+            PostMessageW(
+                Some(self.window),
+                WM_KEYDOWN,
+                WPARAM(key as usize),
+                LPARAM(0),
+            )
+        }
+    }
+}
+
+impl IRawElementProviderSimple_Impl for MenuItemProvider_Impl {
+    fn ProviderOptions(&self) -> Result<ProviderOptions> {
+        Ok(ProviderOptions_ServerSideProvider | ProviderOptions_UseComThreading)
+    }
+
+    fn GetPatternProvider(&self, pattern_id: UIA_PATTERN_ID) -> Result<IUnknown> {
+        let provider = MenuItemProvider {
+            window: self.window,
+            index: self.index,
+        };
+        self.with_item(|_, item| match (item, pattern_id) {
+            (MenuItem::MenuItem { .. }, UIA_InvokePatternId) => {
+                IInvokeProvider::from(provider).cast()
+            }
+            (MenuItem::SubMenu { .. }, UIA_ExpandCollapsePatternId) => {
+                IExpandCollapseProvider::from(provider).cast()
+            }
+            _ => Err(Error::empty()),
+        })
+    }
+
+    fn GetPropertyValue(&self, property_id: UIA_PROPERTY_ID) -> Result<VARIANT> {
+        self.with_item(|menu, item| {
+            let focused = menu.focused_item_index == Some(self.index);
+            Ok(match (item, property_id) {
+                (MenuItem::MenuDivider { .. }, UIA_ControlTypePropertyId) => {
+                    variant_i32(UIA_SeparatorControlTypeId.0)
+                }
+                (_, UIA_ControlTypePropertyId) => variant_i32(UIA_MenuItemControlTypeId.0),
+                (
+                    MenuItem::MenuItem { text, .. } | MenuItem::SubMenu { text, .. },
+                    UIA_NamePropertyId,
+                ) => variant_string(BSTR::from_wide(split_shortcut(unsafe { text.as_wide() }).0)),
+                (MenuItem::MenuItem { text, .. }, UIA_AcceleratorKeyPropertyId) => {
+                    match split_shortcut(unsafe { text.as_wide() }).1 {
+                        Some(shortcut) => variant_string(BSTR::from_wide(shortcut)),
+                        None => VARIANT::default(),
+                    }
+                }
+                (MenuItem::MenuItem { id, .. }, UIA_AutomationIdPropertyId) => {
+                    variant_string(BSTR::from(id.to_string()))
+                }
+                (MenuItem::MenuItem { disabled, .. }, UIA_IsEnabledPropertyId) => {
+                    variant_bool(!disabled)
+                }
+                (MenuItem::MenuItem { disabled, .. }, UIA_IsKeyboardFocusablePropertyId) => {
+                    variant_bool(!disabled)
+                }
+                (MenuItem::SubMenu { .. }, UIA_IsEnabledPropertyId)
+                | (MenuItem::SubMenu { .. }, UIA_IsKeyboardFocusablePropertyId) => {
+                    variant_bool(true)
+                }
+                (MenuItem::MenuDivider { .. }, UIA_IsEnabledPropertyId)
+                | (MenuItem::MenuDivider { .. }, UIA_IsKeyboardFocusablePropertyId) => {
+                    variant_bool(false)
+                }
+                (_, UIA_HasKeyboardFocusPropertyId) => variant_bool(focused),
+                _ => VARIANT::default(),
+            })
+        })
+    }
+
+    fn HostRawElementProvider(&self) -> Result<IRawElementProviderSimple> {
+        Err(Error::empty())
+    }
+}
+
+impl IRawElementProviderFragment_Impl for MenuItemProvider_Impl {
+    fn Navigate(&self, direction: NavigateDirection) -> Result<IRawElementProviderFragment> {
+        let count = self.with_item(|menu, _| Ok(menu.items.len()))?;
+        let index = match direction {
+            NavigateDirection_Parent => {
+                return Ok(MenuProvider {
+                    window: self.window,
+                }
+                .into())
+            }
+            NavigateDirection_NextSibling => self.index + 1,
+            NavigateDirection_PreviousSibling => self.index.wrapping_sub(1),
+            _ => return Err(Error::empty()),
+        };
+        if index < count {
+            Ok(MenuItemProvider {
+                window: self.window,
+                index,
+            }
+            .into())
+        } else {
+            Err(Error::empty())
+        }
+    }
+
+    fn GetRuntimeId(&self) -> Result<*mut SAFEARRAY> {
+        let ids = [UiaAppendRuntimeId as i32, self.index as i32];
+        unsafe {
+            let array = SafeArrayCreateVector(VT_I4, 0, ids.len() as u32);
+            if array.is_null() {
+                return Err(Error::from(E_OUTOFMEMORY));
+            }
+            for (index, id) in ids.iter().enumerate() {
+                SafeArrayPutElement(array, &(index as i32), id as *const i32 as _)?;
+            }
+            Ok(array)
+        }
+    }
+
+    fn BoundingRectangle(&self) -> Result<UiaRect> {
+        self.with_item(|menu, item| {
+            Ok(unsafe { get_item_screen_rect(menu, item_rect(item)) }
+                .map(|rect| to_uia_rect(&rect))
+                .unwrap_or_default())
+        })
+    }
+
+    fn GetEmbeddedFragmentRoots(&self) -> Result<*mut SAFEARRAY> {
+        Ok(std::ptr::null_mut())
+    }
+
+    fn SetFocus(&self) -> Result<()> {
+        let menu = unsafe { get_menu(self.window)? };
+        let mut menu = menu.try_borrow_mut().map_err(|_| element_not_available())?;
+        select_item(&mut menu, Some(self.index));
+        Ok(())
+    }
+
+    fn FragmentRoot(&self) -> Result<IRawElementProviderFragmentRoot> {
+        Ok(MenuProvider {
+            window: self.window,
+        }
+        .into())
+    }
+}
+
+impl IInvokeProvider_Impl for MenuItemProvider_Impl {
+    fn Invoke(&self) -> Result<()> {
+        let id = self.with_item(|_, item| match item {
+            MenuItem::MenuItem {
+                disabled: false,
+                id,
+                ..
+            } => Ok(*id),
+            _ => Err(Error::from(HRESULT(UIA_E_ELEMENTNOTENABLED as i32))),
+        })?;
+        unsafe {
+            let owning_window = get_owning_window(self.window)?;
+            PostMessageW(
+                Some(owning_window),
+                WM_COMMAND,
+                WPARAM(id as usize),
+                LPARAM(0),
+            )?;
+            PostMessageW(Some(self.window), WM_CANCELMODE, WPARAM(0), LPARAM(0))
+        }
+    }
+}
+
+impl IExpandCollapseProvider_Impl for MenuItemProvider_Impl {
+    fn Expand(&self) -> Result<()> {
+        self.select_and_press(VK_RIGHT.0)
+    }
+
+    fn Collapse(&self) -> Result<()> {
+        if self.ExpandCollapseState()? == ExpandCollapseState_Expanded {
+            self.select_and_press(VK_LEFT.0)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn ExpandCollapseState(&self) -> Result<ExpandCollapseState> {
+        self.with_item(|_, item| match item {
+            MenuItem::SubMenu { sub_menu, .. }
+                if sub_menu
+                    .try_borrow()
+                    .is_ok_and(|sub_menu| sub_menu.window.is_some()) =>
+            {
+                Ok(ExpandCollapseState_Expanded)
+            }
+            _ => Ok(ExpandCollapseState_Collapsed),
+        })
+    }
+}
@@ -0,0 +1,457 @@
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Win32::Foundation::{FALSE, HINSTANCE, HWND, LPARAM, LRESULT, RECT, TRUE, WPARAM};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, CreateSolidBrush, DeleteObject, EndPaint, FillRect, HDC, PAINTSTRUCT,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::component::input::{self, InputOptions};
+use crate::component::Component;
+use crate::icon::Icon;
+use crate::theme::convert_to_color_ref;
+use crate::{window_class_name, Dpi, MouseEvent, QT};
+
+pub type ChangeCallback = Box<dyn Fn(&HWND, f64)>;
+
+pub struct NumberBoxEvent {
+    pub on_change: ChangeCallback,
+}
+
+impl Default for NumberBoxEvent {
+    fn default() -> Self {
+        NumberBoxEvent {
+            on_change: Box::new(|_window, _value| {}),
+        }
+    }
+}
+
+pub struct NumberBox;
+
+pub struct NumberBoxOptions {
+    pub width: i32,
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+    pub value: f64,
+    pub event: NumberBoxEvent,
+}
+
+impl Component for NumberBox {
+    type Options = NumberBoxOptions;
+
+    fn create(
+        qt: &QT,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: NumberBoxOptions,
+    ) -> Result<HWND> {
+        qt.create_number_box(parent_window, x, y, options)
+    }
+}
+
+struct State {
+    qt: QT,
+    width: f32,
+    min: f64,
+    max: f64,
+    step: f64,
+    value: f64,
+    event: NumberBoxEvent,
+}
+
+impl State {
+    fn clamp(&self, value: f64) -> f64 {
+        value.max(self.min).min(self.max)
+    }
+
+    fn format(&self, value: f64) -> HSTRING {
+        let step = self.step.to_string();
+        let decimals = step.find('.').map(|i| step.len() - i - 1).unwrap_or(0);
+        HSTRING::from(format!("{:.*}", decimals, value))
+    }
+}
+
+struct Context {
+    state: State,
+    value: f64,
+    input: HWND,
+    decrement_button: HWND,
+    increment_button: HWND,
+    updating: bool,
+    // Whether the input changed by typing since the value was last read or set.
+    changed: bool,
+}
+
+// The input notifies while it handles its own messages, so its text is read and rewritten from
+// posted messages instead.
+const WM_INPUT_CHANGE: u32 = WM_USER + 1;
+const WM_INPUT_KILL_FOCUS: u32 = WM_USER + 2;
+
+impl QT {
+    pub fn create_number_box(
+        &self,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: NumberBoxOptions,
+    ) -> Result<HWND> {
+        let class_name = window_class_name("QT_NUMBER_BOX", Some(window_proc));
+        unsafe {
+            let window_class = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                style: CS_CLASSDC,
+                lpfnWndProc: Some(window_proc),
+                hCursor: LoadCursorW(None, IDC_ARROW)?,
+                ..Default::default()
+            };
+            RegisterClassExW(&window_class);
+            let dpi = Dpi::for_window(parent_window);
+            let boxed = Box::new(State {
+                qt: self.clone(),
+                width: dpi.to_dip(options.width),
+                min: options.min,
+                max: options.max,
+                step: options.step,
+                value: options.value.max(options.min).min(options.max),
+                event: options.event,
+            });
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                w!(""),
+                WS_VISIBLE | WS_CHILD | WS_CLIPCHILDREN,
+                x,
+                y,
+                options.width,
+                dpi.to_physical(32f32),
+                Some(parent_window),
+                None,
+                Some(HINSTANCE(
+                    GetWindowLongPtrW(parent_window, GWLP_HINSTANCE) as _
+                )),
+                Some(Box::<State>::into_raw(boxed) as _),
+            )
+        }
+    }
+}
+
+unsafe fn get_context<'a>(window: HWND) -> Option<&'a mut Context> {
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    if raw.is_null() {
+        None
+    } else {
+        Some(&mut *raw)
+    }
+}
+
+unsafe fn step_from_button(button_window: &HWND, steps: f64) {
+    if let Ok(window) = GetParent(*button_window) {
+        if let Some(context) = get_context(window) {
+            let value = context.value + steps * context.state.step;
+            set_value(window, context, value);
+        }
+    }
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let qt = &state.qt;
+    let decrement_button = qt
+        .create_icon_button(
+            window,
+            0,
+            0,
+            &Icon::chevron_down_regular(),
+            MouseEvent {
+                on_click: Box::new(|button_window| step_from_button(button_window, -1f64)),
+            },
+        )?
+        .window();
+    let increment_button = qt
+        .create_icon_button(
+            window,
+            0,
+            0,
+            &Icon::chevron_up_regular(),
+            MouseEvent {
+                on_click: Box::new(|button_window| step_from_button(button_window, 1f64)),
+            },
+        )?
+        .window();
+
+    let dpi = Dpi::for_window(window);
+    let mut button_rect = RECT::default();
+    GetClientRect(decrement_button, &mut button_rect)?;
+    let gap = dpi.to_physical(state.qt.theme.tokens.spacing_horizontal_xs);
+    let input_width = dpi.to_physical(state.width) - 2 * (button_rect.right + gap);
+    let text = state.format(state.value);
+    let input = qt
+        .create_input(
+            window,
+            0,
+            0,
+            InputOptions {
+                width: input_width,
+                size: input::Size::Medium,
+                appearance: input::Appearance::Outline,
+                corner: None,
+                default_value: Some(PCWSTR(text.as_ptr())),
+                input_type: input::Type::Number,
+                number_format: None,
+                placeholder: None,
+                history_size: None,
+                revert_on_escape: false,
+                event: input::InputEvent::default(),
+            },
+        )?
+        .window();
+
+    let value = state.value;
+    Ok(Context {
+        state,
+        value,
+        input,
+        decrement_button,
+        increment_button,
+        updating: false,
+        changed: false,
+    })
+}
+
+unsafe fn layout(window: HWND, context: &Context) -> Result<()> {
+    let dpi = Dpi::for_window(window);
+    let gap = dpi.to_physical(context.state.qt.theme.tokens.spacing_horizontal_xs);
+    let mut input_rect = RECT::default();
+    GetClientRect(context.input, &mut input_rect)?;
+    let mut button_rect = RECT::default();
+    GetClientRect(context.decrement_button, &mut button_rect)?;
+    let height = input_rect.bottom.max(button_rect.bottom);
+
+    let mut x = input_rect.right + gap;
+    SetWindowPos(
+        context.input,
+        None,
+        0,
+        (height - input_rect.bottom) / 2,
+        0,
+        0,
+        SWP_NOSIZE | SWP_NOZORDER,
+    )?;
+    for button_window in [context.decrement_button, context.increment_button] {
+        SetWindowPos(
+            button_window,
+            None,
+            x,
+            (height - button_rect.bottom) / 2,
+            0,
+            0,
+            SWP_NOSIZE | SWP_NOZORDER,
+        )?;
+        x += button_rect.right + gap;
+    }
+    SetWindowPos(
+        window,
+        None,
+        0,
+        0,
+        dpi.to_physical(context.state.width),
+        height,
+        SWP_NOMOVE | SWP_NOZORDER,
+    )
+}
+
+unsafe fn set_value(window: HWND, context: &mut Context, value: f64) {
+    let value = context.state.clamp(value);
+    let changed = value != context.value;
+    context.value = value;
+    context.changed = false;
+    context.updating = true;
+    _ = SetWindowTextW(context.input, &context.state.format(value));
+    context.updating = false;
+    if changed {
+        (context.state.event.on_change)(&window, value);
+    }
+}
+
+unsafe fn parse_input(context: &Context) -> Option<f64> {
+    let mut buffer = vec![0u16; GetWindowTextLengthW(context.input) as usize + 1];
+    let length = GetWindowTextW(context.input, &mut buffer) as usize;
+    String::from_utf16_lossy(&buffer[..length])
+        .trim()
+        .parse::<f64>()
+        .ok()
+}
+
+unsafe fn on_input_change(window: HWND, context: &mut Context) {
+    if !context.changed {
+        return;
+    }
+    context.changed = false;
+    if let Some(value) = parse_input(context) {
+        if value >= context.state.min && value <= context.state.max && value != context.value {
+            context.value = value;
+            (context.state.event.on_change)(&window, value);
+        }
+    }
+}
+
+unsafe fn on_input_kill_focus(window: HWND, context: &mut Context) {
+    let value = parse_input(context).unwrap_or(context.value);
+    set_value(window, context, value);
+}
+
+unsafe fn fill_background(dc: HDC, rect: &RECT, context: &Context) {
+    let brush = CreateSolidBrush(convert_to_color_ref(
+        &context.state.qt.theme.tokens.color_neutral_background1,
+    ));
+    FillRect(dc, rect, brush);
+    _ = DeleteObject(brush.into());
+}
+
+unsafe fn on_paint(window: HWND, context: &Context) {
+    let mut ps = PAINTSTRUCT::default();
+    let dc = BeginPaint(window, &mut ps);
+    fill_background(dc, &ps.rcPaint, context);
+    _ = EndPaint(window, &ps);
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut State;
+            let state = Box::<State>::from_raw(raw);
+            match on_create(window, *state) {
+                Ok(context) => {
+                    _ = layout(window, &context);
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(FALSE.0 as isize),
+            }
+        },
+        WM_DESTROY => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                _ = Box::<Context>::from_raw(raw);
+            }
+            LRESULT(0)
+        },
+        WM_COMMAND => unsafe {
+            if let Some(context) = get_context(window) {
+                if HWND(l_param.0 as _) == context.input {
+                    match (w_param.0 >> 16) as u32 & 0xffff {
+                        EN_CHANGE if !context.updating => {
+                            context.changed = true;
+                            _ = PostMessageW(Some(window), WM_INPUT_CHANGE, WPARAM(0), LPARAM(0));
+                        }
+                        EN_KILLFOCUS => {
+                            _ = PostMessageW(
+                                Some(window),
+                                WM_INPUT_KILL_FOCUS,
+                                WPARAM(0),
+                                LPARAM(0),
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            LRESULT(0)
+        },
+        WM_INPUT_CHANGE => unsafe {
+            if let Some(context) = get_context(window) {
+                on_input_change(window, context);
+            }
+            LRESULT(0)
+        },
+        WM_INPUT_KILL_FOCUS => unsafe {
+            if let Some(context) = get_context(window) {
+                on_input_kill_focus(window, context);
+            }
+            LRESULT(0)
+        },
+        WM_MOUSEWHEEL => unsafe {
+            if let Some(context) = get_context(window) {
+                let delta = (w_param.0 >> 16) as i16 as f64 / WHEEL_DELTA as f64;
+                let value = context.value + delta * context.state.step;
+                set_value(window, context, value);
+            }
+            LRESULT(0)
+        },
+        WM_PAINT => unsafe {
+            if let Some(context) = get_context(window) {
+                on_paint(window, context);
+            }
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
+            if let Some(context) = get_context(window) {
+                let mut rect = RECT::default();
+                if GetClientRect(window, &mut rect).is_ok() {
+                    fill_background(HDC(w_param.0 as _), &rect, context);
+                }
+            }
+            LRESULT(0)
+        },
+        WM_ERASEBKGND => LRESULT(1),
+        WM_DPICHANGED_AFTERPARENT => unsafe {
+            if let Some(context) = get_context(window) {
+                _ = layout(window, context);
+            }
+            LRESULT(0)
+        },
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{pump_messages, window_text, Host};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use windows::Win32::UI::Input::KeyboardAndMouse::SetFocus;
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn leaving_the_input_reformats_its_text() {
+        let host = Host::new(300, 100);
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let recorded = changes.clone();
+        let window = host.create::<NumberBox>(
+            10,
+            10,
+            NumberBoxOptions {
+                width: 200,
+                min: 0.0,
+                max: 10.0,
+                step: 1.0,
+                value: 1.0,
+                event: NumberBoxEvent {
+                    on_change: Box::new(move |_window, value| recorded.borrow_mut().push(value)),
+                },
+            },
+        );
+        let input = unsafe { get_context(window).unwrap().input };
+        unsafe {
+            _ = SetFocus(Some(input));
+            SetWindowTextW(input, w!("2.7")).unwrap();
+            pump_messages();
+            _ = SetFocus(Some(host.window));
+        }
+        pump_messages();
+        assert_eq!(window_text(input), "3");
+        assert_eq!(*changes.borrow(), [2.7]);
+    }
+}
@@ -1,49 +1,114 @@
 use std::mem::size_of;
 
 use windows::core::*;
+use windows::Foundation::Numerics::Matrix3x2;
 use windows::Win32::Foundation::{FALSE, HINSTANCE, HWND, LPARAM, LRESULT, RECT, TRUE, WPARAM};
 use windows::Win32::Graphics::Direct2D::Common::{
     D2D1_GRADIENT_STOP, D2D_POINT_2F, D2D_RECT_F, D2D_SIZE_U,
 };
 use windows::Win32::Graphics::Direct2D::{
-    D2D1CreateFactory, ID2D1Factory1, ID2D1GradientStopCollection, ID2D1HwndRenderTarget,
-    D2D1_EXTEND_MODE_WRAP, D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_GAMMA_2_2,
-    D2D1_HWND_RENDER_TARGET_PROPERTIES, D2D1_LINEAR_GRADIENT_BRUSH_PROPERTIES,
-    D2D1_RENDER_TARGET_PROPERTIES, D2D1_ROUNDED_RECT,
+    D2D1CreateFactory, ID2D1Brush, ID2D1Factory1, ID2D1GradientStopCollection,
+    ID2D1HwndRenderTarget, ID2D1LinearGradientBrush, D2D1_EXTEND_MODE_CLAMP, D2D1_EXTEND_MODE_WRAP,
+    D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_GAMMA_2_2, D2D1_HWND_RENDER_TARGET_PROPERTIES,
+    D2D1_LINEAR_GRADIENT_BRUSH_PROPERTIES, D2D1_ROUNDED_RECT,
 };
 use windows::Win32::Graphics::Gdi::{
-    BeginPaint, CreateRoundRectRgn, EndPaint, InvalidateRect, SetWindowRgn, PAINTSTRUCT,
+    BeginPaint, CombineRgn, CreateRectRgn, CreateRoundRectRgn, DeleteObject, EndPaint,
+    InvalidateRect, SetWindowRgn, HDC, PAINTSTRUCT, RGN_OR,
 };
 use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
 use windows::Win32::UI::Animation::{
-    IUIAnimationManager2, IUIAnimationTimer, IUIAnimationTimerEventHandler,
-    IUIAnimationTimerEventHandler_Impl, IUIAnimationTimerUpdateHandler,
-    IUIAnimationTransitionLibrary2, IUIAnimationVariable2, UIAnimationManager2, UIAnimationTimer,
-    UIAnimationTransitionLibrary2, UI_ANIMATION_IDLE_BEHAVIOR_DISABLE, UI_ANIMATION_MANAGER_IDLE,
+    IUIAnimationManager2, IUIAnimationStoryboard2, IUIAnimationTimerEventHandler,
+    IUIAnimationTimerEventHandler_Impl, IUIAnimationTransitionLibrary2, IUIAnimationVariable2,
+    UIAnimationTransitionLibrary2, UI_ANIMATION_STORYBOARD_FINISHED,
 };
-use windows::Win32::UI::HiDpi::GetDpiForWindow;
 use windows::Win32::UI::WindowsAndMessaging::*;
 
-use crate::{get_scaling_factor, QT};
+use crate::animation::{invalidate_on_next_frame, is_reduced_motion, AnimationMode};
+use crate::component::Component;
+use crate::{
+    copy_window_text, is_window_of_class, print_client, recover_lost_device,
+    recreate_render_target, render_target_properties, window_class_name, CornerRadius, Dpi,
+    ProgressBarHandle, QT,
+};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Shape {
     Rounded,
     Square,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Thickness {
     Medium,
     Large,
 }
+
+/// How the determinate part of the bar is filled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fill {
+    Solid,
+    /// Blends from the brand color into the compound brand color across the track.
+    Gradient,
+}
+
+pub struct ProgressBarEvent {
+    /// Fired once when a determinate value reaches `max`.
+    pub on_complete: Box<dyn Fn(&HWND)>,
+}
+
+impl Default for ProgressBarEvent {
+    fn default() -> Self {
+        ProgressBarEvent {
+            on_complete: Box::new(|_window| {}),
+        }
+    }
+}
+
+pub struct ProgressBar;
+
+pub struct ProgressBarOptions {
+    pub width: i32,
+    pub shape: Shape,
+    pub corner: Option<CornerRadius>,
+    pub value: Option<f32>,
+    pub max: Option<f32>,
+    pub thickness: Thickness,
+    pub fill: Fill,
+    pub segments: Option<u32>,
+    pub steps: Option<u32>,
+    pub event: ProgressBarEvent,
+}
+
+impl Component for ProgressBar {
+    type Options = ProgressBarOptions;
+
+    fn create(
+        qt: &QT,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: ProgressBarOptions,
+    ) -> Result<HWND> {
+        qt.create_progress_bar(parent_window, x, y, options)
+            .map(|handle| handle.window())
+    }
+}
+
 pub struct State {
     qt: QT,
     shape: Shape,
+    /// Overrides the rounding of `shape`.
+    corner: Option<CornerRadius>,
     value: Option<f32>,
     max: f32,
     thickness: Thickness,
+    fill: Fill,
+    segments: Option<u32>,
+    /// Divides the bar into this many equal steps with thin lines, whatever its value.
+    steps: Option<u32>,
     width: f32,
+    event: ProgressBarEvent,
 }
 
 impl State {
@@ -53,16 +118,94 @@ impl State {
             Thickness::Large => 4f32,
         }
     }
+
+    // Shared by the window region and painting, so that they agree at every DPI.
+    fn get_corner_diameter(&self, dpi: Dpi, rect: &RECT) -> i32 {
+        let tokens = &self.qt.theme.tokens;
+        self.corner
+            .unwrap_or(match self.shape {
+                Shape::Rounded => CornerRadius::Dips(tokens.border_radius_medium),
+                Shape::Square => CornerRadius::Dips(tokens.border_radius_none),
+            })
+            .to_physical_diameter(dpi, rect.right, rect.bottom)
+    }
+
+    fn get_segment_count(&self) -> u32 {
+        self.segments.unwrap_or(1).max(1)
+    }
+}
+
+const SEGMENT_GAP: f32 = 2f32;
+
+// Returns the left and right edges of each segment in physical pixels, so that painting and the
+// window region agree on where the gaps are.
+fn get_segment_bounds(width: i32, segment_count: u32, gap: i32) -> Vec<(i32, i32)> {
+    let segment_count = segment_count as i32;
+    let track_width = (width - gap * (segment_count - 1)).max(0);
+    (0..segment_count)
+        .map(|index| {
+            let left = index * track_width / segment_count + index * gap;
+            let right = (index + 1) * track_width / segment_count + index * gap;
+            (left, right)
+        })
+        .collect()
 }
 
 pub struct Context {
     state: State,
     render_target: ID2D1HwndRenderTarget,
     animation_manager: IUIAnimationManager2,
-    animation_timer: IUIAnimationTimer,
     transition_library: IUIAnimationTransitionLibrary2,
     indeterminate_stop_collection: ID2D1GradientStopCollection,
+    bar_brush: ID2D1Brush,
+    indeterminate_brush: ID2D1LinearGradientBrush,
     indeterminate_left: IUIAnimationVariable2,
+    /// Restarted whenever it finishes, for as long as the bar is indeterminate.
+    indeterminate_storyboard: Option<IUIAnimationStoryboard2>,
+    is_indeterminate_animating: bool,
+    is_complete: bool,
+}
+
+impl Context {
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        self.render_target = recreate_render_target(window, &self.render_target)?;
+        self.indeterminate_stop_collection =
+            create_indeterminate_stop_collection(&self.render_target, &self.state)?;
+        // Recreates the brushes.
+        on_size(window, self)
+    }
+}
+
+const CLASS_NAME: &str = "QT_PROGRESS_BAR";
+
+const WM_SET_VALUE: u32 = WM_USER + 2;
+
+/// Sets the progress value, or switches the bar to indeterminate mode with `None`. Does nothing if
+/// `window` is not a progress bar.
+pub fn set_value(window: HWND, value: Option<f32>) {
+    // Other components use the same private message numbers for their own messages.
+    if !is_window_of_class(window, CLASS_NAME, Some(window_proc)) {
+        return;
+    }
+    unsafe {
+        match value {
+            Some(value) => SendMessageW(
+                window,
+                WM_SET_VALUE,
+                Some(WPARAM(1)),
+                Some(LPARAM(value.to_bits() as isize)),
+            ),
+            None => SendMessageW(window, WM_SET_VALUE, Some(WPARAM(0)), None),
+        };
+    }
+}
+
+impl ProgressBarHandle {
+    /// Like [`set_value`], but may be called from any thread.
+    pub fn set_value(&self, value: Option<f32>) -> Result<()> {
+        let handle = *self;
+        self.post_to_ui(Box::new(move || set_value(handle.window(), value)))
+    }
 }
 
 impl QT {
@@ -71,41 +214,42 @@ impl QT {
         parent_window: HWND,
         x: i32,
         y: i32,
-        width: i32,
-        shape: &Shape,
-        value: Option<f32>,
-        max: Option<f32>,
-        thickness: &Thickness,
-    ) -> Result<HWND> {
-        let class_name: PCWSTR = w!("QT_PROGRESS_BAR");
+        options: ProgressBarOptions,
+    ) -> Result<ProgressBarHandle> {
+        let class_name = window_class_name(CLASS_NAME, Some(window_proc));
         unsafe {
             let window_class = WNDCLASSEXW {
                 cbSize: size_of::<WNDCLASSEXW>() as u32,
-                lpszClassName: class_name,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
                 style: CS_CLASSDC,
                 lpfnWndProc: Some(window_proc),
                 hCursor: LoadCursorW(None, IDC_ARROW)?,
                 ..Default::default()
             };
             RegisterClassExW(&window_class);
-            let scaling_factor = get_scaling_factor(parent_window);
+            let dpi = Dpi::for_window(parent_window);
             let boxed = Box::new(State {
                 qt: self.clone(),
-                value,
-                max: max.unwrap_or(1f32),
-                shape: *shape,
-                thickness: *thickness,
-                width: width as f32 / scaling_factor,
+                value: options.value,
+                max: options.max.unwrap_or(1f32),
+                shape: options.shape,
+                corner: options.corner,
+                thickness: options.thickness,
+                fill: options.fill,
+                segments: options.segments,
+                steps: options.steps,
+                width: dpi.to_dip(options.width),
+                event: options.event,
             });
             CreateWindowExW(
                 WINDOW_EX_STYLE::default(),
-                class_name,
+                &class_name,
                 w!(""),
                 WS_VISIBLE | WS_CHILD,
                 x,
                 y,
-                width,
-                (boxed.as_ref().get_height() * scaling_factor) as i32,
+                options.width,
+                dpi.to_physical(boxed.as_ref().get_height()),
                 Some(parent_window),
                 None,
                 Some(HINSTANCE(
@@ -113,6 +257,7 @@ impl QT {
                 )),
                 Some(Box::<State>::into_raw(boxed) as _),
             )
+            .map(ProgressBarHandle::new)
         }
     }
 }
@@ -128,24 +273,19 @@ impl IUIAnimationTimerEventHandler_Impl for AnimationTimerEventHandler_Impl {
     }
 
     fn OnPostUpdate(&self) -> Result<()> {
+        invalidate_on_next_frame(self.window);
         unsafe {
-            let _ = InvalidateRect(Some(self.window), None, false);
-
             let raw = GetWindowLongPtrW(self.window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return Ok(());
+            }
             let context = &mut *raw;
-            let status = context.animation_manager.GetStatus()?;
-            if status == UI_ANIMATION_MANAGER_IDLE {
-                context.indeterminate_left =
-                    context.animation_manager.CreateAnimationVariable(-0.33)?;
-                let transition = context
-                    .transition_library
-                    .CreateLinearTransition(3.0, 1.0)?;
-                let seconds_now = context.animation_timer.GetTime()?;
-                context.animation_manager.ScheduleTransition(
-                    &context.indeterminate_left,
-                    &transition,
-                    seconds_now,
-                )?;
+            let is_finished = match &context.indeterminate_storyboard {
+                Some(storyboard) => storyboard.GetStatus()? == UI_ANIMATION_STORYBOARD_FINISHED,
+                None => false,
+            };
+            if context.is_indeterminate_animating && is_finished {
+                start_indeterminate_animation(self.window, context)?;
             }
         }
         Ok(())
@@ -156,59 +296,131 @@ impl IUIAnimationTimerEventHandler_Impl for AnimationTimerEventHandler_Impl {
     }
 }
 
-unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
-    let factory = D2D1CreateFactory::<ID2D1Factory1>(D2D1_FACTORY_TYPE_SINGLE_THREADED, None)?;
-    let mut rect = RECT::default();
-    GetClientRect(window, &mut rect)?;
-    let dpi = GetDpiForWindow(window);
-    let render_target = factory.CreateHwndRenderTarget(
-        &D2D1_RENDER_TARGET_PROPERTIES {
-            dpiX: dpi as f32,
-            dpiY: dpi as f32,
-            ..Default::default()
-        },
-        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
-            hwnd: window,
-            pixelSize: D2D_SIZE_U {
-                width: rect.right as u32,
-                height: rect.bottom as u32,
-            },
-            presentOptions: Default::default(),
-        },
+unsafe fn start_indeterminate_animation(window: HWND, context: &mut Context) -> Result<()> {
+    (context.indeterminate_left, context.indeterminate_storyboard) = create_indeterminate_left(
+        &context.state.qt,
+        window,
+        &context.animation_manager,
+        &context.transition_library,
     )?;
+    context.is_indeterminate_animating = true;
+    Ok(())
+}
 
-    let scaling_factor = get_scaling_factor(window);
+// Sweeps the indeterminate segment across the track, or holds it in the middle while the user
+// has turned off animations, since snapping to the end would leave nothing to see.
+unsafe fn create_indeterminate_left(
+    qt: &QT,
+    window: HWND,
+    animation_manager: &IUIAnimationManager2,
+    transition_library: &IUIAnimationTransitionLibrary2,
+) -> Result<(IUIAnimationVariable2, Option<IUIAnimationStoryboard2>)> {
+    if qt.animation_mode() == AnimationMode::Normal && is_reduced_motion() {
+        let indeterminate_left = animation_manager.CreateAnimationVariable((1.0 - 0.33) / 2.0)?;
+        return Ok((indeterminate_left, None));
+    }
+    let indeterminate_left = animation_manager.CreateAnimationVariable(-0.33)?;
+    let transition = transition_library.CreateLinearTransition(3.0, 1.0)?;
+    let storyboard = qt.schedule_transition(window, &indeterminate_left, &transition)?;
+    Ok((indeterminate_left, Some(storyboard)))
+}
+
+// Reads as a whole percentage, or as empty text while indeterminate.
+// The filled fraction of the bar. A `max` of zero or less leaves nothing to divide by, so the bar
+// is either empty or, once the value reaches `max`, complete.
+fn get_fraction(value: f32, max: f32) -> f32 {
+    if max > 0f32 {
+        value.clamp(0f32, max) / max
+    } else if value >= max {
+        1f32
+    } else {
+        0f32
+    }
+}
+
+fn get_text(value: Option<f32>, max: f32) -> Vec<u16> {
+    match value {
+        Some(value) => {
+            let percentage = (get_fraction(value, max) * 100f32).round();
+            format!("{}%", percentage).encode_utf16().collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+unsafe fn on_set_value(window: HWND, context: &mut Context, value: Option<f32>) -> Result<()> {
+    context.state.value = value;
+    match value {
+        Some(value) => {
+            if context.is_indeterminate_animating {
+                context.is_indeterminate_animating = false;
+                if let Some(storyboard) = context.indeterminate_storyboard.take() {
+                    storyboard.Abandon()?;
+                }
+            }
+            let was_complete = context.is_complete;
+            context.is_complete = value >= context.state.max;
+            if context.is_complete && !was_complete {
+                (context.state.event.on_complete)(&window);
+            }
+        }
+        None => {
+            if !context.is_indeterminate_animating {
+                start_indeterminate_animation(window, context)?;
+            }
+        }
+    }
+    _ = InvalidateRect(Some(window), None, false);
+    Ok(())
+}
+
+// Both brushes span the track, so they are created again when the bar is resized instead of on
+// every paint.
+unsafe fn create_bar_brush(
+    render_target: &ID2D1HwndRenderTarget,
+    state: &State,
+    width: f32,
+) -> Result<ID2D1Brush> {
     let tokens = &state.qt.theme.tokens;
-    let corner_diameter = match state.shape {
-        Shape::Rounded => rect
-            .bottom
-            .min((tokens.border_radius_medium * 2f32 * scaling_factor) as i32),
-        Shape::Square => rect
-            .bottom
-            .min((tokens.border_radius_none * 2f32 * scaling_factor) as i32),
-    };
-    let region = CreateRoundRectRgn(
-        0,
-        0,
-        rect.right + 1,
-        rect.bottom + 1,
-        corner_diameter,
-        corner_diameter,
-    );
-    SetWindowRgn(window, Some(region), true);
-    let animation_timer: IUIAnimationTimer =
-        CoCreateInstance(&UIAnimationTimer, None, CLSCTX_INPROC_SERVER)?;
-    let transition_library: IUIAnimationTransitionLibrary2 =
-        CoCreateInstance(&UIAnimationTransitionLibrary2, None, CLSCTX_INPROC_SERVER)?;
-    let animation_manager: IUIAnimationManager2 =
-        CoCreateInstance(&UIAnimationManager2, None, CLSCTX_INPROC_SERVER)?;
-    let timer_update_handler = animation_manager.cast::<IUIAnimationTimerUpdateHandler>()?;
-    animation_timer
-        .SetTimerUpdateHandler(&timer_update_handler, UI_ANIMATION_IDLE_BEHAVIOR_DISABLE)?;
-    let timer_event_handler: IUIAnimationTimerEventHandler =
-        AnimationTimerEventHandler { window }.into();
-    animation_timer.SetTimerEventHandler(&timer_event_handler)?;
-    let indeterminate_stop_collection = render_target.CreateGradientStopCollection(
+    match state.fill {
+        Fill::Solid => render_target
+            .CreateSolidColorBrush(&tokens.color_compound_brand_background, None)?
+            .cast(),
+        Fill::Gradient => {
+            let stop_collection = render_target.CreateGradientStopCollection(
+                &[
+                    D2D1_GRADIENT_STOP {
+                        position: 0.0,
+                        color: tokens.color_brand_background,
+                    },
+                    D2D1_GRADIENT_STOP {
+                        position: 1.0,
+                        color: tokens.color_compound_brand_background,
+                    },
+                ],
+                D2D1_GAMMA_2_2,
+                D2D1_EXTEND_MODE_CLAMP,
+            )?;
+            render_target
+                .CreateLinearGradientBrush(
+                    &D2D1_LINEAR_GRADIENT_BRUSH_PROPERTIES {
+                        startPoint: D2D_POINT_2F { x: 0.0, y: 0.0 },
+                        endPoint: D2D_POINT_2F { x: width, y: 0.0 },
+                    },
+                    None,
+                    &stop_collection,
+                )?
+                .cast()
+        }
+    }
+}
+
+unsafe fn create_indeterminate_stop_collection(
+    render_target: &ID2D1HwndRenderTarget,
+    state: &State,
+) -> Result<ID2D1GradientStopCollection> {
+    let tokens = &state.qt.theme.tokens;
+    render_target.CreateGradientStopCollection(
         &[
             D2D1_GRADIENT_STOP {
                 position: 0.0,
@@ -225,21 +437,104 @@ unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
         ],
         D2D1_GAMMA_2_2,
         D2D1_EXTEND_MODE_WRAP,
+    )
+}
+
+// The indeterminate brush covers the moving segment at the start of the track; painting moves it
+// with a transform.
+unsafe fn create_indeterminate_brush(
+    render_target: &ID2D1HwndRenderTarget,
+    stop_collection: &ID2D1GradientStopCollection,
+    width: f32,
+) -> Result<ID2D1LinearGradientBrush> {
+    render_target.CreateLinearGradientBrush(
+        &D2D1_LINEAR_GRADIENT_BRUSH_PROPERTIES {
+            startPoint: D2D_POINT_2F { x: 0.0, y: 0.0 },
+            endPoint: D2D_POINT_2F {
+                x: width * 0.33,
+                y: 0.0,
+            },
+        },
+        None,
+        stop_collection,
+    )
+}
+
+// Clips the window to one rounded rectangle per segment, which also leaves the gaps between
+// segments transparent for the indeterminate animation.
+unsafe fn set_region(window: HWND, state: &State, rect: &RECT) {
+    let dpi = Dpi::for_window(window);
+    let corner_diameter = state.get_corner_diameter(dpi, rect);
+    let region = CreateRectRgn(0, 0, 0, 0);
+    for (left, right) in get_segment_bounds(
+        rect.right,
+        state.get_segment_count(),
+        dpi.to_physical(SEGMENT_GAP),
+    ) {
+        let segment_region = CreateRoundRectRgn(
+            left,
+            0,
+            right + 1,
+            rect.bottom + 1,
+            corner_diameter,
+            corner_diameter,
+        );
+        CombineRgn(Some(region), Some(region), Some(segment_region), RGN_OR);
+        _ = DeleteObject(segment_region.into());
+    }
+    SetWindowRgn(window, Some(region), true);
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(D2D1_FACTORY_TYPE_SINGLE_THREADED, None)?;
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    let dpi = Dpi::for_window(window);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U {
+                width: rect.right as u32,
+                height: rect.bottom as u32,
+            },
+            presentOptions: Default::default(),
+        },
     )?;
-    let indeterminate_left = animation_manager.CreateAnimationVariable(-0.33)?;
-    if let None = state.value {
-        let transition = transition_library.CreateLinearTransition(3.0, 1.0)?;
-        let seconds_now = animation_timer.GetTime()?;
-        animation_manager.ScheduleTransition(&indeterminate_left, &transition, seconds_now)?;
+
+    set_region(window, &state, &rect);
+    let transition_library: IUIAnimationTransitionLibrary2 =
+        CoCreateInstance(&UIAnimationTransitionLibrary2, None, CLSCTX_INPROC_SERVER)?;
+    let animation_manager = state.qt.animation_manager()?;
+    let timer_event_handler: IUIAnimationTimerEventHandler =
+        AnimationTimerEventHandler { window }.into();
+    state.qt.register_animation(window, &timer_event_handler);
+    let indeterminate_stop_collection =
+        create_indeterminate_stop_collection(&render_target, &state)?;
+    let width = dpi.to_dip(rect.right);
+    let bar_brush = create_bar_brush(&render_target, &state, width)?;
+    let indeterminate_brush =
+        create_indeterminate_brush(&render_target, &indeterminate_stop_collection, width)?;
+    let (indeterminate_left, indeterminate_storyboard) = match state.value {
+        None => {
+            create_indeterminate_left(&state.qt, window, &animation_manager, &transition_library)?
+        }
+        Some(_) => (animation_manager.CreateAnimationVariable(-0.33)?, None),
     };
+    let is_indeterminate_animating = state.value.is_none();
+    let is_complete = state.value.is_some_and(|value| value >= state.max);
     Ok(Context {
         state,
         render_target,
         animation_manager,
-        animation_timer,
         transition_library,
         indeterminate_stop_collection,
+        bar_brush,
+        indeterminate_brush,
         indeterminate_left,
+        indeterminate_storyboard,
+        is_indeterminate_animating,
+        is_complete,
     })
 }
 
@@ -252,50 +547,44 @@ unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
 
     let mut rect = RECT::default();
     GetClientRect(window, &mut rect)?;
-    let scaling_factor = get_scaling_factor(window);
-    let width = rect.right as f32 / scaling_factor;
-    let height = rect.bottom as f32 / scaling_factor;
+    let dpi = Dpi::for_window(window);
+    let width = dpi.to_dip(rect.right);
+    let height = dpi.to_dip(rect.bottom);
 
     match state.value {
         Some(value) => {
-            let bar_width = value.min(state.max) / state.max * width;
-            let corner_radius = match state.shape {
-                Shape::Rounded => (height / 2f32).min(tokens.border_radius_medium),
-                Shape::Square => tokens.border_radius_none,
-            };
-            let bar_rect = D2D1_ROUNDED_RECT {
-                rect: D2D_RECT_F {
-                    left: 0f32,
-                    top: 0f32,
-                    right: bar_width,
-                    bottom: height,
-                },
-                radiusX: corner_radius,
-                radiusY: corner_radius,
-            };
-            let bar_brush = context
-                .render_target
-                .CreateSolidColorBrush(&tokens.color_compound_brand_background, None)?;
-            context
-                .render_target
-                .FillRoundedRectangle(&bar_rect, &bar_brush);
+            let segment_count = state.get_segment_count();
+            let filled_segments = get_fraction(value, state.max) * segment_count as f32;
+            let corner_radius = dpi.to_dip(state.get_corner_diameter(dpi, &rect)) / 2f32;
+            let segment_bounds =
+                get_segment_bounds(rect.right, segment_count, dpi.to_physical(SEGMENT_GAP));
+            for (index, (left, right)) in segment_bounds.into_iter().enumerate() {
+                let fraction = (filled_segments - index as f32).clamp(0f32, 1f32);
+                if fraction == 0f32 {
+                    break;
+                }
+                let left = dpi.to_dip(left);
+                let right = dpi.to_dip(right);
+                let bar_rect = D2D1_ROUNDED_RECT {
+                    rect: D2D_RECT_F {
+                        left,
+                        top: 0f32,
+                        right: left + (right - left) * fraction,
+                        bottom: height,
+                    },
+                    radiusX: corner_radius,
+                    radiusY: corner_radius,
+                };
+                context
+                    .render_target
+                    .FillRoundedRectangle(&bar_rect, &context.bar_brush);
+            }
         }
         None => {
             let left = context.indeterminate_left.GetValue()?;
-            let brush = context.render_target.CreateLinearGradientBrush(
-                &D2D1_LINEAR_GRADIENT_BRUSH_PROPERTIES {
-                    startPoint: D2D_POINT_2F {
-                        x: left as f32 * width,
-                        y: 0.0,
-                    },
-                    endPoint: D2D_POINT_2F {
-                        x: width * 0.33 + left as f32 * width,
-                        y: 0.0,
-                    },
-                },
-                None,
-                &context.indeterminate_stop_collection,
-            )?;
+            context
+                .indeterminate_brush
+                .SetTransform(&Matrix3x2::translation(left as f32 * width, 0.0));
             let indeterminate_rect = D2D_RECT_F {
                 left: left as f32 * width,
                 top: 0f32,
@@ -304,7 +593,26 @@ unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
             };
             context
                 .render_target
-                .FillRectangle(&indeterminate_rect, &brush);
+                .FillRectangle(&indeterminate_rect, &context.indeterminate_brush);
+        }
+    }
+
+    // A medium bar is too thin for the dividers to read as steps.
+    if let Some(steps) = state.steps.filter(|_| state.thickness != Thickness::Medium) {
+        let line_width = dpi.to_dip(1);
+        let divider_brush = context
+            .render_target
+            .CreateSolidColorBrush(&tokens.color_neutral_background1, None)?;
+        for step in 1..steps {
+            // Centered on a whole physical pixel, so the line stays crisp.
+            let x = dpi.to_dip(rect.right * step as i32 / steps as i32) + line_width / 2f32;
+            context.render_target.DrawLine(
+                D2D_POINT_2F { x, y: 0f32 },
+                D2D_POINT_2F { x, y: height },
+                &divider_brush,
+                line_width,
+                None,
+            );
         }
     }
 
@@ -323,45 +631,45 @@ unsafe fn on_paint(window: HWND, context: &Context) -> Result<()> {
     }
 }
 
-unsafe fn on_dpi_changed(window: HWND, context: &Context) -> Result<()> {
-    let scaling_factor = get_scaling_factor(window);
-    let scaled_width = context.state.width * scaling_factor;
-    let scaled_height = context.state.get_height() * scaling_factor;
+unsafe fn on_dpi_changed(window: HWND, context: &mut Context) -> Result<()> {
+    let dpi = Dpi::for_window(window);
+    let scaled_width = dpi.to_physical(context.state.width);
+    let scaled_height = dpi.to_physical(context.state.get_height());
     SetWindowPos(
         window,
         None,
         0,
         0,
-        scaled_width as i32,
-        scaled_height as i32,
+        scaled_width,
+        scaled_height,
         SWP_NOMOVE | SWP_NOZORDER,
     )?;
+    context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+    // Gradient stops belong to the render target's device, which may not survive the change.
+    context.indeterminate_stop_collection =
+        create_indeterminate_stop_collection(&context.render_target, &context.state)?;
+    on_size(window, context)
+}
+
+// Fits the render target and the window region to the client area, however it was resized.
+unsafe fn on_size(window: HWND, context: &mut Context) -> Result<()> {
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
     context.render_target.Resize(&D2D_SIZE_U {
-        width: scaled_width as u32,
-        height: scaled_height as u32,
+        width: rect.right as u32,
+        height: rect.bottom as u32,
     })?;
-    let new_dpi = GetDpiForWindow(window);
-    context.render_target.SetDpi(new_dpi as f32, new_dpi as f32);
     let _ = InvalidateRect(Some(window), None, false);
 
-    let tokens = &context.state.qt.theme.tokens;
-    let corner_diameter = match context.state.shape {
-        Shape::Rounded => {
-            scaled_height.min(tokens.border_radius_medium * 2f32 * scaling_factor) as i32
-        }
-        Shape::Square => {
-            scaled_height.min(tokens.border_radius_none * 2f32 * scaling_factor) as i32
-        }
-    };
-    let region = CreateRoundRectRgn(
-        0,
-        0,
-        scaled_width as i32 + 1,
-        scaled_height as i32 + 1,
-        corner_diameter,
-        corner_diameter,
-    );
-    SetWindowRgn(window, Some(region), true);
+    let dpi = Dpi::for_window(window);
+    let width = dpi.to_dip(rect.right);
+    context.bar_brush = create_bar_brush(&context.render_target, &context.state, width)?;
+    context.indeterminate_brush = create_indeterminate_brush(
+        &context.render_target,
+        &context.indeterminate_stop_collection,
+        width,
+    )?;
+    set_region(window, &context.state, &rect);
     Ok(())
 }
 
@@ -387,30 +695,204 @@ extern "system" fn window_proc(
         },
         WM_DESTROY => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            _ = Box::<Context>::from_raw(raw);
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                let context = Box::<Context>::from_raw(raw);
+                context.state.qt.unregister_animation(window);
+            }
             LRESULT(0)
         },
         WM_PAINT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            let context = &*raw;
+            let context = &mut *raw;
             let mut ps = PAINTSTRUCT::default();
             BeginPaint(window, &mut ps);
-            _ = on_paint(window, context);
+            let result = on_paint(window, context);
             _ = EndPaint(window, &ps);
+            recover_lost_device(window, &result, || {
+                context.recreate_device_resources(window)
+            });
             LRESULT(0)
         },
         WM_PRINTCLIENT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
             let context = &*raw;
-            _ = on_paint(window, context);
+            let dc = HDC(w_param.0 as _);
+            _ = print_client(window, &context.render_target, dc, || {
+                paint(window, context)
+            });
+            LRESULT(0)
+        },
+        WM_SET_VALUE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let value = match w_param.0 {
+                0 => None,
+                _ => Some(f32::from_bits(l_param.0 as u32)),
+            };
+            _ = on_set_value(window, context, value);
+            LRESULT(0)
+        },
+        WM_GETTEXT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return DefWindowProcW(window, message, w_param, l_param);
+            }
+            let state = &(*raw).state;
+            copy_window_text(&get_text(state.value, state.max), w_param, l_param)
+        },
+        WM_GETTEXTLENGTH => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return DefWindowProcW(window, message, w_param, l_param);
+            }
+            let state = &(*raw).state;
+            LRESULT(get_text(state.value, state.max).len() as isize)
+        },
+        WM_SIZE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                _ = on_size(window, &mut *raw);
+            }
             LRESULT(0)
         },
         WM_DPICHANGED_BEFOREPARENT => unsafe {
             let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
-            let context = &*raw;
+            let context = &mut *raw;
             _ = on_dpi_changed(window, context);
             LRESULT(0)
         },
         _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{client_size, pump_messages, window_at, window_text, Host};
+
+    fn text(value: Option<f32>, max: f32) -> String {
+        String::from_utf16(&get_text(value, max)).unwrap()
+    }
+
+    #[test]
+    fn get_text_formats_rounded_percentage() {
+        assert_eq!(text(Some(0f32), 1f32), "0%");
+        assert_eq!(text(Some(0.4), 1f32), "40%");
+        assert_eq!(text(Some(0.126), 1f32), "13%");
+        assert_eq!(text(Some(1f32), 1f32), "100%");
+        assert_eq!(text(Some(25f32), 50f32), "50%");
+    }
+
+    #[test]
+    fn get_text_clamps_value_to_range() {
+        assert_eq!(text(Some(-0.5), 1f32), "0%");
+        assert_eq!(text(Some(3f32), 1f32), "100%");
+    }
+
+    #[test]
+    fn get_text_is_empty_when_indeterminate() {
+        assert_eq!(text(None, 1f32), "");
+    }
+
+    #[test]
+    fn get_text_handles_non_positive_max() {
+        assert_eq!(text(Some(0f32), 0f32), "100%");
+        assert_eq!(text(Some(-1f32), 0f32), "0%");
+        assert_eq!(text(Some(-1f32), -2f32), "100%");
+        assert_eq!(text(Some(-3f32), -2f32), "0%");
+    }
+
+    #[test]
+    fn segment_bounds_leave_equal_gaps() {
+        assert_eq!(get_segment_bounds(100, 1, 2), vec![(0, 100)]);
+        assert_eq!(get_segment_bounds(102, 2, 2), vec![(0, 50), (52, 102)]);
+        assert_eq!(
+            get_segment_bounds(104, 3, 2),
+            vec![(0, 33), (35, 68), (70, 104)]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn window_text_is_the_percentage() {
+        let host = Host::new(300, 100);
+        let options = |value| ProgressBarOptions {
+            width: 200,
+            shape: Shape::Rounded,
+            corner: None,
+            value,
+            max: Some(2f32),
+            thickness: Thickness::Medium,
+            fill: Fill::Solid,
+            segments: None,
+            steps: None,
+            event: ProgressBarEvent::default(),
+        };
+        let determinate = host.create::<ProgressBar>(10, 10, options(Some(0.5)));
+        assert_eq!(window_text(determinate), "25%");
+        let indeterminate = host.create::<ProgressBar>(10, 40, options(None));
+        assert_eq!(window_text(indeterminate), "");
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn corners_and_gaps_reach_parent() {
+        let host = Host::new(300, 100);
+        let window = host.create::<ProgressBar>(
+            10,
+            10,
+            ProgressBarOptions {
+                width: 202,
+                shape: Shape::Rounded,
+                corner: Some(CornerRadius::Pill),
+                value: Some(0.5),
+                max: None,
+                thickness: Thickness::Large,
+                fill: Fill::Solid,
+                segments: Some(2),
+                steps: None,
+                event: ProgressBarEvent::default(),
+            },
+        );
+        let (width, height) = client_size(window);
+        let gap = Dpi::for_window(window).to_physical(SEGMENT_GAP);
+        let bounds = get_segment_bounds(width, 2, gap);
+        let gap_x = (bounds[0].1 + bounds[1].0) / 2;
+        assert_eq!(window_at(window, 0, 0), host.window);
+        assert_eq!(window_at(window, gap_x, height / 2), host.window);
+        assert_eq!(window_at(window, bounds[0].1 / 2, height / 2), window);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn external_resize_follows_the_new_size() {
+        let host = Host::new(400, 100);
+        let window = host.create::<ProgressBar>(
+            10,
+            10,
+            ProgressBarOptions {
+                width: 100,
+                shape: Shape::Rounded,
+                corner: None,
+                value: Some(1.0),
+                max: None,
+                thickness: Thickness::Large,
+                fill: Fill::Solid,
+                segments: None,
+                steps: None,
+                event: ProgressBarEvent::default(),
+            },
+        );
+        let (_, height) = client_size(window);
+        unsafe {
+            SetWindowPos(window, None, 0, 0, 300, height, SWP_NOMOVE | SWP_NOZORDER).unwrap();
+        }
+        pump_messages();
+        let context = unsafe { &*(GetWindowLongPtrW(window, GWLP_USERDATA) as *const Context) };
+        let size = unsafe { context.render_target.GetPixelSize() };
+        assert_eq!((size.width, size.height), (300, height as u32));
+        // The region grew with the window, so the far end takes clicks again.
+        assert_eq!(window_at(window, 300 - height, height / 2), window);
+    }
+}
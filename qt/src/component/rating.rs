@@ -0,0 +1,463 @@
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Foundation::Numerics::Matrix3x2;
+use windows::Win32::Foundation::{FALSE, HINSTANCE, HWND, LPARAM, LRESULT, TRUE, WPARAM};
+use windows::Win32::Graphics::Direct2D::Common::{
+    D2D1_COLOR_F, D2D_RECT_F, D2D_SIZE_F, D2D_SIZE_U,
+};
+use windows::Win32::Graphics::Direct2D::{
+    D2D1CreateFactory, ID2D1DeviceContext5, ID2D1Factory1, ID2D1HwndRenderTarget,
+    ID2D1SvgAttribute, ID2D1SvgDocument, D2D1_ANTIALIAS_MODE_ALIASED, D2D1_FACTORY_OPTIONS,
+    D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_HWND_RENDER_TARGET_PROPERTIES,
+    D2D1_SVG_PAINT_TYPE_COLOR,
+};
+use windows::Win32::Graphics::Gdi::{BeginPaint, EndPaint, InvalidateRect, HDC, PAINTSTRUCT};
+use windows::Win32::UI::Controls::WM_MOUSELEAVE;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    SetFocus, TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT, VIRTUAL_KEY, VK_DOWN, VK_END, VK_HOME,
+    VK_LEFT, VK_RIGHT, VK_UP,
+};
+use windows::Win32::UI::Shell::SHCreateMemStream;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::component::Component;
+use crate::icon::Icon;
+use crate::{
+    handle_disabled_mouse, print_client, recover_lost_device, recreate_render_target,
+    render_target_properties, window_class_name, Dpi, QT,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Size {
+    Small,
+    Medium,
+    Large,
+    ExtraLarge,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    Whole,
+    Half,
+}
+
+pub type ChangeCallback = Box<dyn Fn(&HWND, f32)>;
+
+pub struct RatingEvent {
+    pub on_change: ChangeCallback,
+}
+
+impl Default for RatingEvent {
+    fn default() -> Self {
+        RatingEvent {
+            on_change: Box::new(|_window, _value| {}),
+        }
+    }
+}
+
+pub struct Rating;
+
+pub struct RatingOptions {
+    pub max: u32,
+    pub value: f32,
+    pub step: Step,
+    pub size: Size,
+    pub event: RatingEvent,
+}
+
+impl Component for Rating {
+    type Options = RatingOptions;
+
+    fn create(
+        qt: &QT,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: RatingOptions,
+    ) -> Result<HWND> {
+        qt.create_rating(parent_window, x, y, options)
+    }
+}
+
+struct State {
+    qt: QT,
+    max: u32,
+    value: f32,
+    step: Step,
+    size: Size,
+    event: RatingEvent,
+}
+
+impl State {
+    fn get_star_size(&self) -> f32 {
+        match self.size {
+            Size::Small => 12f32,
+            Size::Medium => 16f32,
+            Size::Large => 20f32,
+            Size::ExtraLarge => 28f32,
+        }
+    }
+
+    fn get_gap(&self) -> f32 {
+        self.qt.theme.tokens.spacing_horizontal_xxs
+    }
+
+    fn get_width(&self) -> f32 {
+        let max = self.max as f32;
+        self.get_star_size() * max + self.get_gap() * (max - 1f32).max(0f32)
+    }
+
+    fn get_step(&self) -> f32 {
+        match self.step {
+            Step::Whole => 1f32,
+            Step::Half => 0.5f32,
+        }
+    }
+
+    fn snap(&self, value: f32) -> f32 {
+        let step = self.get_step();
+        ((value / step).round() * step).clamp(0f32, self.max as f32)
+    }
+}
+
+struct Context {
+    state: State,
+    render_target: ID2D1HwndRenderTarget,
+    regular_svg: ID2D1SvgDocument,
+    filled_svg: ID2D1SvgDocument,
+    hover_value: Option<f32>,
+    mouse_within: bool,
+}
+
+impl Context {
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        (self.regular_svg, self.filled_svg) = create_star_svgs(&render_target, &self.state)?;
+        self.render_target = render_target;
+        Ok(())
+    }
+}
+
+impl QT {
+    pub fn create_rating(
+        &self,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: RatingOptions,
+    ) -> Result<HWND> {
+        let class_name = window_class_name("QT_RATING", Some(window_proc));
+        unsafe {
+            let window_class = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                style: CS_CLASSDC,
+                lpfnWndProc: Some(window_proc),
+                hCursor: LoadCursorW(None, IDC_HAND)?,
+                ..Default::default()
+            };
+            RegisterClassExW(&window_class);
+            let mut state = State {
+                qt: self.clone(),
+                max: options.max,
+                value: 0f32,
+                step: options.step,
+                size: options.size,
+                event: options.event,
+            };
+            state.value = state.snap(options.value);
+            let boxed = Box::new(state);
+            let dpi = Dpi::for_window(parent_window);
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                w!(""),
+                WS_TABSTOP | WS_VISIBLE | WS_CHILD,
+                x,
+                y,
+                dpi.to_physical_ceil(boxed.get_width()),
+                dpi.to_physical_ceil(boxed.get_star_size()),
+                Some(parent_window),
+                None,
+                Some(HINSTANCE(
+                    GetWindowLongPtrW(parent_window, GWLP_HINSTANCE) as _
+                )),
+                Some(Box::<State>::into_raw(boxed) as _),
+            )
+        }
+    }
+}
+
+unsafe fn create_svg(
+    render_target: &ID2D1HwndRenderTarget,
+    icon: &Icon,
+    color: &D2D1_COLOR_F,
+) -> Result<ID2D1SvgDocument> {
+    let stream = SHCreateMemStream(Some(icon.svg_bytes())).ok_or(Error::empty())?;
+    let svg = render_target
+        .cast::<ID2D1DeviceContext5>()?
+        .CreateSvgDocument(
+            &stream,
+            D2D_SIZE_F {
+                width: icon.size as f32,
+                height: icon.size as f32,
+            },
+        )?;
+    let svg_paint = svg.CreatePaint(D2D1_SVG_PAINT_TYPE_COLOR, Some(color), w!(""))?;
+    svg.GetRoot()?
+        .GetFirstChild()?
+        .SetAttributeValue(w!("fill"), &svg_paint.cast::<ID2D1SvgAttribute>()?)?;
+    Ok(svg)
+}
+
+unsafe fn create_star_svgs(
+    render_target: &ID2D1HwndRenderTarget,
+    state: &State,
+) -> Result<(ID2D1SvgDocument, ID2D1SvgDocument)> {
+    let tokens = &state.qt.theme.tokens;
+    let regular_svg = create_svg(
+        render_target,
+        &Icon::star_regular(),
+        &tokens.color_neutral_stroke_accessible,
+    )?;
+    let filled_svg = create_svg(
+        render_target,
+        &Icon::star_filled(),
+        &tokens.color_compound_brand_background,
+    )?;
+    Ok((regular_svg, filled_svg))
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        Some(&D2D1_FACTORY_OPTIONS::default()),
+    )?;
+    let dpi = Dpi::for_window(window);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U {
+                width: dpi.to_physical_ceil(state.get_width()) as u32,
+                height: dpi.to_physical_ceil(state.get_star_size()) as u32,
+            },
+            presentOptions: Default::default(),
+        },
+    )?;
+    let (regular_svg, filled_svg) = create_star_svgs(&render_target, &state)?;
+    Ok(Context {
+        state,
+        render_target,
+        regular_svg,
+        filled_svg,
+        hover_value: None,
+        mouse_within: false,
+    })
+}
+
+unsafe fn paint(context: &Context) -> Result<()> {
+    let state = &context.state;
+    let tokens = &state.qt.theme.tokens;
+    context
+        .render_target
+        .Clear(Some(&tokens.color_neutral_background1));
+
+    let device_context5 = context.render_target.cast::<ID2D1DeviceContext5>()?;
+    let star_size = state.get_star_size();
+    let value = context.hover_value.unwrap_or(state.value);
+    let viewport_size = context.regular_svg.GetViewportSize();
+    let scale = star_size / viewport_size.width;
+    for index in 0..state.max {
+        let left = (star_size + state.get_gap()) * index as f32;
+        let fill = (value - index as f32).clamp(0f32, 1f32);
+        device_context5.SetTransform(
+            &(Matrix3x2 {
+                M11: scale,
+                M22: scale,
+                ..Matrix3x2::identity()
+            } * Matrix3x2::translation(left, 0f32)),
+        );
+        if fill >= 1f32 {
+            device_context5.DrawSvgDocument(&context.filled_svg);
+            continue;
+        }
+        device_context5.DrawSvgDocument(&context.regular_svg);
+        if fill >= 0.5f32 {
+            // The clip is set in the star's own coordinates, so half the viewport is half a star.
+            device_context5.PushAxisAlignedClip(
+                &D2D_RECT_F {
+                    left: 0f32,
+                    top: 0f32,
+                    right: viewport_size.width / 2f32,
+                    bottom: viewport_size.height,
+                },
+                D2D1_ANTIALIAS_MODE_ALIASED,
+            );
+            device_context5.DrawSvgDocument(&context.filled_svg);
+            device_context5.PopAxisAlignedClip();
+        }
+    }
+    device_context5.SetTransform(&Matrix3x2::identity());
+    Ok(())
+}
+
+unsafe fn on_paint(context: &Context) -> Result<()> {
+    context.render_target.BeginDraw();
+    let result = paint(context);
+    context.render_target.EndDraw(None, None).and(result)
+}
+
+fn get_value_at(state: &State, window: HWND, mouse_x: i32) -> f32 {
+    let x = Dpi::for_window(window).to_dip(mouse_x).max(0f32);
+    let star_size = state.get_star_size();
+    let pitch = star_size + state.get_gap();
+    let index = (x / pitch).floor();
+    let offset = x - index * pitch;
+    let value = match state.step {
+        Step::Half if offset < star_size / 2f32 => index + 0.5f32,
+        _ => index + 1f32,
+    };
+    value.min(state.max as f32)
+}
+
+unsafe fn set_hover_value(window: HWND, context: &mut Context, hover_value: Option<f32>) {
+    if context.hover_value != hover_value {
+        context.hover_value = hover_value;
+        _ = InvalidateRect(Some(window), None, false);
+    }
+}
+
+unsafe fn set_value(window: HWND, context: &mut Context, value: f32) {
+    let value = context.state.snap(value);
+    if context.state.value != value {
+        context.state.value = value;
+        _ = InvalidateRect(Some(window), None, false);
+        (context.state.event.on_change)(&window, value);
+    }
+}
+
+unsafe fn on_key_down(window: HWND, context: &mut Context, key: u16) {
+    let step = context.state.get_step();
+    let value = context.state.value;
+    match VIRTUAL_KEY(key) {
+        VK_LEFT | VK_DOWN => set_value(window, context, value - step),
+        VK_RIGHT | VK_UP => set_value(window, context, value + step),
+        VK_HOME => set_value(window, context, 0f32),
+        VK_END => set_value(window, context, context.state.max as f32),
+        _ => {}
+    }
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    if let Some(result) = unsafe { handle_disabled_mouse(window, message) } {
+        return result;
+    }
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut State;
+            let state = Box::<State>::from_raw(raw);
+            match on_create(window, *state) {
+                Ok(context) => {
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(FALSE.0 as isize),
+            }
+        },
+        WM_DESTROY => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                _ = Box::<Context>::from_raw(raw);
+            }
+            LRESULT(0)
+        },
+        WM_PAINT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let mut ps = PAINTSTRUCT::default();
+            BeginPaint(window, &mut ps);
+            let result = on_paint(context);
+            _ = EndPaint(window, &ps);
+            recover_lost_device(window, &result, || {
+                context.recreate_device_resources(window)
+            });
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            let dc = HDC(w_param.0 as _);
+            _ = print_client(window, &context.render_target, dc, || paint(context));
+            LRESULT(0)
+        },
+        WM_DPICHANGED_BEFOREPARENT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            let dpi = Dpi::for_window(window);
+            let width = dpi.to_physical_ceil(context.state.get_width());
+            let height = dpi.to_physical_ceil(context.state.get_star_size());
+            _ = SetWindowPos(window, None, 0, 0, width, height, SWP_NOMOVE | SWP_NOZORDER);
+            context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+            _ = context.render_target.Resize(&D2D_SIZE_U {
+                width: width as u32,
+                height: height as u32,
+            });
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_GETDLGCODE => LRESULT(DLGC_WANTARROWS as isize),
+        WM_MOUSEMOVE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            if !context.mouse_within {
+                context.mouse_within = true;
+                let mut tme = TRACKMOUSEEVENT {
+                    cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
+                    dwFlags: TME_LEAVE,
+                    hwndTrack: window,
+                    dwHoverTime: 0,
+                };
+                _ = TrackMouseEvent(&mut tme);
+            }
+            let mouse_x = l_param.0 as i16 as i32;
+            let value = get_value_at(&context.state, window, mouse_x);
+            set_hover_value(window, context, Some(value));
+            LRESULT(0)
+        },
+        WM_MOUSELEAVE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            context.mouse_within = false;
+            set_hover_value(window, context, None);
+            LRESULT(0)
+        },
+        WM_LBUTTONDOWN => unsafe {
+            _ = SetFocus(Some(window));
+            LRESULT(0)
+        },
+        WM_LBUTTONUP => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let mouse_x = l_param.0 as i16 as i32;
+            let value = get_value_at(&context.state, window, mouse_x);
+            set_value(window, context, value);
+            LRESULT(0)
+        },
+        WM_KEYDOWN => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            on_key_down(window, &mut *raw, w_param.0 as u16);
+            LRESULT(0)
+        },
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
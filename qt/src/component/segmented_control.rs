@@ -0,0 +1,603 @@
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Win32::Foundation::{
+    E_INVALIDARG, FALSE, HINSTANCE, HWND, LPARAM, LRESULT, TRUE, WPARAM,
+};
+use windows::Win32::Graphics::Direct2D::Common::{D2D_POINT_2F, D2D_RECT_F, D2D_SIZE_U};
+use windows::Win32::Graphics::Direct2D::{
+    D2D1CreateFactory, ID2D1Factory1, ID2D1HwndRenderTarget, ID2D1SolidColorBrush,
+    D2D1_DRAW_TEXT_OPTIONS_NONE, D2D1_FACTORY_OPTIONS, D2D1_FACTORY_TYPE_SINGLE_THREADED,
+    D2D1_HWND_RENDER_TARGET_PROPERTIES, D2D1_ROUNDED_RECT,
+};
+use windows::Win32::Graphics::DirectWrite::{
+    DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, DWRITE_FACTORY_TYPE_SHARED,
+    DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL, DWRITE_MEASURING_MODE_NATURAL,
+    DWRITE_PARAGRAPH_ALIGNMENT_CENTER, DWRITE_TEXT_ALIGNMENT_CENTER, DWRITE_TEXT_METRICS,
+};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, CreateRoundRectRgn, EndPaint, InvalidateRect, SetWindowRgn, HDC, PAINTSTRUCT,
+};
+use windows::Win32::UI::Controls::WM_MOUSELEAVE;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    SetFocus, TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT, VIRTUAL_KEY, VK_END, VK_HOME, VK_LEFT,
+    VK_RIGHT,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::component::Component;
+use crate::theme::Tokens;
+use crate::{
+    copy_window_text, handle_disabled_mouse, is_focus_visible, print_client, recover_lost_device,
+    recreate_render_target, render_target_properties, track_input_kind, window_class_name, Dpi, QT,
+};
+
+const HEIGHT: f32 = 32f32;
+
+pub type ChangeCallback = Box<dyn Fn(&HWND, u32)>;
+
+pub struct SegmentedControlEvent {
+    /// Fired with the command id of the newly selected segment.
+    pub on_change: ChangeCallback,
+}
+
+impl Default for SegmentedControlEvent {
+    fn default() -> Self {
+        SegmentedControlEvent {
+            on_change: Box::new(|_window, _command_id| {}),
+        }
+    }
+}
+
+pub struct SegmentedControl;
+
+pub struct SegmentedControlOptions {
+    pub items: Vec<(PCWSTR, u32)>,
+    pub selected_index: usize,
+    pub event: SegmentedControlEvent,
+}
+
+impl Component for SegmentedControl {
+    type Options = SegmentedControlOptions;
+
+    fn create(
+        qt: &QT,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: SegmentedControlOptions,
+    ) -> Result<HWND> {
+        qt.create_segmented_control(
+            parent_window,
+            x,
+            y,
+            options.items,
+            options.selected_index,
+            options.event,
+        )
+    }
+}
+
+struct Segment {
+    text: PCWSTR,
+    command_id: u32,
+    left: f32,
+    right: f32,
+}
+
+struct State {
+    qt: QT,
+    segments: Vec<Segment>,
+    selected_index: usize,
+    event: SegmentedControlEvent,
+}
+
+impl State {
+    fn get_width(&self) -> f32 {
+        self.segments.last().map_or(0f32, |segment| segment.right)
+    }
+}
+
+struct Context {
+    state: State,
+    render_target: ID2D1HwndRenderTarget,
+    text_format: IDWriteTextFormat,
+    brushes: Brushes,
+    hover_index: Option<usize>,
+    mouse_within: bool,
+    mouse_clicking: bool,
+    is_focused: bool,
+    focus_visible: bool,
+}
+
+// They belong to the render target, so they are recreated with it.
+struct Brushes {
+    text: ID2D1SolidColorBrush,
+    background: ID2D1SolidColorBrush,
+    background_hover: ID2D1SolidColorBrush,
+    background_selected: ID2D1SolidColorBrush,
+    stroke: ID2D1SolidColorBrush,
+    focus: ID2D1SolidColorBrush,
+}
+
+impl Brushes {
+    unsafe fn new(render_target: &ID2D1HwndRenderTarget, tokens: &Tokens) -> Result<Self> {
+        Ok(Brushes {
+            text: render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground1, None)?,
+            background: render_target
+                .CreateSolidColorBrush(&tokens.color_neutral_background1, None)?,
+            background_hover: render_target
+                .CreateSolidColorBrush(&tokens.color_neutral_background1_hover, None)?,
+            background_selected: render_target
+                .CreateSolidColorBrush(&tokens.color_neutral_background1_selected, None)?,
+            stroke: render_target.CreateSolidColorBrush(&tokens.color_neutral_stroke1, None)?,
+            focus: render_target.CreateSolidColorBrush(&tokens.color_stroke_focus2, None)?,
+        })
+    }
+}
+
+impl Context {
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        self.brushes = Brushes::new(&render_target, &self.state.qt.theme.tokens)?;
+        self.render_target = render_target;
+        Ok(())
+    }
+}
+
+impl QT {
+    /// A row of connected toggle buttons, of which exactly one is selected. Each item is its text
+    /// and the command id reported by `on_change`.
+    pub fn create_segmented_control(
+        &self,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        items: Vec<(PCWSTR, u32)>,
+        selected_index: usize,
+        event: SegmentedControlEvent,
+    ) -> Result<HWND> {
+        if items.is_empty() {
+            return Err(E_INVALIDARG.into());
+        }
+        let class_name = window_class_name("QT_SEGMENTED_CONTROL", Some(window_proc));
+        unsafe {
+            let window_class = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                style: CS_CLASSDC,
+                lpfnWndProc: Some(window_proc),
+                hCursor: LoadCursorW(None, IDC_ARROW)?,
+                ..Default::default()
+            };
+            RegisterClassExW(&window_class);
+            let segments = layout_segments(self, &items)?;
+            let boxed = Box::new(State {
+                qt: self.clone(),
+                selected_index: selected_index.min(segments.len() - 1),
+                segments,
+                event,
+            });
+            let dpi = Dpi::for_window(parent_window);
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                w!(""),
+                WS_TABSTOP | WS_VISIBLE | WS_CHILD,
+                x,
+                y,
+                dpi.to_physical_ceil(boxed.get_width()),
+                dpi.to_physical_ceil(HEIGHT),
+                Some(parent_window),
+                None,
+                Some(HINSTANCE(
+                    GetWindowLongPtrW(parent_window, GWLP_HINSTANCE) as _
+                )),
+                Some(Box::<State>::into_raw(boxed) as _),
+            )
+        }
+    }
+}
+
+unsafe fn create_text_format(qt: &QT) -> Result<IDWriteTextFormat> {
+    let tokens = &qt.theme.tokens;
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_format = direct_write_factory.CreateTextFormat(
+        tokens.font_family_base,
+        None,
+        tokens.font_weight_semibold,
+        DWRITE_FONT_STYLE_NORMAL,
+        DWRITE_FONT_STRETCH_NORMAL,
+        tokens.font_size_base300,
+        w!(""),
+    )?;
+    text_format.SetTextAlignment(DWRITE_TEXT_ALIGNMENT_CENTER)?;
+    text_format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+    Ok(text_format)
+}
+
+// Each segment is as wide as its text plus the medium button padding, in DIPs.
+unsafe fn layout_segments(qt: &QT, items: &[(PCWSTR, u32)]) -> Result<Vec<Segment>> {
+    let tokens = &qt.theme.tokens;
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_format = create_text_format(qt)?;
+    let mut left = 0f32;
+    let mut segments = Vec::with_capacity(items.len());
+    for (text, command_id) in items {
+        let text_layout = direct_write_factory.CreateTextLayout(
+            text.as_wide(),
+            &text_format,
+            f32::MAX,
+            HEIGHT,
+        )?;
+        let mut metrics = DWRITE_TEXT_METRICS::default();
+        text_layout.GetMetrics(&mut metrics)?;
+        let right = left + metrics.width.ceil() + tokens.spacing_horizontal_m * 2f32;
+        segments.push(Segment {
+            text: *text,
+            command_id: *command_id,
+            left,
+            right,
+        });
+        left = right;
+    }
+    Ok(segments)
+}
+
+// Only the outer corners are rounded; the segments meet at straight edges.
+unsafe fn set_region(window: HWND, context: &Context) {
+    let dpi = Dpi::for_window(window);
+    let corner_diameter =
+        dpi.to_physical(context.state.qt.theme.tokens.border_radius_medium * 2f32);
+    let region = CreateRoundRectRgn(
+        0,
+        0,
+        dpi.to_physical_ceil(context.state.get_width()) + 1,
+        dpi.to_physical_ceil(HEIGHT) + 1,
+        corner_diameter,
+        corner_diameter,
+    );
+    SetWindowRgn(window, Some(region), true);
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        Some(&D2D1_FACTORY_OPTIONS::default()),
+    )?;
+    let dpi = Dpi::for_window(window);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U {
+                width: dpi.to_physical_ceil(state.get_width()) as u32,
+                height: dpi.to_physical_ceil(HEIGHT) as u32,
+            },
+            presentOptions: Default::default(),
+        },
+    )?;
+    let tokens = &state.qt.theme.tokens;
+    let text_format = create_text_format(&state.qt)?;
+    let brushes = Brushes::new(&render_target, tokens)?;
+    let context = Context {
+        state,
+        render_target,
+        text_format,
+        brushes,
+        hover_index: None,
+        mouse_within: false,
+        mouse_clicking: false,
+        is_focused: false,
+        focus_visible: false,
+    };
+    set_region(window, &context);
+    Ok(context)
+}
+
+unsafe fn paint(context: &Context) -> Result<()> {
+    let state = &context.state;
+    let tokens = &state.qt.theme.tokens;
+    let render_target = &context.render_target;
+    render_target.Clear(Some(&tokens.color_neutral_background1));
+
+    let stroke_width = tokens.stroke_width_thin;
+    let width = state.get_width();
+    for (index, segment) in state.segments.iter().enumerate() {
+        let brush = if index == state.selected_index {
+            &context.brushes.background_selected
+        } else if context.hover_index == Some(index) {
+            &context.brushes.background_hover
+        } else {
+            &context.brushes.background
+        };
+        let segment_rect = D2D_RECT_F {
+            left: segment.left,
+            top: 0f32,
+            right: segment.right,
+            bottom: HEIGHT,
+        };
+        render_target.FillRectangle(&segment_rect, brush);
+        render_target.DrawText(
+            segment.text.as_wide(),
+            &context.text_format,
+            &segment_rect,
+            &context.brushes.text,
+            D2D1_DRAW_TEXT_OPTIONS_NONE,
+            DWRITE_MEASURING_MODE_NATURAL,
+        );
+        // Adjacent segments share one divider instead of each drawing its own border.
+        if index > 0 {
+            render_target.DrawLine(
+                D2D_POINT_2F {
+                    x: segment.left,
+                    y: 0f32,
+                },
+                D2D_POINT_2F {
+                    x: segment.left,
+                    y: HEIGHT,
+                },
+                &context.brushes.stroke,
+                stroke_width,
+                None,
+            );
+        }
+    }
+    let corner_radius = tokens.border_radius_medium;
+    render_target.DrawRoundedRectangle(
+        &D2D1_ROUNDED_RECT {
+            rect: D2D_RECT_F {
+                left: stroke_width * 0.5,
+                top: stroke_width * 0.5,
+                right: width - stroke_width * 0.5,
+                bottom: HEIGHT - stroke_width * 0.5,
+            },
+            radiusX: corner_radius,
+            radiusY: corner_radius,
+        },
+        &context.brushes.stroke,
+        stroke_width,
+        None,
+    );
+
+    if context.is_focused && context.focus_visible {
+        let segment = &state.segments[state.selected_index];
+        let focus_width = stroke_width * 2f32;
+        render_target.DrawRoundedRectangle(
+            &D2D1_ROUNDED_RECT {
+                rect: D2D_RECT_F {
+                    left: segment.left + focus_width * 0.5,
+                    top: focus_width * 0.5,
+                    right: segment.right - focus_width * 0.5,
+                    bottom: HEIGHT - focus_width * 0.5,
+                },
+                radiusX: corner_radius,
+                radiusY: corner_radius,
+            },
+            &context.brushes.focus,
+            focus_width,
+            None,
+        );
+    }
+    Ok(())
+}
+
+unsafe fn on_paint(context: &Context) -> Result<()> {
+    context.render_target.BeginDraw();
+    let result = paint(context);
+    context.render_target.EndDraw(None, None).and(result)
+}
+
+fn get_index_at(state: &State, window: HWND, mouse_x: i32) -> Option<usize> {
+    let x = Dpi::for_window(window).to_dip(mouse_x);
+    state
+        .segments
+        .iter()
+        .position(|segment| x >= segment.left && x < segment.right)
+}
+
+unsafe fn set_hover_index(window: HWND, context: &mut Context, hover_index: Option<usize>) {
+    if context.hover_index != hover_index {
+        context.hover_index = hover_index;
+        _ = InvalidateRect(Some(window), None, false);
+    }
+}
+
+unsafe fn select(window: HWND, context: &mut Context, index: usize) {
+    if index >= context.state.segments.len() || context.state.selected_index == index {
+        return;
+    }
+    context.state.selected_index = index;
+    _ = InvalidateRect(Some(window), None, false);
+    let command_id = context.state.segments[index].command_id;
+    (context.state.event.on_change)(&window, command_id);
+}
+
+unsafe fn on_key_down(window: HWND, context: &mut Context, key: u16) {
+    let selected_index = context.state.selected_index;
+    let last_index = context.state.segments.len() - 1;
+    context.focus_visible = true;
+    match VIRTUAL_KEY(key) {
+        VK_LEFT => select(window, context, selected_index.saturating_sub(1)),
+        VK_RIGHT => select(window, context, (selected_index + 1).min(last_index)),
+        VK_HOME => select(window, context, 0),
+        VK_END => select(window, context, last_index),
+        _ => {}
+    }
+    _ = InvalidateRect(Some(window), None, false);
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    if let Some(result) = unsafe { handle_disabled_mouse(window, message) } {
+        return result;
+    }
+    track_input_kind(message);
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut State;
+            let state = Box::<State>::from_raw(raw);
+            match on_create(window, *state) {
+                Ok(context) => {
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(FALSE.0 as isize),
+            }
+        },
+        WM_DESTROY => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                _ = Box::<Context>::from_raw(raw);
+            }
+            LRESULT(0)
+        },
+        WM_PAINT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let mut ps = PAINTSTRUCT::default();
+            BeginPaint(window, &mut ps);
+            let result = on_paint(context);
+            _ = EndPaint(window, &ps);
+            recover_lost_device(window, &result, || {
+                context.recreate_device_resources(window)
+            });
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            let dc = HDC(w_param.0 as _);
+            _ = print_client(window, &context.render_target, dc, || paint(context));
+            LRESULT(0)
+        },
+        WM_DPICHANGED_BEFOREPARENT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            let dpi = Dpi::for_window(window);
+            let width = dpi.to_physical_ceil(context.state.get_width());
+            let height = dpi.to_physical_ceil(HEIGHT);
+            _ = SetWindowPos(window, None, 0, 0, width, height, SWP_NOMOVE | SWP_NOZORDER);
+            context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+            _ = context.render_target.Resize(&D2D_SIZE_U {
+                width: width as u32,
+                height: height as u32,
+            });
+            set_region(window, context);
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_GETDLGCODE => LRESULT(DLGC_WANTARROWS as isize),
+        WM_GETTEXT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return DefWindowProcW(window, message, w_param, l_param);
+            }
+            let state = &(*raw).state;
+            let text = state.segments[state.selected_index].text.as_wide();
+            copy_window_text(text, w_param, l_param)
+        },
+        WM_GETTEXTLENGTH => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return DefWindowProcW(window, message, w_param, l_param);
+            }
+            let state = &(*raw).state;
+            LRESULT(state.segments[state.selected_index].text.len() as isize)
+        },
+        WM_MOUSEMOVE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            if !context.mouse_within {
+                context.mouse_within = true;
+                let mut tme = TRACKMOUSEEVENT {
+                    cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
+                    dwFlags: TME_LEAVE,
+                    hwndTrack: window,
+                    dwHoverTime: 0,
+                };
+                _ = TrackMouseEvent(&mut tme);
+            }
+            let mouse_x = l_param.0 as i16 as i32;
+            let index = get_index_at(&context.state, window, mouse_x);
+            set_hover_index(window, context, index);
+            LRESULT(0)
+        },
+        WM_MOUSELEAVE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            context.mouse_within = false;
+            set_hover_index(window, context, None);
+            LRESULT(0)
+        },
+        WM_LBUTTONDOWN => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            (*raw).mouse_clicking = true;
+            _ = SetFocus(Some(window));
+            (*raw).mouse_clicking = false;
+            LRESULT(0)
+        },
+        WM_LBUTTONUP => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let mouse_x = l_param.0 as i16 as i32;
+            if let Some(index) = get_index_at(&context.state, window, mouse_x) {
+                select(window, context, index);
+            }
+            LRESULT(0)
+        },
+        WM_SETFOCUS => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            // The focus ring is only for keyboard users.
+            (*raw).is_focused = true;
+            (*raw).focus_visible = is_focus_visible();
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_KILLFOCUS => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            (*raw).is_focused = false;
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_KEYDOWN => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            on_key_down(window, &mut *raw, w_param.0 as u16);
+            LRESULT(0)
+        },
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{window_text, Host};
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn window_text_is_the_selected_segment() {
+        let host = Host::new(400, 100);
+        let window = host.create::<SegmentedControl>(
+            10,
+            10,
+            SegmentedControlOptions {
+                items: vec![(w!("Day"), 1), (w!("Week"), 2), (w!("Month"), 3)],
+                selected_index: 1,
+                event: SegmentedControlEvent::default(),
+            },
+        );
+        assert_eq!(window_text(window), "Week");
+        unsafe {
+            SendMessageW(window, WM_KEYDOWN, Some(WPARAM(VK_RIGHT.0 as usize)), None);
+        }
+        assert_eq!(window_text(window), "Month");
+    }
+}
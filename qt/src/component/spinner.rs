@@ -0,0 +1,631 @@
+use std::f32::consts::PI;
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Win32::Foundation::{FALSE, HINSTANCE, HWND, LPARAM, LRESULT, RECT, TRUE, WPARAM};
+use windows::Win32::Graphics::Direct2D::Common::{
+    D2D1_FIGURE_BEGIN_HOLLOW, D2D1_FIGURE_END_OPEN, D2D_POINT_2F, D2D_SIZE_F, D2D_SIZE_U,
+};
+use windows::Win32::Graphics::Direct2D::{
+    D2D1CreateFactory, ID2D1Factory1, ID2D1HwndRenderTarget, ID2D1StrokeStyle1, D2D1_ARC_SEGMENT,
+    D2D1_ARC_SIZE_LARGE, D2D1_ARC_SIZE_SMALL, D2D1_CAP_STYLE_ROUND, D2D1_DRAW_TEXT_OPTIONS_NONE,
+    D2D1_ELLIPSE, D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_HWND_RENDER_TARGET_PROPERTIES,
+    D2D1_STROKE_STYLE_PROPERTIES1, D2D1_SWEEP_DIRECTION_CLOCKWISE,
+};
+use windows::Win32::Graphics::DirectWrite::{
+    DWriteCreateFactory, IDWriteFactory, IDWriteTextLayout, DWRITE_FACTORY_TYPE_SHARED,
+    DWRITE_TEXT_METRICS,
+};
+use windows::Win32::Graphics::Gdi::{BeginPaint, EndPaint, InvalidateRect, HDC, PAINTSTRUCT};
+use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+use windows::Win32::UI::Animation::{
+    IUIAnimationManager2, IUIAnimationStoryboard2, IUIAnimationTimerEventHandler,
+    IUIAnimationTimerEventHandler_Impl, IUIAnimationTransitionLibrary2, IUIAnimationVariable2,
+    UIAnimationTransitionLibrary2, UI_ANIMATION_STORYBOARD_FINISHED,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::animation::{invalidate_on_next_frame, is_reduced_motion, AnimationMode};
+use crate::component::Component;
+use crate::{
+    print_client, recover_lost_device, recreate_render_target, render_target_properties,
+    window_class_name, Dpi, QT,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Size {
+    ExtraSmall,
+    Small,
+    Medium,
+    Large,
+}
+
+impl Size {
+    fn get_diameter(&self) -> f32 {
+        match self {
+            Size::ExtraSmall => 16f32,
+            Size::Small => 24f32,
+            Size::Medium => 32f32,
+            Size::Large => 40f32,
+        }
+    }
+
+    fn get_stroke_width(&self) -> f32 {
+        match self {
+            Size::ExtraSmall | Size::Small => 2f32,
+            Size::Medium | Size::Large => 3f32,
+        }
+    }
+}
+
+pub struct Spinner;
+
+pub struct SpinnerOptions {
+    pub size: Size,
+    pub label: Option<PCWSTR>,
+}
+
+impl Component for Spinner {
+    type Options = SpinnerOptions;
+
+    fn create(
+        qt: &QT,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        options: SpinnerOptions,
+    ) -> Result<HWND> {
+        qt.create_spinner(parent_window, x, y, &options.size, options.label)
+    }
+}
+
+pub struct State {
+    qt: QT,
+    size: Size,
+    label: Option<HSTRING>,
+}
+
+impl State {
+    fn get_label_spacing(&self) -> f32 {
+        let tokens = &self.qt.theme.tokens;
+        match self.size {
+            Size::ExtraSmall | Size::Small => tokens.spacing_horizontal_s,
+            Size::Medium | Size::Large => tokens.spacing_horizontal_m,
+        }
+    }
+}
+
+pub struct Context {
+    state: State,
+    factory: ID2D1Factory1,
+    render_target: ID2D1HwndRenderTarget,
+    stroke_style: ID2D1StrokeStyle1,
+    label_layout: Option<IDWriteTextLayout>,
+    animation_manager: IUIAnimationManager2,
+    transition_library: IUIAnimationTransitionLibrary2,
+    rotation: IUIAnimationVariable2,
+    sweep: IUIAnimationVariable2,
+    /// Restarted whenever it finishes, so the ring keeps turning.
+    storyboard: Option<IUIAnimationStoryboard2>,
+}
+
+impl Context {
+    // Brushes are created on every paint, so only the render target needs replacing.
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        self.render_target = recreate_render_target(window, &self.render_target)?;
+        Ok(())
+    }
+}
+
+// One full turn of the ring, during which the tail grows and shrinks back once.
+const PERIOD: f64 = 1.5;
+const SHORT_SWEEP: f64 = 45.0;
+const LONG_SWEEP: f64 = 270.0;
+
+impl QT {
+    pub fn create_spinner(
+        &self,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        size: &Size,
+        label: Option<PCWSTR>,
+    ) -> Result<HWND> {
+        let class_name = window_class_name("QT_SPINNER", Some(window_proc));
+        unsafe {
+            let window_class = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                style: CS_CLASSDC,
+                lpfnWndProc: Some(window_proc),
+                hCursor: LoadCursorW(None, IDC_ARROW)?,
+                ..Default::default()
+            };
+            RegisterClassExW(&window_class);
+            let dpi = Dpi::for_window(parent_window);
+            let diameter = dpi.to_physical(size.get_diameter());
+            let boxed = Box::new(State {
+                qt: self.clone(),
+                size: *size,
+                label: label.map(|label| label.to_hstring()),
+            });
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                label.unwrap_or(w!("")),
+                WS_VISIBLE | WS_CHILD,
+                x,
+                y,
+                diameter,
+                diameter,
+                Some(parent_window),
+                None,
+                Some(HINSTANCE(
+                    GetWindowLongPtrW(parent_window, GWLP_HINSTANCE) as _
+                )),
+                Some(Box::<State>::into_raw(boxed) as _),
+            )
+        }
+    }
+}
+
+#[implement(IUIAnimationTimerEventHandler)]
+struct AnimationTimerEventHandler {
+    window: HWND,
+}
+
+impl IUIAnimationTimerEventHandler_Impl for AnimationTimerEventHandler_Impl {
+    fn OnPreUpdate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn OnPostUpdate(&self) -> Result<()> {
+        invalidate_on_next_frame(self.window);
+        unsafe {
+            let raw = GetWindowLongPtrW(self.window, GWLP_USERDATA) as *mut Context;
+            if raw.is_null() {
+                return Ok(());
+            }
+            let context = &mut *raw;
+            let is_finished = match &context.storyboard {
+                Some(storyboard) => storyboard.GetStatus()? == UI_ANIMATION_STORYBOARD_FINISHED,
+                None => false,
+            };
+            if is_finished {
+                start_animation(self.window, context)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn OnRenderingTooSlow(&self, _frames_per_second: u32) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Holds the ring still, with a long tail, while the user has turned off animations.
+unsafe fn start_animation(window: HWND, context: &mut Context) -> Result<()> {
+    if context.state.qt.animation_mode() == AnimationMode::Normal && is_reduced_motion() {
+        context.rotation = context.animation_manager.CreateAnimationVariable(0.0)?;
+        context.sweep = context
+            .animation_manager
+            .CreateAnimationVariable(LONG_SWEEP)?;
+        context.storyboard = None;
+        return Ok(());
+    }
+    context.rotation = context.animation_manager.CreateAnimationVariable(0.0)?;
+    context.sweep = context
+        .animation_manager
+        .CreateAnimationVariable(SHORT_SWEEP)?;
+    let storyboard = context.animation_manager.CreateStoryboard()?;
+    let rotate = context
+        .transition_library
+        .CreateLinearTransition(PERIOD, 360.0)?;
+    storyboard.AddTransition(&context.rotation, &rotate)?;
+    let grow = context
+        .transition_library
+        .CreateAccelerateDecelerateTransition(PERIOD / 2.0, LONG_SWEEP, 0.5, 0.5)?;
+    storyboard.AddTransition(&context.sweep, &grow)?;
+    let shrink = context
+        .transition_library
+        .CreateAccelerateDecelerateTransition(PERIOD / 2.0, SHORT_SWEEP, 0.5, 0.5)?;
+    storyboard.AddTransition(&context.sweep, &shrink)?;
+    context.state.qt.schedule_storyboard(window, &storyboard)?;
+    context.storyboard = Some(storyboard);
+    Ok(())
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(D2D1_FACTORY_TYPE_SINGLE_THREADED, None)?;
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    let dpi = Dpi::for_window(window);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U {
+                width: rect.right as u32,
+                height: rect.bottom as u32,
+            },
+            presentOptions: Default::default(),
+        },
+    )?;
+    let stroke_style = factory.CreateStrokeStyle(
+        &D2D1_STROKE_STYLE_PROPERTIES1 {
+            startCap: D2D1_CAP_STYLE_ROUND,
+            endCap: D2D1_CAP_STYLE_ROUND,
+            ..Default::default()
+        },
+        None,
+    )?;
+
+    let label_layout = match &state.label {
+        Some(label) => {
+            let direct_write_factory =
+                DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+            let typography_styles = &state.qt.theme.typography_styles;
+            let text_format = match state.size {
+                Size::ExtraSmall | Size::Small => &typography_styles.body1,
+                Size::Medium | Size::Large => &typography_styles.body2,
+            }
+            .create_text_format(&direct_write_factory)?;
+            Some(direct_write_factory.CreateTextLayout(label, &text_format, f32::MAX, f32::MAX)?)
+        }
+        None => None,
+    };
+
+    let transition_library: IUIAnimationTransitionLibrary2 =
+        CoCreateInstance(&UIAnimationTransitionLibrary2, None, CLSCTX_INPROC_SERVER)?;
+    let animation_manager = state.qt.animation_manager()?;
+    let timer_event_handler: IUIAnimationTimerEventHandler =
+        AnimationTimerEventHandler { window }.into();
+    state.qt.register_animation(window, &timer_event_handler);
+    let rotation = animation_manager.CreateAnimationVariable(0.0)?;
+    let sweep = animation_manager.CreateAnimationVariable(SHORT_SWEEP)?;
+
+    let mut context = Context {
+        state,
+        factory,
+        render_target,
+        stroke_style,
+        label_layout,
+        animation_manager,
+        transition_library,
+        rotation,
+        sweep,
+        storyboard: None,
+    };
+    layout(window, &context)?;
+    start_animation(window, &mut context)?;
+    Ok(context)
+}
+
+unsafe fn get_label_metrics(context: &Context) -> Result<Option<DWRITE_TEXT_METRICS>> {
+    match &context.label_layout {
+        Some(label_layout) => {
+            let mut metrics = DWRITE_TEXT_METRICS::default();
+            label_layout.GetMetrics(&mut metrics)?;
+            Ok(Some(metrics))
+        }
+        None => Ok(None),
+    }
+}
+
+// Sizes the window to the ring plus the label to its right.
+unsafe fn layout(window: HWND, context: &Context) -> Result<()> {
+    let dpi = Dpi::for_window(window);
+    let diameter = context.state.size.get_diameter();
+    let (width, height) = match get_label_metrics(context)? {
+        Some(metrics) => (
+            diameter + context.state.get_label_spacing() + metrics.width,
+            diameter.max(metrics.height),
+        ),
+        None => (diameter, diameter),
+    };
+    SetWindowPos(
+        window,
+        None,
+        0,
+        0,
+        dpi.to_physical_ceil(width),
+        dpi.to_physical_ceil(height),
+        SWP_NOMOVE | SWP_NOZORDER,
+    )?;
+    on_size(window, context)
+}
+
+fn point_on_ring(center: f32, radius: f32, degrees: f32) -> D2D_POINT_2F {
+    let radians = degrees * PI / 180f32;
+    D2D_POINT_2F {
+        x: center + radius * radians.sin(),
+        y: center - radius * radians.cos(),
+    }
+}
+
+unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
+    let state = &context.state;
+    let tokens = &state.qt.theme.tokens;
+    context
+        .render_target
+        .Clear(Some(&tokens.color_neutral_background1));
+
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    let dpi = Dpi::for_window(window);
+    let height = dpi.to_dip(rect.bottom);
+    let diameter = state.size.get_diameter();
+    let stroke_width = state.size.get_stroke_width();
+    let radius = (diameter - stroke_width) / 2f32;
+    let center = diameter / 2f32;
+    let top = (height - diameter) / 2f32;
+
+    let track_brush = context
+        .render_target
+        .CreateSolidColorBrush(&tokens.color_brand_stroke2_contrast, None)?;
+    context.render_target.DrawEllipse(
+        &D2D1_ELLIPSE {
+            point: D2D_POINT_2F {
+                x: center,
+                y: top + center,
+            },
+            radiusX: radius,
+            radiusY: radius,
+        },
+        &track_brush,
+        stroke_width,
+        None,
+    );
+
+    let rotation = context.rotation.GetValue()? as f32;
+    let sweep = context.sweep.GetValue()? as f32;
+    let start = point_on_ring(center, radius, rotation);
+    let end = point_on_ring(center, radius, rotation + sweep);
+    let tail = context.factory.CreatePathGeometry()?;
+    let sink = tail.Open()?;
+    sink.BeginFigure(
+        D2D_POINT_2F {
+            x: start.x,
+            y: top + start.y,
+        },
+        D2D1_FIGURE_BEGIN_HOLLOW,
+    );
+    sink.AddArc(&D2D1_ARC_SEGMENT {
+        point: D2D_POINT_2F {
+            x: end.x,
+            y: top + end.y,
+        },
+        size: D2D_SIZE_F {
+            width: radius,
+            height: radius,
+        },
+        rotationAngle: 0f32,
+        sweepDirection: D2D1_SWEEP_DIRECTION_CLOCKWISE,
+        arcSize: if sweep > 180f32 {
+            D2D1_ARC_SIZE_LARGE
+        } else {
+            D2D1_ARC_SIZE_SMALL
+        },
+    });
+    sink.EndFigure(D2D1_FIGURE_END_OPEN);
+    sink.Close()?;
+    let tail_brush = context
+        .render_target
+        .CreateSolidColorBrush(&tokens.color_brand_stroke1, None)?;
+    context
+        .render_target
+        .DrawGeometry(&tail, &tail_brush, stroke_width, &context.stroke_style);
+
+    if let (Some(label_layout), Some(metrics)) =
+        (&context.label_layout, get_label_metrics(context)?)
+    {
+        let text_brush = context
+            .render_target
+            .CreateSolidColorBrush(&tokens.color_neutral_foreground1, None)?;
+        context.render_target.DrawTextLayout(
+            D2D_POINT_2F {
+                x: diameter + state.get_label_spacing(),
+                y: (height - metrics.height) / 2f32,
+            },
+            label_layout,
+            &text_brush,
+            D2D1_DRAW_TEXT_OPTIONS_NONE,
+        );
+    }
+
+    Ok(())
+}
+
+unsafe fn on_paint(window: HWND, context: &Context) -> Result<()> {
+    context.render_target.BeginDraw();
+    let result = paint(window, context);
+    match result {
+        Ok(_) => context.render_target.EndDraw(None, None),
+        Err(_) => {
+            context.render_target.EndDraw(None, None)?;
+            result
+        }
+    }
+}
+
+unsafe fn on_dpi_changed(window: HWND, context: &Context) -> Result<()> {
+    let dpi = Dpi::for_window(window);
+    context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+    layout(window, context)
+}
+
+unsafe fn on_size(window: HWND, context: &Context) -> Result<()> {
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    context.render_target.Resize(&D2D_SIZE_U {
+        width: rect.right as u32,
+        height: rect.bottom as u32,
+    })?;
+    let _ = InvalidateRect(Some(window), None, false);
+    Ok(())
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut State;
+            let state = Box::<State>::from_raw(raw);
+            match on_create(window, *state) {
+                Ok(context) => {
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(FALSE.0 as isize),
+            }
+        },
+        WM_DESTROY => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                let context = Box::<Context>::from_raw(raw);
+                context.state.qt.unregister_animation(window);
+            }
+            LRESULT(0)
+        },
+        WM_PAINT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let mut ps = PAINTSTRUCT::default();
+            BeginPaint(window, &mut ps);
+            let result = on_paint(window, context);
+            _ = EndPaint(window, &ps);
+            recover_lost_device(window, &result, || {
+                context.recreate_device_resources(window)
+            });
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            let dc = HDC(w_param.0 as _);
+            _ = print_client(window, &context.render_target, dc, || {
+                paint(window, context)
+            });
+            LRESULT(0)
+        },
+        WM_SIZE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                _ = on_size(window, &*raw);
+            }
+            LRESULT(0)
+        },
+        WM_DPICHANGED_BEFOREPARENT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            _ = on_dpi_changed(window, context);
+            LRESULT(0)
+        },
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{is_near, pump_messages, rgb, Capture, Host};
+
+    fn assert_point(point: D2D_POINT_2F, x: f32, y: f32) {
+        assert!((point.x - x).abs() < 1e-4 && (point.y - y).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ring_angles_run_clockwise_from_the_top() {
+        assert_point(point_on_ring(16f32, 10f32, 0f32), 16f32, 6f32);
+        assert_point(point_on_ring(16f32, 10f32, 90f32), 26f32, 16f32);
+        assert_point(point_on_ring(16f32, 10f32, 180f32), 16f32, 26f32);
+        assert_point(point_on_ring(16f32, 10f32, 270f32), 6f32, 16f32);
+        assert_point(point_on_ring(16f32, 10f32, 450f32), 26f32, 16f32);
+    }
+
+    fn create_manual(host: &Host) -> HWND {
+        host.qt.set_animation_mode(AnimationMode::Manual).unwrap();
+        host.create::<Spinner>(
+            10,
+            10,
+            SpinnerOptions {
+                size: Size::Large,
+                label: None,
+            },
+        )
+    }
+
+    fn get_context(window: HWND) -> &'static Context {
+        unsafe { &*(GetWindowLongPtrW(window, GWLP_USERDATA) as *const Context) }
+    }
+
+    // The physical pixel at `degrees` on the middle of the ring of a large spinner.
+    fn ring_pixel(window: HWND, capture: &Capture, degrees: f32) -> u32 {
+        let size = Size::Large;
+        let radius = (size.get_diameter() - size.get_stroke_width()) / 2f32;
+        let point = point_on_ring(size.get_diameter() / 2f32, radius, degrees);
+        let dpi = Dpi::for_window(window);
+        capture.pixel(dpi.to_physical(point.x), dpi.to_physical(point.y))
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn manual_ticks_reach_the_long_tail_halfway_through_a_turn() {
+        let host = Host::new(100, 100);
+        let window = create_manual(&host);
+        host.qt.tick_animations(PERIOD / 2.0).unwrap();
+        let context = get_context(window);
+        unsafe {
+            assert!((context.rotation.GetValue().unwrap() - 180.0).abs() < 1e-6);
+            assert!((context.sweep.GetValue().unwrap() - LONG_SWEEP).abs() < 1e-6);
+        }
+
+        // The tail runs clockwise from the bottom round to the right, leaving a gap before it.
+        let tokens = &host.qt.theme.tokens;
+        let tail = rgb(&tokens.color_brand_stroke1);
+        let track = rgb(&tokens.color_brand_stroke2_contrast);
+        let capture = Capture::of(window);
+        for degrees in [200f32, 270f32, 360f32, 420f32] {
+            assert!(is_near(ring_pixel(window, &capture, degrees), tail, 0x20));
+        }
+        assert!(is_near(ring_pixel(window, &capture, 135f32), track, 0x20));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn manual_ticks_render_identical_frames() {
+        let host = Host::new(100, 100);
+        let first = create_manual(&host);
+        let second = create_manual(&host);
+        for _ in 0..3 {
+            let before = Capture::of(first);
+            host.qt.tick_animations(0.3).unwrap();
+            pump_messages();
+            let frame = Capture::of(first);
+            assert_eq!(frame.pixels, Capture::of(second).pixels);
+            assert_ne!(frame.pixels, before.pixels);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn destroy_clears_the_context() {
+        let host = Host::new(100, 100);
+        let window = create_manual(&host);
+        unsafe {
+            SendMessageW(window, WM_DESTROY, None, None);
+            assert_eq!(GetWindowLongPtrW(window, GWLP_USERDATA), 0);
+            // Frames after the context is gone, and the real destruction, find nothing to free.
+            host.qt.tick_animations(0.1).unwrap();
+            DestroyWindow(window).unwrap();
+        }
+        pump_messages();
+    }
+}
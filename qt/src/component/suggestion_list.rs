@@ -0,0 +1,408 @@
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Win32::Foundation::{
+    FALSE, HINSTANCE, HWND, LPARAM, LRESULT, POINT, RECT, TRUE, WPARAM,
+};
+use windows::Win32::Graphics::Direct2D::Common::{D2D_RECT_F, D2D_SIZE_U};
+use windows::Win32::Graphics::Direct2D::{
+    D2D1CreateFactory, ID2D1Factory1, ID2D1HwndRenderTarget, ID2D1SolidColorBrush,
+    D2D1_DRAW_TEXT_OPTIONS_CLIP, D2D1_FACTORY_OPTIONS, D2D1_FACTORY_TYPE_SINGLE_THREADED,
+    D2D1_HWND_RENDER_TARGET_PROPERTIES, D2D1_ROUNDED_RECT,
+};
+use windows::Win32::Graphics::DirectWrite::{
+    DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, DWRITE_FACTORY_TYPE_SHARED,
+    DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL, DWRITE_MEASURING_MODE_NATURAL,
+    DWRITE_PARAGRAPH_ALIGNMENT_CENTER, DWRITE_WORD_WRAPPING_NO_WRAP,
+};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, CreateRoundRectRgn, EndPaint, GetMonitorInfoW, InvalidateRect, MonitorFromRect,
+    SetWindowRgn, HDC, MONITORINFO, MONITOR_DEFAULTTONEAREST, PAINTSTRUCT,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::{
+    print_client, recover_lost_device, recreate_render_target, render_target_properties,
+    window_class_name, Dpi, QT,
+};
+
+pub(crate) const WM_ACCEPT_SUGGESTION: u32 = WM_USER + 1;
+
+const MAX_ITEM_COUNT: usize = 8;
+const ITEM_HEIGHT: f32 = 32f32;
+
+struct State {
+    qt: QT,
+    input: HWND,
+}
+
+struct Context {
+    state: State,
+    items: Vec<HSTRING>,
+    selected_index: Option<usize>,
+    render_target: ID2D1HwndRenderTarget,
+    text_format: IDWriteTextFormat,
+    text_brush: ID2D1SolidColorBrush,
+    selected_brush: ID2D1SolidColorBrush,
+}
+
+impl Context {
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        let tokens = &self.state.qt.theme.tokens;
+        self.text_brush =
+            render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground1, None)?;
+        self.selected_brush =
+            render_target.CreateSolidColorBrush(&tokens.color_neutral_background1_hover, None)?;
+        self.render_target = render_target;
+        Ok(())
+    }
+}
+
+pub(crate) unsafe fn create(qt: &QT, input: HWND) -> Result<HWND> {
+    let class_name = window_class_name("QT_SUGGESTION_LIST", Some(window_proc));
+    let window_class = WNDCLASSEXW {
+        cbSize: size_of::<WNDCLASSEXW>() as u32,
+        lpszClassName: PCWSTR(class_name.as_ptr()),
+        style: CS_DROPSHADOW,
+        lpfnWndProc: Some(window_proc),
+        hCursor: LoadCursorW(None, IDC_ARROW)?,
+        ..Default::default()
+    };
+    RegisterClassExW(&window_class);
+    let boxed = Box::new(State {
+        qt: qt.clone(),
+        input,
+    });
+    CreateWindowExW(
+        WS_EX_NOACTIVATE | WS_EX_TOOLWINDOW,
+        &class_name,
+        w!(""),
+        WS_POPUP,
+        0,
+        0,
+        0,
+        0,
+        Some(GetAncestor(input, GA_ROOT)),
+        None,
+        Some(HINSTANCE(GetWindowLongPtrW(input, GWLP_HINSTANCE) as _)),
+        Some(Box::<State>::into_raw(boxed) as _),
+    )
+}
+
+unsafe fn get_context<'a>(window: HWND) -> Option<&'a mut Context> {
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    if raw.is_null() {
+        None
+    } else {
+        Some(&mut *raw)
+    }
+}
+
+pub(crate) unsafe fn show(window: HWND, items: Vec<String>) -> Result<()> {
+    if let Some(context) = get_context(window) {
+        context.items = items
+            .iter()
+            .take(MAX_ITEM_COUNT)
+            .map(HSTRING::from)
+            .collect();
+        context.selected_index = None;
+        reposition(window)?;
+        _ = InvalidateRect(Some(window), None, false);
+    }
+    Ok(())
+}
+
+pub(crate) unsafe fn hide(window: HWND) {
+    _ = ShowWindow(window, SW_HIDE);
+    if let Some(context) = get_context(window) {
+        context.items.clear();
+        context.selected_index = None;
+    }
+}
+
+pub(crate) unsafe fn is_visible(window: HWND) -> bool {
+    IsWindowVisible(window).as_bool()
+}
+
+pub(crate) unsafe fn get_item(window: HWND, index: usize) -> Option<HSTRING> {
+    get_context(window).and_then(|context| context.items.get(index).cloned())
+}
+
+pub(crate) unsafe fn get_selected_index(window: HWND) -> Option<usize> {
+    get_context(window).and_then(|context| context.selected_index)
+}
+
+pub(crate) unsafe fn move_selection(window: HWND, delta: isize) {
+    if let Some(context) = get_context(window) {
+        let count = context.items.len() as isize;
+        if count == 0 {
+            return;
+        }
+        let index = match context.selected_index {
+            None if delta > 0 => 0,
+            None => count - 1,
+            Some(index) => (index as isize + delta).rem_euclid(count),
+        };
+        select(window, context, Some(index as usize));
+    }
+}
+
+fn select(window: HWND, context: &mut Context, index: Option<usize>) {
+    if context.selected_index != index {
+        context.selected_index = index;
+        unsafe {
+            _ = InvalidateRect(Some(window), None, false);
+        }
+    }
+}
+
+// Keeps the list under (or, near the bottom of the monitor, above) the input.
+pub(crate) unsafe fn reposition(window: HWND) -> Result<()> {
+    let Some(context) = get_context(window) else {
+        return Ok(());
+    };
+    if context.items.is_empty() {
+        return Ok(());
+    }
+    let mut input_rect = RECT::default();
+    GetWindowRect(context.state.input, &mut input_rect)?;
+    let dpi = Dpi::for_window(context.state.input);
+    let tokens = &context.state.qt.theme.tokens;
+    let padding = tokens.spacing_horizontal_xs;
+    let gap = dpi.to_physical(tokens.spacing_horizontal_xs);
+    let width = input_rect.right - input_rect.left;
+    let height = dpi.to_physical(ITEM_HEIGHT * context.items.len() as f32 + padding * 2f32);
+
+    let monitor = MonitorFromRect(&input_rect, MONITOR_DEFAULTTONEAREST);
+    let mut info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    _ = GetMonitorInfoW(monitor, &mut info);
+    let mut y = input_rect.bottom + gap;
+    if y + height > info.rcWork.bottom && input_rect.top - gap - height >= info.rcWork.top {
+        y = input_rect.top - gap - height;
+    }
+    let x = input_rect
+        .left
+        .min(info.rcWork.right - width)
+        .max(info.rcWork.left);
+    SetWindowPos(
+        window,
+        Some(HWND_TOP),
+        x,
+        y,
+        width,
+        height,
+        SWP_NOACTIVATE | SWP_SHOWWINDOW,
+    )?;
+    context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+    context.render_target.Resize(&D2D_SIZE_U {
+        width: width as u32,
+        height: height as u32,
+    })?;
+    let corner_diameter = dpi.to_physical(tokens.border_radius_medium * 2f32);
+    let region = CreateRoundRectRgn(
+        0,
+        0,
+        width + 1,
+        height + 1,
+        corner_diameter,
+        corner_diameter,
+    );
+    SetWindowRgn(window, Some(region), true);
+    Ok(())
+}
+
+unsafe fn hit_test(window: HWND, context: &Context, point: POINT) -> Option<usize> {
+    let dpi = Dpi::for_window(context.state.input);
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect).ok()?;
+    if point.x < rect.left || point.x >= rect.right {
+        return None;
+    }
+    let y = dpi.to_dip(point.y) - context.state.qt.theme.tokens.spacing_horizontal_xs;
+    if y < 0f32 {
+        return None;
+    }
+    let index = (y / ITEM_HEIGHT) as usize;
+    if index < context.items.len() {
+        Some(index)
+    } else {
+        None
+    }
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        Some(&D2D1_FACTORY_OPTIONS::default()),
+    )?;
+    let dpi = Dpi::for_window(state.input);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U::default(),
+            presentOptions: Default::default(),
+        },
+    )?;
+    let tokens = &state.qt.theme.tokens;
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_format = direct_write_factory.CreateTextFormat(
+        tokens.font_family_base,
+        None,
+        tokens.font_weight_regular,
+        DWRITE_FONT_STYLE_NORMAL,
+        DWRITE_FONT_STRETCH_NORMAL,
+        tokens.font_size_base300,
+        w!(""),
+    )?;
+    text_format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+    text_format.SetWordWrapping(DWRITE_WORD_WRAPPING_NO_WRAP)?;
+    let text_brush =
+        render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground1, None)?;
+    let selected_brush =
+        render_target.CreateSolidColorBrush(&tokens.color_neutral_background1_hover, None)?;
+    Ok(Context {
+        state,
+        items: Vec::new(),
+        selected_index: None,
+        render_target,
+        text_format,
+        text_brush,
+        selected_brush,
+    })
+}
+
+unsafe fn paint(window: HWND, context: &Context) -> Result<()> {
+    let tokens = &context.state.qt.theme.tokens;
+    context
+        .render_target
+        .Clear(Some(&tokens.color_neutral_background1));
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    let width = Dpi::for_window(context.state.input).to_dip(rect.right);
+    let padding = tokens.spacing_horizontal_xs;
+    for (index, item) in context.items.iter().enumerate() {
+        let item_rect = D2D_RECT_F {
+            left: padding,
+            top: padding + ITEM_HEIGHT * index as f32,
+            right: width - padding,
+            bottom: padding + ITEM_HEIGHT * (index + 1) as f32,
+        };
+        if context.selected_index == Some(index) {
+            context.render_target.FillRoundedRectangle(
+                &D2D1_ROUNDED_RECT {
+                    rect: item_rect,
+                    radiusX: tokens.border_radius_medium,
+                    radiusY: tokens.border_radius_medium,
+                },
+                &context.selected_brush,
+            );
+        }
+        context.render_target.DrawText(
+            item,
+            &context.text_format,
+            &D2D_RECT_F {
+                left: item_rect.left + tokens.spacing_horizontal_s,
+                right: item_rect.right - tokens.spacing_horizontal_s,
+                ..item_rect
+            },
+            &context.text_brush,
+            D2D1_DRAW_TEXT_OPTIONS_CLIP,
+            DWRITE_MEASURING_MODE_NATURAL,
+        );
+    }
+    Ok(())
+}
+
+unsafe fn on_paint(window: HWND, context: &Context) -> Result<()> {
+    let mut ps = PAINTSTRUCT::default();
+    BeginPaint(window, &mut ps);
+    context.render_target.BeginDraw();
+    let result = paint(window, context).and(context.render_target.EndDraw(None, None));
+    _ = EndPaint(window, &ps);
+    result
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut State;
+            let state = Box::<State>::from_raw(raw);
+            match on_create(window, *state) {
+                Ok(context) => {
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(FALSE.0 as isize),
+            }
+        },
+        WM_DESTROY => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                _ = Box::<Context>::from_raw(raw);
+            }
+            LRESULT(0)
+        },
+        WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as isize),
+        WM_MOUSEMOVE => unsafe {
+            if let Some(context) = get_context(window) {
+                let point = POINT {
+                    x: l_param.0 as i16 as i32,
+                    y: (l_param.0 >> 16) as i16 as i32,
+                };
+                let index = hit_test(window, context, point);
+                select(window, context, index);
+            }
+            LRESULT(0)
+        },
+        WM_LBUTTONUP => unsafe {
+            if let Some(context) = get_context(window) {
+                let point = POINT {
+                    x: l_param.0 as i16 as i32,
+                    y: (l_param.0 >> 16) as i16 as i32,
+                };
+                if let Some(index) = hit_test(window, context, point) {
+                    SendMessageW(
+                        context.state.input,
+                        WM_ACCEPT_SUGGESTION,
+                        Some(WPARAM(index)),
+                        None,
+                    );
+                }
+            }
+            LRESULT(0)
+        },
+        WM_PAINT => unsafe {
+            if let Some(context) = get_context(window) {
+                let result = on_paint(window, context);
+                recover_lost_device(window, &result, || {
+                    context.recreate_device_resources(window)
+                });
+            }
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
+            if let Some(context) = get_context(window) {
+                let dc = HDC(w_param.0 as _);
+                _ = print_client(window, &context.render_target, dc, || {
+                    paint(window, context)
+                });
+            }
+            LRESULT(0)
+        },
+        WM_ERASEBKGND => LRESULT(1),
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
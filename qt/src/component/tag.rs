@@ -0,0 +1,560 @@
+use std::mem::{replace, size_of};
+
+use windows::core::*;
+use windows::Foundation::Numerics::Matrix3x2;
+use windows::Win32::Foundation::{FALSE, HINSTANCE, HWND, LPARAM, LRESULT, TRUE, WPARAM};
+use windows::Win32::Graphics::Direct2D::Common::{D2D_RECT_F, D2D_SIZE_U};
+use windows::Win32::Graphics::Direct2D::{
+    D2D1CreateFactory, ID2D1DeviceContext5, ID2D1Factory1, ID2D1HwndRenderTarget,
+    ID2D1SolidColorBrush, ID2D1SvgDocument, D2D1_DRAW_TEXT_OPTIONS_NONE, D2D1_FACTORY_OPTIONS,
+    D2D1_FACTORY_TYPE_SINGLE_THREADED, D2D1_HWND_RENDER_TARGET_PROPERTIES, D2D1_ROUNDED_RECT,
+};
+use windows::Win32::Graphics::DirectWrite::{
+    DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, DWRITE_FACTORY_TYPE_SHARED,
+    DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL, DWRITE_MEASURING_MODE_NATURAL,
+    DWRITE_PARAGRAPH_ALIGNMENT_CENTER, DWRITE_TEXT_METRICS,
+};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, CreateRoundRectRgn, EndPaint, InvalidateRect, SetWindowRgn, HDC, PAINTSTRUCT,
+};
+use windows::Win32::UI::Controls::WM_MOUSELEAVE;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    SetFocus, TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT, VIRTUAL_KEY, VK_BACK, VK_DELETE,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::component::button::set_svg_color;
+use crate::component::menu::create_svg_document;
+use crate::component::Component;
+use crate::icon::Icon;
+use crate::theme::Tokens;
+use crate::{
+    handle_disabled_mouse, is_focus_visible, print_client, recover_lost_device,
+    recreate_render_target, render_target_properties, track_input_kind, window_class_name, Dpi, QT,
+};
+
+const HEIGHT: f32 = 32f32;
+// Dismissal is posted, so `on_dismiss` runs with no borrow of the context, and may destroy the tag.
+const WM_DISMISS: u32 = WM_USER + 1;
+const DISMISS_ICON_SIZE: f32 = 20f32;
+
+pub struct TagEvent {
+    pub on_dismiss: Box<dyn Fn(&HWND)>,
+}
+
+impl Default for TagEvent {
+    fn default() -> Self {
+        TagEvent {
+            on_dismiss: Box::new(|_window| {}),
+        }
+    }
+}
+
+pub struct Tag;
+
+pub struct TagOptions {
+    pub text: PCWSTR,
+    pub dismissible: bool,
+    pub event: TagEvent,
+}
+
+impl Component for Tag {
+    type Options = TagOptions;
+
+    fn create(qt: &QT, parent_window: HWND, x: i32, y: i32, options: TagOptions) -> Result<HWND> {
+        qt.create_tag(
+            parent_window,
+            x,
+            y,
+            options.text,
+            options.dismissible,
+            options.event,
+        )
+    }
+}
+
+struct State {
+    qt: QT,
+    text: PCWSTR,
+    dismissible: bool,
+    event: TagEvent,
+    text_width: f32,
+}
+
+impl State {
+    fn get_dismiss_width(&self) -> f32 {
+        if self.dismissible {
+            DISMISS_ICON_SIZE + self.qt.theme.tokens.spacing_horizontal_xs * 2f32
+        } else {
+            0f32
+        }
+    }
+
+    fn get_text_right(&self) -> f32 {
+        self.qt.theme.tokens.spacing_horizontal_s + self.text_width
+    }
+
+    fn get_width(&self) -> f32 {
+        let tokens = &self.qt.theme.tokens;
+        if self.dismissible {
+            self.get_text_right() + tokens.spacing_horizontal_xxs + self.get_dismiss_width()
+        } else {
+            self.get_text_right() + tokens.spacing_horizontal_s
+        }
+    }
+}
+
+struct Context {
+    state: State,
+    render_target: ID2D1HwndRenderTarget,
+    text_format: IDWriteTextFormat,
+    brushes: Brushes,
+    dismiss_svg: Option<ID2D1SvgDocument>,
+    mouse_within_dismiss: bool,
+    mouse_clicking: bool,
+    is_focused: bool,
+    focus_visible: bool,
+}
+
+// They belong to the render target, so they are recreated with it.
+struct Brushes {
+    text: ID2D1SolidColorBrush,
+    background: ID2D1SolidColorBrush,
+    focus: ID2D1SolidColorBrush,
+}
+
+impl Brushes {
+    unsafe fn new(render_target: &ID2D1HwndRenderTarget, tokens: &Tokens) -> Result<Self> {
+        Ok(Brushes {
+            text: render_target.CreateSolidColorBrush(&tokens.color_neutral_foreground1, None)?,
+            background: render_target
+                .CreateSolidColorBrush(&tokens.color_neutral_background3, None)?,
+            focus: render_target.CreateSolidColorBrush(&tokens.color_stroke_focus2, None)?,
+        })
+    }
+}
+
+impl Context {
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        self.brushes = Brushes::new(&render_target, &self.state.qt.theme.tokens)?;
+        self.dismiss_svg = create_dismiss_svg(&render_target, &self.state)?;
+        self.render_target = render_target;
+        Ok(())
+    }
+}
+
+impl QT {
+    /// A rounded label for filters and tags. A dismissible tag has a dismiss button at its
+    /// trailing edge, and can also be dismissed with Delete or Backspace while focused.
+    pub fn create_tag(
+        &self,
+        parent_window: HWND,
+        x: i32,
+        y: i32,
+        text: PCWSTR,
+        dismissible: bool,
+        event: TagEvent,
+    ) -> Result<HWND> {
+        let class_name = window_class_name("QT_TAG", Some(window_proc));
+        unsafe {
+            let window_class = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                style: CS_CLASSDC,
+                lpfnWndProc: Some(window_proc),
+                hCursor: LoadCursorW(None, IDC_ARROW)?,
+                ..Default::default()
+            };
+            RegisterClassExW(&window_class);
+            let text_width = measure_text(self, text)?;
+            let boxed = Box::new(State {
+                qt: self.clone(),
+                text,
+                dismissible,
+                event,
+                text_width,
+            });
+            let dpi = Dpi::for_window(parent_window);
+            let style = if dismissible {
+                WS_TABSTOP | WS_VISIBLE | WS_CHILD
+            } else {
+                WS_VISIBLE | WS_CHILD
+            };
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                // The label is also the window text, so DefWindowProc answers WM_GETTEXT with it.
+                text,
+                style,
+                x,
+                y,
+                dpi.to_physical_ceil(boxed.get_width()),
+                dpi.to_physical_ceil(HEIGHT),
+                Some(parent_window),
+                None,
+                Some(HINSTANCE(
+                    GetWindowLongPtrW(parent_window, GWLP_HINSTANCE) as _
+                )),
+                Some(Box::<State>::into_raw(boxed) as _),
+            )
+        }
+    }
+}
+
+unsafe fn create_text_format(qt: &QT) -> Result<IDWriteTextFormat> {
+    let tokens = &qt.theme.tokens;
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_format = direct_write_factory.CreateTextFormat(
+        tokens.font_family_base,
+        None,
+        tokens.font_weight_regular,
+        DWRITE_FONT_STYLE_NORMAL,
+        DWRITE_FONT_STRETCH_NORMAL,
+        tokens.font_size_base300,
+        w!(""),
+    )?;
+    text_format.SetParagraphAlignment(DWRITE_PARAGRAPH_ALIGNMENT_CENTER)?;
+    Ok(text_format)
+}
+
+unsafe fn measure_text(qt: &QT, text: PCWSTR) -> Result<f32> {
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_layout = direct_write_factory.CreateTextLayout(
+        text.as_wide(),
+        &create_text_format(qt)?,
+        f32::MAX,
+        HEIGHT,
+    )?;
+    let mut metrics = DWRITE_TEXT_METRICS::default();
+    text_layout.GetMetrics(&mut metrics)?;
+    Ok(metrics.width.ceil())
+}
+
+unsafe fn set_region(window: HWND, context: &Context) {
+    let dpi = Dpi::for_window(window);
+    let corner_diameter =
+        dpi.to_physical(context.state.qt.theme.tokens.border_radius_medium * 2f32);
+    let region = CreateRoundRectRgn(
+        0,
+        0,
+        dpi.to_physical_ceil(context.state.get_width()) + 1,
+        dpi.to_physical_ceil(HEIGHT) + 1,
+        corner_diameter,
+        corner_diameter,
+    );
+    SetWindowRgn(window, Some(region), true);
+}
+
+unsafe fn create_dismiss_svg(
+    render_target: &ID2D1HwndRenderTarget,
+    state: &State,
+) -> Result<Option<ID2D1SvgDocument>> {
+    if !state.dismissible {
+        return Ok(None);
+    }
+    let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
+    let svg = create_svg_document(&device_context5, &Icon::dismiss_regular())?;
+    set_svg_color(&svg, &state.qt.theme.tokens.color_neutral_foreground2)?;
+    Ok(Some(svg))
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        Some(&D2D1_FACTORY_OPTIONS::default()),
+    )?;
+    let dpi = Dpi::for_window(window);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U {
+                width: dpi.to_physical_ceil(state.get_width()) as u32,
+                height: dpi.to_physical_ceil(HEIGHT) as u32,
+            },
+            presentOptions: Default::default(),
+        },
+    )?;
+    let tokens = &state.qt.theme.tokens;
+    let text_format = create_text_format(&state.qt)?;
+    let brushes = Brushes::new(&render_target, tokens)?;
+    let dismiss_svg = create_dismiss_svg(&render_target, &state)?;
+    let context = Context {
+        state,
+        render_target,
+        text_format,
+        brushes,
+        dismiss_svg,
+        mouse_within_dismiss: false,
+        mouse_clicking: false,
+        is_focused: false,
+        focus_visible: false,
+    };
+    set_region(window, &context);
+    Ok(context)
+}
+
+unsafe fn paint(context: &Context) -> Result<()> {
+    let state = &context.state;
+    let tokens = &state.qt.theme.tokens;
+    let render_target = &context.render_target;
+    let width = state.get_width();
+    let corner_radius = tokens.border_radius_medium;
+    render_target.Clear(Some(&tokens.color_neutral_background3));
+    render_target.FillRoundedRectangle(
+        &D2D1_ROUNDED_RECT {
+            rect: D2D_RECT_F {
+                left: 0f32,
+                top: 0f32,
+                right: width,
+                bottom: HEIGHT,
+            },
+            radiusX: corner_radius,
+            radiusY: corner_radius,
+        },
+        &context.brushes.background,
+    );
+    render_target.DrawText(
+        state.text.as_wide(),
+        &context.text_format,
+        &D2D_RECT_F {
+            left: tokens.spacing_horizontal_s,
+            top: 0f32,
+            right: state.get_text_right(),
+            bottom: HEIGHT,
+        },
+        &context.brushes.text,
+        D2D1_DRAW_TEXT_OPTIONS_NONE,
+        DWRITE_MEASURING_MODE_NATURAL,
+    );
+
+    if let Some(svg) = &context.dismiss_svg {
+        let device_context5 = render_target.cast::<ID2D1DeviceContext5>()?;
+        device_context5.SetTransform(&Matrix3x2::translation(
+            width - state.get_dismiss_width() + tokens.spacing_horizontal_xs,
+            (HEIGHT - DISMISS_ICON_SIZE) / 2f32,
+        ));
+        device_context5.DrawSvgDocument(svg);
+        device_context5.SetTransform(&Matrix3x2::identity());
+    }
+
+    if context.is_focused && context.focus_visible {
+        let focus_width = tokens.stroke_width_thin * 2f32;
+        render_target.DrawRoundedRectangle(
+            &D2D1_ROUNDED_RECT {
+                rect: D2D_RECT_F {
+                    left: focus_width * 0.5,
+                    top: focus_width * 0.5,
+                    right: width - focus_width * 0.5,
+                    bottom: HEIGHT - focus_width * 0.5,
+                },
+                radiusX: corner_radius,
+                radiusY: corner_radius,
+            },
+            &context.brushes.focus,
+            focus_width,
+            None,
+        );
+    }
+    Ok(())
+}
+
+unsafe fn on_paint(context: &Context) -> Result<()> {
+    context.render_target.BeginDraw();
+    let result = paint(context);
+    context.render_target.EndDraw(None, None).and(result)
+}
+
+fn is_within_dismiss(state: &State, window: HWND, mouse_x: i32) -> bool {
+    let x = Dpi::for_window(window).to_dip(mouse_x);
+    state.dismissible && x >= state.get_width() - state.get_dismiss_width()
+}
+
+// The dismiss icon darkens while hovered and darkens further while pressed.
+unsafe fn update_dismiss_color(window: HWND, context: &Context) {
+    let Some(svg) = &context.dismiss_svg else {
+        return;
+    };
+    let tokens = &context.state.qt.theme.tokens;
+    let color = if context.mouse_within_dismiss && context.mouse_clicking {
+        &tokens.color_neutral_foreground1_pressed
+    } else if context.mouse_within_dismiss {
+        &tokens.color_neutral_foreground1_hover
+    } else {
+        &tokens.color_neutral_foreground2
+    };
+    _ = set_svg_color(svg, color);
+    _ = InvalidateRect(Some(window), None, false);
+}
+
+unsafe fn set_mouse_within_dismiss(window: HWND, context: &mut Context, within: bool) {
+    if context.mouse_within_dismiss != within {
+        context.mouse_within_dismiss = within;
+        update_dismiss_color(window, context);
+    }
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    if let Some(result) = unsafe { handle_disabled_mouse(window, message) } {
+        return result;
+    }
+    track_input_kind(message);
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut State;
+            let state = Box::<State>::from_raw(raw);
+            match on_create(window, *state) {
+                Ok(context) => {
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(FALSE.0 as isize),
+            }
+        },
+        WM_DESTROY => unsafe {
+            let raw = SetWindowLongPtrW(window, GWLP_USERDATA, 0) as *mut Context;
+            if !raw.is_null() {
+                _ = Box::<Context>::from_raw(raw);
+            }
+            LRESULT(0)
+        },
+        WM_PAINT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let mut ps = PAINTSTRUCT::default();
+            BeginPaint(window, &mut ps);
+            let result = on_paint(context);
+            _ = EndPaint(window, &ps);
+            recover_lost_device(window, &result, || {
+                context.recreate_device_resources(window)
+            });
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            let dc = HDC(w_param.0 as _);
+            _ = print_client(window, &context.render_target, dc, || paint(context));
+            LRESULT(0)
+        },
+        WM_DPICHANGED_BEFOREPARENT => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            let dpi = Dpi::for_window(window);
+            let width = dpi.to_physical_ceil(context.state.get_width());
+            let height = dpi.to_physical_ceil(HEIGHT);
+            _ = SetWindowPos(window, None, 0, 0, width, height, SWP_NOMOVE | SWP_NOZORDER);
+            context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+            _ = context.render_target.Resize(&D2D_SIZE_U {
+                width: width as u32,
+                height: height as u32,
+            });
+            set_region(window, context);
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_MOUSEMOVE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            let mouse_x = l_param.0 as i16 as i32;
+            let within = is_within_dismiss(&context.state, window, mouse_x);
+            if within && !context.mouse_within_dismiss {
+                let mut tme = TRACKMOUSEEVENT {
+                    cbSize: size_of::<TRACKMOUSEEVENT>() as u32,
+                    dwFlags: TME_LEAVE,
+                    hwndTrack: window,
+                    dwHoverTime: 0,
+                };
+                _ = TrackMouseEvent(&mut tme);
+            }
+            set_mouse_within_dismiss(window, context, within);
+            LRESULT(0)
+        },
+        WM_MOUSELEAVE => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            context.mouse_clicking = false;
+            set_mouse_within_dismiss(window, context, false);
+            LRESULT(0)
+        },
+        WM_LBUTTONDOWN => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            if !context.state.dismissible {
+                return LRESULT(0);
+            }
+            _ = SetFocus(Some(window));
+            let mouse_x = l_param.0 as i16 as i32;
+            if is_within_dismiss(&context.state, window, mouse_x) {
+                context.mouse_clicking = true;
+                update_dismiss_color(window, context);
+            }
+            LRESULT(0)
+        },
+        WM_LBUTTONUP => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &mut *raw;
+            if !context.mouse_clicking {
+                return LRESULT(0);
+            }
+            context.mouse_clicking = false;
+            update_dismiss_color(window, context);
+            let mouse_x = l_param.0 as i16 as i32;
+            if is_within_dismiss(&context.state, window, mouse_x) {
+                _ = PostMessageW(Some(window), WM_DISMISS, WPARAM(0), LPARAM(0));
+            }
+            LRESULT(0)
+        },
+        WM_SETFOCUS => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            (*raw).is_focused = true;
+            (*raw).focus_visible = is_focus_visible();
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_KILLFOCUS => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            (*raw).is_focused = false;
+            _ = InvalidateRect(Some(window), None, false);
+            LRESULT(0)
+        },
+        WM_KEYDOWN => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            let context = &*raw;
+            match VIRTUAL_KEY(w_param.0 as u16) {
+                VK_DELETE | VK_BACK if context.state.dismissible => {
+                    _ = PostMessageW(Some(window), WM_DISMISS, WPARAM(0), LPARAM(0));
+                    LRESULT(0)
+                }
+                _ => DefWindowProcW(window, message, w_param, l_param),
+            }
+        },
+        WM_DISMISS => unsafe {
+            on_dismiss(window);
+            LRESULT(0)
+        },
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
+
+// The callback is moved out of the context while it runs, and only put back if the tag survived.
+unsafe fn on_dismiss(window: HWND) {
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    if raw.is_null() {
+        return;
+    }
+    let on_dismiss = replace(&mut (*raw).state.event.on_dismiss, Box::new(|_window| {}));
+    on_dismiss(&window);
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    if !raw.is_null() {
+        (*raw).state.event.on_dismiss = on_dismiss;
+    }
+}
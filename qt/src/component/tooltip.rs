@@ -0,0 +1,412 @@
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Win32::Foundation::{FALSE, HINSTANCE, HWND, LPARAM, LRESULT, RECT, TRUE, WPARAM};
+use windows::Win32::Graphics::Direct2D::Common::{D2D_POINT_2F, D2D_SIZE_U};
+use windows::Win32::Graphics::Direct2D::{
+    D2D1CreateFactory, ID2D1Factory1, ID2D1HwndRenderTarget, ID2D1SolidColorBrush,
+    D2D1_DRAW_TEXT_OPTIONS_NONE, D2D1_FACTORY_OPTIONS, D2D1_FACTORY_TYPE_SINGLE_THREADED,
+    D2D1_HWND_RENDER_TARGET_PROPERTIES,
+};
+use windows::Win32::Graphics::DirectWrite::{
+    DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, IDWriteTextLayout,
+    DWRITE_FACTORY_TYPE_SHARED, DWRITE_TEXT_METRICS,
+};
+use windows::Win32::Graphics::Gdi::{
+    BeginPaint, CreateRoundRectRgn, EndPaint, GetMonitorInfoW, InvalidateRect, MonitorFromRect,
+    SetWindowRgn, HDC, MONITORINFO, MONITOR_DEFAULTTONEAREST, PAINTSTRUCT,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::shadow::{self, Elevation};
+use crate::{
+    print_client, recover_lost_device, recreate_render_target, render_target_properties,
+    window_class_name, Dpi, QT,
+};
+
+const MAX_WIDTH: f32 = 240f32;
+const PADDING_HORIZONTAL: f32 = 11f32;
+const PADDING_TOP: f32 = 4f32;
+const PADDING_BOTTOM: f32 = 6f32;
+const SHOW_TIMER_ID: usize = 1;
+const POLL_TIMER_ID: usize = 2;
+const POLL_INTERVAL: u32 = 150;
+// Polls the cursor may spend outside both the target and the popup, e.g. while crossing the gap
+// between them, before the popup is dismissed.
+const DISMISS_POLLS: u32 = 2;
+
+/// What a [`HoverTimer`] timer asks its popup to do.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum HoverAction {
+    Show,
+    Dismiss,
+}
+
+/// The show delay and dismiss polling shared by tooltips and hover cards. Its timers are set on
+/// `window`, which hands their `WM_TIMER` ids to [`HoverTimer::on_timer`].
+pub(crate) struct HoverTimer {
+    window: HWND,
+    delay: u32,
+    is_pending: bool,
+    outside_polls: u32,
+}
+
+impl HoverTimer {
+    pub(crate) fn new(window: HWND, delay: u32) -> Self {
+        HoverTimer {
+            window,
+            delay,
+            is_pending: false,
+            outside_polls: 0,
+        }
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        self.is_pending
+    }
+
+    /// Asks for [`HoverAction::Show`] once the delay has passed, unless a show is already pending.
+    pub(crate) unsafe fn schedule(&mut self) {
+        if !self.is_pending {
+            self.is_pending = true;
+            SetTimer(Some(self.window), SHOW_TIMER_ID, self.delay, None);
+        }
+    }
+
+    /// Stops both the pending show and the dismiss polling.
+    pub(crate) unsafe fn cancel(&mut self) {
+        self.is_pending = false;
+        _ = KillTimer(Some(self.window), SHOW_TIMER_ID);
+        _ = KillTimer(Some(self.window), POLL_TIMER_ID);
+    }
+
+    /// Polls the cursor while the popup is shown, asking for [`HoverAction::Dismiss`] once it has
+    /// stayed away for a while.
+    pub(crate) unsafe fn start_polling(&mut self) {
+        self.outside_polls = 0;
+        SetTimer(Some(self.window), POLL_TIMER_ID, POLL_INTERVAL, None);
+    }
+
+    /// Handles a timer of this helper; `is_hovered` tells whether the cursor is over the target or
+    /// the popup. Returns `None` for other timers and for polls that change nothing.
+    pub(crate) unsafe fn on_timer(
+        &mut self,
+        id: usize,
+        is_hovered: impl FnOnce() -> bool,
+    ) -> Option<HoverAction> {
+        match id {
+            SHOW_TIMER_ID => {
+                _ = KillTimer(Some(self.window), SHOW_TIMER_ID);
+                self.is_pending = false;
+                Some(HoverAction::Show)
+            }
+            POLL_TIMER_ID => {
+                if is_hovered() {
+                    self.outside_polls = 0;
+                    return None;
+                }
+                self.outside_polls += 1;
+                (self.outside_polls >= DISMISS_POLLS).then_some(HoverAction::Dismiss)
+            }
+            _ => None,
+        }
+    }
+}
+
+struct State {
+    qt: QT,
+    owner: HWND,
+}
+
+struct Context {
+    state: State,
+    text_layout: Option<IDWriteTextLayout>,
+    render_target: ID2D1HwndRenderTarget,
+    text_format: IDWriteTextFormat,
+    text_brush: ID2D1SolidColorBrush,
+    shadow: HWND,
+}
+
+impl Context {
+    unsafe fn recreate_device_resources(&mut self, window: HWND) -> Result<()> {
+        let render_target = recreate_render_target(window, &self.render_target)?;
+        self.text_brush = render_target
+            .CreateSolidColorBrush(&self.state.qt.theme.tokens.color_neutral_foreground1, None)?;
+        self.render_target = render_target;
+        Ok(())
+    }
+}
+
+pub(crate) unsafe fn create(qt: &QT, owner: HWND) -> Result<HWND> {
+    let class_name = window_class_name("QT_TOOLTIP", Some(window_proc));
+    let window_class = WNDCLASSEXW {
+        cbSize: size_of::<WNDCLASSEXW>() as u32,
+        lpszClassName: PCWSTR(class_name.as_ptr()),
+        style: CS_SAVEBITS,
+        lpfnWndProc: Some(window_proc),
+        hCursor: LoadCursorW(None, IDC_ARROW)?,
+        ..Default::default()
+    };
+    RegisterClassExW(&window_class);
+    let boxed = Box::new(State {
+        qt: qt.clone(),
+        owner,
+    });
+    CreateWindowExW(
+        WS_EX_NOACTIVATE | WS_EX_TOOLWINDOW | WS_EX_TRANSPARENT,
+        &class_name,
+        w!(""),
+        WS_POPUP,
+        0,
+        0,
+        0,
+        0,
+        Some(owner),
+        None,
+        Some(HINSTANCE(GetWindowLongPtrW(owner, GWLP_HINSTANCE) as _)),
+        Some(Box::<State>::into_raw(boxed) as _),
+    )
+}
+
+unsafe fn get_context<'a>(window: HWND) -> Option<&'a mut Context> {
+    let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+    if raw.is_null() {
+        None
+    } else {
+        Some(&mut *raw)
+    }
+}
+
+// Shows the tooltip to the right of `anchor` (in screen coordinates), or to its left when there is
+// no room on the right.
+pub(crate) unsafe fn show(window: HWND, text: PCWSTR, anchor: &RECT) -> Result<()> {
+    let Some(context) = get_context(window) else {
+        return Ok(());
+    };
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_layout = direct_write_factory.CreateTextLayout(
+        text.as_wide(),
+        &context.text_format,
+        MAX_WIDTH - PADDING_HORIZONTAL * 2f32,
+        f32::MAX,
+    )?;
+    let mut metrics = DWRITE_TEXT_METRICS::default();
+    text_layout.GetMetrics(&mut metrics)?;
+    context.text_layout = Some(text_layout);
+
+    let dpi = Dpi::for_window(context.state.owner);
+    let gap = dpi.to_physical(context.state.qt.theme.tokens.spacing_horizontal_xs);
+    let width = dpi.to_physical_ceil(metrics.width + PADDING_HORIZONTAL * 2f32);
+    let height = dpi.to_physical_ceil(metrics.height + PADDING_TOP + PADDING_BOTTOM);
+
+    let work_area = get_work_area(anchor);
+    let mut x = anchor.right + gap;
+    if x + width > work_area.right {
+        x = (anchor.left - gap - width).max(work_area.left);
+    }
+    let y = anchor.top.min(work_area.bottom - height).max(work_area.top);
+    SetWindowPos(
+        window,
+        Some(HWND_TOP),
+        x,
+        y,
+        width,
+        height,
+        SWP_NOACTIVATE | SWP_SHOWWINDOW,
+    )?;
+    context.render_target.SetDpi(dpi.0 as f32, dpi.0 as f32);
+    context.render_target.Resize(&D2D_SIZE_U {
+        width: width as u32,
+        height: height as u32,
+    })?;
+    let tokens = &context.state.qt.theme.tokens;
+    let corner_diameter = dpi.to_physical(tokens.border_radius_medium * 2f32);
+    let region = CreateRoundRectRgn(
+        0,
+        0,
+        width + 1,
+        height + 1,
+        corner_diameter,
+        corner_diameter,
+    );
+    SetWindowRgn(window, Some(region), true);
+    _ = InvalidateRect(Some(window), None, false);
+    shadow::show_beneath(
+        context.shadow,
+        window,
+        tokens,
+        Elevation::Shadow2,
+        tokens.border_radius_medium,
+    )
+}
+
+/// The work area of the monitor nearest to `rect`, which popups must stay within.
+pub(crate) unsafe fn get_work_area(rect: &RECT) -> RECT {
+    let monitor = MonitorFromRect(rect, MONITOR_DEFAULTTONEAREST);
+    let mut info = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    _ = GetMonitorInfoW(monitor, &mut info);
+    info.rcWork
+}
+
+pub(crate) unsafe fn hide(window: HWND) {
+    _ = ShowWindow(window, SW_HIDE);
+    if let Some(context) = get_context(window) {
+        context.text_layout = None;
+        shadow::hide(context.shadow);
+    }
+}
+
+unsafe fn on_create(window: HWND, state: State) -> Result<Context> {
+    let factory = D2D1CreateFactory::<ID2D1Factory1>(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        Some(&D2D1_FACTORY_OPTIONS::default()),
+    )?;
+    let dpi = Dpi::for_window(state.owner);
+    let render_target = factory.CreateHwndRenderTarget(
+        &render_target_properties(dpi),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U::default(),
+            presentOptions: Default::default(),
+        },
+    )?;
+    let direct_write_factory = DWriteCreateFactory::<IDWriteFactory>(DWRITE_FACTORY_TYPE_SHARED)?;
+    let text_format = state
+        .qt
+        .theme
+        .typography_styles
+        .body1
+        .create_text_format(&direct_write_factory)?;
+    let text_brush = render_target
+        .CreateSolidColorBrush(&state.qt.theme.tokens.color_neutral_foreground1, None)?;
+    let shadow = shadow::create(state.owner)?;
+    Ok(Context {
+        state,
+        text_layout: None,
+        render_target,
+        text_format,
+        text_brush,
+        shadow,
+    })
+}
+
+unsafe fn paint(context: &Context) -> Result<()> {
+    context.render_target.Clear(Some(
+        &context.state.qt.theme.tokens.color_neutral_background1,
+    ));
+    if let Some(text_layout) = &context.text_layout {
+        context.render_target.DrawTextLayout(
+            D2D_POINT_2F {
+                x: PADDING_HORIZONTAL,
+                y: PADDING_TOP,
+            },
+            text_layout,
+            &context.text_brush,
+            D2D1_DRAW_TEXT_OPTIONS_NONE,
+        );
+    }
+    Ok(())
+}
+
+unsafe fn on_paint(window: HWND, context: &Context) -> Result<()> {
+    let mut ps = PAINTSTRUCT::default();
+    BeginPaint(window, &mut ps);
+    context.render_target.BeginDraw();
+    let result = paint(context).and(context.render_target.EndDraw(None, None));
+    _ = EndPaint(window, &ps);
+    result
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_CREATE => unsafe {
+            let cs = l_param.0 as *const CREATESTRUCTW;
+            let raw = (*cs).lpCreateParams as *mut State;
+            let state = Box::<State>::from_raw(raw);
+            match on_create(window, *state) {
+                Ok(context) => {
+                    let boxed = Box::new(context);
+                    SetWindowLongPtrW(window, GWLP_USERDATA, Box::<Context>::into_raw(boxed) as _);
+                    LRESULT(TRUE.0 as isize)
+                }
+                Err(_) => LRESULT(FALSE.0 as isize),
+            }
+        },
+        WM_DESTROY => unsafe {
+            let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Context;
+            if !raw.is_null() {
+                SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+                let context = Box::<Context>::from_raw(raw);
+                _ = DestroyWindow(context.shadow);
+            }
+            LRESULT(0)
+        },
+        WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as isize),
+        WM_NCHITTEST => LRESULT(HTTRANSPARENT as isize),
+        WM_PAINT => unsafe {
+            if let Some(context) = get_context(window) {
+                let result = on_paint(window, context);
+                recover_lost_device(window, &result, || {
+                    context.recreate_device_resources(window)
+                });
+            }
+            LRESULT(0)
+        },
+        WM_PRINTCLIENT => unsafe {
+            if let Some(context) = get_context(window) {
+                let dc = HDC(w_param.0 as _);
+                _ = print_client(window, &context.render_target, dc, || paint(context));
+            }
+            LRESULT(0)
+        },
+        WM_ERASEBKGND => LRESULT(1),
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Host;
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn shadow_follows_the_tooltip() {
+        let host = Host::new(200, 100);
+        unsafe {
+            let tooltip = create(&host.qt, host.window).unwrap();
+            let anchor = RECT {
+                left: 10,
+                top: 10,
+                right: 50,
+                bottom: 30,
+            };
+            show(tooltip, w!("Tip"), &anchor).unwrap();
+            let shadow = get_context(tooltip).unwrap().shadow;
+            assert!(IsWindowVisible(shadow).as_bool());
+            assert_eq!(GetWindow(tooltip, GW_HWNDNEXT).unwrap(), shadow);
+            let mut tooltip_rect = RECT::default();
+            GetWindowRect(tooltip, &mut tooltip_rect).unwrap();
+            let mut shadow_rect = RECT::default();
+            GetWindowRect(shadow, &mut shadow_rect).unwrap();
+            // The shadow reaches past every side, and furthest below, where it is offset.
+            assert!(shadow_rect.left < tooltip_rect.left);
+            assert!(shadow_rect.top < tooltip_rect.top);
+            assert!(shadow_rect.right > tooltip_rect.right);
+            assert!(shadow_rect.bottom > tooltip_rect.bottom);
+
+            hide(tooltip);
+            assert!(!IsWindowVisible(shadow).as_bool());
+            DestroyWindow(tooltip).unwrap();
+            assert!(!IsWindow(Some(shadow)).as_bool());
+        }
+    }
+}
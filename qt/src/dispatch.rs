@@ -0,0 +1,149 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::mem::size_of;
+use std::sync::{Mutex, OnceLock};
+
+use windows::core::{w, Result, PCWSTR};
+use windows::Win32::Foundation::{E_FAIL, E_INVALIDARG, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, GetWindowThreadProcessId, PeekMessageW,
+    PostMessageW, RegisterClassExW, RegisterWindowMessageW, HWND_MESSAGE, MSG, PM_REMOVE,
+    WINDOW_EX_STYLE, WINDOW_STYLE, WNDCLASSEXW,
+};
+
+use crate::{window_class_name, ComponentHandle, QT};
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+/// The message-only window of each UI thread that has a `QT`, keyed by thread id. Posting holds
+/// the lock, so a dispatcher is never destroyed between being looked up and being posted to.
+static DISPATCHERS: Mutex<BTreeMap<u32, isize>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    static DISPATCHER: Dispatcher = const { Dispatcher(Cell::new(None)) };
+}
+
+struct Dispatcher(Cell<Option<HWND>>);
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        let Some(window) = self.0.get() else {
+            return;
+        };
+        let Ok(mut dispatchers) = DISPATCHERS.lock() else {
+            return;
+        };
+        dispatchers.remove(&unsafe { GetWindowThreadProcessId(window, None) });
+        unsafe {
+            // Callbacks that never ran are still owned by their messages.
+            let mut message = MSG::default();
+            while PeekMessageW(
+                &mut message,
+                Some(window),
+                post_message(),
+                post_message(),
+                PM_REMOVE,
+            )
+            .as_bool()
+            {
+                drop(Box::from_raw(message.lParam.0 as *mut Callback));
+            }
+            _ = DestroyWindow(window);
+        }
+    }
+}
+
+fn post_message() -> u32 {
+    static MESSAGE: OnceLock<u32> = OnceLock::new();
+    *MESSAGE.get_or_init(|| unsafe { RegisterWindowMessageW(w!("QT_POSTTOUI")) })
+}
+
+/// Every `QT` calls this on the thread it is created on, so that thread can receive callbacks.
+pub(crate) fn create_dispatcher() -> Result<()> {
+    DISPATCHER.with(|dispatcher| {
+        if dispatcher.0.get().is_some() {
+            return Ok(());
+        }
+        let class_name = window_class_name("QT_DISPATCHER", Some(window_proc));
+        unsafe {
+            let window_class = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                lpfnWndProc: Some(window_proc),
+                ..Default::default()
+            };
+            RegisterClassExW(&window_class);
+            let window = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                &class_name,
+                w!(""),
+                WINDOW_STYLE::default(),
+                0,
+                0,
+                0,
+                0,
+                Some(HWND_MESSAGE),
+                None,
+                None,
+                None,
+            )?;
+            DISPATCHERS
+                .lock()
+                .map_err(|_| E_FAIL)?
+                .insert(GetWindowThreadProcessId(window, None), window.0 as isize);
+            dispatcher.0.set(Some(window));
+        }
+        Ok(())
+    })
+}
+
+impl QT {
+    /// Runs `callback` on the thread that owns `window`, e.g. to update components once work on
+    /// another thread is done. May be called from any thread, but the owning thread must have
+    /// created a `QT` and must keep pumping messages. Fails with `E_INVALIDARG` if it has not.
+    pub fn post_to_ui(window: HWND, callback: Box<dyn FnOnce() + Send>) -> Result<()> {
+        let thread_id = unsafe { GetWindowThreadProcessId(window, None) };
+        let dispatchers = DISPATCHERS.lock().map_err(|_| E_FAIL)?;
+        let Some(&dispatcher) = dispatchers.get(&thread_id) else {
+            return Err(E_INVALIDARG.into());
+        };
+        // A fat pointer does not fit in an LPARAM, so the box is boxed again.
+        let callback = Box::into_raw(Box::new(callback));
+        unsafe {
+            PostMessageW(
+                Some(HWND(dispatcher as _)),
+                post_message(),
+                WPARAM(0),
+                LPARAM(callback as isize),
+            )
+            .inspect_err(|_| drop(Box::from_raw(callback)))
+        }
+    }
+}
+
+impl<C> ComponentHandle<C> {
+    /// Like [`QT::post_to_ui`] with this handle's window.
+    pub fn post_to_ui(&self, callback: Box<dyn FnOnce() + Send>) -> Result<()> {
+        QT::post_to_ui(self.window(), callback)
+    }
+
+    /// The component window, for use on its own thread, e.g. in a callback given to
+    /// [`ComponentHandle::post_to_ui`].
+    pub fn window(&self) -> HWND {
+        self.window
+    }
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    if message == post_message() {
+        let callback = unsafe { Box::from_raw(l_param.0 as *mut Callback) };
+        callback();
+        return LRESULT(0);
+    }
+    unsafe { DefWindowProcW(window, message, w_param, l_param) }
+}
@@ -0,0 +1,82 @@
+use std::cell::RefCell;
+
+use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetFocus, GetKeyState, IsWindowEnabled, SetFocus, VK_F6, VK_SHIFT,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetNextDlgTabItem, IsChild, IsWindow, IsWindowVisible, MSG, WM_KEYDOWN,
+};
+
+use crate::{track_input_kind, QT};
+
+thread_local! {
+    /// Focus is per thread, so regions are too. Kept sorted by order.
+    static REGIONS: RefCell<Vec<(HWND, i32)>> = const { RefCell::new(Vec::new()) };
+}
+
+impl QT {
+    /// Adds `window`, e.g. a toolbar or a content pane, to the regions that F6 and Shift+F6 cycle
+    /// focus through, in ascending `order`. Registering a window again moves it to `order`.
+    pub fn register_focus_region(&self, window: HWND, order: i32) {
+        REGIONS.with_borrow_mut(|regions| {
+            regions.retain(|(region, _)| *region != window);
+            let index = regions.partition_point(|(_, region_order)| *region_order <= order);
+            regions.insert(index, (window, order));
+        });
+    }
+
+    pub fn unregister_focus_region(&self, window: HWND) {
+        REGIONS.with_borrow_mut(|regions| regions.retain(|(region, _)| *region != window));
+    }
+
+    /// Moves focus to the next region on F6, or to the previous one on Shift+F6. Region navigation
+    /// is opt-in: call this from the message loop before `TranslateMessage`, as with
+    /// `IsDialogMessageW`. Returns true if it handled `message`, which must then not be dispatched.
+    pub fn translate_focus_region_key(message: &MSG) -> bool {
+        if message.message != WM_KEYDOWN || message.wParam.0 != VK_F6.0 as usize {
+            return false;
+        }
+        let regions: Vec<HWND> = REGIONS.with_borrow(|regions| {
+            regions
+                .iter()
+                .map(|(region, _)| *region)
+                .filter(|region| unsafe {
+                    IsWindow(Some(*region)).as_bool()
+                        && IsWindowVisible(*region).as_bool()
+                        && IsWindowEnabled(*region).as_bool()
+                })
+                .collect()
+        });
+        if regions.is_empty() {
+            return false;
+        }
+        unsafe {
+            let backward = GetKeyState(VK_SHIFT.0 as i32) < 0;
+            let focus = GetFocus();
+            let current = regions
+                .iter()
+                .position(|region| *region == focus || IsChild(*region, focus).as_bool());
+            let count = regions.len();
+            let next = match (current, backward) {
+                (Some(index), false) => (index + 1) % count,
+                (Some(index), true) => (index + count - 1) % count,
+                (None, false) => 0,
+                (None, true) => count - 1,
+            };
+            // The key never reaches a component, so it is recorded here for the focus ring.
+            track_input_kind(message.message);
+            focus_region(regions[next]);
+        }
+        true
+    }
+}
+
+// Focuses the first tab stop inside the region, or the region itself if it has none.
+unsafe fn focus_region(region: HWND) {
+    let target = GetNextDlgTabItem(region, None, false)
+        .ok()
+        .filter(|item| IsChild(region, *item).as_bool())
+        .unwrap_or(region);
+    _ = SetFocus(Some(target));
+}
@@ -1,10 +1,140 @@
-use windows::core::PCSTR;
+use std::rc::Rc;
+
+use windows::core::{Result, PCSTR};
+use windows::Win32::Foundation::E_INVALIDARG;
 
 pub mod calendar_month;
+pub mod checkmark;
+pub mod checkmark_circle;
+pub mod chevron_down;
 pub mod chevron_right;
+pub mod chevron_up;
+pub mod dismiss;
+pub mod error_circle;
+pub mod star;
+pub mod warning;
+
+#[derive(Clone)]
+pub(crate) enum IconData {
+    Static(PCSTR),
+    Owned(Rc<[u8]>),
+}
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct Icon {
-    pub(crate) svg: PCSTR,
+    pub(crate) svg: IconData,
     pub(crate) size: usize,
 }
+
+impl Icon {
+    /// An icon from SVG markup encoded as standard base64, e.g. embedded in the binary or fetched
+    /// from a web API. Whitespace in `data` is ignored. Fails with `E_INVALIDARG` if `data` is not
+    /// valid base64, including when its padding is missing or its last character has stray bits.
+    pub fn from_base64_svg(data: &str, size: usize) -> Result<Icon> {
+        let svg = decode_base64(data).ok_or(E_INVALIDARG)?;
+        Ok(Icon {
+            svg: IconData::Owned(svg.into()),
+            size,
+        })
+    }
+
+    pub(crate) fn svg_bytes(&self) -> &[u8] {
+        match &self.svg {
+            IconData::Static(svg) => unsafe { svg.as_bytes() },
+            IconData::Owned(svg) => svg,
+        }
+    }
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    let mut sextets = 0;
+    let mut padding = 0;
+    for char in data.bytes().filter(|char| !char.is_ascii_whitespace()) {
+        let value = match char {
+            b'A'..=b'Z' => char - b'A',
+            b'a'..=b'z' => char - b'a' + 26,
+            b'0'..=b'9' => char - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => {
+                padding += 1;
+                continue;
+            }
+            _ => return None,
+        };
+        // Padding only ever ends the data.
+        if padding > 0 {
+            return None;
+        }
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        sextets += 1;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    // The last quantum is padded to four characters, and a lone sextet cannot hold a byte.
+    if sextets % 4 == 1 || (sextets + padding) % 4 != 0 || padding > 2 {
+        return None;
+    }
+    // Bits left over after the last byte must be zero, or two encodings would decode alike.
+    if buffer & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_each_padding_variant() {
+        assert_eq!(decode_base64("").as_deref(), Some(&b""[..]));
+        assert_eq!(decode_base64("PHN2Zz4=").as_deref(), Some(&b"<svg>"[..]));
+        assert_eq!(decode_base64("PHN2").as_deref(), Some(&b"<sv"[..]));
+        assert_eq!(decode_base64("PHM=").as_deref(), Some(&b"<s"[..]));
+        assert_eq!(decode_base64("PA==").as_deref(), Some(&b"<"[..]));
+    }
+
+    #[test]
+    fn ignores_whitespace() {
+        assert_eq!(
+            decode_base64("PHN2\r\n Zz4=\n").as_deref(),
+            Some(&b"<svg>"[..])
+        );
+    }
+
+    #[test]
+    fn rejects_missing_padding() {
+        assert_eq!(decode_base64("PHN2Zz4"), None);
+        assert_eq!(decode_base64("PA"), None);
+        assert_eq!(decode_base64("PA="), None);
+    }
+
+    #[test]
+    fn rejects_mismatched_padding() {
+        assert_eq!(decode_base64("PHM=="), None);
+        assert_eq!(decode_base64("PHN2===="), None);
+        assert_eq!(decode_base64("P==="), None);
+        assert_eq!(decode_base64("PA==PA=="), None);
+    }
+
+    #[test]
+    fn rejects_nonzero_trailing_bits() {
+        assert_eq!(decode_base64("PB=="), None);
+        assert_eq!(decode_base64("PHN="), None);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(decode_base64("PHN2Zz4-"), None);
+        assert_eq!(decode_base64("<svg>"), None);
+        assert_eq!(decode_base64("PHN2\0Zz4="), None);
+        assert_eq!(decode_base64("===="), None);
+    }
+}
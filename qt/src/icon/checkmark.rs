@@ -0,0 +1,15 @@
+use crate::icon::{Icon, IconData};
+use windows_core::s;
+
+impl Icon {
+    pub fn checkmark_regular() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M7.03 13.9L3.56 10a.75.75 0 0 0-1.12 1l4 4.5c.29.32.79.34 1.09.03l10.5-10.5a.75.75 0 0 0-1.06-1.06l-9.94 9.94z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+}
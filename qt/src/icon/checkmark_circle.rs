@@ -0,0 +1,15 @@
+use crate::icon::{Icon, IconData};
+use windows_core::s;
+
+impl Icon {
+    pub fn checkmark_circle_filled() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M10 2a8 8 0 1 1 0 16 8 8 0 0 1 0-16zm3.36 5.65a.5.5 0 0 0-.64-.06l-.07.06L9 11.3 7.35 9.65l-.07-.06a.5.5 0 0 0-.7.7l.07.07 2 2 .07.06c.17.11.4.11.56 0l.07-.06 4-4 .07-.08a.5.5 0 0 0-.06-.63z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use crate::icon::{Icon, IconData};
+use windows_core::s;
+
+impl Icon {
+    pub fn chevron_down_regular() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M15.85292 7.64582C16.04853 7.84073 16.0491 8.15731 15.85418 8.35292L10.3892 13.8374C10.17426 14.0531 9.8249 14.0531 9.61 13.8374L4.145 8.35292C3.9501 8.15731 3.9507 7.84073 4.1463 7.64582C4.3419 7.4509 4.6585 7.45147 4.8534 7.64708L9.9996 12.8117L15.14582 7.64708C15.34073 7.45147 15.65731 7.4509 15.85292 7.64582Z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+    pub fn chevron_down_filled() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M15.79306 7.73271C16.07875 8.03263 16.06721 8.50737 15.76729 8.79306L10.51682 13.7944C10.22715 14.0703 9.7719 14.0703 9.4822 13.7944L4.2318 8.79306C3.9319 8.50737 3.9203 8.03263 4.206 7.73271C4.4917 7.43279 4.9664 7.42125 5.2664 7.70694L9.9995 12.2155L14.73271 7.70694C15.03263 7.42125 15.50736 7.43279 15.79306 7.73271Z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+}
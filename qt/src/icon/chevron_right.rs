@@ -1,24 +1,24 @@
-use crate::icon::Icon;
+use crate::icon::{Icon, IconData};
 use windows_core::s;
 
 impl Icon {
     pub fn chevron_right_regular() -> Icon {
         Icon {
-            svg: s!(
+            svg: IconData::Static(s!(
                 r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
 <path d="M7.64582 4.14708C7.84073 3.95147 8.15731 3.9509 8.35292 4.14582L13.8374 9.6108C14.0531 9.82574 14.0531 10.1751 13.8374 10.39L8.35292 15.855C8.15731 16.0499 7.84073 16.0493 7.64582 15.8537C7.4509 15.6581 7.45147 15.3415 7.64708 15.1466L12.8117 10.0004L7.64708 4.85418C7.45147 4.65927 7.4509 4.34269 7.64582 4.14708Z" fill="#212121"/>
 </svg>"##
-            ),
+            )),
             size: 20,
         }
     }
     pub fn chevron_right_filled() -> Icon {
         Icon {
-            svg: s!(
+            svg: IconData::Static(s!(
                 r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
 <path d="M7.73271 4.20694C8.03263 3.92125 8.50737 3.93279 8.79306 4.23271L13.7944 9.48318C14.0703 9.77285 14.0703 10.2281 13.7944 10.5178L8.79306 15.7682C8.50737 16.0681 8.03263 16.0797 7.73271 15.794C7.43279 15.5083 7.42125 15.0336 7.70694 14.7336L12.2155 10.0005L7.70694 5.26729C7.42125 4.96737 7.43279 4.49264 7.73271 4.20694Z" fill="#212121"/>
 </svg>"##
-            ),
+            )),
             size: 20,
         }
     }
@@ -0,0 +1,25 @@
+use crate::icon::{Icon, IconData};
+use windows_core::s;
+
+impl Icon {
+    pub fn chevron_up_regular() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M4.14708 12.35418C3.95147 12.15927 3.9509 11.84269 4.14582 11.64708L9.6108 6.1626C9.82574 5.9469 10.1751 5.9469 10.39 6.1626L15.855 11.64708C16.0499 11.84269 16.0493 12.15927 15.8537 12.35418C15.6581 12.5491 15.3415 12.54853 15.1466 12.35292L10.0004 7.1883L4.85418 12.35292C4.65927 12.54853 4.34269 12.5491 4.14708 12.35418Z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+    pub fn chevron_up_filled() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M4.20694 12.26729C3.92125 11.96737 3.93279 11.49263 4.23271 11.20694L9.48318 6.2056C9.77285 5.9297 10.2281 5.9297 10.5178 6.2056L15.7682 11.20694C16.0681 11.49263 16.0797 11.96737 15.794 12.26729C15.5083 12.56721 15.0336 12.57875 14.7336 12.29306L10.0005 7.7845L5.26729 12.29306C4.96737 12.57875 4.49264 12.56721 4.20694 12.26729Z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use crate::icon::{Icon, IconData};
+use windows_core::s;
+
+impl Icon {
+    pub fn dismiss_regular() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M4.09 4.22l.06-.07a.5.5 0 0 1 .63-.06l.07.06L10 9.29l5.15-5.14a.5.5 0 0 1 .63-.06l.07.06c.18.17.2.44.06.63l-.06.07L10.71 10l5.14 5.15c.18.17.2.44.06.63l-.06.07a.5.5 0 0 1-.63.06l-.07-.06L10 10.71l-5.15 5.14a.5.5 0 0 1-.63.06l-.07-.06a.5.5 0 0 1-.06-.63l.06-.07L9.29 10 4.15 4.85a.5.5 0 0 1-.06-.63l.06-.07-.06.07z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+}
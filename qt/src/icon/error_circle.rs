@@ -0,0 +1,15 @@
+use crate::icon::{Icon, IconData};
+use windows_core::s;
+
+impl Icon {
+    pub fn error_circle_filled() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M10 2a8 8 0 1 1 0 16 8 8 0 0 1 0-16zm0 10.5a.75.75 0 1 0 0 1.5.75.75 0 0 0 0-1.5zM10 6a.5.5 0 0 0-.5.41v4.68a.5.5 0 0 0 1 0V6.41A.5.5 0 0 0 10 6z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use crate::icon::{Icon, IconData};
+use windows::core::s;
+
+impl Icon {
+    pub fn star_regular() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M9.10433 2.89923C9.47114 2.15598 10.531 2.15599 10.8978 2.89923L12.8282 6.81073L17.1448 7.43797C17.9651 7.55715 18.2926 8.56513 17.699 9.14366L14.5755 12.1883L15.3129 16.4875C15.453 17.3044 14.5956 17.9274 13.8619 17.5417L10.0011 15.5119L6.14018 17.5417C5.40655 17.9274 4.54913 17.3044 4.68924 16.4875L5.4266 12.1883L2.30308 9.14366C1.70956 8.56513 2.03708 7.55715 2.8573 7.43797L7.17389 6.81073L9.10433 2.89923ZM10.0011 3.34174L8.07066 7.25323C7.92499 7.54839 7.64343 7.75298 7.31771 7.80031L3.00112 8.42755L6.12463 11.4722C6.36033 11.7019 6.46788 12.0329 6.41224 12.3573L5.67488 16.6566L9.53577 14.6268C9.82712 14.4736 10.1751 14.4736 10.4664 14.6268L14.3273 16.6566L13.5899 12.3573C13.5343 12.0329 13.6418 11.7019 13.8775 11.4722L17.001 8.42755L12.6845 7.80031C12.3587 7.75298 12.0772 7.54839 11.9315 7.25323L10.0011 3.34174Z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+    pub fn star_filled() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M9.10433 2.89923C9.47114 2.15598 10.531 2.15599 10.8978 2.89923L12.8282 6.81073L17.1448 7.43797C17.9651 7.55715 18.2926 8.56513 17.699 9.14366L14.5755 12.1883L15.3129 16.4875C15.453 17.3044 14.5956 17.9274 13.8619 17.5417L10.0011 15.5119L6.14018 17.5417C5.40655 17.9274 4.54913 17.3044 4.68924 16.4875L5.4266 12.1883L2.30308 9.14366C1.70956 8.56513 2.03708 7.55715 2.8573 7.43797L7.17389 6.81073L9.10433 2.89923Z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+}
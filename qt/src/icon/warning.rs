@@ -0,0 +1,15 @@
+use crate::icon::{Icon, IconData};
+use windows_core::s;
+
+impl Icon {
+    pub fn warning_filled() -> Icon {
+        Icon {
+            svg: IconData::Static(s!(
+                r##"<svg width="20" height="20" viewBox="0 0 20 20" fill="none" xmlns="http://www.w3.org/2000/svg">
+<path d="M8.68 2.79a1.5 1.5 0 0 1 2.64 0l6.5 12A1.5 1.5 0 0 1 16.5 17h-13a1.5 1.5 0 0 1-1.32-2.21l6.5-12zM10.5 7.5a.5.5 0 0 0-1 0v4a.5.5 0 0 0 1 0v-4zm.25 6.25a.75.75 0 1 0-1.5 0 .75.75 0 0 0 1.5 0z" fill="#212121"/>
+</svg>"##
+            )),
+            size: 20,
+        }
+    }
+}
@@ -1,12 +1,47 @@
 extern crate self as qt;
 
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+use std::mem::size_of;
 use std::rc::Rc;
+use std::sync::{Mutex, OnceLock};
 
-use windows::Win32::Foundation::HWND;
+use windows::core::{w, Interface, Result, HSTRING};
+use windows::Win32::Foundation::{
+    BOOL, COLORREF, D2DERR_RECREATE_TARGET, HWND, LPARAM, LRESULT, RECT, TRUE, WPARAM,
+};
+use windows::Win32::Graphics::Direct2D::Common::{
+    D2D1_ALPHA_MODE_IGNORE, D2D1_COLOR_F, D2D1_PIXEL_FORMAT, D2D_SIZE_U,
+};
+use windows::Win32::Graphics::Direct2D::{
+    ID2D1GdiInteropRenderTarget, ID2D1HwndRenderTarget, D2D1_DC_INITIALIZE_MODE_COPY,
+    D2D1_HWND_RENDER_TARGET_PROPERTIES, D2D1_RENDER_TARGET_PROPERTIES,
+    D2D1_RENDER_TARGET_USAGE_GDI_COMPATIBLE,
+};
+use windows::Win32::Graphics::Dwm::{
+    DwmSetWindowAttribute, DWMWA_USE_IMMERSIVE_DARK_MODE, DWMWA_WINDOW_CORNER_PREFERENCE,
+    DWMWINDOWATTRIBUTE, DWM_WINDOW_CORNER_PREFERENCE,
+};
+use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_B8G8R8A8_UNORM;
+use windows::Win32::Graphics::Gdi::{
+    BitBlt, InvalidateRect, RedrawWindow, HBRUSH, HDC, RDW_ERASE, RDW_FRAME, RDW_INVALIDATE,
+    SRCCOPY,
+};
 use windows::Win32::UI::HiDpi::GetDpiForWindow;
-use windows::Win32::UI::WindowsAndMessaging::USER_DEFAULT_SCREEN_DPI;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetKeyState, IsWindowEnabled, VK_LBUTTON, VK_MBUTTON, VK_RBUTTON, VK_TAB,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    EnumChildWindows, GetClassNameW, GetClientRect, GetParent, LoadCursorW, RegisterWindowMessageW,
+    SendMessageW, SetCursor, HTTRANSPARENT, IDC_ARROW, USER_DEFAULT_SCREEN_DPI, WM_KEYDOWN,
+    WM_LBUTTONDOWN, WM_MBUTTONDOWN, WM_NCHITTEST, WM_RBUTTONDOWN, WM_SETCURSOR, WM_SYSKEYDOWN,
+    WNDPROC,
+};
 
-use crate::theme::Theme;
+use crate::animation::AnimationClock;
+use crate::theme::{convert_to_color_ref, Theme};
 
 pub struct MouseEvent {
     pub on_click: Box<dyn Fn(&HWND)>,
@@ -20,23 +55,705 @@ impl Default for MouseEvent {
     }
 }
 
+/// Entry point for creating components.
+///
+/// `QT` is deliberately neither `Send` nor `Sync`: the windows it creates belong to the thread
+/// that created them and must only be driven from that thread's message loop. Components keep a
+/// clone of it alive for their whole lifetime. To use the library on another UI thread, construct
+/// a separate `QT` there with [`QT::default`]. Worker threads update components through a
+/// [`ComponentHandle`] or [`QT::post_to_ui`] instead.
 #[derive(Clone)]
 pub struct QT {
     theme: Rc<Theme>,
+    animation: Rc<AnimationClock>,
 }
 
 impl QT {
     pub fn default() -> Self {
+        let theme = Theme::web_light();
+        let animation = AnimationClock::new(&theme.tokens);
+        // Without a dispatcher, worker threads cannot post to this thread; nothing else breaks.
+        _ = dispatch::create_dispatcher();
         QT {
-            theme: Rc::new(Theme::web_light()),
+            theme: Rc::new(theme),
+            animation: Rc::new(animation),
         }
     }
+
+    /// A brush of the theme's window background, for parents to paint behind components. It is
+    /// owned by the theme and must not be deleted.
+    pub fn background_brush(&self) -> HBRUSH {
+        self.theme.background_brush()
+    }
+
+    pub fn background_colorref(&self) -> COLORREF {
+        convert_to_color_ref(&self.theme.tokens.color_neutral_background2)
+    }
+
+    /// Repaints every component among the descendants of `parent`, e.g. after the parent changes
+    /// the background color it reports. Only windows created by this crate are affected.
+    pub fn refresh_children(&self, parent: HWND) {
+        unsafe {
+            _ = EnumChildWindows(Some(parent), Some(refresh_child), LPARAM(0));
+        }
+    }
+}
+
+extern "system" fn refresh_child(window: HWND, _l_param: LPARAM) -> BOOL {
+    let class_name = get_class_name(window);
+    if CLASS_NAMES
+        .lock()
+        .is_ok_and(|names| names.contains(&class_name))
+    {
+        unsafe {
+            _ = RedrawWindow(
+                Some(window),
+                None,
+                None,
+                RDW_INVALIDATE | RDW_ERASE | RDW_FRAME,
+            );
+        }
+    }
+    TRUE
+}
+
+/// The registered message a component sends to its parent to learn the color behind its
+/// transparent areas. `wParam` is the component window and `lParam` points to a `COLORREF` for the
+/// parent to fill in. The parent returns nonzero when it has handled the message.
+pub fn background_color_message() -> u32 {
+    static MESSAGE: OnceLock<u32> = OnceLock::new();
+    *MESSAGE.get_or_init(|| unsafe { RegisterWindowMessageW(w!("QT_GETBACKGROUNDCOLOR")) })
+}
+
+/// Asks the parent for its background color, or returns `fallback` if the parent does not answer.
+pub(crate) unsafe fn get_parent_background_color(
+    window: HWND,
+    fallback: &D2D1_COLOR_F,
+) -> D2D1_COLOR_F {
+    let Ok(parent) = GetParent(window) else {
+        return *fallback;
+    };
+    let mut color = COLORREF::default();
+    let handled = SendMessageW(
+        parent,
+        background_color_message(),
+        Some(WPARAM(window.0 as usize)),
+        Some(LPARAM(&mut color as *mut COLORREF as isize)),
+    );
+    if handled.0 == 0 {
+        return *fallback;
+    }
+    D2D1_COLOR_F {
+        r: (color.0 & 0xff) as f32 / 255.0,
+        g: ((color.0 >> 8) & 0xff) as f32 / 255.0,
+        b: ((color.0 >> 16) & 0xff) as f32 / 255.0,
+        a: 1.0,
+    }
+}
+
+/// A component window that can be moved to other threads, returned by the `create_*` function of
+/// the component `C`, e.g. [`ButtonHandle`] by [`QT::create_button`].
+///
+/// Its methods only post to the window's thread, which then applies them. A component's internal
+/// state is never touched from the calling thread, so every function taking an `HWND` must still
+/// be called on the thread that created the component.
+pub struct ComponentHandle<C> {
+    window: HWND,
+    component: PhantomData<fn() -> C>,
+}
+
+pub type ButtonHandle = ComponentHandle<component::button::Button>;
+pub type InputHandle = ComponentHandle<component::input::Input>;
+pub type ProgressBarHandle = ComponentHandle<component::progress_bar::ProgressBar>;
+
+// Posting may be done from any thread, and that is all a handle does with the window.
+unsafe impl<C> Send for ComponentHandle<C> {}
+unsafe impl<C> Sync for ComponentHandle<C> {}
+
+impl<C> ComponentHandle<C> {
+    pub(crate) fn new(window: HWND) -> Self {
+        ComponentHandle {
+            window,
+            component: PhantomData,
+        }
+    }
+}
+
+impl<C> Clone for ComponentHandle<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for ComponentHandle<C> {}
+
+impl<C> PartialEq for ComponentHandle<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.window == other.window
+    }
+}
+
+impl<C> Eq for ComponentHandle<C> {}
+
+impl<C> Debug for ComponentHandle<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ComponentHandle")
+            .field(&self.window)
+            .finish()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Dpi(pub u32);
+
+/// A size in device-independent pixels. Components round it up to whole physical pixels.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct SizeDips {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Overrides the corner rounding a component otherwise takes from its shape and the theme.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CornerRadius {
+    /// Fully rounded ends, e.g. for search fields.
+    Pill,
+    /// A radius in DIPs. Zero gives sharp corners.
+    Dips(f32),
+}
+
+/// Whether the parts of a component outside its visible content take mouse input. Corners cut off
+/// by a component's rounded shape, and the gaps between progress bar segments, always let clicks
+/// through to the parent, since window regions leave them out. Inputs and progress bars paint the
+/// rest of their shape, so only buttons take this setting.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum HitTestBehavior {
+    #[default]
+    Normal,
+    /// The parent gets mouse input over the empty space around the content of components that
+    /// paint no background there, e.g. beside the text of a transparent button.
+    TransparentOutsideContent,
+}
+
+impl CornerRadius {
+    /// The corner diameter in physical pixels of a `width` by `height` region. Painting uses
+    /// half of it converted back to DIPs, so the region and the painted outline always agree.
+    pub(crate) fn to_physical_diameter(self, dpi: Dpi, width: i32, height: i32) -> i32 {
+        let max = width.min(height).max(0);
+        match self {
+            CornerRadius::Pill => max,
+            CornerRadius::Dips(radius) => dpi.to_physical(radius.max(0f32) * 2f32).min(max),
+        }
+    }
+}
+
+impl Dpi {
+    pub fn for_window(window: HWND) -> Self {
+        unsafe { Dpi(GetDpiForWindow(window)) }
+    }
+
+    pub fn scaling_factor(&self) -> f32 {
+        self.0 as f32 / USER_DEFAULT_SCREEN_DPI as f32
+    }
+
+    pub fn to_physical(&self, dip: f32) -> i32 {
+        (dip * self.scaling_factor()).round() as i32
+    }
+
+    pub fn to_physical_ceil(&self, dip: f32) -> i32 {
+        (dip * self.scaling_factor()).ceil() as i32
+    }
+
+    pub fn to_dip(&self, physical: i32) -> f32 {
+        physical as f32 / self.scaling_factor()
+    }
+}
+
+/// Window classes are registered once per copy of this crate and shared by every `QT`
+/// instance. The class name is suffixed with the address of its window procedure, so two
+/// copies of the crate loaded into one process (e.g. by separate plugins) never collide.
+fn format_class_name(name: &str, window_proc: WNDPROC) -> String {
+    let address = window_proc.map_or(0, |window_proc| window_proc as usize);
+    format!("{}_{:x}", name, address)
+}
+
+fn get_class_name(window: HWND) -> String {
+    let mut buffer = [0u16; 256];
+    let length = unsafe { GetClassNameW(window, &mut buffer) };
+    String::from_utf16_lossy(&buffer[..length.max(0) as usize])
+}
+
+/// Whether `window` was created from the class that [`window_class_name`] returns for `name` and
+/// `window_proc`. Public functions taking any `HWND` check this before reading the component's
+/// context from `GWLP_USERDATA`.
+pub(crate) fn is_window_of_class(window: HWND, name: &str, window_proc: WNDPROC) -> bool {
+    get_class_name(window) == format_class_name(name, window_proc)
+}
+
+pub(crate) fn window_class_name(name: &str, window_proc: WNDPROC) -> HSTRING {
+    let class_name = format_class_name(name, window_proc);
+    if let Ok(mut names) = CLASS_NAMES.lock() {
+        names.insert(class_name.clone());
+    }
+    HSTRING::from(class_name)
+}
+
+// Every class name handed out above, so that components can be told apart from other windows.
+static CLASS_NAMES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+pub(crate) fn render_target_properties(dpi: Dpi) -> D2D1_RENDER_TARGET_PROPERTIES {
+    D2D1_RENDER_TARGET_PROPERTIES {
+        pixelFormat: D2D1_PIXEL_FORMAT {
+            format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            alphaMode: D2D1_ALPHA_MODE_IGNORE,
+        },
+        dpiX: dpi.0 as f32,
+        dpiY: dpi.0 as f32,
+        usage: D2D1_RENDER_TARGET_USAGE_GDI_COMPATIBLE,
+        ..Default::default()
+    }
 }
 
-pub(crate) fn get_scaling_factor(window: HWND) -> f32 {
-    unsafe { GetDpiForWindow(window) as f32 / USER_DEFAULT_SCREEN_DPI as f32 }
+/// Whether drawing failed because the device was lost, e.g. to a driver update or a remote desktop
+/// reconnect. The render target and everything created from it must then be recreated.
+pub(crate) fn is_device_lost(result: &Result<()>) -> bool {
+    matches!(result, Err(error) if error.code() == D2DERR_RECREATE_TARGET)
 }
 
+#[cfg(test)]
+thread_local! {
+    static FORCE_DEVICE_LOST: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Makes the next paint of any component on this thread count as having lost the device.
+#[cfg(test)]
+pub(crate) fn force_device_lost() {
+    FORCE_DEVICE_LOST.set(true);
+}
+
+/// Handles the `result` of painting `window`: after a lost device, `recreate` rebuilds the
+/// component's device resources and a repaint is queued. When recreation fails too, e.g. while
+/// the driver restarts, nothing is queued, so the window waits for its next invalidation instead
+/// of retrying in a loop. Returns whether a repaint was queued.
+pub(crate) unsafe fn recover_lost_device(
+    window: HWND,
+    result: &Result<()>,
+    recreate: impl FnOnce() -> Result<()>,
+) -> bool {
+    #[cfg(test)]
+    let is_lost = FORCE_DEVICE_LOST.take() || is_device_lost(result);
+    #[cfg(not(test))]
+    let is_lost = is_device_lost(result);
+    if !is_lost || recreate().is_err() {
+        return false;
+    }
+    _ = InvalidateRect(Some(window), None, false);
+    true
+}
+
+/// Replaces a lost render target with one for the current client area. It comes from the same
+/// factory, so factory resources like stroke styles keep working with it.
+pub(crate) unsafe fn recreate_render_target(
+    window: HWND,
+    render_target: &ID2D1HwndRenderTarget,
+) -> Result<ID2D1HwndRenderTarget> {
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    render_target.GetFactory()?.CreateHwndRenderTarget(
+        &render_target_properties(Dpi::for_window(window)),
+        &D2D1_HWND_RENDER_TARGET_PROPERTIES {
+            hwnd: window,
+            pixelSize: D2D_SIZE_U {
+                width: rect.right as u32,
+                height: rect.bottom as u32,
+            },
+            presentOptions: Default::default(),
+        },
+    )
+}
+
+/// Handles `WM_PRINTCLIENT` for Direct2D components: `paint` draws into the component's own
+/// render target, whose content is then copied into the supplied DC through GDI interop.
+pub(crate) unsafe fn print_client(
+    window: HWND,
+    render_target: &ID2D1HwndRenderTarget,
+    dc: HDC,
+    paint: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let mut rect = RECT::default();
+    GetClientRect(window, &mut rect)?;
+    render_target.BeginDraw();
+    let result = paint().and_then(|_| {
+        let interop = render_target.cast::<ID2D1GdiInteropRenderTarget>()?;
+        let source = interop.GetDC(D2D1_DC_INITIALIZE_MODE_COPY)?;
+        let copied = BitBlt(
+            dc,
+            0,
+            0,
+            rect.right,
+            rect.bottom,
+            Some(source),
+            0,
+            0,
+            SRCCOPY,
+        );
+        interop.ReleaseDC(None)?;
+        copied
+    });
+    render_target.EndDraw(None, None).and(result)
+}
+
+/// Asks DWM for a dark caption on a top-level window when `theme` is dark. Windows 10 builds
+/// before 20H1 only know the attribute under its undocumented number, 19, so that is tried next.
+/// Older systems reject both and keep the light caption.
+pub(crate) unsafe fn apply_title_bar_theme(window: HWND, theme: &Theme) {
+    if !theme.is_dark() {
+        return;
+    }
+    let value = TRUE;
+    for attribute in [
+        DWMWA_USE_IMMERSIVE_DARK_MODE,
+        DWMWINDOWATTRIBUTE(DWMWA_USE_IMMERSIVE_DARK_MODE.0 - 1),
+    ] {
+        if DwmSetWindowAttribute(
+            window,
+            attribute,
+            &value as *const _ as _,
+            size_of::<BOOL>() as u32,
+        )
+        .is_ok()
+        {
+            return;
+        }
+    }
+}
+
+/// Lets DWM round the corners of `window`, which also gives a popup the soft Fluent shadow in place
+/// of the square `CS_DROPSHADOW` one. Returns false before Windows 11, which cannot, so that
+/// callers fall back to their own window region.
+pub(crate) unsafe fn apply_corner_preference(
+    window: HWND,
+    preference: DWM_WINDOW_CORNER_PREFERENCE,
+) -> bool {
+    DwmSetWindowAttribute(
+        window,
+        DWMWA_WINDOW_CORNER_PREFERENCE,
+        &preference as *const _ as _,
+        size_of::<DWM_WINDOW_CORNER_PREFERENCE>() as u32,
+    )
+    .is_ok()
+}
+
+/// Answers `WM_GETTEXT` with `text` the way `DefWindowProc` does: the copy is truncated to fit the
+/// buffer, always terminated, and the number of characters copied is returned.
+pub(crate) unsafe fn copy_window_text(text: &[u16], w_param: WPARAM, l_param: LPARAM) -> LRESULT {
+    let max_length = w_param.0;
+    let dest = l_param.0 as *mut u16;
+    if max_length == 0 || dest.is_null() {
+        return LRESULT(0);
+    }
+    let length = text.len().min(max_length - 1);
+    std::ptr::copy_nonoverlapping(text.as_ptr(), dest, length);
+    *dest.add(length) = 0;
+    LRESULT(length as isize)
+}
+
+thread_local! {
+    static LAST_INPUT_FROM_MOUSE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Components report the messages they receive so that a later focus change can tell whether the
+/// user was last on the keyboard or the mouse.
+pub(crate) fn track_input_kind(message: u32) {
+    match message {
+        WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN => LAST_INPUT_FROM_MOUSE.set(true),
+        WM_KEYDOWN | WM_SYSKEYDOWN => LAST_INPUT_FROM_MOUSE.set(false),
+        _ => {}
+    }
+}
+
+/// Whether a component that is receiving focus should draw its focus ring. Tab always shows it
+/// and a pressed mouse button never does; otherwise the last input on this thread decides.
+pub(crate) fn is_focus_visible() -> bool {
+    unsafe {
+        if GetKeyState(VK_TAB.0 as i32) < 0 {
+            return true;
+        }
+        if [VK_LBUTTON, VK_RBUTTON, VK_MBUTTON]
+            .iter()
+            .any(|key| GetKeyState(key.0 as i32) < 0)
+        {
+            return false;
+        }
+    }
+    !LAST_INPUT_FROM_MOUSE.get()
+}
+
+pub(crate) fn is_enabled(window: HWND) -> bool {
+    unsafe { IsWindowEnabled(window).as_bool() }
+}
+
+/// Like native disabled controls, a disabled component lets clicks through to its parent and
+/// shows the arrow cursor. Returns the result for mouse messages a disabled component answers.
+pub(crate) unsafe fn handle_disabled_mouse(window: HWND, message: u32) -> Option<LRESULT> {
+    if is_enabled(window) {
+        return None;
+    }
+    match message {
+        WM_NCHITTEST => Some(LRESULT(HTTRANSPARENT as isize)),
+        WM_SETCURSOR => {
+            let cursor = LoadCursorW(None, IDC_ARROW).ok()?;
+            SetCursor(Some(cursor));
+            Some(LRESULT(TRUE.0 as isize))
+        }
+        _ => None,
+    }
+}
+
+pub mod animation;
 pub mod component;
+mod dispatch;
+mod focus_region;
 pub mod icon;
+mod shadow;
+#[cfg(test)]
+mod test_support;
 mod theme;
+mod type_ahead;
+
+#[cfg(test)]
+mod tests {
+    use windows::Win32::Foundation::E_FAIL;
+    use windows::Win32::Graphics::Gdi::{GetUpdateRect, ValidateRect};
+    use windows::Win32::UI::WindowsAndMessaging::DestroyWindow;
+
+    use super::*;
+    use crate::component::button::{Button, ButtonOptions};
+    use crate::component::input::{self, Input, InputOptions};
+    use crate::component::progress_bar::{self, ProgressBar, ProgressBarOptions};
+    use crate::component::rating::{self, Rating, RatingOptions};
+    use crate::component::segmented_control::{SegmentedControl, SegmentedControlOptions};
+    use crate::component::spinner::{self, Spinner, SpinnerOptions};
+    use crate::component::tag::{Tag, TagOptions};
+    use crate::component::Component;
+    use crate::test_support::{is_near, pump_messages, rgb, Capture, Host};
+
+    // A memory DIB starts out black, so background pixels show that the component drew into it,
+    // and the others that it drew its content and not only a cleared surface.
+    fn assert_prints<C: Component>(host: &Host, options: C::Options) {
+        let window = host.create::<C>(10, 10, options);
+        pump_messages();
+        let capture = Capture::of(window);
+        let background = rgb(&host.qt.theme.tokens.color_neutral_background1);
+        let printed = capture
+            .pixels
+            .iter()
+            .filter(|pixel| is_near(**pixel, background, 0x08))
+            .count();
+        assert!(printed > 0, "nothing was printed");
+        assert!(
+            printed < capture.pixels.len(),
+            "only the background was printed"
+        );
+        unsafe { DestroyWindow(window).unwrap() };
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn print_client_draws_content_into_a_memory_dc() {
+        let host = Host::new(400, 200);
+        assert_prints::<Button>(
+            &host,
+            ButtonOptions {
+                text: w!("Print"),
+                ..ButtonOptions::default()
+            },
+        );
+        assert_prints::<Input>(
+            &host,
+            InputOptions {
+                width: 200,
+                size: input::Size::Medium,
+                appearance: input::Appearance::Outline,
+                corner: None,
+                default_value: Some(w!("Printed text")),
+                input_type: input::Type::Text,
+                number_format: None,
+                placeholder: None,
+                history_size: None,
+                revert_on_escape: false,
+                event: Default::default(),
+            },
+        );
+        assert_prints::<ProgressBar>(
+            &host,
+            ProgressBarOptions {
+                width: 200,
+                shape: progress_bar::Shape::Rounded,
+                corner: None,
+                value: Some(0.5),
+                max: None,
+                thickness: progress_bar::Thickness::Large,
+                fill: progress_bar::Fill::Solid,
+                segments: None,
+                steps: None,
+                event: Default::default(),
+            },
+        );
+        assert_prints::<Rating>(
+            &host,
+            RatingOptions {
+                max: 5,
+                value: 3.0,
+                step: rating::Step::Whole,
+                size: rating::Size::Large,
+                event: Default::default(),
+            },
+        );
+        assert_prints::<SegmentedControl>(
+            &host,
+            SegmentedControlOptions {
+                items: vec![(w!("Day"), 1), (w!("Week"), 2)],
+                selected_index: 0,
+                event: Default::default(),
+            },
+        );
+        assert_prints::<Spinner>(
+            &host,
+            SpinnerOptions {
+                size: spinner::Size::Large,
+                label: Some(w!("Loading")),
+            },
+        );
+        assert_prints::<Tag>(
+            &host,
+            TagOptions {
+                text: w!("Printed"),
+                dismissible: true,
+                event: Default::default(),
+            },
+        );
+    }
+
+    #[test]
+    fn only_recreate_target_means_device_lost() {
+        assert!(is_device_lost(&Err(windows::core::Error::from_hresult(
+            D2DERR_RECREATE_TARGET
+        ))));
+        assert!(!is_device_lost(&Err(windows::core::Error::from_hresult(
+            E_FAIL
+        ))));
+        assert!(!is_device_lost(&Ok(())));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn failed_recreation_queues_no_repaint() {
+        let host = Host::new(100, 100);
+        let lost = Err(D2DERR_RECREATE_TARGET.into());
+        unsafe {
+            _ = ValidateRect(Some(host.window), None);
+            assert!(!recover_lost_device(host.window, &lost, || Err(
+                E_FAIL.into()
+            )));
+            assert!(!GetUpdateRect(host.window, None, false).as_bool());
+            assert!(recover_lost_device(host.window, &lost, || Ok(())));
+            assert!(GetUpdateRect(host.window, None, false).as_bool());
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]
+    fn recreation_waits_for_a_lost_device() {
+        let host = Host::new(100, 100);
+        unsafe {
+            assert!(!recover_lost_device(host.window, &Ok(()), || {
+                panic!("recreated a working device")
+            }));
+            force_device_lost();
+            assert!(recover_lost_device(host.window, &Ok(()), || Ok(())));
+        }
+    }
+
+    #[test]
+    fn pill_diameter_is_the_shorter_side() {
+        assert_eq!(
+            CornerRadius::Pill.to_physical_diameter(Dpi(96), 120, 32),
+            32
+        );
+        assert_eq!(
+            CornerRadius::Pill.to_physical_diameter(Dpi(144), 20, 48),
+            20
+        );
+    }
+
+    const DPIS: [u32; 4] = [96, 120, 144, 192];
+
+    #[test]
+    fn scaling_factor_follows_dpi() {
+        let factors = DPIS.map(|dpi| Dpi(dpi).scaling_factor());
+        assert_eq!(factors, [1f32, 1.25f32, 1.5f32, 2f32]);
+    }
+
+    #[test]
+    fn to_physical_rounds_to_nearest_pixel() {
+        for dpi in DPIS {
+            for dip in [0.5f32, 1f32, 2f32, 4.4f32, 12f32, 32f32, 96.6f32] {
+                let exact = dip * dpi as f32 / 96f32;
+                let physical = Dpi(dpi).to_physical(dip);
+                assert!(
+                    (physical as f32 - exact).abs() <= 0.5,
+                    "{dip} DIPs at {dpi} DPI"
+                );
+                let ceil = Dpi(dpi).to_physical_ceil(dip);
+                assert!(ceil as f32 >= exact && (ceil as f32 - exact) < 1f32);
+            }
+        }
+    }
+
+    #[test]
+    fn to_dip_inverts_to_physical() {
+        for dpi in DPIS {
+            for physical in [0, 1, 5, 30, 125] {
+                let dpi = Dpi(dpi);
+                assert_eq!(dpi.to_physical(dpi.to_dip(physical)), physical);
+            }
+        }
+    }
+
+    #[test]
+    fn physical_sizes_grow_with_dpi() {
+        for dip in [1f32, 2.5f32, 24f32, 32f32, 40f32, 64f32] {
+            let sizes = DPIS.map(|dpi| Dpi(dpi).to_physical(dip));
+            assert!(
+                sizes.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{dip} DIPs"
+            );
+        }
+    }
+
+    #[test]
+    fn radius_diameter_scales_with_dpi() {
+        let corner = CornerRadius::Dips(4f32);
+        assert_eq!(corner.to_physical_diameter(Dpi(96), 120, 32), 8);
+        assert_eq!(corner.to_physical_diameter(Dpi(120), 120, 32), 10);
+        assert_eq!(corner.to_physical_diameter(Dpi(144), 120, 32), 12);
+        assert_eq!(corner.to_physical_diameter(Dpi(192), 120, 32), 16);
+    }
+
+    #[test]
+    fn radius_diameter_is_clamped() {
+        assert_eq!(
+            CornerRadius::Dips(100f32).to_physical_diameter(Dpi(96), 120, 32),
+            32
+        );
+        assert_eq!(
+            CornerRadius::Dips(-2f32).to_physical_diameter(Dpi(96), 120, 32),
+            0
+        );
+        assert_eq!(
+            CornerRadius::Dips(4f32).to_physical_diameter(Dpi(96), -5, 32),
+            0
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! Fluent elevation for top-level surfaces. The shadow is drawn with per-pixel alpha into a layered
+//! window that sits right beneath the surface, since the surface itself fills its whole window.
+
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Win32::Foundation::{
+    COLORREF, HINSTANCE, HWND, LPARAM, LRESULT, POINT, RECT, SIZE, WPARAM,
+};
+use windows::Win32::Graphics::Direct2D::Common::D2D1_COLOR_F;
+use windows::Win32::Graphics::Gdi::{
+    CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, SelectObject, AC_SRC_ALPHA,
+    AC_SRC_OVER, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, BLENDFUNCTION, DIB_RGB_COLORS,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::theme::Tokens;
+use crate::{window_class_name, Dpi};
+
+/// The Fluent shadow levels in use: `Shadow2` for tooltips and `Shadow8` for dialogs.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Elevation {
+    Shadow2,
+    Shadow8,
+}
+
+/// One blurred copy of the surface's shape, in DIPs. `blur` is the CSS blur radius.
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct ShadowLayer {
+    offset_x: f32,
+    offset_y: f32,
+    blur: f32,
+    color: D2D1_COLOR_F,
+}
+
+impl Elevation {
+    // Each level is its own key shadow over the ambient one that every level shares.
+    fn get_layers(self, tokens: &Tokens) -> [ShadowLayer; 2] {
+        let key = match self {
+            Elevation::Shadow2 => ShadowLayer {
+                offset_x: tokens.shadow_2_offset_x,
+                offset_y: tokens.shadow_2_offset_y,
+                blur: tokens.shadow_2_blur,
+                color: tokens.shadow_2_color,
+            },
+            Elevation::Shadow8 => ShadowLayer {
+                offset_x: tokens.shadow_8_offset_x,
+                offset_y: tokens.shadow_8_offset_y,
+                blur: tokens.shadow_8_blur,
+                color: tokens.shadow_8_color,
+            },
+        };
+        let ambient = ShadowLayer {
+            offset_x: 0f32,
+            offset_y: 0f32,
+            blur: tokens.shadow_ambient_blur,
+            color: tokens.shadow_ambient_color,
+        };
+        [key, ambient]
+    }
+}
+
+// How far the layers reach beyond the surface: a Gaussian blur fades out within three standard
+// deviations, which is one and a half times the CSS blur radius.
+fn get_margin(layers: &[ShadowLayer], scale: f32) -> i32 {
+    layers
+        .iter()
+        .map(|layer| {
+            let reach = layer.blur * 1.5f32 + layer.offset_x.abs().max(layer.offset_y.abs());
+            (reach * scale).ceil() as i32
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+// The signed distance from a point to a rounded rectangle centered on the origin, negative inside.
+fn get_rounded_rect_distance(
+    x: f32,
+    y: f32,
+    half_width: f32,
+    half_height: f32,
+    radius: f32,
+) -> f32 {
+    let radius = radius.min(half_width).min(half_height).max(0f32);
+    let qx = x.abs() - (half_width - radius);
+    let qy = y.abs() - (half_height - radius);
+    let outside = qx.max(0f32).hypot(qy.max(0f32));
+    let inside = qx.max(qy).min(0f32);
+    outside + inside - radius
+}
+
+// Abramowitz and Stegun 7.1.26, within 1.5e-7 of the exact function.
+fn erf(x: f64) -> f64 {
+    let t = 1f64 / (1f64 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1f64 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+// How much of a layer covers a point `distance` away from the edge of the shape, for a blur with
+// a standard deviation of half the blur radius, as in CSS.
+fn get_coverage(distance: f32, blur: f32) -> f32 {
+    if blur <= 0f32 {
+        return if distance <= 0f32 { 1f32 } else { 0f32 };
+    }
+    let deviation = blur / 2f32;
+    let x = distance / (deviation * std::f32::consts::SQRT_2);
+    0.5f32 * (1f32 - erf(x as f64) as f32)
+}
+
+/// The premultiplied `0xAARRGGBB` pixels of a shadow for a surface of `width` by `height` pixels
+/// with corners of `radius` pixels, row by row from the top. The surface sits `margin` pixels in
+/// from every side.
+fn render(
+    layers: &[ShadowLayer],
+    scale: f32,
+    width: i32,
+    height: i32,
+    radius: f32,
+    margin: i32,
+) -> Vec<u32> {
+    let full_width = width + margin * 2;
+    let full_height = height + margin * 2;
+    let mut pixels = Vec::with_capacity((full_width * full_height) as usize);
+    for row in 0..full_height {
+        for column in 0..full_width {
+            // Pixel centers, relative to the center of the surface.
+            let x = column as f32 + 0.5f32 - margin as f32 - width as f32 / 2f32;
+            let y = row as f32 + 0.5f32 - margin as f32 - height as f32 / 2f32;
+            let mut pixel = [0f32; 4];
+            for layer in layers {
+                let distance = get_rounded_rect_distance(
+                    x - layer.offset_x * scale,
+                    y - layer.offset_y * scale,
+                    width as f32 / 2f32,
+                    height as f32 / 2f32,
+                    radius,
+                );
+                let alpha = layer.color.a * get_coverage(distance, layer.blur * scale);
+                let source = [
+                    layer.color.r * alpha,
+                    layer.color.g * alpha,
+                    layer.color.b * alpha,
+                    alpha,
+                ];
+                for (channel, value) in pixel.iter_mut().zip(source) {
+                    *channel = value + *channel * (1f32 - alpha);
+                }
+            }
+            let [r, g, b, a] = pixel.map(|channel| (channel * 255f32).round() as u32);
+            pixels.push(a << 24 | r << 16 | g << 8 | b);
+        }
+    }
+    pixels
+}
+
+/// A hidden shadow window for surfaces owned by `owner`.
+pub(crate) unsafe fn create(owner: HWND) -> Result<HWND> {
+    let class_name = window_class_name("QT_SHADOW", Some(window_proc));
+    let window_class = WNDCLASSEXW {
+        cbSize: size_of::<WNDCLASSEXW>() as u32,
+        lpszClassName: PCWSTR(class_name.as_ptr()),
+        lpfnWndProc: Some(window_proc),
+        ..Default::default()
+    };
+    RegisterClassExW(&window_class);
+    CreateWindowExW(
+        WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_NOACTIVATE | WS_EX_TOOLWINDOW,
+        &class_name,
+        w!(""),
+        WS_POPUP,
+        0,
+        0,
+        0,
+        0,
+        Some(owner),
+        None,
+        Some(HINSTANCE(GetWindowLongPtrW(owner, GWLP_HINSTANCE) as _)),
+        None,
+    )
+}
+
+/// Shows `shadow` around the current window rectangle of `surface`, right beneath it in the
+/// z-order. `radius` is the corner radius of the surface in DIPs.
+pub(crate) unsafe fn show_beneath(
+    shadow: HWND,
+    surface: HWND,
+    tokens: &Tokens,
+    elevation: Elevation,
+    radius: f32,
+) -> Result<()> {
+    let mut rect = RECT::default();
+    GetWindowRect(surface, &mut rect)?;
+    let scale = Dpi::for_window(surface).scaling_factor();
+    let layers = elevation.get_layers(tokens);
+    let margin = get_margin(&layers, scale);
+    let width = rect.right - rect.left;
+    let height = rect.bottom - rect.top;
+    let size = SIZE {
+        cx: width + margin * 2,
+        cy: height + margin * 2,
+    };
+    let position = POINT {
+        x: rect.left - margin,
+        y: rect.top - margin,
+    };
+
+    let pixels = render(&layers, scale, width, height, radius * scale, margin);
+    let dc = CreateCompatibleDC(None);
+    let info = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: size.cx,
+            // Negative for a top-down bitmap.
+            biHeight: -size.cy,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut bits = std::ptr::null_mut();
+    let result =
+        CreateDIBSection(Some(dc), &info, DIB_RGB_COLORS, &mut bits, None, 0).and_then(|bitmap| {
+            std::ptr::copy_nonoverlapping(pixels.as_ptr(), bits as *mut u32, pixels.len());
+            let previous = SelectObject(dc, bitmap.into());
+            let blend = BLENDFUNCTION {
+                BlendOp: AC_SRC_OVER as u8,
+                BlendFlags: 0,
+                SourceConstantAlpha: 255,
+                AlphaFormat: AC_SRC_ALPHA as u8,
+            };
+            let result = UpdateLayeredWindow(
+                shadow,
+                None,
+                Some(&position),
+                Some(&size),
+                Some(dc),
+                Some(&POINT::default()),
+                COLORREF(0),
+                Some(&blend),
+                ULW_ALPHA,
+            );
+            SelectObject(dc, previous);
+            _ = DeleteObject(bitmap.into());
+            result
+        });
+    _ = DeleteDC(dc);
+    result?;
+    SetWindowPos(
+        shadow,
+        Some(surface),
+        0,
+        0,
+        0,
+        0,
+        SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE | SWP_SHOWWINDOW,
+    )
+}
+
+pub(crate) unsafe fn hide(shadow: HWND) {
+    _ = ShowWindow(shadow, SW_HIDE);
+}
+
+extern "system" fn window_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_MOUSEACTIVATE => LRESULT(MA_NOACTIVATE as isize),
+        WM_NCHITTEST => LRESULT(HTTRANSPARENT as isize),
+        _ => unsafe { DefWindowProcW(window, message, w_param, l_param) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::Theme;
+
+    fn alpha(pixel: u32) -> u32 {
+        pixel >> 24
+    }
+
+    #[test]
+    fn coverage_is_half_on_the_edge() {
+        assert!((get_coverage(0f32, 8f32) - 0.5f32).abs() < 1e-4);
+        assert!(get_coverage(-12f32, 8f32) > 0.998f32);
+        assert!(get_coverage(12f32, 8f32) < 0.002f32);
+        assert_eq!(get_coverage(0.5f32, 0f32), 0f32);
+        assert_eq!(get_coverage(-0.5f32, 0f32), 1f32);
+    }
+
+    #[test]
+    fn distance_follows_the_rounded_corners() {
+        // 10 by 10 with corners of 2, centered on the origin.
+        assert_eq!(
+            get_rounded_rect_distance(0f32, 0f32, 5f32, 5f32, 2f32),
+            -5f32
+        );
+        assert_eq!(
+            get_rounded_rect_distance(8f32, 0f32, 5f32, 5f32, 2f32),
+            3f32
+        );
+        let corner = get_rounded_rect_distance(5f32, 5f32, 5f32, 5f32, 2f32);
+        assert!((corner - (2f32 * 2f32.sqrt() - 2f32)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn margin_covers_blur_and_offset() {
+        let theme = Theme::web_light();
+        let tokens = &theme.tokens;
+        let layers = Elevation::Shadow8.get_layers(tokens);
+        // 8 DIPs of blur reach 12 DIPs out, and the shadow is moved 4 DIPs down.
+        assert_eq!(get_margin(&layers, 1f32), 16);
+        assert_eq!(get_margin(&layers, 1.5f32), 24);
+        assert!(
+            get_margin(&Elevation::Shadow2.get_layers(tokens), 1f32) < get_margin(&layers, 1f32)
+        );
+    }
+
+    #[test]
+    fn shadow_is_heavier_below_the_surface() {
+        let theme = Theme::web_light();
+        let tokens = &theme.tokens;
+        let layers = Elevation::Shadow8.get_layers(tokens);
+        let margin = get_margin(&layers, 1f32);
+        let (width, height) = (80, 60);
+        let pixels = render(&layers, 1f32, width, height, 4f32, margin);
+        let full_width = width + margin * 2;
+        let at = |x: i32, y: i32| pixels[(y * full_width + x) as usize];
+        let center_x = margin + width / 2;
+        let above = at(center_x, margin - 3);
+        let below = at(center_x, margin + height + 2);
+        assert!(alpha(below) > alpha(above));
+        // The corners of the image are beyond the reach of every layer.
+        assert_eq!(at(0, 0), 0);
+        // Under the surface, both layers are fully there.
+        let under = alpha(at(center_x, margin + height / 2));
+        let expected =
+            1f32 - (1f32 - tokens.shadow_8_color.a) * (1f32 - tokens.shadow_ambient_color.a);
+        assert!(under.abs_diff((expected * 255f32).round() as u32) <= 1);
+    }
+
+    #[test]
+    fn shadow_pixels_are_premultiplied() {
+        let color = D2D1_COLOR_F {
+            r: 1f32,
+            g: 0.5f32,
+            b: 0f32,
+            a: 0.5f32,
+        };
+        let layer = ShadowLayer {
+            offset_x: 0f32,
+            offset_y: 0f32,
+            blur: 0f32,
+            color,
+        };
+        let pixels = render(&[layer], 1f32, 2, 2, 0f32, 1);
+        // The surface sits one pixel in from every side.
+        assert_eq!(pixels[5], 0x80_80_40_00);
+        assert_eq!(pixels[0], 0);
+    }
+}
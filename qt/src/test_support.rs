@@ -0,0 +1,273 @@
+//! Helpers for unit tests that drive real windows. Such tests only run on Windows, and are marked
+//! with `#[cfg_attr(not(target_os = "windows"), ignore = "needs a Windows desktop")]`.
+
+use std::cell::{Cell, RefCell};
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, WPARAM};
+use windows::Win32::Graphics::Direct2D::Common::D2D1_COLOR_F;
+use windows::Win32::Graphics::Gdi::{
+    ClientToScreen, CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, SelectObject,
+    BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+};
+use windows::Win32::System::Ole::OleInitialize;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    SendInput, INPUT, INPUT_0, INPUT_MOUSE, MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_LEFTDOWN,
+    MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MOVE, MOUSEINPUT, MOUSE_EVENT_FLAGS,
+};
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use crate::component::Component;
+use crate::QT;
+
+thread_local! {
+    static HOST_CLICKS: Cell<usize> = const { Cell::new(0) };
+    static HOST_NOTIFICATIONS: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A top-level window hosting the components under test, destroyed when dropped.
+pub(crate) struct Host {
+    pub(crate) window: HWND,
+    pub(crate) qt: QT,
+}
+
+impl Host {
+    /// A visible window with a client area of `width` by `height` physical pixels.
+    pub(crate) fn new(width: i32, height: i32) -> Self {
+        unsafe {
+            // Inputs register as drop targets, which needs OLE on the test's thread.
+            _ = OleInitialize(None);
+            let class_name = w!("QT_TEST_HOST");
+            let window_class = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpszClassName: class_name,
+                lpfnWndProc: Some(host_proc),
+                ..Default::default()
+            };
+            RegisterClassExW(&window_class);
+            let mut rect = RECT {
+                left: 0,
+                top: 0,
+                right: width,
+                bottom: height,
+            };
+            _ = AdjustWindowRectEx(
+                &mut rect,
+                WS_OVERLAPPEDWINDOW,
+                false,
+                WINDOW_EX_STYLE::default(),
+            );
+            let window = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                class_name,
+                w!(""),
+                WS_OVERLAPPEDWINDOW | WS_VISIBLE,
+                0,
+                0,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+            Host {
+                window,
+                qt: QT::default(),
+            }
+        }
+    }
+
+    pub(crate) fn create<C: Component>(&self, x: i32, y: i32, options: C::Options) -> HWND {
+        C::create(&self.qt, self.window, x, y, options).unwrap()
+    }
+
+    /// How many left clicks have reached the host's own window procedure on this thread.
+    pub(crate) fn clicks(&self) -> usize {
+        HOST_CLICKS.get()
+    }
+
+    /// The `WM_COMMAND` notification codes that have reached the host on this thread, in order.
+    pub(crate) fn notifications(&self) -> Vec<u32> {
+        HOST_NOTIFICATIONS.with_borrow(|codes| codes.clone())
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        unsafe {
+            _ = DestroyWindow(self.window);
+        }
+        pump_messages();
+    }
+}
+
+extern "system" fn host_proc(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_LBUTTONDOWN => HOST_CLICKS.set(HOST_CLICKS.get() + 1),
+        WM_COMMAND => HOST_NOTIFICATIONS
+            .with_borrow_mut(|codes| codes.push((w_param.0 >> 16) as u32 & 0xffff)),
+        _ => {}
+    }
+    unsafe { DefWindowProcW(window, message, w_param, l_param) }
+}
+
+/// Clicks the real mouse at the client point `x`, `y` of `window`, and handles the messages
+/// that follow. The point must be on the primary monitor, and since the mouse is shared, tests
+/// running in parallel should not click at the same time.
+pub(crate) fn click(window: HWND, x: i32, y: i32) {
+    let mut point = POINT { x, y };
+    unsafe {
+        _ = ClientToScreen(window, &mut point);
+        _ = SetForegroundWindow(GetAncestor(window, GA_ROOT));
+        // Absolute coordinates are normalized to 0..=65535 across the primary monitor.
+        let dx = point.x * 65536 / GetSystemMetrics(SM_CXSCREEN);
+        let dy = point.y * 65536 / GetSystemMetrics(SM_CYSCREEN);
+        let input = |flags: MOUSE_EVENT_FLAGS| INPUT {
+            r#type: INPUT_MOUSE,
+            Anonymous: INPUT_0 {
+                mi: MOUSEINPUT {
+                    dx,
+                    dy,
+                    dwFlags: flags | MOUSEEVENTF_ABSOLUTE,
+                    ..Default::default()
+                },
+            },
+        };
+        SendInput(
+            &[
+                input(MOUSEEVENTF_MOVE),
+                input(MOUSEEVENTF_LEFTDOWN),
+                input(MOUSEEVENTF_LEFTUP),
+            ],
+            size_of::<INPUT>() as i32,
+        );
+    }
+    // Injected input reaches the thread's queue asynchronously.
+    for _ in 0..20 {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        pump_messages();
+    }
+}
+
+/// Handles every message already queued for the thread, e.g. posted ones and pending paints.
+pub(crate) fn pump_messages() {
+    unsafe {
+        let mut message = MSG::default();
+        while PeekMessageW(&mut message, None, 0, 0, PM_REMOVE).as_bool() {
+            _ = TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    }
+}
+
+pub(crate) fn client_size(window: HWND) -> (i32, i32) {
+    let mut rect = RECT::default();
+    unsafe { GetClientRect(window, &mut rect).unwrap() };
+    (rect.right, rect.bottom)
+}
+
+/// The window a click at the client point `x`, `y` of `window` would reach. Like real clicks, this
+/// skips the parts of windows outside their regions and those that answer `HTTRANSPARENT`.
+pub(crate) fn window_at(window: HWND, x: i32, y: i32) -> HWND {
+    let mut point = POINT { x, y };
+    unsafe {
+        _ = ClientToScreen(window, &mut point);
+        WindowFromPoint(point)
+    }
+}
+
+/// The client area of a component as drawn by its `WM_PRINTCLIENT` handler, in `0x00RRGGBB`
+/// pixels, row by row from the top.
+pub(crate) struct Capture {
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    pub(crate) pixels: Vec<u32>,
+}
+
+impl Capture {
+    pub(crate) fn of(window: HWND) -> Self {
+        let (width, height) = client_size(window);
+        unsafe {
+            let dc = CreateCompatibleDC(None);
+            let info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: width,
+                    // Negative for a top-down bitmap.
+                    biHeight: -height,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let mut bits = std::ptr::null_mut();
+            let bitmap =
+                CreateDIBSection(Some(dc), &info, DIB_RGB_COLORS, &mut bits, None, 0).unwrap();
+            let previous = SelectObject(dc, bitmap.into());
+            SendMessageW(
+                window,
+                WM_PRINTCLIENT,
+                Some(WPARAM(dc.0 as usize)),
+                Some(LPARAM(PRF_CLIENT as isize)),
+            );
+            let pixels = std::slice::from_raw_parts(bits as *const u32, (width * height) as usize)
+                .iter()
+                .map(|pixel| pixel & 0x00ff_ffff)
+                .collect();
+            SelectObject(dc, previous);
+            _ = DeleteObject(bitmap.into());
+            _ = DeleteDC(dc);
+            Capture {
+                width,
+                height,
+                pixels,
+            }
+        }
+    }
+
+    pub(crate) fn pixel(&self, x: i32, y: i32) -> u32 {
+        // A point off the right edge would otherwise read the next row.
+        assert!(
+            (0..self.width).contains(&x) && (0..self.height).contains(&y),
+            "({x}, {y}) is outside the {}x{} capture",
+            self.width,
+            self.height
+        );
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+/// `color` as an opaque `0x00RRGGBB` pixel.
+pub(crate) fn rgb(color: &D2D1_COLOR_F) -> u32 {
+    let channel = |value: f32| (value.clamp(0f32, 1f32) * 255f32).round() as u32;
+    channel(color.r) << 16 | channel(color.g) << 8 | channel(color.b)
+}
+
+/// Whether each channel of `pixel` is within `tolerance` of `expected`, which leaves room for
+/// antialiasing and ClearType.
+pub(crate) fn is_near(pixel: u32, expected: u32, tolerance: u32) -> bool {
+    (0..3).all(|channel| {
+        let shift = channel * 8;
+        let a = pixel >> shift & 0xff;
+        let b = expected >> shift & 0xff;
+        a.abs_diff(b) <= tolerance
+    })
+}
+
+pub(crate) fn window_text(window: HWND) -> String {
+    unsafe {
+        let mut buffer = [0u16; 256];
+        let length = GetWindowTextW(window, &mut buffer);
+        String::from_utf16_lossy(&buffer[..length as usize])
+    }
+}
@@ -1,16 +1,28 @@
+use std::cell::OnceCell;
+use std::mem::size_of;
+
 use windows::core::w;
 use windows::core::Result;
 use windows::core::PCWSTR;
+use windows::Win32::Foundation::COLORREF;
 use windows::Win32::Graphics::Direct2D::Common::D2D1_COLOR_F;
 use windows::Win32::Graphics::DirectWrite::{
     IDWriteFactory, IDWriteTextFormat, DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STYLE_NORMAL,
     DWRITE_FONT_WEIGHT, DWRITE_FONT_WEIGHT_REGULAR, DWRITE_FONT_WEIGHT_SEMI_BOLD,
     DWRITE_LINE_SPACING_METHOD_DEFAULT,
 };
+use windows::Win32::Graphics::Gdi::{CreateSolidBrush, DeleteObject, HBRUSH};
+use windows::Win32::UI::Accessibility::{HCF_HIGHCONTRASTON, HIGHCONTRASTW};
+use windows::Win32::UI::WindowsAndMessaging::{
+    SystemParametersInfoW, SPI_GETHIGHCONTRAST, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+};
+
 pub(crate) struct Tokens {
     pub color_neutral_background1: D2D1_COLOR_F,
     pub color_neutral_background1_hover: D2D1_COLOR_F,
     pub color_neutral_background1_pressed: D2D1_COLOR_F,
+    pub color_neutral_background1_selected: D2D1_COLOR_F,
+    pub color_neutral_background2: D2D1_COLOR_F,
     pub color_neutral_background3: D2D1_COLOR_F,
     pub color_neutral_background6: D2D1_COLOR_F,
     pub color_brand_background: D2D1_COLOR_F,
@@ -18,17 +30,27 @@ pub(crate) struct Tokens {
     pub color_brand_background_pressed: D2D1_COLOR_F,
     pub color_compound_brand_background: D2D1_COLOR_F,
     pub color_compound_brand_stroke: D2D1_COLOR_F,
+    pub color_brand_stroke1: D2D1_COLOR_F,
+    pub color_brand_stroke2_contrast: D2D1_COLOR_F,
+    pub color_brand_foreground_link: D2D1_COLOR_F,
+    pub color_brand_foreground_link_hover: D2D1_COLOR_F,
+    pub color_brand_foreground_link_pressed: D2D1_COLOR_F,
     pub color_neutral_foreground1: D2D1_COLOR_F,
     pub color_neutral_foreground1_hover: D2D1_COLOR_F,
     pub color_neutral_foreground1_pressed: D2D1_COLOR_F,
     pub color_neutral_foreground_on_brand: D2D1_COLOR_F,
     pub color_neutral_foreground2: D2D1_COLOR_F,
+    pub color_neutral_foreground3: D2D1_COLOR_F,
     pub color_neutral_foreground_disabled: D2D1_COLOR_F,
     pub color_neutral_stroke1: D2D1_COLOR_F,
     pub color_neutral_stroke1_hover: D2D1_COLOR_F,
     pub color_neutral_stroke1_pressed: D2D1_COLOR_F,
     pub color_neutral_stroke2: D2D1_COLOR_F,
     pub color_neutral_stroke_accessible: D2D1_COLOR_F,
+    pub color_stroke_focus2: D2D1_COLOR_F,
+    pub color_status_success_foreground1: D2D1_COLOR_F,
+    pub color_status_warning_foreground1: D2D1_COLOR_F,
+    pub color_status_danger_foreground1: D2D1_COLOR_F,
     pub stroke_width_thin: f32,
     pub font_family_base: PCWSTR,
     pub font_weight_regular: DWRITE_FONT_WEIGHT,
@@ -42,17 +64,38 @@ pub(crate) struct Tokens {
     pub line_height_base300: f32,
     pub line_height_base400: f32,
     pub line_height_base500: f32,
+    pub spacing_horizontal_xxs: f32,
     pub spacing_horizontal_xs: f32,
     pub spacing_horizontal_s_nudge: f32,
     pub spacing_horizontal_s: f32,
     pub spacing_horizontal_m: f32,
     pub spacing_vertical_s_nudge: f32,
     pub border_radius_none: f32,
+    pub border_radius_small: f32,
     pub border_radius_medium: f32,
-    pub curve_easy_ease: [f64; 4],
+    pub border_radius_large: f32,
+    pub shadow_2_offset_x: f32,
+    pub shadow_2_offset_y: f32,
+    pub shadow_2_blur: f32,
+    pub shadow_2_color: D2D1_COLOR_F,
+    pub shadow_8_offset_x: f32,
+    pub shadow_8_offset_y: f32,
+    pub shadow_8_blur: f32,
+    pub shadow_8_color: D2D1_COLOR_F,
+    pub shadow_ambient_blur: f32,
+    pub shadow_ambient_color: D2D1_COLOR_F,
+    pub curve_accelerate_max: [f64; 4],
+    pub curve_accelerate_min: [f64; 4],
+    pub curve_decelerate_max: [f64; 4],
     pub curve_decelerate_mid: [f64; 4],
-    pub duration_faster: f64,
+    pub curve_decelerate_min: [f64; 4],
+    pub curve_easy_ease_max: [f64; 4],
+    pub curve_easy_ease: [f64; 4],
+    pub duration_ultra_fast: f64,
+    pub duration_fast: f64,
     pub duration_normal: f64,
+    pub duration_gentle: f64,
+    pub duration_slow: f64,
 }
 
 macro_rules! rgb {
@@ -74,12 +117,21 @@ macro_rules! rgb {
     }};
 }
 
+macro_rules! rgba {
+    ($hex:expr, $alpha:expr) => {{
+        let color = rgb!($hex);
+        D2D1_COLOR_F { a: $alpha, ..color }
+    }};
+}
+
 impl Tokens {
     fn web_light() -> Self {
         Tokens {
             color_neutral_background1: rgb!("#ffffff"),
             color_neutral_background1_hover: rgb!("#f5f5f5"),
             color_neutral_background1_pressed: rgb!("#e0e0e0"),
+            color_neutral_background1_selected: rgb!("#ebebeb"),
+            color_neutral_background2: rgb!("#fafafa"),
             color_neutral_background3: rgb!("#f5f5f5"),
             color_neutral_background6: rgb!("#e6e6e6"),
             color_brand_background: rgb!("#0f6cbd"),
@@ -87,17 +139,27 @@ impl Tokens {
             color_brand_background_pressed: rgb!("#0c3b5e"),
             color_compound_brand_background: rgb!("#0f6cbd"),
             color_compound_brand_stroke: rgb!("#0f6cbd"),
+            color_brand_stroke1: rgb!("#0f6cbd"),
+            color_brand_stroke2_contrast: rgb!("#b4d6fa"),
+            color_brand_foreground_link: rgb!("#115ea3"),
+            color_brand_foreground_link_hover: rgb!("#0f548c"),
+            color_brand_foreground_link_pressed: rgb!("#0c3b5e"),
             color_neutral_foreground1: rgb!("#242424"),
             color_neutral_foreground1_hover: rgb!("#242424"),
             color_neutral_foreground1_pressed: rgb!("#242424"),
             color_neutral_foreground_on_brand: rgb!("#ffffff"),
             color_neutral_foreground2: rgb!("#424242"),
+            color_neutral_foreground3: rgb!("#616161"),
             color_neutral_foreground_disabled: rgb!("#bdbdbd"),
             color_neutral_stroke1: rgb!("#d1d1d1"),
             color_neutral_stroke1_hover: rgb!("#c7c7c7"),
             color_neutral_stroke1_pressed: rgb!("#b3b3b3"),
             color_neutral_stroke2: rgb!("#e0e0e0"),
             color_neutral_stroke_accessible: rgb!("#616161"),
+            color_stroke_focus2: rgb!("#000000"),
+            color_status_success_foreground1: rgb!("#0e700e"),
+            color_status_warning_foreground1: rgb!("#bc4b09"),
+            color_status_danger_foreground1: rgb!("#b10e1c"),
             stroke_width_thin: 1.0,
             font_family_base: w!("Segoe UI"),
             font_weight_regular: DWRITE_FONT_WEIGHT_REGULAR,
@@ -111,17 +173,38 @@ impl Tokens {
             line_height_base300: 20f32,
             line_height_base400: 22f32,
             line_height_base500: 28f32,
+            spacing_horizontal_xxs: 2f32,
             spacing_horizontal_xs: 4f32,
             spacing_horizontal_s_nudge: 6f32,
             spacing_horizontal_s: 8f32,
             spacing_horizontal_m: 12f32,
             spacing_vertical_s_nudge: 6f32,
             border_radius_none: 0f32,
+            border_radius_small: 2f32,
             border_radius_medium: 4f32,
-            curve_easy_ease: [0.33, 0.0, 0.67, 1.0],
+            border_radius_large: 6f32,
+            shadow_2_offset_x: 0f32,
+            shadow_2_offset_y: 1f32,
+            shadow_2_blur: 2f32,
+            shadow_2_color: rgba!("#000000", 0.14),
+            shadow_8_offset_x: 0f32,
+            shadow_8_offset_y: 4f32,
+            shadow_8_blur: 8f32,
+            shadow_8_color: rgba!("#000000", 0.14),
+            shadow_ambient_blur: 2f32,
+            shadow_ambient_color: rgba!("#000000", 0.12),
+            curve_accelerate_max: [0.9, 0.1, 1.0, 0.2],
+            curve_accelerate_min: [0.8, 0.0, 0.78, 1.0],
+            curve_decelerate_max: [0.1, 0.9, 0.2, 1.0],
             curve_decelerate_mid: [0.0, 0.0, 0.0, 1.0],
-            duration_faster: 0.1,
+            curve_decelerate_min: [0.33, 0.0, 0.1, 1.0],
+            curve_easy_ease_max: [0.8, 0.0, 0.2, 1.0],
+            curve_easy_ease: [0.33, 0.0, 0.67, 1.0],
+            duration_ultra_fast: 0.05,
+            duration_fast: 0.1,
             duration_normal: 0.2,
+            duration_gentle: 0.25,
+            duration_slow: 0.3,
         }
     }
 }
@@ -161,6 +244,7 @@ pub(crate) struct TypographyStyles {
     pub body1: TypographyStyle,
     pub body2: TypographyStyle,
     pub subtitle1: TypographyStyle,
+    pub subtitle2: TypographyStyle,
 }
 
 impl TypographyStyles {
@@ -190,6 +274,12 @@ impl TypographyStyles {
                 font_weight: tokens.font_weight_semibold,
                 line_height: tokens.line_height_base500,
             },
+            subtitle2: TypographyStyle {
+                font_family: tokens.font_family_base,
+                font_size: tokens.font_size_base400,
+                font_weight: tokens.font_weight_semibold,
+                line_height: tokens.line_height_base400,
+            },
         }
     }
 }
@@ -197,6 +287,7 @@ impl TypographyStyles {
 pub(crate) struct Theme {
     pub tokens: Tokens,
     pub typography_styles: TypographyStyles,
+    background_brush: OnceCell<HBRUSH>,
 }
 
 impl Theme {
@@ -209,6 +300,73 @@ impl Theme {
         Theme {
             tokens,
             typography_styles,
+            background_brush: OnceCell::new(),
         }
     }
+
+    /// The brush is created on first use and lives as long as the theme, so a new theme gets a
+    /// new brush.
+    pub(crate) fn background_brush(&self) -> HBRUSH {
+        *self.background_brush.get_or_init(|| unsafe {
+            CreateSolidBrush(convert_to_color_ref(&self.tokens.color_neutral_background2))
+        })
+    }
+
+    /// A theme is dark when its base background is closer to black than to white.
+    pub(crate) fn is_dark(&self) -> bool {
+        let background = &self.tokens.color_neutral_background1;
+        0.2126 * background.r + 0.7152 * background.g + 0.0722 * background.b < 0.5
+    }
+}
+
+impl Drop for Theme {
+    fn drop(&mut self) {
+        if let Some(brush) = self.background_brush.get() {
+            unsafe {
+                _ = DeleteObject((*brush).into());
+            }
+        }
+    }
+}
+
+pub(crate) fn convert_to_color_ref(from: &D2D1_COLOR_F) -> COLORREF {
+    let r = (from.r * 255.0) as u32;
+    let g = (from.g * 255.0) as u32;
+    let b = (from.b * 255.0) as u32;
+    COLORREF(b << 16 | g << 8 | r)
+}
+
+/// Components should fall back to system colors while a high contrast theme is on.
+pub(crate) fn is_high_contrast() -> bool {
+    let mut high_contrast = HIGHCONTRASTW {
+        cbSize: size_of::<HIGHCONTRASTW>() as u32,
+        ..Default::default()
+    };
+    unsafe {
+        SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            high_contrast.cbSize,
+            Some(&mut high_contrast as *mut _ as _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS::default(),
+        )
+        .is_ok()
+            && (high_contrast.dwFlags & HCF_HIGHCONTRASTON).0 != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn web_light_is_not_dark() {
+        assert!(!Theme::web_light().is_dark());
+    }
+
+    #[test]
+    fn dark_background_makes_a_dark_theme() {
+        let mut tokens = Tokens::web_light();
+        tokens.color_neutral_background1 = rgb!("#292929");
+        assert!(Theme::from(tokens).is_dark());
+    }
 }
@@ -0,0 +1,119 @@
+use windows::core::PCWSTR;
+use windows::Win32::Globalization::{CompareStringOrdinal, CSTR_EQUAL};
+
+const RESET_TIMEOUT: u32 = 1000;
+
+pub(crate) struct TypeAhead {
+    buffer: Vec<u16>,
+    last_time: u32,
+}
+
+impl TypeAhead {
+    pub(crate) fn new() -> Self {
+        TypeAhead {
+            buffer: Vec::new(),
+            last_time: 0,
+        }
+    }
+
+    // Typing the same character repeatedly cycles through the items starting with it.
+    pub(crate) fn search(
+        &mut self,
+        char: u16,
+        time: u32,
+        count: usize,
+        current: Option<usize>,
+        text_at: impl Fn(usize) -> Option<PCWSTR>,
+    ) -> Option<usize> {
+        if self.buffer.is_empty() || time.wrapping_sub(self.last_time) > RESET_TIMEOUT {
+            self.buffer.clear();
+        }
+        self.last_time = time;
+        self.buffer.push(char);
+
+        let is_repeated = self.buffer.iter().all(|a| *a == char);
+        let (prefix, start) = match (is_repeated, current) {
+            (true, Some(current)) => (&self.buffer[..1], current + 1),
+            (false, Some(current)) => (&self.buffer[..], current),
+            (_, None) => (&self.buffer[..], 0),
+        };
+        (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|index| match text_at(*index) {
+                Some(text) => unsafe { starts_with(text.as_wide(), prefix) },
+                None => false,
+            })
+    }
+}
+
+unsafe fn starts_with(text: &[u16], prefix: &[u16]) -> bool {
+    text.len() >= prefix.len()
+        && CompareStringOrdinal(&text[..prefix.len()], prefix, true) == CSTR_EQUAL
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::core::w;
+
+    use super::*;
+
+    const ITEMS: [Option<PCWSTR>; 5] = [
+        Some(w!("Apple")),
+        Some(w!("banana")),
+        None,
+        Some(w!("Blueberry")),
+        Some(w!("Cherry")),
+    ];
+
+    fn search(
+        type_ahead: &mut TypeAhead,
+        text: &str,
+        time: u32,
+        current: Option<usize>,
+    ) -> Option<usize> {
+        let mut index = current;
+        for char in text.encode_utf16() {
+            index = type_ahead.search(char, time, ITEMS.len(), index, |index| ITEMS[index]);
+        }
+        index
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs Windows")]
+    fn matches_prefix_ignoring_case() {
+        assert_eq!(search(&mut TypeAhead::new(), "c", 0, None), Some(4));
+        assert_eq!(search(&mut TypeAhead::new(), "BL", 0, None), Some(3));
+        assert_eq!(search(&mut TypeAhead::new(), "x", 0, None), None);
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs Windows")]
+    fn repeated_character_cycles_and_skips_items_without_text() {
+        let mut type_ahead = TypeAhead::new();
+        assert_eq!(search(&mut type_ahead, "b", 0, None), Some(1));
+        assert_eq!(search(&mut type_ahead, "b", 10, Some(1)), Some(3));
+        assert_eq!(search(&mut type_ahead, "b", 20, Some(3)), Some(1));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs Windows")]
+    fn longer_prefix_keeps_the_current_match() {
+        let mut type_ahead = TypeAhead::new();
+        assert_eq!(search(&mut type_ahead, "ba", 0, Some(0)), Some(1));
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "windows"), ignore = "needs Windows")]
+    fn pause_starts_a_new_search() {
+        let mut type_ahead = TypeAhead::new();
+        assert_eq!(search(&mut type_ahead, "b", 0, None), Some(1));
+        assert_eq!(
+            search(&mut type_ahead, "c", RESET_TIMEOUT + 1, Some(1)),
+            Some(4)
+        );
+        assert_eq!(
+            search(&mut type_ahead, "h", RESET_TIMEOUT + 2, Some(4)),
+            Some(4)
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! 100 buttons whose hover animations all run at once, for profiling the frame scheduler.
+use std::mem::size_of;
+
+use windows::core::*;
+use windows::Win32::Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::Graphics::Gdi::{BeginPaint, EndPaint, FillRect, PAINTSTRUCT};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Ole::OleInitialize;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use quelthalas::component::button::ButtonOptions;
+use quelthalas::{Dpi, QT};
+
+const COLUMNS: i32 = 10;
+const ROWS: i32 = 10;
+const TOGGLE_TIMER_ID: usize = 1;
+const TOGGLE_INTERVAL: u32 = 400;
+// Declared in Win32_UI_Controls, which the sample does not otherwise need.
+const WM_MOUSELEAVE: u32 = 0x02A3;
+
+struct State {
+    qt: QT,
+    buttons: Vec<HWND>,
+    hovered: bool,
+}
+
+fn main() -> Result<()> {
+    unsafe {
+        let instance = HINSTANCE::from(GetModuleHandleW(None)?);
+        OleInitialize(None)?;
+
+        let class_name = w!("Stress windows class");
+        let wc = WNDCLASSEXW {
+            cbSize: size_of::<WNDCLASSEXW>() as u32,
+            lpfnWndProc: Some(window_process),
+            lpszClassName: class_name,
+            ..Default::default()
+        };
+        RegisterClassExW(&wc);
+
+        let window = CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            class_name,
+            w!("Quel'Thalas stress test"),
+            WS_OVERLAPPEDWINDOW,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            None,
+            None,
+            Some(instance),
+            None,
+        )?;
+
+        let _ = ShowWindow(window, SW_SHOW);
+
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, None, 0, 0).into() {
+            let _ = TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+
+        Ok(())
+    }
+}
+
+extern "system" fn window_process(
+    window: HWND,
+    message: u32,
+    w_param: WPARAM,
+    l_param: LPARAM,
+) -> LRESULT {
+    unsafe {
+        match message {
+            WM_CREATE => {
+                let qt = QT::default();
+                let dpi = Dpi::for_window(window);
+                let mut buttons = Vec::new();
+                for row in 0..ROWS {
+                    for column in 0..COLUMNS {
+                        if let Ok(button) = qt.create_button(
+                            window,
+                            10 + column * dpi.to_physical(100f32),
+                            10 + row * dpi.to_physical(40f32),
+                            ButtonOptions {
+                                text: w!("Button"),
+                                ..ButtonOptions::default()
+                            },
+                        ) {
+                            buttons.push(button.window());
+                        }
+                    }
+                }
+                SetTimer(Some(window), TOGGLE_TIMER_ID, TOGGLE_INTERVAL, None);
+                SetWindowLongPtrW(
+                    window,
+                    GWLP_USERDATA,
+                    Box::into_raw(Box::new(State {
+                        qt,
+                        buttons,
+                        hovered: false,
+                    })) as _,
+                );
+                DefWindowProcW(window, message, w_param, l_param)
+            }
+            // Every button enters or leaves its hover state at the same moment.
+            WM_TIMER => {
+                let state = &mut *(GetWindowLongPtrW(window, GWLP_USERDATA) as *mut State);
+                state.hovered = !state.hovered;
+                for button in &state.buttons {
+                    if state.hovered {
+                        SendMessageW(*button, WM_MOUSEMOVE, None, Some(LPARAM(0x0005_0005)));
+                    } else {
+                        SendMessageW(*button, WM_MOUSELEAVE, None, None);
+                    }
+                }
+                LRESULT(0)
+            }
+            WM_DESTROY => {
+                _ = KillTimer(Some(window), TOGGLE_TIMER_ID);
+                drop(Box::from_raw(
+                    GetWindowLongPtrW(window, GWLP_USERDATA) as *mut State
+                ));
+                PostQuitMessage(0);
+                LRESULT(0)
+            }
+            WM_PAINT => {
+                let mut ps = PAINTSTRUCT::default();
+                let hdc = BeginPaint(window, &mut ps);
+                let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *const State;
+                if !raw.is_null() {
+                    FillRect(hdc, &ps.rcPaint, (*raw).qt.background_brush());
+                }
+                _ = EndPaint(window, &ps);
+                LRESULT(0)
+            }
+            _ => DefWindowProcW(window, message, w_param, l_param),
+        }
+    }
+}
@@ -1,27 +1,32 @@
 //#![windows_subsystem = "windows"]
 use std::mem::size_of;
+use std::thread;
+use std::time::Duration;
 
 use windows::core::*;
 use windows::Win32::Foundation::{COLORREF, HINSTANCE, HWND, LPARAM, LRESULT, WPARAM};
-use windows::Win32::Graphics::Gdi::{
-    BeginPaint, CreateSolidBrush, EndPaint, FillRect, PAINTSTRUCT,
-};
-use windows::Win32::System::Com::{CoInitializeEx, COINIT_MULTITHREADED};
+use windows::Win32::Graphics::Gdi::{BeginPaint, EndPaint, FillRect, PAINTSTRUCT};
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
-use windows::Win32::UI::HiDpi::GetDpiForWindow;
+use windows::Win32::System::Ole::OleInitialize;
 use windows::Win32::UI::WindowsAndMessaging::*;
 
-use quelthalas::component::button::IconPosition;
+use quelthalas::component::button::{ButtonOptions, IconPosition};
 use quelthalas::component::dialog::DialogResult;
+use quelthalas::component::hover_card::HoverCardLink;
+use quelthalas::component::input::InputOptions;
 use quelthalas::component::menu::MenuInfo;
-use quelthalas::component::{button, dialog, input, progress_bar};
+use quelthalas::component::number_box::{NumberBoxEvent, NumberBoxOptions};
+use quelthalas::component::progress_bar::ProgressBarOptions;
+use quelthalas::component::{
+    button, dialog, input, progress_bar, rating, segmented_control, spinner, tag,
+};
 use quelthalas::icon::Icon;
-use quelthalas::{MouseEvent, QT};
+use quelthalas::{background_color_message, CornerRadius, Dpi, HitTestBehavior, MouseEvent, QT};
 
 fn main() -> Result<()> {
     unsafe {
         let instance = HINSTANCE::from(GetModuleHandleW(None)?);
-        CoInitializeEx(None, COINIT_MULTITHREADED).ok()?;
+        OleInitialize(None)?;
 
         //Register the window class
         let class_name = w!("Sample windows class");
@@ -53,6 +58,9 @@ fn main() -> Result<()> {
 
         let mut message = MSG::default();
         while GetMessageW(&mut message, None, 0, 0).into() {
+            if QT::translate_focus_region_key(&message) {
+                continue;
+            }
             let _ = TranslateMessage(&message);
             DispatchMessageW(&message);
         }
@@ -71,145 +79,296 @@ extern "system" fn window_process(
         match message {
             WM_CREATE => {
                 let qt = QT::default();
-                let scaling_factor = GetDpiForWindow(window) / USER_DEFAULT_SCREEN_DPI;
+                let dpi = Dpi::for_window(window);
                 let icon = Icon::calendar_month_regular();
 
-                _ = qt.create_button(
+                let rounded_button = qt.create_button(
                     window,
                     20,
                     30,
-                    w!("Rounded"),
-                    &button::Appearance::Secondary,
-                    None,
-                    None,
-                    &button::Shape::Rounded,
-                    &button::Size::Medium,
-                    MouseEvent::default(),
+                    ButtonOptions {
+                        text: w!("Rounded"),
+                        ..ButtonOptions::default()
+                    },
                 );
+                // F6 and Shift+F6 jump between the first button and the first input, which stand
+                // in for a toolbar and a content area.
+                if let Ok(rounded_button) = rounded_button {
+                    qt.register_focus_region(rounded_button.window(), 0);
+                }
                 _ = qt.create_button(
                     window,
-                    20 + 110 * scaling_factor as i32,
+                    20 + dpi.to_physical(110f32),
                     30,
-                    w!("Circular"),
-                    &button::Appearance::Secondary,
-                    None,
-                    None,
-                    &button::Shape::Circular,
-                    &button::Size::Medium,
-                    MouseEvent::default(),
+                    ButtonOptions {
+                        text: w!("Circular"),
+                        shape: button::Shape::Circular,
+                        ..ButtonOptions::default()
+                    },
                 );
                 _ = qt.create_button(
                     window,
-                    20 + 220 * scaling_factor as i32,
+                    20 + dpi.to_physical(220f32),
                     30,
-                    w!("Square"),
-                    &button::Appearance::Secondary,
-                    None,
-                    None,
-                    &button::Shape::Square,
-                    &button::Size::Medium,
-                    MouseEvent::default(),
+                    ButtonOptions {
+                        text: w!("Square"),
+                        shape: button::Shape::Square,
+                        ..ButtonOptions::default()
+                    },
                 );
-                _ = qt.create_button(
+                if let Ok(button) = qt.create_button(
                     window,
-                    20 + 330 * scaling_factor as i32,
+                    20 + dpi.to_physical(330f32),
                     30,
-                    w!("Primary"),
-                    &button::Appearance::Primary,
-                    Some(&icon),
-                    None,
-                    &button::Shape::Rounded,
-                    &button::Size::Medium,
-                    MouseEvent::default(),
-                );
+                    ButtonOptions {
+                        text: w!("Primary"),
+                        appearance: button::Appearance::Primary,
+                        icon: Some(icon.clone()),
+                        ..ButtonOptions::default()
+                    },
+                ) {
+                    _ = qt.attach_hover_card(
+                        button.window(),
+                        w!("Primary buttons"),
+                        w!("Use a primary button for the most important action in a view."),
+                        Some(&icon),
+                        Some(HoverCardLink {
+                            text: w!("Learn more"),
+                            event: MouseEvent::default(),
+                        }),
+                        None,
+                    );
+                }
                 _ = qt.create_button(
                     window,
                     20,
-                    30 + 50 * scaling_factor as i32,
-                    w!("Small with calender icon"),
-                    &button::Appearance::Secondary,
-                    Some(&icon),
-                    None,
-                    &button::Shape::Rounded,
-                    &button::Size::Small,
-                    MouseEvent::default(),
+                    30 + dpi.to_physical(50f32),
+                    ButtonOptions {
+                        text: w!("Small with calender icon"),
+                        secondary_text: Some(w!("Pick a date for the meeting")),
+                        icon: Some(icon.clone()),
+                        size: button::Size::Small,
+                        ..ButtonOptions::default()
+                    },
                 );
                 _ = qt.create_button(
                     window,
                     20,
-                    30 + 100 * scaling_factor as i32,
-                    w!("With calendar icon after contents"),
-                    &button::Appearance::Secondary,
-                    Some(&icon),
-                    Some(&IconPosition::After),
-                    &button::Shape::Rounded,
-                    &button::Size::Medium,
-                    MouseEvent::default(),
+                    30 + dpi.to_physical(100f32),
+                    ButtonOptions {
+                        text: w!("With calendar icon after contents"),
+                        icon: Some(icon.clone()),
+                        icon_position: Some(IconPosition::After),
+                        ..ButtonOptions::default()
+                    },
                 );
                 _ = qt.create_button(
                     window,
                     20,
-                    30 + 150 * scaling_factor as i32,
-                    w!("Large with calender icon"),
-                    &button::Appearance::Secondary,
-                    Some(&icon),
-                    None,
-                    &button::Shape::Rounded,
-                    &button::Size::Large,
-                    MouseEvent::default(),
+                    30 + dpi.to_physical(150f32),
+                    ButtonOptions {
+                        text: w!("Large with calender icon"),
+                        icon: Some(icon.clone()),
+                        size: button::Size::Large,
+                        ..ButtonOptions::default()
+                    },
                 );
-                _ = qt.create_input(
+                let search_input = qt.create_input(
                     window,
                     20,
-                    30 + 200 * scaling_factor as i32,
-                    200 * scaling_factor as i32,
-                    &input::Size::Medium,
-                    &input::Appearance::Outline,
-                    Some(w!("Default text")),
-                    &input::Type::Text,
-                    None,
+                    30 + dpi.to_physical(200f32),
+                    InputOptions {
+                        width: dpi.to_physical(200f32),
+                        size: input::Size::Medium,
+                        appearance: input::Appearance::Outline,
+                        corner: Some(CornerRadius::Pill),
+                        default_value: Some(w!("Default text")),
+                        input_type: input::Type::Text,
+                        number_format: None,
+                        placeholder: None,
+                        history_size: None,
+                        revert_on_escape: false,
+                        event: input::InputEvent {
+                            on_query: Some(Box::new(|text| {
+                                let text = text.to_lowercase();
+                                ["Apple", "Apricot", "Banana", "Blueberry", "Cherry", "Grape"]
+                                    .iter()
+                                    .filter(|fruit| fruit.to_lowercase().starts_with(&text))
+                                    .map(|fruit| fruit.to_string())
+                                    .collect()
+                            })),
+                            ..Default::default()
+                        },
+                    },
                 );
-                _ = qt.create_input(
+                if let Ok(search_input) = search_input {
+                    qt.register_focus_region(search_input.window(), 1);
+                }
+                let filled_input = qt.create_input(
                     window,
-                    20 + 220 * scaling_factor as i32,
-                    30 + 200 * scaling_factor as i32,
-                    200 * scaling_factor as i32,
-                    &input::Size::Medium,
-                    &input::Appearance::FilledLighter,
-                    Some(w!("Filled lighter")),
-                    &input::Type::Text,
-                    None,
+                    20 + dpi.to_physical(220f32),
+                    30 + dpi.to_physical(200f32),
+                    InputOptions {
+                        width: dpi.to_physical(200f32),
+                        size: input::Size::Medium,
+                        appearance: input::Appearance::FilledLighter,
+                        corner: None,
+                        default_value: Some(w!("Filled lighter")),
+                        input_type: input::Type::Text,
+                        number_format: None,
+                        placeholder: None,
+                        history_size: Some(20),
+                        revert_on_escape: true,
+                        event: input::InputEvent::default(),
+                    },
                 );
                 _ = qt.create_input(
                     window,
                     20,
-                    30 + 250 * scaling_factor as i32,
-                    380 * scaling_factor as i32,
-                    &input::Size::Small,
-                    &input::Appearance::Outline,
-                    None,
-                    &input::Type::Password,
-                    Some(w!("Small with placeholder")),
+                    30 + dpi.to_physical(250f32),
+                    InputOptions {
+                        width: dpi.to_physical(380f32),
+                        size: input::Size::Small,
+                        appearance: input::Appearance::Outline,
+                        corner: None,
+                        default_value: None,
+                        input_type: input::Type::Password,
+                        number_format: None,
+                        placeholder: Some(w!("Small with placeholder")),
+                        history_size: None,
+                        revert_on_escape: false,
+                        event: input::InputEvent::default(),
+                    },
                 );
                 _ = qt.create_progress_bar(
                     window,
                     20,
-                    30 + 300 * scaling_factor as i32,
-                    400 * scaling_factor as i32,
-                    &progress_bar::Shape::Rounded,
-                    None,
-                    None,
-                    &progress_bar::Thickness::Medium,
+                    30 + dpi.to_physical(300f32),
+                    ProgressBarOptions {
+                        width: dpi.to_physical(400f32),
+                        shape: progress_bar::Shape::Rounded,
+                        corner: None,
+                        value: None,
+                        max: None,
+                        thickness: progress_bar::Thickness::Medium,
+                        fill: progress_bar::Fill::Solid,
+                        segments: None,
+                        steps: None,
+                        event: progress_bar::ProgressBarEvent::default(),
+                    },
                 );
-                _ = qt.create_progress_bar(
+                if let Ok(progress_bar) = qt.create_progress_bar(
                     window,
                     20,
-                    30 + 325 * scaling_factor as i32,
-                    400 * scaling_factor as i32,
-                    &progress_bar::Shape::Rounded,
-                    Some(0.4),
-                    None,
-                    &progress_bar::Thickness::Large,
+                    30 + dpi.to_physical(325f32),
+                    ProgressBarOptions {
+                        width: dpi.to_physical(400f32),
+                        shape: progress_bar::Shape::Rounded,
+                        corner: None,
+                        value: Some(0.4),
+                        max: None,
+                        thickness: progress_bar::Thickness::Large,
+                        fill: progress_bar::Fill::Gradient,
+                        segments: Some(5),
+                        steps: None,
+                        event: progress_bar::ProgressBarEvent::default(),
+                    },
+                ) {
+                    // A worker thread drives the bar through a handle that can cross threads, and
+                    // marks the filled input as done once the bar is full.
+                    let filled_input = filled_input.ok();
+                    thread::spawn(move || {
+                        for step in 4..=10 {
+                            thread::sleep(Duration::from_millis(500));
+                            _ = progress_bar.set_value(Some(step as f32 / 10.0));
+                        }
+                        if let Some(filled_input) = filled_input {
+                            _ = filled_input.set_status(Some(input::Status::Success));
+                        }
+                    });
+                }
+                _ = qt.create_spinner(
+                    window,
+                    20 + dpi.to_physical(420f32),
+                    30 + dpi.to_physical(300f32),
+                    &spinner::Size::Small,
+                    Some(w!("Loading...")),
+                );
+                _ = qt.create_number_box(
+                    window,
+                    20,
+                    30 + dpi.to_physical(350f32),
+                    NumberBoxOptions {
+                        width: dpi.to_physical(200f32),
+                        min: 0f64,
+                        max: 10f64,
+                        step: 0.5,
+                        value: 2f64,
+                        event: NumberBoxEvent::default(),
+                    },
+                );
+                _ = qt.create_rating(
+                    window,
+                    20 + dpi.to_physical(220f32),
+                    30 + dpi.to_physical(356f32),
+                    rating::RatingOptions {
+                        max: 5,
+                        value: 3.5,
+                        step: rating::Step::Half,
+                        size: rating::Size::Large,
+                        event: rating::RatingEvent::default(),
+                    },
+                );
+                _ = qt.create_button(
+                    window,
+                    20,
+                    30 + dpi.to_physical(400f32),
+                    ButtonOptions {
+                        text: w!("Forgot password?"),
+                        appearance: button::Appearance::Transparent,
+                        alignment: Some(button::Alignment::Start),
+                        hit_test: HitTestBehavior::TransparentOutsideContent,
+                        ..ButtonOptions::default()
+                    },
+                );
+                _ = qt.create_segmented_control(
+                    window,
+                    20,
+                    30 + dpi.to_physical(450f32),
+                    vec![(w!("Day"), 0), (w!("Week"), 1), (w!("Month"), 2)],
+                    1,
+                    segmented_control::SegmentedControlEvent::default(),
+                );
+                _ = qt.create_tag(
+                    window,
+                    20,
+                    30 + dpi.to_physical(495f32),
+                    w!("Design"),
+                    false,
+                    tag::TagEvent::default(),
+                );
+                _ = qt.create_tag(
+                    window,
+                    20 + dpi.to_physical(100f32),
+                    30 + dpi.to_physical(495f32),
+                    w!("Removable"),
+                    true,
+                    tag::TagEvent {
+                        on_dismiss: Box::new(|tag| {
+                            _ = DestroyWindow(*tag);
+                        }),
+                    },
+                );
+                _ = qt.create_split_button(
+                    window,
+                    20 + dpi.to_physical(220f32),
+                    30 + dpi.to_physical(450f32),
+                    ButtonOptions {
+                        text: w!("Save"),
+                        appearance: button::Appearance::Primary,
+                        ..ButtonOptions::default()
+                    },
+                    MouseEvent::default(),
                 );
                 SetWindowLongPtrW(
                     window,
@@ -243,31 +402,49 @@ extern "system" fn window_process(
             WM_PAINT => {
                 let mut ps = PAINTSTRUCT::default();
                 let hdc = BeginPaint(window, &mut ps);
-                FillRect(hdc, &ps.rcPaint, CreateSolidBrush(COLORREF(0xfafafa)));
+                let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *const QT;
+                if !raw.is_null() {
+                    FillRect(hdc, &ps.rcPaint, (*raw).background_brush());
+                }
                 _ = EndPaint(window, &ps);
                 LRESULT(0)
             }
             WM_CONTEXTMENU => {
-                let x = l_param.0 as i16 as i32;
-                let y = (l_param.0 >> 16) as i16 as i32;
-
                 let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *const QT;
                 let qt = &*raw;
                 let menu_list = vec![
                     MenuInfo::MenuItem {
-                        text: w!("New"),
+                        text: w!("New\tCtrl+N"),
                         command_id: 0,
                         disabled: false,
+                        disabled_reason: None,
                     },
                     MenuInfo::MenuItem {
-                        text: w!("New window"),
+                        text: w!("New window\tCtrl+Shift+N"),
                         command_id: 1,
                         disabled: false,
+                        disabled_reason: None,
                     },
                     MenuInfo::MenuItem {
-                        text: w!("Open file"),
+                        text: w!("Open file\tCtrl+O"),
                         command_id: 2,
                         disabled: true,
+                        disabled_reason: Some(w!("Select a file first")),
+                    },
+                    MenuInfo::DynamicSubMenu {
+                        text: w!("Open recent"),
+                        build: Box::new(|| {
+                            [w!("notes.txt"), w!("report.docx")]
+                                .into_iter()
+                                .enumerate()
+                                .map(|(index, text)| MenuInfo::MenuItem {
+                                    text,
+                                    command_id: 10 + index as u32,
+                                    disabled: false,
+                                    disabled_reason: None,
+                                })
+                                .collect()
+                        }),
                     },
                     MenuInfo::MenuDivider,
                     MenuInfo::SubMenu {
@@ -277,17 +454,20 @@ extern "system" fn window_process(
                                 text: w!("Settings"),
                                 command_id: 30,
                                 disabled: false,
+                                disabled_reason: None,
                             },
                             MenuInfo::MenuItem {
                                 text: w!("Online services settings"),
                                 command_id: 31,
                                 disabled: false,
+                                disabled_reason: None,
                             },
                             MenuInfo::MenuDivider,
                             MenuInfo::MenuItem {
                                 text: w!("Extensions"),
                                 command_id: 32,
                                 disabled: false,
+                                disabled_reason: None,
                             },
                             MenuInfo::SubMenu {
                                 text: w!("Appearance"),
@@ -296,30 +476,57 @@ extern "system" fn window_process(
                                         text: w!("Centered layout"),
                                         command_id: 30,
                                         disabled: false,
+                                        disabled_reason: None,
+                                    },
+                                    MenuInfo::CheckItem {
+                                        text: w!("Word wrap"),
+                                        command_id: 34,
+                                        checked: true,
+                                        disabled: false,
+                                    },
+                                    MenuInfo::CheckItem {
+                                        text: w!("Minimap"),
+                                        command_id: 35,
+                                        checked: false,
+                                        disabled: false,
                                     },
                                     MenuInfo::MenuItem {
                                         text: w!("Zen"),
                                         command_id: 31,
                                         disabled: false,
+                                        disabled_reason: None,
                                     },
                                     MenuInfo::MenuItem {
                                         text: w!("Zoom in"),
                                         command_id: 32,
                                         disabled: true,
+                                        disabled_reason: None,
                                     },
                                     MenuInfo::MenuItem {
                                         text: w!("Zoom out"),
                                         command_id: 33,
                                         disabled: false,
+                                        disabled_reason: None,
                                     },
                                 ],
                             },
                         ],
                     },
                 ];
+                // From the keyboard both are -1, and the menu is placed at the focused window.
+                let x = l_param.0 as i16 as i32;
+                let y = (l_param.0 >> 16) as i16 as i32;
                 _ = qt.open_menu(window, menu_list, x, y);
                 LRESULT::default()
             }
+            _ if message == background_color_message() => {
+                let raw = GetWindowLongPtrW(window, GWLP_USERDATA) as *const QT;
+                if raw.is_null() {
+                    return LRESULT(0);
+                }
+                *(l_param.0 as *mut COLORREF) = (*raw).background_colorref();
+                LRESULT(1)
+            }
             _ => DefWindowProcW(window, message, w_param, l_param),
         }
     }